@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+
+/// An algorithm run when a signal is aborted.
+type AbortAlgorithm = Box<dyn FnOnce()>;
+
+// https://dom.spec.whatwg.org/#interface-AbortSignal
+#[derive(Default)]
+pub struct AbortSignal {
+    aborted: bool,
+    abort_algorithms: Vec<AbortAlgorithm>,
+}
+
+impl AbortSignal {
+    // https://dom.spec.whatwg.org/#dom-abortsignal-aborted
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    // https://dom.spec.whatwg.org/#abortsignal-add
+    /// Add an algorithm to run when the signal is aborted, nothing is added to an aborted signal.
+    pub(crate) fn add(signal: &Rc<RefCell<AbortSignal>>, algorithm: impl FnOnce() + 'static) {
+        let mut signal = signal.borrow_mut();
+
+        if !signal.aborted {
+            signal.abort_algorithms.push(Box::new(algorithm));
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#abortsignal-signal-abort
+    // NOTE: signals are not event targets here, so no abort event is fired.
+    fn signal_abort(signal: &Rc<RefCell<AbortSignal>>) {
+        let algorithms = {
+            let mut signal = signal.borrow_mut();
+
+            if signal.aborted {
+                return;
+            }
+
+            signal.aborted = true;
+
+            std::mem::take(&mut signal.abort_algorithms)
+        };
+
+        for algorithm in algorithms {
+            algorithm();
+        }
+    }
+}
+
+// https://dom.spec.whatwg.org/#interface-abortcontroller
+#[derive(Default)]
+pub struct AbortController {
+    signal: Rc<RefCell<AbortSignal>>,
+}
+
+impl AbortController {
+    // https://dom.spec.whatwg.org/#dom-abortcontroller-abortcontroller
+    pub fn new() -> AbortController {
+        AbortController::default()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-abortcontroller-signal
+    pub fn signal(&self) -> Rc<RefCell<AbortSignal>> {
+        self.signal.clone()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-abortcontroller-abort
+    /// Abort the signal of the controller, running its abort algorithms.
+    pub fn abort(&self) {
+        AbortSignal::signal_abort(&self.signal);
+    }
+}
@@ -1,82 +1,427 @@
 use crate::dom::node::Node;
 
-use std::cell::{RefCell, Ref};
-use std::borrow::Borrow;
-use std::ops::Index;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::rc::{Rc, Weak};
 
 
 thread_local! {
-    static ARENA: RefCell<Vec<RefCell<Node>>> = RefCell::new(Vec::new());
+    /// The arenas of the live doms on this thread, a node id refers to its arena by the index in this
+    /// list. The registry only holds weak references, the nodes are owned by the arena of their dom
+    /// and dropped together with it.
+    static ARENAS: RefCell<Vec<ArenaEntry>> = const { RefCell::new(Vec::new()) };
 }
 
-/// NodeId is an index to a node inside the arena.
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub struct NodeId(usize);
+/// NodeId is a generational index to a node inside the arena of a dom.
+///
+/// Accessing a node fails loudly when its dom has been dropped or the node has been freed, a stale
+/// id never refers to a different node. `Dom::try_get` checks an id without panicking. A node id does
+/// not keep its node alive, a [`NodeHandle`] does.
+///
+/// A node id is resolved through the arenas of the current thread, so it can not be sent to another
+/// thread, just like its dom.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId {
+    arena: u32,
+    index: u32,
+    generation: u32,
+    thread: PhantomData<*const ()>,
+}
 
-pub struct Arena {
-    nodes: Vec<Node>,
+impl NodeId {
+    fn new(arena: u32, index: u32, generation: u32) -> NodeId {
+        NodeId {
+            arena,
+            index,
+            generation,
+            thread: PhantomData,
+        }
+    }
+}
+
+impl std::fmt::Debug for NodeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NodeId")
+            .field("arena", &self.arena)
+            .field("index", &self.index)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+/// A handle to a node that keeps it alive, garbage collection treats every live handle as a root
+/// and compaction updates the id of the handle.
+///
+/// The clones of a handle share its root, the node is no longer kept alive once the last clone is
+/// dropped. Hold a handle instead of a node id where the node may be detached while it is held, like
+/// in an event listener.
+#[derive(Clone)]
+pub struct NodeHandle(Rc<Cell<NodeId>>);
+
+impl NodeHandle {
+    /// Create a handle to the node, panics when the node has been freed.
+    pub fn new(id: NodeId) -> NodeHandle {
+        let slots = slots(id);
+        let mut slots = slots.borrow_mut();
+
+        if !slots.slots.get(id.index as usize).is_some_and(|slot| slot.generation == id.generation && slot.node.is_some()) {
+            panic!("{id:?} has been freed");
+        }
+
+        let handle = NodeHandle(Rc::new(Cell::new(id)));
+
+        slots.roots.push(Rc::downgrade(&handle.0));
+
+        handle
+    }
+
+    /// The current id of the node.
+    #[inline]
+    pub fn id(&self) -> NodeId {
+        self.0.get()
+    }
+
+    /// Point the handle to another node, the clones of the handle follow it.
+    pub(crate) fn set(&mut self, id: NodeId) {
+        match is_same_arena(self.id(), id) {
+            true => self.0.set(id),
+            false => *self = NodeHandle::new(id),
+        }
+    }
+}
+
+impl std::fmt::Debug for NodeHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("NodeHandle").field(&self.id()).finish()
+    }
+}
+
+struct Slot {
+    generation: u32,
+
+    /// The node in the slot, none when the slot is on the free list.
+    node: Option<Rc<RefCell<Node>>>,
+}
+
+#[derive(Default)]
+struct Slots {
+    slots: Vec<Slot>,
+
+    /// The indices of the free slots, a freed slot is reused with its generation raised.
+    free: Vec<u32>,
+
+    /// The generation of the slots pushed onto the end of the arena.
+    generation: u32,
+
+    /// The handles to the nodes of the arena, the dropped handles are removed when collecting.
+    roots: Vec<Weak<Cell<NodeId>>>,
 }
 
-impl Index<NodeId> for Arena {
-    type Output = Node;
+impl Slots {
+    /// The ids of the live handles, dropping the handles that are gone.
+    fn roots(&mut self) -> Vec<NodeId> {
+        self.roots.retain(|root| root.strong_count() > 0);
 
-    fn index(&self, id: NodeId) -> &Node {
-        &self.nodes[id.0]
+        self.roots.iter().filter_map(Weak::upgrade).map(|root| root.get()).collect()
     }
 }
 
+struct ArenaEntry {
+    slots: Weak<RefCell<Slots>>,
+
+    /// The generation of the slots of the arena, raised past every generation of the arena when it
+    /// is dropped so the ids of the next arena in this entry never match the ids of the old one.
+    generation: u32,
+}
+
+/// The arena of a dom, the nodes of the dom are dropped together with the arena.
+pub struct Arena {
+    id: u32,
+    slots: Rc<RefCell<Slots>>,
+}
+
 impl Arena {
     pub fn new() -> Arena {
+        let slots = Rc::new(RefCell::new(Slots::default()));
+
+        let id = ARENAS.with_borrow_mut(|arenas| {
+            let id = match arenas.iter().position(|entry| entry.slots.strong_count() == 0) {
+                Some(id) => id,
+                None => {
+                    arenas.push(ArenaEntry {
+                        slots: Weak::new(),
+                        generation: 0,
+                    });
+
+                    arenas.len() - 1
+                },
+            };
+
+            arenas[id].slots = Rc::downgrade(&slots);
+
+            slots.borrow_mut().generation = arenas[id].generation;
+
+            id
+        });
+
         Arena {
-            nodes: Vec::new(),
+            id: id as u32,
+            slots,
+        }
+    }
+
+    /// Insert a cyclic node into the arena.
+    pub fn insert_cyclic(&self, f: impl FnOnce(NodeId) -> Node) -> NodeId {
+        let id = NodeId::new(self.id, self.slots.borrow().slots.len() as u32, self.slots.borrow().generation);
+
+        self.slots.borrow_mut().slots.push(Slot {
+            generation: id.generation,
+            node: Some(Rc::new(RefCell::new(f(id)))),
+        });
+
+        id
+    }
+
+    /// The count of nodes in the arena.
+    pub fn len(&self) -> usize {
+        let slots = self.slots.borrow();
+
+        slots.slots.len() - slots.free.len()
+    }
+
+    /// Check if the node is a node of this arena that has not been removed.
+    pub fn contains(&self, id: NodeId) -> bool {
+        id.arena == self.id && contains(id)
+    }
+
+    /// Free the nodes that can not be reached from the roots or the handles by following the
+    /// references of the nodes, returns the count of freed nodes.
+    pub fn collect(&self, roots: impl IntoIterator<Item = NodeId>) -> usize {
+        let mut reachable = vec![false; self.slots.borrow().slots.len()];
+
+        let mut pending = self.slots.borrow_mut().roots();
+
+        pending.extend(roots);
+
+        while let Some(id) = pending.pop() {
+            if !self.contains(id) || reachable[id.index as usize] {
+                continue;
+            }
+
+            reachable[id.index as usize] = true;
+
+            with_mut(id, |node| node.for_each_reference(&mut |reference| pending.push(*reference)));
         }
+
+        // NOTE: the freed nodes are dropped after the slots are released, dropping a node drops the
+        // closures it holds and those may access the arena.
+        let freed = {
+            let mut slots = self.slots.borrow_mut();
+            let slots = &mut *slots;
+
+            let mut freed = Vec::new();
+
+            for (index, slot) in slots.slots.iter_mut().enumerate().filter(|(index, _)| !reachable[*index]) {
+                if let Some(node) = slot.node.take() {
+                    slot.generation = slot.generation.wrapping_add(1);
+
+                    slots.free.push(index as u32);
+
+                    freed.push(node);
+                }
+            }
+
+            freed
+        };
+
+        freed.len()
     }
 
-    pub fn insert(&mut self, node: Node) -> NodeId {
-        self.nodes.push(node);
+    /// Move the nodes to the front of the arena, dropping the free slots, and return the new id of
+    /// every node keyed by its old id. The references between the nodes and the handles are updated.
+    pub fn compact(&self) -> HashMap<NodeId, NodeId> {
+        let mut slots = self.slots.borrow_mut();
+
+        // NOTE: the moved nodes get a generation no slot had before, so an old id never refers to
+        // the node that was moved into its slot.
+        let generation = slots.slots.iter()
+            .map(|slot| slot.generation)
+            .fold(slots.generation, u32::max)
+            .wrapping_add(1);
+
+        let mut ids = HashMap::new();
+
+        for (index, slot) in std::mem::take(&mut slots.slots).into_iter().enumerate() {
+            let Some(node) = slot.node else {
+                continue;
+            };
+
+            let id = NodeId::new(self.id, slots.slots.len() as u32, generation);
+
+            ids.insert(NodeId::new(self.id, index as u32, slot.generation), id);
+
+            slots.slots.push(Slot {
+                generation,
+                node: Some(node),
+            });
+        }
+
+        slots.free.clear();
+
+        slots.generation = generation;
+
+        for node in slots.slots.iter().flat_map(|slot| slot.node.as_ref()) {
+            node.borrow_mut().for_each_reference(&mut |id| {
+                if let Some(new) = ids.get(id) {
+                    *id = *new;
+                }
+            });
+        }
+
+        slots.roots.retain(|root| root.strong_count() > 0);
+
+        for root in slots.roots.iter().filter_map(Weak::upgrade) {
+            if let Some(new) = ids.get(&root.get()) {
+                root.set(*new);
+            }
+        }
 
-        NodeId(self.nodes.len() - 1)
+        ids
     }
+}
 
-    pub fn with_mut(&mut self, id: NodeId, f: impl FnOnce(&mut Node)) {
-        f(&mut self.nodes[id.0])
+impl Drop for Arena {
+    fn drop(&mut self) {
+        let slots = self.slots.borrow();
+
+        let generation = slots.slots.iter()
+            .map(|slot| slot.generation)
+            .fold(slots.generation, u32::max);
+
+        // NOTE: the thread local is gone when a dom is dropped while the thread exits, no arena
+        // is created after that.
+        let _ = ARENAS.try_with(|arenas| arenas.borrow_mut()[self.id as usize].generation = generation.wrapping_add(1));
     }
 }
 
-/// Insert a node into the arena.
+/// The slots of the arena the node belongs to, panics when the dom of the node has been dropped.
+fn slots(id: NodeId) -> Rc<RefCell<Slots>> {
+    ARENAS.with_borrow(|arenas| arenas.get(id.arena as usize).and_then(|entry| entry.slots.upgrade()))
+        .unwrap_or_else(|| panic!("{id:?} belongs to a dropped dom"))
+}
+
+/// Insert a node into the arena of its node document.
 #[inline]
 pub fn insert(node: Node) -> NodeId {
-    ARENA.with_borrow_mut(|arena| {
-        arena.push(RefCell::new(node));
+    let document = node.node_document;
 
-        NodeId(arena.len() - 1)
-    })
+    insert_into(document, Rc::new(RefCell::new(node)))
 }
 
-/// Insert a cyclic node into the arena.
-#[inline]
-pub fn insert_cyclic(f: impl Fn(NodeId) -> Node) -> NodeId {
-    ARENA.with_borrow_mut(|arena| {
-        arena.push(RefCell::new(f(NodeId(arena.len()))));
+/// Insert a node into the arena of the document.
+fn insert_into(document: NodeId, node: Rc<RefCell<Node>>) -> NodeId {
+    let arena = document.arena;
+
+    let slots = slots(document);
+    let mut slots = slots.borrow_mut();
+
+    let node = Some(node);
 
-        NodeId(arena.len() - 1)
-    })
+    if let Some(index) = slots.free.pop() {
+        let slot = &mut slots.slots[index as usize];
+
+        slot.node = node;
+
+        return NodeId::new(arena, index, slot.generation);
+    }
+
+    let id = NodeId::new(arena, slots.slots.len() as u32, slots.generation);
+
+    slots.slots.push(Slot {
+        generation: id.generation,
+        node,
+    });
+
+    id
+}
+
+/// Move a node into the arena of the document and return its new id, the node is freed from its old
+/// arena so the old id is stale afterwards. The handles to the node are moved along, the ids the node
+/// refers to are left as they are.
+pub fn transfer(id: NodeId, document: NodeId) -> NodeId {
+    let (node, roots) = {
+        let slots = slots(id);
+        let mut slots = slots.borrow_mut();
+
+        let slot = slots.slots.get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation && slot.node.is_some())
+            .unwrap_or_else(|| panic!("{id:?} has been freed"));
+
+        let node = slot.node.take().expect("node in slot");
+
+        slot.generation = slot.generation.wrapping_add(1);
+
+        slots.free.push(id.index);
+
+        let roots = slots.roots.extract_if(.., |root| root.upgrade().is_some_and(|root| root.get() == id)).collect::<Vec<_>>();
+
+        (node, roots)
+    };
+
+    node.borrow_mut().node_document = document;
+
+    let new = insert_into(document, node);
+
+    for root in roots.iter().filter_map(Weak::upgrade) {
+        root.set(new);
+    }
+
+    slots(document).borrow_mut().roots.extend(roots);
+
+    new
 }
 
-/// Get a reference to a node from the arena.
+/// Get a shared reference to a node from the arena, borrow it to read the node.
 #[inline]
-pub fn get<T: Borrow<NodeId>>(id: T) -> Ref<'static, Node> {
-    ARENA.with_borrow(|arena| unsafe {
-        std::mem::transmute::<Ref<'_, Node>, Ref<'static, Node>>(arena[id.borrow().0].borrow())
-    })
+pub fn get<T: std::borrow::Borrow<NodeId>>(id: T) -> Rc<RefCell<Node>> {
+    let id = *std::borrow::Borrow::<NodeId>::borrow(&id);
+
+    let slots = slots(id);
+    let slots = slots.borrow();
+
+    match slots.slots.get(id.index as usize) {
+        Some(Slot { generation, node: Some(node) }) if *generation == id.generation => node.clone(),
+        _ => panic!("{id:?} has been freed"),
+    }
 }
 
 /// Safely mutate a node from the arena.
 #[inline]
-pub fn with_mut<A: Borrow<NodeId>, B>(id: A, f: impl FnOnce(&mut Node) -> B) -> B {
-    ARENA.with_borrow(|arena| {
-        f(&mut arena[id.borrow().0].borrow_mut())
-    })
+pub fn with_mut<A: std::borrow::Borrow<NodeId>, B>(id: A, f: impl FnOnce(&mut Node) -> B) -> B {
+    f(&mut get(id).borrow_mut())
 }
 
+/// Get a shared reference to a node from the arena, or none when the node has been freed or its dom
+/// has been dropped.
+pub fn try_get<T: std::borrow::Borrow<NodeId>>(id: T) -> Option<Rc<RefCell<Node>>> {
+    let id = *std::borrow::Borrow::<NodeId>::borrow(&id);
 
+    let slots = ARENAS.with_borrow(|arenas| arenas.get(id.arena as usize).and_then(|entry| entry.slots.upgrade()))?;
+    let slots = slots.borrow();
+
+    match slots.slots.get(id.index as usize) {
+        Some(Slot { generation, node: Some(node) }) if *generation == id.generation => Some(node.clone()),
+        _ => None,
+    }
+}
+
+/// Check if the node is still in its arena, this is false once the node or its dom has been dropped.
+pub fn contains(id: NodeId) -> bool {
+    try_get(id).is_some()
+}
+
+/// Check if the nodes belong to the same arena.
+pub fn is_same_arena(a: NodeId, b: NodeId) -> bool {
+    a.arena == b.arena
+}
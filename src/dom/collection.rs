@@ -0,0 +1,313 @@
+use crate::dom::arena::{self, NodeId, NodeHandle};
+use crate::dom::iterators::{NodeIterator, TreeIterator};
+use crate::dom::node::NodeType;
+use crate::dom::node::element::Element;
+use crate::dom::node::document::Document;
+use crate::names::{Namespace, QualifiedName, atoms};
+use crate::parser::quirks::QuirksMode;
+
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+
+
+/// The nodes of a live collection along with the mutation count of the document they were collected at.
+struct Snapshot {
+    document: NodeId,
+    mutation_count: u64,
+    nodes: Vec<NodeId>,
+}
+
+/// A live view of the children or descendants of a root node that pass a filter.
+///
+/// The nodes are collected lazily and cached until the document of the root is mutated, the root
+/// is held by a handle.
+#[derive(Clone)]
+struct LiveNodes {
+    root: NodeHandle,
+    descendants: bool,
+    filter: Rc<dyn Fn(NodeId) -> bool>,
+    snapshot: Rc<RefCell<Option<Snapshot>>>,
+}
+
+impl LiveNodes {
+    fn new(root: NodeId, descendants: bool, filter: impl Fn(NodeId) -> bool + 'static) -> LiveNodes {
+        LiveNodes {
+            root: NodeHandle::new(root),
+            descendants,
+            filter: Rc::new(filter),
+            snapshot: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    fn nodes(&self) -> Ref<'_, Vec<NodeId>> {
+        let root = self.root.id();
+        let document = arena::get(root).borrow().node_document;
+        let mutation_count = arena::get(document).borrow().downcast_ref::<Document>().mutation_count;
+
+        let stale = self.snapshot.borrow().as_ref()
+            .is_none_or(|snapshot| snapshot.document != document || snapshot.mutation_count != mutation_count);
+
+        if stale {
+            let nodes = match self.descendants {
+                true => TreeIterator::descendants(root).filter(|node| (self.filter)(*node)).collect(),
+                false => NodeIterator::new(arena::get(root).borrow().first_child, |node| node.next_sibling)
+                    .filter(|node| (self.filter)(*node))
+                    .collect(),
+            };
+
+            self.snapshot.replace(Some(Snapshot {
+                document,
+                mutation_count,
+                nodes,
+            }));
+        }
+
+        Ref::map(self.snapshot.borrow(), |snapshot| &snapshot.as_ref().expect("snapshot was just collected").nodes)
+    }
+}
+
+// https://dom.spec.whatwg.org/#interface-nodelist
+/// A live list of the children of a node.
+#[derive(Clone)]
+pub struct NodeList {
+    nodes: LiveNodes,
+}
+
+impl NodeList {
+    pub(crate) fn children(node: NodeId) -> NodeList {
+        NodeList {
+            nodes: LiveNodes::new(node, false, |_| true),
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-nodelist-length
+    pub fn length(&self) -> usize {
+        self.nodes.nodes().len()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-nodelist-item
+    pub fn item(&self, index: usize) -> Option<NodeId> {
+        self.nodes.nodes().get(index).copied()
+    }
+
+    /// A copy of the nodes currently in the list, in tree order.
+    pub fn to_vec(&self) -> Vec<NodeId> {
+        self.nodes.nodes().clone()
+    }
+}
+
+// https://dom.spec.whatwg.org/#interface-htmlcollection
+/// A live collection of elements.
+#[derive(Clone)]
+pub struct HtmlCollection {
+    elements: LiveNodes,
+}
+
+impl HtmlCollection {
+    fn new(root: NodeId, descendants: bool, filter: impl Fn(&Element) -> bool + 'static) -> HtmlCollection {
+        HtmlCollection {
+            elements: LiveNodes::new(root, descendants, move |node| match &arena::get(node).borrow().node_type {
+                NodeType::Element(element) => filter(element),
+                _ => false,
+            }),
+        }
+    }
+
+    /// The element children of the node.
+    pub(crate) fn children(node: NodeId) -> HtmlCollection {
+        HtmlCollection::new(node, false, |_| true)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-getelementsbytagname
+    /// The element descendants of the root with the qualified name, `*` matches every element.
+    pub(crate) fn with_qualified_name(root: NodeId, qualified_name: &str) -> HtmlCollection {
+        if qualified_name == "*" {
+            return HtmlCollection::new(root, true, |_| true);
+        }
+
+        let lowercase = qualified_name.to_ascii_lowercase();
+        let qualified_name = qualified_name.to_string();
+
+        HtmlCollection::new(root, true, move |element| match element.name.is_namespace(Namespace::Html) {
+            true => element.name.qualified() == lowercase,
+            false => element.name.qualified() == qualified_name,
+        })
+    }
+
+    // https://dom.spec.whatwg.org/#concept-getelementsbytagnamens
+    /// The element descendants of the root with the namespace and local name, `*` matches any namespace or local name.
+    pub(crate) fn with_namespace_and_local_name(root: NodeId, namespace: Option<&str>, local_name: &str) -> HtmlCollection {
+        let namespace = match namespace.filter(|namespace| !namespace.is_empty()) {
+            Some("*") => None,
+            namespace => Some(namespace.map(Namespace::from)),
+        };
+
+        let local_name = (local_name != "*").then(|| local_name.to_string());
+
+        HtmlCollection::new(root, true, move |element| {
+            namespace.as_ref().is_none_or(|namespace| element.name.namespace == *namespace)
+                && local_name.as_ref().is_none_or(|local_name| element.name.local_name == local_name.as_str())
+        })
+    }
+
+    // https://dom.spec.whatwg.org/#concept-getelementsbyclassname
+    /// The element descendants of the root which have every class in the space separated class names.
+    pub(crate) fn with_class_names(root: NodeId, class_names: &str) -> HtmlCollection {
+        let class_names = class_names.split_ascii_whitespace().map(str::to_string).collect::<Vec<String>>();
+
+        if class_names.is_empty() {
+            return HtmlCollection::new(root, true, |_| false);
+        }
+
+        let document = arena::get(root).borrow().node_document;
+        let quirks = arena::get(document).borrow().downcast_ref::<Document>().quirks_mode == QuirksMode::Quirks;
+
+        HtmlCollection::new(root, true, move |element| {
+            let Some(classes) = element.attribute(QualifiedName::new(None, atoms::CLASS)) else {
+                return false;
+            };
+
+            class_names.iter().all(|class_name| {
+                classes.value.split_ascii_whitespace().any(|class| match quirks {
+                    true => class.eq_ignore_ascii_case(class_name),
+                    false => class == class_name,
+                })
+            })
+        })
+    }
+
+    // https://dom.spec.whatwg.org/#dom-htmlcollection-length
+    pub fn length(&self) -> usize {
+        self.elements.nodes().len()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-htmlcollection-item
+    pub fn item(&self, index: usize) -> Option<NodeId> {
+        self.elements.nodes().get(index).copied()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-htmlcollection-nameditem-key
+    /// Return the first element with the key as id, or as name if it is a html element.
+    pub fn named_item(&self, key: &str) -> Option<NodeId> {
+        if key.is_empty() {
+            return None;
+        }
+
+        self.elements.nodes().iter().copied().find(|element| {
+            let node = arena::get(element);
+            let node = node.borrow();
+            let element = node.downcast_ref::<Element>();
+
+            let has_value = |name| element.attribute(QualifiedName::new(None, name)).is_some_and(|attribute| attribute.value == key);
+
+            has_value(atoms::ID) || (element.name.is_namespace(Namespace::Html) && has_value(atoms::NAME))
+        })
+    }
+
+    /// A copy of the elements currently in the collection, in tree order.
+    pub fn to_vec(&self) -> Vec<NodeId> {
+        self.elements.nodes().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_html;
+    use crate::dom::node::Node;
+    use crate::dom::node::element::Element;
+    use crate::dom::testing::{element, create_element};
+
+    #[test]
+    fn collections_are_live() {
+        let mut dom = parse_html("<div id=a><p class='x y'></p><P class=x></P></div>");
+
+        let a = element(&dom, "a");
+
+        let paragraphs = dom.get_elements_by_tag_name("p");
+        let xs = dom.get_elements_by_class_name(" x ");
+        let xys = Element::get_elements_by_class_name(a, "y x");
+
+        assert_eq!(paragraphs.length(), 2);
+        assert_eq!(xs.length(), 2);
+        assert_eq!(xys.length(), 1);
+
+        let p = create_element(&mut dom, "p");
+
+        dom.append_child(a, p).expect("append");
+
+        assert_eq!(paragraphs.length(), 3);
+        assert_eq!(paragraphs.item(2), Some(p));
+        assert_eq!(xs.length(), 2);
+
+        Element::set_attribute(p, "class", "y x").expect("valid");
+
+        assert_eq!(xs.to_vec(), paragraphs.to_vec());
+        assert_eq!(xys.length(), 2);
+
+        let first = paragraphs.item(0).expect("first paragraph");
+
+        dom.remove(first);
+
+        assert_eq!(paragraphs.length(), 2);
+        assert_eq!(xys.to_vec(), vec![p]);
+        assert_eq!(paragraphs.item(2), None);
+    }
+
+    #[test]
+    fn root_is_not_included() {
+        let dom = parse_html("<div id=a class=x><div class=x></div></div>");
+
+        let a = element(&dom, "a");
+
+        assert_eq!(Element::get_elements_by_tag_name(a, "div").length(), 1);
+        assert_eq!(Element::get_elements_by_class_name(a, "x").length(), 1);
+        assert_eq!(Element::get_elements_by_class_name(a, "  ").length(), 0);
+        assert_eq!(dom.get_elements_by_tag_name("*").length(), 5);
+    }
+
+    #[test]
+    fn namespaces() {
+        let dom = parse_html("<svg><foreignObject></foreignObject></svg><foreignobject></foreignobject>");
+
+        let svg = "http://www.w3.org/2000/svg";
+
+        assert_eq!(dom.get_elements_by_tag_name("foreignObject").length(), 2);
+        assert_eq!(dom.get_elements_by_tag_name("FOREIGNOBJECT").length(), 1);
+        assert_eq!(dom.get_elements_by_tag_name_ns(Some(svg), "*").length(), 2);
+        assert_eq!(dom.get_elements_by_tag_name_ns(Some("*"), "foreignObject").length(), 1);
+        assert_eq!(dom.get_elements_by_tag_name_ns(None, "*").length(), 0);
+    }
+
+    #[test]
+    fn named_items() {
+        let dom = parse_html("<form name=a></form><p id=a></p><svg name=b></svg>");
+
+        let elements = dom.get_elements_by_tag_name("*");
+        let form = dom.query_selector("form").expect("valid");
+
+        assert_eq!(elements.named_item("a"), form);
+        assert_eq!(elements.named_item("b"), None);
+        assert_eq!(elements.named_item(""), None);
+    }
+
+    #[test]
+    fn child_nodes() {
+        let mut dom = parse_html("<div id=a>x<!--y--><p></p></div>");
+
+        let a = element(&dom, "a");
+
+        let child_nodes = Node::child_nodes(a);
+        let children = Element::children(a);
+
+        assert_eq!(child_nodes.length(), 3);
+        assert_eq!(children.length(), 1);
+
+        let text = dom.create_text_node(String::from("z"));
+
+        dom.insert_before(a, text, child_nodes.item(0)).expect("insert");
+
+        assert_eq!(child_nodes.item(0), Some(text));
+        assert_eq!(child_nodes.length(), 4);
+        assert_eq!(children.length(), 1);
+    }
+}
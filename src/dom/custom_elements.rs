@@ -0,0 +1,793 @@
+use crate::dom::arena::{self, NodeId};
+use crate::dom::exception::DomException;
+use crate::dom::iterators::TreeIterator;
+use crate::dom::node::NodeType;
+use crate::dom::node::element::Element;
+use crate::dom::node::document::Document;
+use crate::names::{QualifiedName, Namespace, LocalName, Atom};
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+
+const RESERVED_NAMES: &[&str] = &[
+    "annotation-xml", "color-profile", "font-face", "font-face-src", "font-face-uri", "font-face-format", "font-face-name",
+    "missing-glyph",
+];
+
+/// Local names that do not map to HTMLUnknownElement in the html namespace.
+const HTML_ELEMENTS: &[&str] = &[
+    "a", "abbr", "acronym", "address", "area", "article", "aside", "audio", "b", "base", "basefont", "bdi", "bdo", "bgsound",
+    "big", "blink", "blockquote", "body", "br", "button", "canvas", "caption", "center", "cite", "code", "col", "colgroup",
+    "data", "datalist", "dd", "del", "details", "dfn", "dialog", "dir", "div", "dl", "dt", "em", "embed", "fieldset",
+    "figcaption", "figure", "font", "footer", "form", "frame", "frameset", "h1", "h2", "h3", "h4", "h5", "h6", "head",
+    "header", "hgroup", "hr", "html", "i", "iframe", "img", "input", "ins", "isindex", "kbd", "keygen", "label", "legend",
+    "li", "link", "listing", "main", "map", "mark", "marquee", "menu", "meta", "meter", "multicol", "nav", "nextid",
+    "nobr", "noembed", "noframes", "noscript", "object", "ol", "optgroup", "option", "output", "p", "param", "picture",
+    "plaintext", "pre", "progress", "q", "rb", "rp", "rt", "rtc", "ruby", "s", "samp", "script", "search", "section",
+    "select", "slot", "small", "source", "spacer", "span", "strike", "strong", "style", "sub", "summary", "sup", "table",
+    "tbody", "td", "template", "textarea", "tfoot", "th", "thead", "time", "title", "tr", "track", "tt", "u", "ul", "var",
+    "video", "wbr", "xmp",
+];
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#prod-pcenchar
+fn is_pcen_char(character: char) -> bool {
+    matches!(character,
+        '-' | '.' | '0'..='9' | '_' | 'a'..='z' | '\u{b7}' | '\u{c0}'..='\u{d6}' | '\u{d8}'..='\u{f6}' | '\u{f8}'..='\u{37d}'
+            | '\u{37f}'..='\u{1fff}' | '\u{200c}'..='\u{200d}' | '\u{203f}'..='\u{2040}' | '\u{2070}'..='\u{218f}'
+            | '\u{2c00}'..='\u{2fef}' | '\u{3001}'..='\u{d7ff}' | '\u{f900}'..='\u{fdcf}' | '\u{fdf0}'..='\u{fffd}'
+            | '\u{10000}'..='\u{effff}'
+    )
+}
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#valid-custom-element-name
+/// Check if the name is a valid custom element name.
+pub fn is_valid_custom_element_name(name: &str) -> bool {
+    name.starts_with(|character: char| character.is_ascii_lowercase())
+        && name.contains('-')
+        && name.chars().all(is_pcen_char)
+        && !RESERVED_NAMES.contains(&name)
+}
+
+/// The constructor of a custom element, it is called with the element that is being upgraded.
+pub type CustomElementConstructor = Rc<dyn Fn(NodeId) -> Result<(), DomException>>;
+
+/// The connected and disconnected callbacks, called with the element.
+pub type LifecycleCallback = Rc<dyn Fn(NodeId)>;
+
+/// The adopted callback, called with the element, the old document and the new document.
+pub type AdoptedCallback = Rc<dyn Fn(NodeId, NodeId, NodeId)>;
+
+/// The attribute changed callback, called with the element, the local name, the old value, the new
+/// value and the namespace of the attribute.
+pub type AttributeChangedCallback = Rc<dyn Fn(NodeId, LocalName, Option<String>, Option<String>, Option<Namespace>)>;
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#concept-custom-element-definition-lifecycle-callbacks
+#[derive(Clone, Default)]
+pub struct LifecycleCallbacks {
+    pub connected: Option<LifecycleCallback>,
+    pub disconnected: Option<LifecycleCallback>,
+    pub adopted: Option<AdoptedCallback>,
+    pub attribute_changed: Option<AttributeChangedCallback>,
+}
+
+/// A lifecycle callback to enqueue on a custom element, together with its arguments.
+pub enum LifecycleReaction {
+    Connected,
+    Disconnected,
+    Adopted {
+        old_document: NodeId,
+        new_document: NodeId,
+    },
+    AttributeChanged {
+        local_name: LocalName,
+        old_value: Option<String>,
+        value: Option<String>,
+        namespace: Option<Namespace>,
+    },
+}
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#custom-element-reaction-queue
+pub enum CustomElementReaction {
+    Upgrade(Rc<CustomElementDefinition>),
+    Callback(Box<dyn FnOnce()>),
+}
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#custom-element-reactions-stack
+/// The reactions stack of a document, every dom is its own agent so it has a stack of its own.
+#[derive(Default)]
+pub(crate) struct CustomElementReactionsStack {
+    element_queues: Vec<Vec<NodeId>>,
+    backup_element_queue: Vec<NodeId>,
+    processing_backup_element_queue: bool,
+}
+
+impl CustomElementReactionsStack {
+    /// Visit the elements in the element queues, the elements are kept alive until their reactions
+    /// have been invoked.
+    pub(crate) fn for_each_reference(&mut self, f: &mut impl FnMut(&mut NodeId)) {
+        self.element_queues.iter_mut()
+            .flatten()
+            .chain(self.backup_element_queue.iter_mut())
+            .for_each(f);
+    }
+}
+
+/// The arguments to [`CustomElementRegistry::define`].
+#[derive(Clone)]
+pub struct ElementDefinition {
+    pub constructor: CustomElementConstructor,
+
+    /// The local name of the built-in element this definition customizes.
+    pub extends: Option<String>,
+
+    pub observed_attributes: Vec<String>,
+
+    /// The disabled features, `internals` and `shadow` are recognized.
+    pub disabled_features: Vec<String>,
+
+    pub form_associated: bool,
+
+    pub callbacks: LifecycleCallbacks,
+}
+
+impl ElementDefinition {
+    pub fn new(constructor: impl Fn(NodeId) -> Result<(), DomException> + 'static) -> ElementDefinition {
+        ElementDefinition {
+            constructor: Rc::new(constructor),
+            extends: None,
+            observed_attributes: Vec::new(),
+            disabled_features: Vec::new(),
+            form_associated: false,
+            callbacks: LifecycleCallbacks::default(),
+        }
+    }
+}
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#custom-element-definition
+pub struct CustomElementDefinition {
+    pub name: LocalName,
+    pub local_name: LocalName,
+    pub constructor: CustomElementConstructor,
+    pub observed_attributes: Vec<LocalName>,
+    pub disable_internals: bool,
+    pub disable_shadow: bool,
+    pub form_associated: bool,
+    pub callbacks: LifecycleCallbacks,
+}
+
+impl CustomElementDefinition {
+    /// Check if the definition is for a customized built-in element.
+    #[inline]
+    pub fn is_customized_built_in(&self) -> bool {
+        self.name != self.local_name
+    }
+}
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#customelementregistry
+pub struct CustomElementRegistry {
+    definitions: Vec<Rc<CustomElementDefinition>>,
+    scoped: bool,
+    scoped_documents: Vec<NodeId>,
+    document: Option<NodeId>,
+}
+
+impl CustomElementRegistry {
+    /// Create the global registry of a document.
+    pub fn new(document: NodeId) -> Rc<RefCell<CustomElementRegistry>> {
+        Rc::new(RefCell::new(CustomElementRegistry {
+            definitions: Vec::new(),
+            scoped: false,
+            scoped_documents: Vec::new(),
+            document: Some(document),
+        }))
+    }
+
+    /// Create a scoped registry, it is only used by the elements and shadow roots it is given to.
+    pub fn new_scoped() -> Rc<RefCell<CustomElementRegistry>> {
+        Rc::new(RefCell::new(CustomElementRegistry {
+            definitions: Vec::new(),
+            scoped: true,
+            scoped_documents: Vec::new(),
+            document: None,
+        }))
+    }
+
+    #[inline]
+    pub fn is_scoped(&self) -> bool {
+        self.scoped
+    }
+
+    /// Add the document to the scoped document set, this does nothing for global registries.
+    pub fn add_scoped_document(&mut self, document: NodeId) {
+        if self.scoped && !self.scoped_documents.contains(&document) {
+            self.scoped_documents.push(document);
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/custom-elements.html#dom-customelementregistry-define
+    pub fn define(registry: &Rc<RefCell<CustomElementRegistry>>, name: &str, definition: ElementDefinition) -> Result<(), DomException> {
+        let mut local_name = name;
+
+        {
+            let this = registry.borrow();
+
+            if !is_valid_custom_element_name(name) {
+                return Err(DomException::SyntaxError);
+            }
+
+            if this.definitions.iter().any(|existing| existing.name == name || Rc::ptr_eq(&existing.constructor, &definition.constructor)) {
+                return Err(DomException::NotSupportedError);
+            }
+
+            if let Some(extends) = definition.extends.as_deref() {
+                if is_valid_custom_element_name(extends) || !HTML_ELEMENTS.contains(&extends) {
+                    return Err(DomException::NotSupportedError);
+                }
+
+                local_name = extends;
+            }
+        }
+
+        let definition = Rc::new(CustomElementDefinition {
+            name: Atom::from(name),
+            local_name: Atom::from(local_name),
+            constructor: definition.constructor,
+            observed_attributes: definition.observed_attributes.iter().map(Atom::from).collect(),
+            disable_internals: definition.disabled_features.iter().any(|feature| feature == "internals"),
+            disable_shadow: definition.disabled_features.iter().any(|feature| feature == "shadow"),
+            form_associated: definition.form_associated,
+            callbacks: definition.callbacks,
+        });
+
+        let documents = {
+            let mut this = registry.borrow_mut();
+
+            this.definitions.push(definition.clone());
+
+            // NOTE: a registry can outlive the doms of its documents, those documents are pruned.
+            this.scoped_documents.retain(|document| arena::contains(*document));
+
+            match this.scoped {
+                true => this.scoped_documents.clone(),
+                false => this.document.into_iter().filter(|document| arena::contains(*document)).collect(),
+            }
+        };
+
+        let registry = NullOrCustomElementRegistry::new(registry.clone());
+
+        // NOTE: the documents of a scoped registry can belong to different doms, each has its own
+        // reactions stack.
+        for document in documents {
+            let candidates = TreeIterator::shadow_including(document)
+                .filter(|node| {
+                    match &arena::get(node).borrow().node_type {
+                        NodeType::Element(element) => {
+                            element.custom_element_registry == registry
+                                && element.name.is_html(definition.local_name.clone())
+                                && (!definition.is_customized_built_in() || element.is_value.as_deref() == Some(name))
+                        },
+                        _ => false,
+                    }
+                })
+                .collect::<Vec<NodeId>>();
+
+            ce_reactions(document, || {
+                for candidate in candidates {
+                    enqueue_upgrade_reaction(candidate, definition.clone());
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    // https://html.spec.whatwg.org/multipage/custom-elements.html#dom-customelementregistry-get
+    /// Return the definition with the name, if it exists.
+    pub fn get(&self, name: &str) -> Option<Rc<CustomElementDefinition>> {
+        self.definitions.iter()
+            .find(|definition| definition.name == name)
+            .cloned()
+    }
+
+    // https://html.spec.whatwg.org/multipage/custom-elements.html#look-up-a-custom-element-definition
+    fn look_up(&self, name: QualifiedName, is: Option<&str>) -> Option<Rc<CustomElementDefinition>> {
+        if !name.is_namespace(Namespace::Html) {
+            return None;
+        }
+
+        self.definitions.iter()
+            .find(|definition| definition.name == name.local_name && definition.local_name == name.local_name)
+            .or_else(|| {
+                self.definitions.iter()
+                    .find(|definition| is.is_some_and(|is| definition.name == is) && definition.local_name == name.local_name)
+            })
+            .cloned()
+    }
+}
+
+/// A custom element registry that may be null.
+#[derive(Clone, Default)]
+pub struct NullOrCustomElementRegistry {
+    registry: Option<Rc<RefCell<CustomElementRegistry>>>,
+}
+
+impl PartialEq for NullOrCustomElementRegistry {
+    fn eq(&self, other: &NullOrCustomElementRegistry) -> bool {
+        match (&self.registry, &other.registry) {
+            (Some(registry), Some(other)) => Rc::ptr_eq(registry, other),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl NullOrCustomElementRegistry {
+    pub fn new(registry: Rc<RefCell<CustomElementRegistry>>) -> NullOrCustomElementRegistry {
+        NullOrCustomElementRegistry {
+            registry: Some(registry),
+        }
+    }
+
+    /// Visit the documents of the registry.
+    pub(crate) fn for_each_reference(&self, f: &mut impl FnMut(&mut NodeId)) {
+        if let Some(registry) = &self.registry {
+            let registry = &mut *registry.borrow_mut();
+
+            registry.scoped_documents.iter_mut().chain(registry.document.iter_mut()).for_each(f);
+        }
+    }
+
+    #[inline]
+    pub fn registry(&self) -> Option<&Rc<RefCell<CustomElementRegistry>>> {
+        self.registry.as_ref()
+    }
+
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        self.registry.is_none()
+    }
+
+    pub fn is_global_custom_element_registry(&self) -> bool {
+        self.registry.as_ref()
+            .map(|registry| !registry.borrow().scoped).unwrap_or_default()
+    }
+
+    pub fn effective_global_custom_element_registry(&self) -> NullOrCustomElementRegistry {
+        NullOrCustomElementRegistry {
+            registry: self.registry.clone().filter(|registry| !registry.borrow().scoped)
+        }
+    }
+
+    /// Look up a custom element definition, a null registry never has any definitions.
+    pub fn look_up(&self, name: QualifiedName, is: Option<&str>) -> Option<Rc<CustomElementDefinition>> {
+        self.registry.as_ref().and_then(|registry| registry.borrow().look_up(name, is))
+    }
+}
+
+
+/// Run the closure with the reactions stack of the document of the node.
+fn with_reactions<R>(node: NodeId, f: impl FnOnce(&mut CustomElementReactionsStack) -> R) -> R {
+    let document = arena::get(node).borrow().node_document;
+
+    arena::with_mut(document, |document| f(&mut document.downcast_mut::<Document>().custom_element_reactions))
+}
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#cereactions
+/// Run the closure with a new element queue on the reactions stack of the document of the node, the
+/// reactions enqueued while it runs are invoked before returning.
+pub fn ce_reactions<R>(node: NodeId, f: impl FnOnce() -> R) -> R {
+    with_reactions(node, |reactions| reactions.element_queues.push(Vec::new()));
+
+    let result = f();
+
+    let queue = with_reactions(node, |reactions| reactions.element_queues.pop().unwrap_or_default());
+
+    invoke_custom_element_reactions(queue);
+
+    result
+}
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#enqueue-an-element-on-the-appropriate-element-queue
+fn enqueue_element(element: NodeId) {
+    with_reactions(element, |reactions| {
+        match reactions.element_queues.last_mut() {
+            Some(queue) => queue.push(element),
+            None => {
+                reactions.backup_element_queue.push(element);
+
+                reactions.processing_backup_element_queue = true;
+            },
+        }
+    });
+}
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#enqueue-a-custom-element-callback-reaction
+/// Enqueue a lifecycle callback of the element definition, nothing is enqueued if the definition
+/// has no such callback or the attribute is not observed.
+pub fn enqueue_callback_reaction(element: NodeId, reaction: LifecycleReaction) {
+    let Some(definition) = arena::get(element).borrow().downcast_ref::<Element>().custom_element_definition.clone() else {
+        return;
+    };
+
+    let callback: Box<dyn FnOnce()> = match reaction {
+        LifecycleReaction::Connected => match definition.callbacks.connected.clone() {
+            Some(callback) => Box::new(move || callback(element)),
+            None => return,
+        },
+        LifecycleReaction::Disconnected => match definition.callbacks.disconnected.clone() {
+            Some(callback) => Box::new(move || callback(element)),
+            None => return,
+        },
+        LifecycleReaction::Adopted { old_document, new_document } => match definition.callbacks.adopted.clone() {
+            Some(callback) => Box::new(move || callback(element, old_document, new_document)),
+            None => return,
+        },
+        LifecycleReaction::AttributeChanged { local_name, old_value, value, namespace } => {
+            match definition.callbacks.attribute_changed.clone() {
+                Some(callback) if definition.observed_attributes.contains(&local_name) => {
+                    Box::new(move || callback(element, local_name, old_value, value, namespace))
+                },
+                _ => return,
+            }
+        },
+    };
+
+    arena::with_mut(element, |node| {
+        node.downcast_mut::<Element>().custom_element_reaction_queue.push_back(CustomElementReaction::Callback(callback));
+    });
+
+    enqueue_element(element);
+}
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#enqueue-a-custom-element-upgrade-reaction
+pub fn enqueue_upgrade_reaction(element: NodeId, definition: Rc<CustomElementDefinition>) {
+    arena::with_mut(element, |node| {
+        node.downcast_mut::<Element>().custom_element_reaction_queue.push_back(CustomElementReaction::Upgrade(definition));
+    });
+
+    enqueue_element(element);
+}
+
+// https://html.spec.whatwg.org/multipage/custom-elements.html#invoke-custom-element-reactions
+// NOTE: the elements of a dropped dom are skipped, their reactions were dropped with them.
+fn invoke_custom_element_reactions(queue: Vec<NodeId>) {
+    for element in queue.into_iter().filter(|element| arena::contains(*element)) {
+        loop {
+            let reaction = arena::with_mut(element, |node| {
+                node.downcast_mut::<Element>().custom_element_reaction_queue.pop_front()
+            });
+
+            match reaction {
+                // NOTE: a failed upgrade leaves the element in the failed state, which is how the
+                // exception is reported.
+                Some(CustomElementReaction::Upgrade(definition)) => {
+                    let _ = Element::upgrade(element, definition);
+                },
+                Some(CustomElementReaction::Callback(callback)) => callback(),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Invoke the reactions in the backup element queue of the document, reactions end up there when
+/// they are enqueued outside of [`ce_reactions`].
+pub fn process_backup_element_queue(document: NodeId) {
+    loop {
+        let queue = with_reactions(document, |reactions| {
+            match reactions.processing_backup_element_queue {
+                true => std::mem::take(&mut reactions.backup_element_queue),
+                false => Vec::new(),
+            }
+        });
+
+        if queue.is_empty() {
+            with_reactions(document, |reactions| reactions.processing_backup_element_queue = false);
+
+            break;
+        }
+
+        invoke_custom_element_reactions(queue);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_html;
+    use crate::dom::{Dom, ElementCreationOptions};
+    use crate::dom::node::element::CustomElementState;
+    use crate::dom::testing::{element, create_element};
+
+    fn state(element: NodeId) -> CustomElementState {
+        arena::get(element).borrow().downcast_ref::<Element>().custom_element_state
+    }
+
+    /// A definition whose constructor records the elements it was called with.
+    fn recording() -> (ElementDefinition, Rc<RefCell<Vec<NodeId>>>) {
+        let constructed = Rc::new(RefCell::new(Vec::new()));
+
+        let definition = ElementDefinition::new({
+            let constructed = constructed.clone();
+
+            move |element| {
+                constructed.borrow_mut().push(element);
+
+                Ok(())
+            }
+        });
+
+        (definition, constructed)
+    }
+
+    #[test]
+    fn custom_element_names() {
+        for name in ["my-element", "a-", "x-\u{e9}", "math-\u{10000}"] {
+            assert!(is_valid_custom_element_name(name), "{name:?} is valid");
+        }
+
+        for name in ["element", "My-element", "-a", "1-a", "font-face", "my element", "my-Element"] {
+            assert!(!is_valid_custom_element_name(name), "{name:?} is not valid");
+        }
+    }
+
+    #[test]
+    fn defining() {
+        let dom = Dom::new();
+        let registry = dom.custom_element_registry();
+
+        let (definition, _) = recording();
+
+        assert_eq!(CustomElementRegistry::define(&registry, "element", definition.clone()), Err(DomException::SyntaxError));
+        assert_eq!(CustomElementRegistry::define(&registry, "my-element", definition.clone()), Ok(()));
+        assert_eq!(CustomElementRegistry::define(&registry, "my-other", definition.clone()), Err(DomException::NotSupportedError));
+        assert_eq!(CustomElementRegistry::define(&registry, "my-element", recording().0), Err(DomException::NotSupportedError));
+
+        let extends = |extends: &str| ElementDefinition {
+            extends: Some(extends.to_string()),
+            ..recording().0
+        };
+
+        assert_eq!(CustomElementRegistry::define(&registry, "my-a", extends("my-element")), Err(DomException::NotSupportedError));
+        assert_eq!(CustomElementRegistry::define(&registry, "my-b", extends("unknown")), Err(DomException::NotSupportedError));
+        assert_eq!(CustomElementRegistry::define(&registry, "my-button", extends("button")), Ok(()));
+
+        let definition = registry.borrow().get("my-button").expect("defined");
+
+        assert!(definition.is_customized_built_in());
+        assert_eq!(definition.local_name, "button");
+        assert!(registry.borrow().get("my-b").is_none());
+    }
+
+    #[test]
+    fn upgrading_existing_elements() {
+        let dom = parse_html("<my-element id=a></my-element><button id=b is=my-button></button><my-other id=c></my-other>");
+
+        let (a, b, c) = (element(&dom, "a"), element(&dom, "b"), element(&dom, "c"));
+
+        assert_eq!(state(a), CustomElementState::Undefined);
+        assert!(!dom.query_selector_all(":defined").expect("valid").contains(&a));
+
+        let registry = dom.custom_element_registry();
+
+        let (definition, constructed) = recording();
+
+        CustomElementRegistry::define(&registry, "my-element", definition).expect("valid definition");
+
+        let (definition, _) = recording();
+
+        let button = ElementDefinition {
+            extends: Some(String::from("button")),
+            ..definition
+        };
+
+        CustomElementRegistry::define(&registry, "my-button", button).expect("valid definition");
+
+        assert_eq!(constructed.take(), vec![a]);
+        assert_eq!(state(a), CustomElementState::Custom);
+        assert_eq!(state(b), CustomElementState::Custom);
+        assert_eq!(state(c), CustomElementState::Undefined);
+        assert!(dom.query_selector_all(":defined").expect("valid").contains(&a));
+    }
+
+    #[test]
+    fn defining_after_a_scoped_dom_is_dropped() {
+        let registry = CustomElementRegistry::new_scoped();
+
+        let options = || ElementCreationOptions {
+            custom_element_registry: Some(registry.clone()),
+            ..ElementCreationOptions::default()
+        };
+
+        let mut dom = Dom::new();
+
+        let element = dom.create_element("my-element", options()).expect("valid name");
+
+        dom.append_child(dom.document(), element).expect("document element");
+
+        {
+            let mut dropped = Dom::new();
+
+            let element = dropped.create_element("my-element", options()).expect("valid name");
+
+            dropped.append_child(dropped.document(), element).expect("document element");
+        }
+
+        let (definition, constructed) = recording();
+
+        CustomElementRegistry::define(&registry, "my-element", definition).expect("valid definition");
+
+        assert_eq!(constructed.take(), vec![element]);
+        assert_eq!(state(element), CustomElementState::Custom);
+        assert_eq!(registry.borrow().scoped_documents, vec![dom.document()]);
+    }
+
+    #[test]
+    fn constructing_new_elements() {
+        let mut dom = Dom::new();
+        let registry = dom.custom_element_registry();
+
+        let (definition, constructed) = recording();
+
+        CustomElementRegistry::define(&registry, "my-element", definition).expect("valid definition");
+        CustomElementRegistry::define(&registry, "my-failing", ElementDefinition::new(|_| Err(DomException::NotSupportedError))).expect("valid definition");
+
+        let element = create_element(&mut dom, "my-element");
+        let failing = create_element(&mut dom, "my-failing");
+
+        assert_eq!(constructed.take(), vec![element]);
+        assert_eq!(state(element), CustomElementState::Custom);
+        assert_eq!(state(failing), CustomElementState::Failed);
+        assert!(arena::get(failing).borrow().downcast_ref::<Element>().custom_element_definition.is_none());
+    }
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// Define my-element with callbacks that log the reactions, the title attribute is observed.
+    fn define_logging(dom: &Dom) -> Log {
+        let log = Log::default();
+
+        let callback = |name: &'static str| -> LifecycleCallback {
+            let log = log.clone();
+
+            Rc::new(move |_| log.borrow_mut().push(name.to_string()))
+        };
+
+        let attribute_changed: AttributeChangedCallback = Rc::new({
+            let log = log.clone();
+
+            move |_, name, old_value, value, _| log.borrow_mut().push(format!("{name}: {old_value:?} -> {value:?}"))
+        });
+
+        let adopted: AdoptedCallback = Rc::new({
+            let log = log.clone();
+
+            move |_, old_document, new_document| log.borrow_mut().push(format!("adopted: {old_document:?} -> {new_document:?}"))
+        });
+
+        let definition = ElementDefinition {
+            observed_attributes: vec![String::from("title")],
+            callbacks: LifecycleCallbacks {
+                connected: Some(callback("connected")),
+                disconnected: Some(callback("disconnected")),
+                adopted: Some(adopted),
+                attribute_changed: Some(attribute_changed),
+            },
+            ..ElementDefinition::new(|_| Ok(()))
+        };
+
+        CustomElementRegistry::define(&dom.custom_element_registry(), "my-element", definition).expect("valid definition");
+
+        log
+    }
+
+    #[test]
+    fn lifecycle_reactions() {
+        let mut dom = parse_html("<div id=a></div>");
+
+        let a = element(&dom, "a");
+        let log = define_logging(&dom);
+
+        let element = create_element(&mut dom, "my-element");
+
+        Element::set_attribute(element, "title", "x").expect("valid");
+        Element::set_attribute(element, "lang", "en").expect("valid");
+
+        dom.append_child(a, element).expect("append");
+
+        Element::set_attribute(element, "title", "y").expect("valid");
+        Element::remove_attribute(element, "title");
+
+        dom.remove(a);
+
+        assert_eq!(log.take(), vec![
+            "title: None -> Some(\"x\")",
+            "connected",
+            "title: Some(\"x\") -> Some(\"y\")",
+            "title: Some(\"y\") -> None",
+            "disconnected",
+        ]);
+    }
+
+    #[test]
+    fn adopted_reactions() {
+        let mut dom = Dom::new();
+        let mut other = parse_html("<div id=a></div>");
+
+        let a = element(&other, "a");
+        let log = define_logging(&dom);
+
+        let element = create_element(&mut dom, "my-element");
+        let adopted = other.append_child(a, element).expect("append");
+
+        assert_eq!(log.take(), vec![
+            format!("adopted: {:?} -> {:?}", dom.document(), other.document()),
+            String::from("connected"),
+        ]);
+
+        other.remove(adopted);
+
+        assert_eq!(log.take(), vec!["disconnected"]);
+
+        let element = dom.adopt_node(adopted).expect("adopt");
+
+        assert_eq!(log.take(), vec![format!("adopted: {:?} -> {:?}", other.document(), dom.document())]);
+        assert_eq!(state(element), CustomElementState::Custom);
+    }
+
+    #[test]
+    fn upgrade_reactions() {
+        let dom = parse_html("<my-element title=x lang=en></my-element>");
+
+        let log = define_logging(&dom);
+
+        assert_eq!(log.take(), vec!["title: None -> Some(\"x\")", "connected"]);
+    }
+
+    #[test]
+    fn reactions_are_invoked_when_their_scope_ends() {
+        let mut dom = Dom::new();
+
+        let log = define_logging(&dom);
+        let element = create_element(&mut dom, "my-element");
+        let other = create_element(&mut dom, "my-element");
+
+        ce_reactions(element, || {
+            enqueue_callback_reaction(element, LifecycleReaction::Connected);
+
+            Element::set_attribute(other, "title", "x").expect("valid");
+
+            assert_eq!(log.take(), vec!["title: None -> Some(\"x\")"]);
+        });
+
+        assert_eq!(log.take(), vec!["connected"]);
+
+        enqueue_callback_reaction(element, LifecycleReaction::Connected);
+
+        assert!(log.borrow().is_empty());
+
+        dom.perform_microtask_checkpoint();
+
+        assert_eq!(log.take(), vec!["connected"]);
+    }
+
+    #[test]
+    fn each_dom_processes_its_own_backup_queue() {
+        let mut first = Dom::new();
+        let mut second = Dom::new();
+
+        let log = define_logging(&first);
+        let element = create_element(&mut first, "my-element");
+
+        enqueue_callback_reaction(element, LifecycleReaction::Connected);
+
+        second.perform_microtask_checkpoint();
+
+        assert!(log.borrow().is_empty());
+
+        first.perform_microtask_checkpoint();
+
+        assert_eq!(log.take(), vec!["connected"]);
+    }
+}
@@ -0,0 +1,781 @@
+use crate::dom::abort::AbortSignal;
+use crate::dom::arena::{self, NodeId};
+use crate::dom::exception::DomException;
+use crate::dom::node::{Node, NodeType};
+use crate::dom::node::document::Document;
+use crate::dom::node::shadow_root::ShadowRootMode;
+use crate::dom::node::slot;
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+
+/// The callback of an event listener, called with the event being dispatched.
+pub type EventListenerCallback = Rc<dyn Fn(&mut Event)>;
+
+// https://dom.spec.whatwg.org/#dom-event-eventphase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventPhase {
+    None,
+    Capturing,
+    AtTarget,
+    Bubbling,
+}
+
+// https://dom.spec.whatwg.org/#dictdef-eventinit
+#[derive(Debug, Clone, Default)]
+pub struct EventInit {
+    pub bubbles: bool,
+    pub cancelable: bool,
+    pub composed: bool,
+
+    /// The related target of focus and mouse events, there are no separate interfaces for them.
+    pub related_target: Option<NodeId>,
+}
+
+// https://dom.spec.whatwg.org/#concept-event-path
+#[derive(Debug, Clone)]
+struct PathItem {
+    invocation_target: NodeId,
+    shadow_adjusted_target: Option<NodeId>,
+    related_target: Option<NodeId>,
+    root_of_closed_tree: bool,
+    slot_in_closed_tree: bool,
+}
+
+// https://dom.spec.whatwg.org/#interface-event
+pub struct Event {
+    event_type: String,
+    target: Option<NodeId>,
+    related_target: Option<NodeId>,
+    current_target: Option<NodeId>,
+    event_phase: EventPhase,
+    path: Vec<PathItem>,
+    bubbles: bool,
+    cancelable: bool,
+    composed: bool,
+    is_trusted: bool,
+    stop_propagation: bool,
+    stop_immediate_propagation: bool,
+    canceled: bool,
+    in_passive_listener: bool,
+    dispatch: bool,
+}
+
+impl Event {
+    // https://dom.spec.whatwg.org/#dom-event-event
+    pub fn new(event_type: &str, init: EventInit) -> Event {
+        Event {
+            event_type: event_type.to_string(),
+            target: None,
+            related_target: init.related_target,
+            current_target: None,
+            event_phase: EventPhase::None,
+            path: Vec::new(),
+            bubbles: init.bubbles,
+            cancelable: init.cancelable,
+            composed: init.composed,
+            is_trusted: false,
+            stop_propagation: false,
+            stop_immediate_propagation: false,
+            canceled: false,
+            in_passive_listener: false,
+            dispatch: false,
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-type
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-target
+    pub fn target(&self) -> Option<NodeId> {
+        self.target
+    }
+
+    pub fn related_target(&self) -> Option<NodeId> {
+        self.related_target
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-currenttarget
+    pub fn current_target(&self) -> Option<NodeId> {
+        self.current_target
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-eventphase
+    pub fn event_phase(&self) -> EventPhase {
+        self.event_phase
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-bubbles
+    pub fn bubbles(&self) -> bool {
+        self.bubbles
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-cancelable
+    pub fn cancelable(&self) -> bool {
+        self.cancelable
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-composed
+    pub fn composed(&self) -> bool {
+        self.composed
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-istrusted
+    /// Check if the event was dispatched by the dom rather than through [`EventTarget::dispatch_event`].
+    pub fn is_trusted(&self) -> bool {
+        self.is_trusted
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-defaultprevented
+    pub fn default_prevented(&self) -> bool {
+        self.canceled
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-stoppropagation
+    /// Stop the event from reaching any other target, the listeners of the current target still run.
+    pub fn stop_propagation(&mut self) {
+        self.stop_propagation = true;
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-stopimmediatepropagation
+    /// Stop the event from reaching any other listener.
+    pub fn stop_immediate_propagation(&mut self) {
+        self.stop_propagation = true;
+        self.stop_immediate_propagation = true;
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-preventdefault
+    /// Cancel the event, this does nothing for events that are not cancelable or in passive listeners.
+    pub fn prevent_default(&mut self) {
+        if self.cancelable && !self.in_passive_listener {
+            self.canceled = true;
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-event-composedpath
+    /// The invocation targets of the path of the event, without the targets in closed shadow trees
+    /// hidden from the current target.
+    pub fn composed_path(&self) -> Vec<NodeId> {
+        let Some(current_target) = self.current_target.filter(|_| !self.path.is_empty()) else {
+            return Vec::new();
+        };
+
+        let mut composed_path = vec![current_target];
+
+        let mut current_target_index = 0;
+        let mut current_target_hidden_subtree_level = 0;
+
+        for (index, item) in self.path.iter().enumerate().rev() {
+            if item.root_of_closed_tree {
+                current_target_hidden_subtree_level += 1;
+            }
+
+            if item.invocation_target == current_target {
+                current_target_index = index;
+
+                break;
+            }
+
+            if item.slot_in_closed_tree {
+                current_target_hidden_subtree_level -= 1;
+            }
+        }
+
+        let mut current_hidden_level = current_target_hidden_subtree_level;
+        let mut max_hidden_level = current_target_hidden_subtree_level;
+
+        for item in self.path[..current_target_index].iter().rev() {
+            if item.root_of_closed_tree {
+                current_hidden_level += 1;
+            }
+
+            if current_hidden_level <= max_hidden_level {
+                composed_path.insert(0, item.invocation_target);
+            }
+
+            if item.slot_in_closed_tree {
+                current_hidden_level -= 1;
+
+                max_hidden_level = max_hidden_level.min(current_hidden_level);
+            }
+        }
+
+        let mut current_hidden_level = current_target_hidden_subtree_level;
+        let mut max_hidden_level = current_target_hidden_subtree_level;
+
+        for item in self.path[current_target_index + 1..].iter() {
+            if item.slot_in_closed_tree {
+                current_hidden_level += 1;
+            }
+
+            if current_hidden_level <= max_hidden_level {
+                composed_path.push(item.invocation_target);
+            }
+
+            if item.root_of_closed_tree {
+                current_hidden_level -= 1;
+
+                max_hidden_level = max_hidden_level.min(current_hidden_level);
+            }
+        }
+
+        composed_path
+    }
+}
+
+// https://dom.spec.whatwg.org/#concept-event-listener
+pub(crate) struct EventListener {
+    event_type: String,
+    callback: EventListenerCallback,
+    capture: bool,
+    passive: bool,
+    once: bool,
+    removed: Cell<bool>,
+}
+
+// https://dom.spec.whatwg.org/#dictdef-eventlisteneroptions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EventListenerOptions {
+    pub capture: bool,
+}
+
+// https://dom.spec.whatwg.org/#dictdef-addeventlisteneroptions
+#[derive(Clone, Default)]
+pub struct AddEventListenerOptions {
+    pub capture: bool,
+    pub once: bool,
+
+    /// Set when the listener never cancels the event, the default depends on the event type and
+    /// the target when none.
+    pub passive: Option<bool>,
+
+    /// The listener is removed when the signal is aborted.
+    pub signal: Option<Rc<RefCell<AbortSignal>>>,
+}
+
+// https://dom.spec.whatwg.org/#interface-eventtarget
+/// The event target capability of nodes, listeners are closures that are called with the event.
+pub trait EventTarget {
+    fn add_event_listener(&self, event_type: &str, callback: EventListenerCallback, options: AddEventListenerOptions);
+
+    fn remove_event_listener(&self, event_type: &str, callback: &EventListenerCallback, options: EventListenerOptions);
+
+    fn dispatch_event(&self, event: &mut Event) -> Result<bool, DomException>;
+}
+
+impl EventTarget for NodeId {
+    // https://dom.spec.whatwg.org/#add-an-event-listener
+    fn add_event_listener(&self, event_type: &str, callback: EventListenerCallback, options: AddEventListenerOptions) {
+        let target = *self;
+
+        if options.signal.as_ref().is_some_and(|signal| signal.borrow().aborted()) {
+            return;
+        }
+
+        let exists = arena::get(target).borrow().event_listener_list.iter().any(|listener| {
+            listener.event_type == event_type && Rc::ptr_eq(&listener.callback, &callback) && listener.capture == options.capture
+        });
+
+        if exists {
+            return;
+        }
+
+        let listener = Rc::new(EventListener {
+            event_type: event_type.to_string(),
+            callback,
+            capture: options.capture,
+            passive: options.passive.unwrap_or_else(|| default_passive(event_type, target)),
+            once: options.once,
+            removed: Cell::new(false),
+        });
+
+        arena::with_mut(target, |node| node.event_listener_list.push(listener.clone()));
+
+        if let Some(signal) = options.signal {
+            AbortSignal::add(&signal, move || remove_listener(target, &listener));
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-eventtarget-removeeventlistener
+    fn remove_event_listener(&self, event_type: &str, callback: &EventListenerCallback, options: EventListenerOptions) {
+        let listener = arena::get(self).borrow().event_listener_list.iter()
+            .find(|listener| listener.event_type == event_type && Rc::ptr_eq(&listener.callback, callback) && listener.capture == options.capture)
+            .cloned();
+
+        if let Some(listener) = listener {
+            remove_listener(*self, &listener);
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-eventtarget-dispatchevent
+    /// Dispatch the event at the node and return false if it was canceled.
+    fn dispatch_event(&self, event: &mut Event) -> Result<bool, DomException> {
+        if event.dispatch {
+            return Err(DomException::InvalidStateError);
+        }
+
+        event.is_trusted = false;
+
+        Ok(dispatch(*self, event))
+    }
+}
+
+// https://dom.spec.whatwg.org/#default-passive-value
+fn default_passive(event_type: &str, target: NodeId) -> bool {
+    if !matches!(event_type, "touchstart" | "touchmove" | "wheel" | "mousewheel") {
+        return false;
+    }
+
+    let document = arena::get(target).borrow().node_document;
+
+    target == document || Some(target) == Document::document_element(document) || Some(target) == Document::body(document)
+}
+
+// https://dom.spec.whatwg.org/#remove-an-event-listener
+fn remove_listener(target: NodeId, listener: &Rc<EventListener>) {
+    listener.removed.set(true);
+
+    arena::with_mut(target, |node| node.event_listener_list.retain(|other| !Rc::ptr_eq(other, listener)));
+}
+
+// https://dom.spec.whatwg.org/#concept-event-fire
+/// Fire a trusted event at the target and return false if it was canceled.
+pub(crate) fn fire_event(target: NodeId, event_type: &str, init: EventInit) -> bool {
+    let mut event = Event::new(event_type, init);
+
+    event.is_trusted = true;
+
+    dispatch(target, &mut event)
+}
+
+// https://dom.spec.whatwg.org/#retarget
+/// Retarget the node against the other node, moving it out of the shadow trees the other node is not in.
+fn retarget(node: Option<NodeId>, other: NodeId) -> Option<NodeId> {
+    let mut node = node?;
+
+    loop {
+        let root = Node::root(node);
+
+        let host = match &arena::get(root).borrow().node_type {
+            NodeType::ShadowRoot(shadow_root) => shadow_root.host,
+            _ => return Some(node),
+        };
+
+        if Node::is_shadow_including_inclusive_ancestor(root, other) {
+            return Some(node);
+        }
+
+        node = host;
+    }
+}
+
+// https://dom.spec.whatwg.org/#get-the-parent
+fn get_the_parent(node: NodeId, event: &Event) -> Option<NodeId> {
+    let current = arena::get(node);
+    let current = current.borrow();
+
+    match &current.node_type {
+        NodeType::ShadowRoot(shadow_root) => {
+            let first = event.path.first().map(|item| item.invocation_target);
+
+            match !event.composed && first.is_some_and(|first| Node::root(first) == node) {
+                true => None,
+                false => Some(shadow_root.host),
+            }
+        },
+        // NOTE: the parent of a document is its window, which we do not have.
+        NodeType::Document(_) => None,
+        _ => current.assigned_slot.or(current.parent),
+    }
+}
+
+// https://dom.spec.whatwg.org/#concept-event-path-append
+fn append_to_event_path(
+    event: &mut Event,
+    invocation_target: NodeId,
+    shadow_adjusted_target: Option<NodeId>,
+    related_target: Option<NodeId>,
+    slot_in_closed_tree: bool,
+) {
+    let root_of_closed_tree = matches!(
+        &arena::get(invocation_target).borrow().node_type,
+        NodeType::ShadowRoot(shadow_root) if shadow_root.mode == ShadowRootMode::Closed
+    );
+
+    event.path.push(PathItem {
+        invocation_target,
+        shadow_adjusted_target,
+        related_target,
+        root_of_closed_tree,
+        slot_in_closed_tree,
+    });
+}
+
+/// Check if the root of the node is a shadow root.
+fn is_in_shadow_tree(node: NodeId) -> bool {
+    matches!(arena::get(Node::root(node)).borrow().node_type, NodeType::ShadowRoot(_))
+}
+
+// https://dom.spec.whatwg.org/#concept-event-dispatch
+/// Dispatch the event through its path, capturing from the root down to the target and bubbling
+/// back up, and return false if it was canceled.
+fn dispatch(target: NodeId, event: &mut Event) -> bool {
+    event.dispatch = true;
+
+    let related_target = retarget(event.related_target, target);
+
+    let mut clear_targets = false;
+
+    if Some(target) != related_target || event.related_target == Some(target) {
+        append_to_event_path(event, target, Some(target), related_target, false);
+
+        let mut target = target;
+
+        let is_assigned_slottable = |node: NodeId| slot::is_slottable(node) && arena::get(node).borrow().assigned_slot.is_some();
+
+        let mut slottable = is_assigned_slottable(target).then_some(target);
+        let mut slot_in_closed_tree = false;
+        let mut parent = get_the_parent(target, event);
+
+        while let Some(current) = parent {
+            if slottable.is_some() {
+                slottable = None;
+
+                let closed = matches!(
+                    &arena::get(Node::root(current)).borrow().node_type,
+                    NodeType::ShadowRoot(shadow_root) if shadow_root.mode == ShadowRootMode::Closed
+                );
+
+                if closed {
+                    slot_in_closed_tree = true;
+                }
+            }
+
+            if is_assigned_slottable(current) {
+                slottable = Some(current);
+            }
+
+            let related_target = retarget(event.related_target, current);
+
+            if Node::is_shadow_including_inclusive_ancestor(Node::root(target), current) {
+                append_to_event_path(event, current, None, related_target, slot_in_closed_tree);
+            } else if Some(current) == related_target {
+                parent = None;
+            } else {
+                target = current;
+
+                append_to_event_path(event, current, Some(target), related_target, slot_in_closed_tree);
+            }
+
+            if parent.is_some() {
+                parent = get_the_parent(current, event);
+            }
+
+            slot_in_closed_tree = false;
+        }
+
+        let clear_targets_item = event.path.iter().rev()
+            .find(|item| item.shadow_adjusted_target.is_some())
+            .expect("the target is in the path");
+
+        clear_targets = clear_targets_item.shadow_adjusted_target.into_iter()
+            .chain(clear_targets_item.related_target)
+            .any(is_in_shadow_tree);
+
+        for index in (0..event.path.len()).rev() {
+            event.event_phase = match event.path[index].shadow_adjusted_target {
+                Some(_) => EventPhase::AtTarget,
+                None => EventPhase::Capturing,
+            };
+
+            invoke(event, index, EventPhase::Capturing);
+        }
+
+        for index in 0..event.path.len() {
+            if event.path[index].shadow_adjusted_target.is_some() {
+                event.event_phase = EventPhase::AtTarget;
+            } else {
+                if !event.bubbles {
+                    continue;
+                }
+
+                event.event_phase = EventPhase::Bubbling;
+            }
+
+            invoke(event, index, EventPhase::Bubbling);
+        }
+    }
+
+    event.event_phase = EventPhase::None;
+    event.current_target = None;
+    event.path.clear();
+
+    event.dispatch = false;
+    event.stop_propagation = false;
+    event.stop_immediate_propagation = false;
+
+    if clear_targets {
+        event.target = None;
+        event.related_target = None;
+    }
+
+    !event.canceled
+}
+
+// https://dom.spec.whatwg.org/#concept-event-listener-invoke
+// NOTE: the legacy prefixed event types are not supported, so there is no need to know whether a
+// listener was found.
+fn invoke(event: &mut Event, index: usize, phase: EventPhase) {
+    event.target = event.path[..=index].iter().rev().find_map(|item| item.shadow_adjusted_target);
+    event.related_target = event.path[index].related_target;
+
+    if event.stop_propagation {
+        return;
+    }
+
+    let current_target = event.path[index].invocation_target;
+
+    event.current_target = Some(current_target);
+
+    let listeners = arena::get(current_target).borrow().event_listener_list.clone();
+
+    inner_invoke(event, current_target, &listeners, phase);
+}
+
+// https://dom.spec.whatwg.org/#concept-event-listener-inner-invoke
+fn inner_invoke(event: &mut Event, current_target: NodeId, listeners: &[Rc<EventListener>], phase: EventPhase) {
+    for listener in listeners {
+        if listener.removed.get() || listener.event_type != event.event_type {
+            continue;
+        }
+
+        if (phase == EventPhase::Capturing && !listener.capture) || (phase == EventPhase::Bubbling && listener.capture) {
+            continue;
+        }
+
+        if listener.once {
+            remove_listener(current_target, listener);
+        }
+
+        event.in_passive_listener = listener.passive;
+
+        (listener.callback)(event);
+
+        event.in_passive_listener = false;
+
+        if event.stop_immediate_propagation {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_html;
+    use crate::dom::{Dom, ShadowRootInit};
+    use crate::dom::testing::{element, create_element};
+    use crate::dom::abort::AbortController;
+    use crate::dom::node::element::Element;
+
+    type Log = Rc<RefCell<Vec<(NodeId, EventPhase)>>>;
+
+    /// A listener which logs the current target and phase of the events it is called with.
+    fn logger(log: &Log) -> EventListenerCallback {
+        let log = log.clone();
+
+        Rc::new(move |event: &mut Event| log.borrow_mut().push((event.current_target().expect("dispatching"), event.event_phase())))
+    }
+
+    fn capture() -> AddEventListenerOptions {
+        AddEventListenerOptions {
+            capture: true,
+            ..AddEventListenerOptions::default()
+        }
+    }
+
+    #[test]
+    fn capture_and_bubble() {
+        let dom = parse_html("<div id=a><p id=b></p></div>");
+
+        let document = dom.document();
+        let (a, b) = (element(&dom, "a"), element(&dom, "b"));
+
+        let log = Log::default();
+
+        for target in [document, a, b] {
+            target.add_event_listener("x", logger(&log), AddEventListenerOptions::default());
+            target.add_event_listener("x", logger(&log), capture());
+        }
+
+        let mut event = Event::new("x", EventInit { bubbles: true, ..EventInit::default() });
+
+        assert_eq!(b.dispatch_event(&mut event), Ok(true));
+        assert_eq!(event.target(), Some(b));
+        assert_eq!(event.event_phase(), EventPhase::None);
+        assert!(!event.is_trusted());
+
+        assert_eq!(log.take(), vec![
+            (document, EventPhase::Capturing),
+            (a, EventPhase::Capturing),
+            (b, EventPhase::AtTarget),
+            (b, EventPhase::AtTarget),
+            (a, EventPhase::Bubbling),
+            (document, EventPhase::Bubbling),
+        ]);
+
+        assert_eq!(b.dispatch_event(&mut Event::new("x", EventInit::default())), Ok(true));
+
+        assert_eq!(log.take(), vec![
+            (document, EventPhase::Capturing),
+            (a, EventPhase::Capturing),
+            (b, EventPhase::AtTarget),
+            (b, EventPhase::AtTarget),
+        ]);
+    }
+
+    #[test]
+    fn stopping_propagation() {
+        let dom = parse_html("<div id=a><p id=b></p></div>");
+
+        let (a, b) = (element(&dom, "a"), element(&dom, "b"));
+
+        let log = Log::default();
+
+        a.add_event_listener("x", Rc::new(|event: &mut Event| event.stop_propagation()), capture());
+        a.add_event_listener("x", logger(&log), capture());
+        b.add_event_listener("x", logger(&log), AddEventListenerOptions::default());
+
+        b.dispatch_event(&mut Event::new("x", EventInit::default())).expect("not dispatching");
+
+        assert_eq!(log.take(), vec![(a, EventPhase::Capturing)]);
+
+        a.add_event_listener("y", Rc::new(|event: &mut Event| event.stop_immediate_propagation()), capture());
+        a.add_event_listener("y", logger(&log), capture());
+        b.add_event_listener("y", logger(&log), AddEventListenerOptions::default());
+
+        b.dispatch_event(&mut Event::new("y", EventInit::default())).expect("not dispatching");
+
+        assert!(log.borrow().is_empty());
+    }
+
+    #[test]
+    fn canceling() {
+        let mut dom = Dom::new();
+        let target = create_element(&mut dom, "div");
+
+        target.add_event_listener("x", Rc::new(|event: &mut Event| event.prevent_default()), AddEventListenerOptions::default());
+
+        let passive = AddEventListenerOptions {
+            passive: Some(true),
+            ..AddEventListenerOptions::default()
+        };
+
+        target.add_event_listener("y", Rc::new(|event: &mut Event| event.prevent_default()), passive);
+
+        let mut event = Event::new("x", EventInit { cancelable: true, ..EventInit::default() });
+
+        assert_eq!(target.dispatch_event(&mut event), Ok(false));
+        assert!(event.default_prevented());
+
+        assert_eq!(target.dispatch_event(&mut Event::new("x", EventInit::default())), Ok(true));
+        assert_eq!(target.dispatch_event(&mut Event::new("y", EventInit { cancelable: true, ..EventInit::default() })), Ok(true));
+    }
+
+    #[test]
+    fn adding_and_removing_listeners() {
+        let mut dom = Dom::new();
+        let target = create_element(&mut dom, "div");
+
+        let log = Log::default();
+        let listener = logger(&log);
+
+        target.add_event_listener("x", listener.clone(), AddEventListenerOptions::default());
+        target.add_event_listener("x", listener.clone(), AddEventListenerOptions::default());
+
+        let once = AddEventListenerOptions {
+            once: true,
+            ..AddEventListenerOptions::default()
+        };
+
+        target.add_event_listener("x", logger(&log), once);
+
+        let controller = AbortController::new();
+
+        let with_signal = AddEventListenerOptions {
+            signal: Some(controller.signal()),
+            ..AddEventListenerOptions::default()
+        };
+
+        target.add_event_listener("x", logger(&log), with_signal);
+
+        let dispatch = || target.dispatch_event(&mut Event::new("x", EventInit::default())).expect("not dispatching");
+
+        dispatch();
+        assert_eq!(log.take().len(), 3);
+
+        dispatch();
+        assert_eq!(log.take().len(), 2);
+
+        controller.abort();
+
+        dispatch();
+        assert_eq!(log.take().len(), 1);
+
+        target.remove_event_listener("x", &listener, EventListenerOptions { capture: true });
+
+        dispatch();
+        assert_eq!(log.take().len(), 1);
+
+        target.remove_event_listener("x", &listener, EventListenerOptions::default());
+
+        dispatch();
+        assert!(log.borrow().is_empty());
+    }
+
+    #[test]
+    fn retargeting_out_of_shadow_trees() {
+        let mut dom = parse_html("<div id=host></div>");
+
+        let document = dom.document();
+        let host = element(&dom, "host");
+
+        let shadow_root = Element::attach_shadow(host, ShadowRootInit::new(ShadowRootMode::Open)).expect("valid host");
+        let inner = create_element(&mut dom, "span");
+
+        dom.append_child(shadow_root, inner).expect("append to shadow root");
+
+        let targets = Rc::new(RefCell::new(Vec::new()));
+
+        for current in [document, host, shadow_root, inner] {
+            let targets = targets.clone();
+
+            current.add_event_listener("x", Rc::new(move |event: &mut Event| targets.borrow_mut().push(event.target())), AddEventListenerOptions::default());
+        }
+
+        let mut event = Event::new("x", EventInit { bubbles: true, composed: true, ..EventInit::default() });
+
+        inner.dispatch_event(&mut event).expect("not dispatching");
+
+        assert_eq!(targets.take(), vec![Some(inner), Some(inner), Some(host), Some(host)]);
+        assert_eq!(event.target(), Some(host));
+
+        let path = Rc::new(RefCell::new(Vec::new()));
+
+        inner.add_event_listener("y", Rc::new({
+            let path = path.clone();
+
+            move |event: &mut Event| *path.borrow_mut() = event.composed_path()
+        }), AddEventListenerOptions::default());
+
+        let mut event = Event::new("y", EventInit { bubbles: true, ..EventInit::default() });
+
+        inner.dispatch_event(&mut event).expect("not dispatching");
+
+        assert_eq!(path.take(), vec![inner, shadow_root]);
+        assert_eq!(event.target(), None);
+    }
+}
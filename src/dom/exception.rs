@@ -0,0 +1,71 @@
+use crate::selectors::SelectorParseError;
+
+use std::fmt;
+
+
+// https://webidl.spec.whatwg.org/#idl-DOMException-error-names
+/// An exception thrown by a dom operation, the variants are named after the spec error names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomException {
+    /// The index is not in the allowed range.
+    IndexSizeError,
+
+    /// The operation would yield an incorrect node tree.
+    HierarchyRequestError,
+
+    /// The string contains invalid characters.
+    InvalidCharacterError,
+
+    /// The string did not match the expected pattern.
+    SyntaxError,
+
+    /// The operation is not supported.
+    NotSupportedError,
+
+    /// The object is in the wrong document.
+    WrongDocumentError,
+
+    /// The object can not be found here.
+    NotFoundError,
+
+    /// The object is in an invalid state.
+    InvalidStateError,
+
+    /// The operation is not allowed by namespaces in xml.
+    NamespaceError,
+
+    /// The arguments are not valid, this is a simple TypeError rather than a DOMException in the spec.
+    TypeError,
+}
+
+impl DomException {
+    /// The error name as used by the spec.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DomException::IndexSizeError => "IndexSizeError",
+            DomException::HierarchyRequestError => "HierarchyRequestError",
+            DomException::InvalidCharacterError => "InvalidCharacterError",
+            DomException::SyntaxError => "SyntaxError",
+            DomException::NotSupportedError => "NotSupportedError",
+            DomException::WrongDocumentError => "WrongDocumentError",
+            DomException::NotFoundError => "NotFoundError",
+            DomException::InvalidStateError => "InvalidStateError",
+            DomException::NamespaceError => "NamespaceError",
+            DomException::TypeError => "TypeError",
+        }
+    }
+}
+
+impl fmt::Display for DomException {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::error::Error for DomException {}
+
+impl From<SelectorParseError> for DomException {
+    fn from(_: SelectorParseError) -> DomException {
+        DomException::SyntaxError
+    }
+}
@@ -1,6 +1,8 @@
-use crate::dom::node::Node;
+use crate::dom::node::{Node, NodeType};
 use crate::dom::arena::{self, NodeId};
 
+use std::iter::FusedIterator;
+
 
 /// A NodeIterator is an iterator over nodes, a NodeIterator is cheaply cloned.
 #[derive(Clone)]
@@ -22,30 +24,323 @@ impl Iterator for NodeIterator {
     type Item = NodeId;
 
     fn next(&mut self) -> Option<NodeId> {
-        match &self.prev {
-            Some(prev) => {
-                let next = (self.f)(&arena::get(prev));
+        let current = self.prev?;
+
+        self.prev = (self.f)(&arena::get(current).borrow());
+
+        Some(current)
+    }
+}
+
+/// Step from a node to the next node of an axis, given the node at the other end of the iterator.
+type Step = fn(NodeId, NodeId) -> Option<NodeId>;
+
+/// An AxisIterator is a double-ended iterator over the nodes between two ends of an axis, an
+/// AxisIterator is cheaply cloned.
+///
+/// The iterator ends when both ends meet, or when a step runs out of nodes because the tree was
+/// mutated while iterating.
+#[derive(Clone)]
+pub struct AxisIterator {
+    front: Option<NodeId>,
+    back: Option<NodeId>,
+    step: Step,
+    step_back: Step,
+}
+
+impl AxisIterator {
+    fn new(front: Option<NodeId>, back: Option<NodeId>, step: Step, step_back: Step) -> AxisIterator {
+        AxisIterator {
+            front: front.filter(|_| back.is_some()),
+            back: back.filter(|_| front.is_some()),
+            step,
+            step_back,
+        }
+    }
+
+    pub(crate) fn children(node: &Node) -> AxisIterator {
+        AxisIterator::new(node.first_child, node.last_child, next_sibling, previous_sibling)
+    }
+
+    pub(crate) fn inclusive_ancestors(node: NodeId) -> AxisIterator {
+        AxisIterator::new(Some(node), Some(Node::root(node)), parent, child_towards)
+    }
+
+    pub(crate) fn following_siblings(node: NodeId) -> AxisIterator {
+        let current = arena::get(node);
+        let current = current.borrow();
+
+        let last_sibling = current.parent.and_then(|parent| arena::get(parent).borrow().last_child);
+
+        AxisIterator::new(current.next_sibling, last_sibling, next_sibling, previous_sibling)
+    }
+
+    pub(crate) fn preceding_siblings(node: NodeId) -> AxisIterator {
+        let current = arena::get(node);
+        let current = current.borrow();
+
+        let first_sibling = current.parent.and_then(|parent| arena::get(parent).borrow().first_child);
+
+        AxisIterator::new(current.previous_sibling, first_sibling, previous_sibling, next_sibling)
+    }
+
+    pub(crate) fn following(node: NodeId) -> AxisIterator {
+        let last = last_inclusive_descendant(Node::root(node));
+
+        AxisIterator::new(following(node, last), Some(last), following, preceding)
+    }
+
+    pub(crate) fn preceding(node: NodeId) -> AxisIterator {
+        let root = Node::root(node);
+
+        AxisIterator::new(preceding(node, root), Some(root), preceding, following)
+    }
+
+    pub(crate) fn post_order_descendants(node: NodeId) -> AxisIterator {
+        let current = arena::get(node);
+        let current = current.borrow();
+
+        let first = current.first_child.map(first_inclusive_descendant);
+
+        AxisIterator::new(first, current.last_child, post_order_next, post_order_previous)
+    }
+}
+
+impl Iterator for AxisIterator {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let (front, back) = (self.front?, self.back?);
+
+        self.front = (front != back).then(|| (self.step)(front, back)).flatten();
+
+        if self.front.is_none() {
+            self.back = None;
+        }
+
+        Some(front)
+    }
+}
+
+impl DoubleEndedIterator for AxisIterator {
+    fn next_back(&mut self) -> Option<NodeId> {
+        let (front, back) = (self.front?, self.back?);
+
+        self.back = (front != back).then(|| (self.step_back)(back, front)).flatten();
+
+        if self.back.is_none() {
+            self.front = None;
+        }
+
+        Some(back)
+    }
+}
+
+impl FusedIterator for AxisIterator {}
+
+fn next_sibling(node: NodeId, _: NodeId) -> Option<NodeId> {
+    arena::get(node).borrow().next_sibling
+}
+
+fn previous_sibling(node: NodeId, _: NodeId) -> Option<NodeId> {
+    arena::get(node).borrow().previous_sibling
+}
+
+fn parent(node: NodeId, _: NodeId) -> Option<NodeId> {
+    arena::get(node).borrow().parent
+}
+
+/// Return the child of the ancestor that is an inclusive ancestor of the node.
+fn child_towards(ancestor: NodeId, node: NodeId) -> Option<NodeId> {
+    let mut node = node;
+
+    loop {
+        let parent = arena::get(node).borrow().parent?;
+
+        if parent == ancestor {
+            return Some(node);
+        }
+
+        node = parent;
+    }
+}
+
+fn first_inclusive_descendant(node: NodeId) -> NodeId {
+    let mut node = node;
+
+    while let Some(first_child) = arena::get(node).borrow().first_child {
+        node = first_child;
+    }
+
+    node
+}
+
+/// Return the last inclusive descendant of the node in tree order.
+pub(crate) fn last_inclusive_descendant(node: NodeId) -> NodeId {
+    let mut node = node;
+
+    while let Some(last_child) = arena::get(node).borrow().last_child {
+        node = last_child;
+    }
+
+    node
+}
+
+/// Return the node after the node in tree order.
+fn following(node: NodeId, _: NodeId) -> Option<NodeId> {
+    if let Some(first_child) = arena::get(node).borrow().first_child {
+        return Some(first_child);
+    }
+
+    let mut node = node;
+
+    loop {
+        let current = arena::get(node);
+        let current = current.borrow();
+
+        if let Some(next_sibling) = current.next_sibling {
+            return Some(next_sibling);
+        }
 
-                next.map(|next| self.prev.replace(next)).unwrap_or_else(|| self.prev)
-            },
-            None => None,
+        node = current.parent?;
+    }
+}
+
+/// Return the node before the node in tree order.
+fn preceding(node: NodeId, _: NodeId) -> Option<NodeId> {
+    let current = arena::get(node);
+    let current = current.borrow();
+
+    match current.previous_sibling {
+        Some(previous_sibling) => Some(last_inclusive_descendant(previous_sibling)),
+        None => current.parent,
+    }
+}
+
+fn post_order_next(node: NodeId, _: NodeId) -> Option<NodeId> {
+    let current = arena::get(node);
+    let current = current.borrow();
+
+    match current.next_sibling {
+        Some(next_sibling) => Some(first_inclusive_descendant(next_sibling)),
+        None => current.parent,
+    }
+}
+
+fn post_order_previous(node: NodeId, _: NodeId) -> Option<NodeId> {
+    if let Some(last_child) = arena::get(node).borrow().last_child {
+        return Some(last_child);
+    }
+
+    let mut node = node;
+
+    loop {
+        let current = arena::get(node);
+        let current = current.borrow();
+
+        if let Some(previous_sibling) = current.previous_sibling {
+            return Some(previous_sibling);
         }
+
+        node = current.parent?;
     }
 }
 
-/// TreeIterator is an iterator over all tree descendants of a node.
+/// Return the shadow root of the node if it is a shadow host.
+fn shadow_root(node: NodeId) -> Option<NodeId> {
+    match &arena::get(node).borrow().node_type {
+        NodeType::Element(element) => element.shadow_root,
+        _ => None,
+    }
+}
+
+/// Return the host of the node if it is a shadow root.
+fn host(node: NodeId) -> Option<NodeId> {
+    match &arena::get(node).borrow().node_type {
+        NodeType::ShadowRoot(shadow_root) => Some(shadow_root.host),
+        _ => None,
+    }
+}
+
+/// TreeIterator is an iterator over all tree descendants of a node, in tree order.
+///
+/// A shadow-including iterator visits the shadow tree of a shadow host right after the host and
+/// before its children.
 #[derive(Clone)]
 pub struct TreeIterator {
     prev: Option<NodeId>,
-    depth: usize,
+    root: Option<NodeId>,
+    shadow_including: bool,
 }
 
-// TODO: implement shadow including inclusive descendants
 impl TreeIterator {
-    pub fn new(prev: Option<NodeId>) -> TreeIterator {
+    /// Iterate over the inclusive descendants of root.
+    pub fn new(root: Option<NodeId>) -> TreeIterator {
         TreeIterator {
-            prev,
-            depth: 0,
+            prev: root,
+            root,
+            shadow_including: false,
+        }
+    }
+
+    /// Iterate over the descendants of root.
+    pub fn descendants(root: NodeId) -> TreeIterator {
+        TreeIterator {
+            prev: arena::get(root).borrow().first_child,
+            root: Some(root),
+            shadow_including: false,
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-shadow-including-inclusive-descendant
+    /// Iterate over the shadow-including inclusive descendants of root, in shadow-including tree order.
+    pub fn shadow_including(root: NodeId) -> TreeIterator {
+        TreeIterator {
+            prev: Some(root),
+            root: Some(root),
+            shadow_including: true,
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-shadow-including-descendant
+    /// Iterate over the shadow-including descendants of root, in shadow-including tree order.
+    pub fn shadow_including_descendants(root: NodeId) -> TreeIterator {
+        let mut iterator = TreeIterator::shadow_including(root);
+
+        iterator.next();
+
+        iterator
+    }
+
+    fn following(&self, node: NodeId) -> Option<NodeId> {
+        let mut node = node;
+
+        loop {
+            if Some(node) == self.root {
+                return None;
+            }
+
+            let current = arena::get(node);
+
+            let current = current.borrow();
+
+            if let Some(sibling) = current.next_sibling {
+                return Some(sibling);
+            }
+
+            node = match current.parent {
+                Some(parent) => parent,
+                None if self.shadow_including => {
+                    let host = host(node)?;
+
+                    if let Some(first_child) = arena::get(host).borrow().first_child {
+                        return Some(first_child);
+                    }
+
+                    host
+                },
+                None => return None,
+            };
         }
     }
 }
@@ -54,26 +349,105 @@ impl Iterator for TreeIterator {
     type Item = NodeId;
 
     fn next(&mut self) -> Option<NodeId> {
-        let prev = self.prev.take()?;
+        let prev = self.prev?;
 
-        if let Some(child) = arena::get(prev).first_child {
-            self.prev = Some(child);
+        let first_child = self.shadow_including
+            .then(|| shadow_root(prev))
+            .flatten()
+            .or_else(|| arena::get(prev).borrow().first_child);
 
-            self.depth += 1;
-        } else {
-            for node in NodeIterator::new(self.prev, |node| node.parent) {
-                if let Some(sibling) = arena::get(node).next_sibling && self.depth > 0 {
-                    self.prev = Some(sibling);
-
-                    break;
-                } else if self.depth > 0 {
-                    self.depth -= 1;
-                }
-            }
-        }
+        self.prev = first_child.or_else(|| self.following(prev));
 
         Some(prev)
     }
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_html;
+    use crate::dom::{arena, Dom, NodeId};
+    use crate::dom::node::Node;
+    use crate::dom::node::element::Element;
+    use crate::dom::testing::element;
+
+    /// A document with a subtree rooted at #r: `r(a(b, c(d)), e(f))`.
+    fn document() -> (Dom, NodeId) {
+        let dom = parse_html("<div id=r><p id=a><i id=b></i><i id=c><b id=d></b></i></p><p id=e><i id=f></i></p></div>");
+
+        let root = element(&dom, "r");
+
+        (dom, root)
+    }
+
+    fn ids(nodes: impl Iterator<Item = NodeId>) -> Vec<String> {
+        nodes.filter_map(Element::id).collect()
+    }
+
+    #[test]
+    fn ancestors_and_siblings() {
+        let (dom, root) = document();
+
+        let (c, d) = (element(&dom, "c"), element(&dom, "d"));
+
+        assert_eq!(ids(Node::inclusive_ancestors(d)), vec!["d", "c", "a", "r"]);
+        assert_eq!(Node::inclusive_ancestors(d).next_back(), Some(dom.document()));
+        assert_eq!(ids(Node::inclusive_ancestors(d).rev()).first().map(String::as_str), Some("r"));
+
+        assert_eq!(ids(Node::reverse_children(root)), vec!["e", "a"]);
+        assert_eq!(ids(Node::preceding_siblings(c)), vec!["b"]);
+        assert_eq!(ids(Node::following_siblings(element(&dom, "a"))), vec!["e"]);
+        assert_eq!(ids(Node::following_siblings(c)), Vec::<String>::new());
+    }
+
+    #[test]
+    fn following_and_preceding() {
+        let (dom, _) = document();
+
+        let c = element(&dom, "c");
+
+        assert_eq!(ids(Node::following(c)), vec!["d", "e", "f"]);
+        assert_eq!(ids(Node::following(c).rev()), vec!["f", "e", "d"]);
+        assert_eq!(ids(Node::preceding(c)), vec!["b", "a", "r"]);
+        assert_eq!(ids(Node::preceding(element(&dom, "f"))), vec!["e", "d", "c", "b", "a", "r"]);
+    }
+
+    #[test]
+    fn post_order() {
+        let (_dom, root) = document();
+
+        assert_eq!(ids(Node::post_order_descendants(root)), vec!["b", "d", "c", "a", "f", "e"]);
+        assert_eq!(ids(Node::post_order_descendants(root).rev()), vec!["e", "f", "a", "c", "d", "b"]);
+    }
+
+    #[test]
+    fn both_ends_meet() {
+        let (_dom, root) = document();
+
+        let mut iterator = Node::post_order_descendants(root);
+
+        assert_eq!(ids([iterator.next(), iterator.next_back(), iterator.next(), iterator.next_back()].into_iter().flatten()), vec!["b", "e", "d", "f"]);
+        assert_eq!(ids([iterator.next_back(), iterator.next()].into_iter().flatten()), vec!["a", "c"]);
+        assert_eq!(iterator.next(), None);
+        assert_eq!(iterator.next_back(), None);
+    }
+
+    #[test]
+    fn removing_the_yielded_node() {
+        let (mut dom, root) = document();
+
+        let mut visited = Vec::new();
+
+        let children = arena::get(root).borrow().children();
+
+        for child in children {
+            visited.push(child);
+
+            dom.remove(child);
+        }
+
+        assert_eq!(ids(visited.into_iter()), vec!["a", "e"]);
+        assert_eq!(arena::get(root).borrow().children().next(), None);
+    }
+}
@@ -1,30 +1,525 @@
 mod inheritance;
-mod iterators;
-mod node;
-mod arena;
+pub(crate) mod iterators;
+pub(crate) mod node;
+pub(crate) mod arena;
+mod sink;
+mod exception;
+mod collection;
+mod serializer;
+pub mod custom_elements;
+pub mod mutation_observer;
+pub mod event;
+pub mod abort;
+pub mod traversal;
 
-use arena::{Arena, NodeId};
-use node::{Node, NodeType};
-use node::document::Document;
+#[cfg(test)]
+pub(crate) mod testing;
+
+use arena::Arena;
+use node::NodeType;
+use node::document_type::DocumentType;
 use node::comment::Comment;
+use node::text::CdataSection;
+use node::processing_instruction::ProcessingInstruction;
+use custom_elements::{CustomElementRegistry, NullOrCustomElementRegistry};
+use traversal::{NodeFilter, NodeIterator, TreeWalker};
+
+use crate::parser::quirks::QuirksMode;
+use crate::parser::error::TreeBuilderError;
+use crate::tokenizer::Position;
+use crate::names::{self, QualifiedName, Atom};
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+pub use arena::{NodeId, NodeHandle};
+pub use node::{Node, GetRootNodeOptions};
+pub use exception::DomException;
+pub use collection::{HtmlCollection, NodeList};
+pub use node::character_data::CharacterData;
+pub use node::text::Text;
+pub use node::element::Element;
+pub use node::attribute::Attribute;
+pub use node::document::Document;
+pub use node::document_fragment::DocumentFragment;
+pub use node::shadow_root::{ShadowRoot, ShadowRootInit, ShadowRootMode, SlotAssignmentMode};
+pub use node::slot::AssignedNodesOptions;
+pub use serializer::GetHtmlOptions;
+
+
+// https://dom.spec.whatwg.org/#dictdef-elementcreationoptions
+#[derive(Clone, Default)]
+pub struct ElementCreationOptions {
+    pub is: Option<String>,
+
+    /// The registry of the element, the global registry of the document is used when none.
+    pub custom_element_registry: Option<Rc<RefCell<CustomElementRegistry>>>,
+}
 
 
 pub struct Dom {
     document: NodeId,
     arena: Arena,
+    errors: Vec<(TreeBuilderError, Option<Position>)>,
+}
+
+impl Default for Dom {
+    fn default() -> Dom {
+        Dom::new()
+    }
 }
 
 impl Dom {
     pub fn new() -> Dom {
+        let arena = Arena::new();
+
+        let document = arena.insert_cyclic(|document| Node::new(NodeType::Document(Document::default()), document));
+
+        arena::with_mut(document, |node| {
+            let registry = NullOrCustomElementRegistry::new(CustomElementRegistry::new(document));
+
+            node.downcast_mut::<Document>().custom_element_registry = registry;
+        });
+
         Dom {
-            document: arena::insert_cyclic(|document| Node::new(NodeType::Document(Document::default()), document)),
-            arena: Arena::new(),
+            document,
+            arena,
+            errors: Vec::new(),
         }
     }
 
+    /// The document node.
+    #[inline]
+    pub fn document(&self) -> NodeId {
+        self.document
+    }
+
+    /// Check if the node belongs to this dom, the ids of other doms and removed nodes do not.
+    #[inline]
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.arena.contains(node)
+    }
+
+    /// Get the node if it belongs to this dom, none for the ids of other doms and removed nodes.
+    pub fn try_get(&self, node: NodeId) -> Option<Rc<RefCell<Node>>> {
+        arena::try_get(node).filter(|_| self.arena.contains(node))
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+    /// Serialize the children of the document, shadow roots are left out.
+    pub fn to_html(&self) -> String {
+        serializer::serialize_children(self.document, &GetHtmlOptions::default())
+    }
+
+    /// The count of nodes in the dom, including the nodes that are not collected yet.
+    #[inline]
+    pub fn node_count(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Free the nodes that can not be reached from the document or a [`NodeHandle`], and return the
+    /// count of freed nodes. The nodes held by walkers, collections, node iterators, queued reactions
+    /// and records are kept as well.
+    ///
+    /// # Stale ids
+    ///
+    /// A node id does not keep its node alive. A detached node whose id is only held by the caller or
+    /// captured by an event listener, a mutation callback or a custom element callback is freed, and
+    /// accessing the id of a freed node panics. Hold a [`NodeHandle`] to keep the node alive.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.arena.collect([self.document])
+    }
+
+    /// Renumber the nodes so the arena has no free slots, and return the new id of every node keyed
+    /// by its old id. The handles, walkers and collections follow their nodes, every other old id is
+    /// stale afterwards and panics when accessed, including the ids captured by closures.
+    ///
+    /// A microtask checkpoint is performed first, so no queued reaction or record refers to an old id.
+    pub fn compact(&mut self) -> HashMap<NodeId, NodeId> {
+        self.perform_microtask_checkpoint();
+
+        let ids = self.arena.compact();
+
+        if let Some(document) = ids.get(&self.document) {
+            self.document = *document;
+        }
+
+        ids
+    }
+
+    /// The parse errors reported while building the dom, with the source position if known.
+    #[inline]
+    pub fn errors(&self) -> &[(TreeBuilderError, Option<Position>)] {
+        &self.errors
+    }
+
+    /// The global custom element registry of the document.
+    pub fn custom_element_registry(&self) -> Rc<RefCell<CustomElementRegistry>> {
+        arena::get(self.document).borrow().downcast_ref::<Document>().custom_element_registry.registry()
+            .cloned()
+            .expect("document has a global registry")
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-createelement
+    /// Create a html element, custom elements are constructed synchronously.
+    pub fn create_element(&mut self, local_name: &str, options: ElementCreationOptions) -> Result<NodeId, DomException> {
+        if !names::is_valid_element_local_name(local_name) {
+            return Err(DomException::InvalidCharacterError);
+        }
+
+        let name = QualifiedName::html(Atom::from(local_name.to_ascii_lowercase().as_str()));
+
+        let registry = options.custom_element_registry
+            .map(NullOrCustomElementRegistry::new)
+            .unwrap_or_default();
+
+        Ok(custom_elements::ce_reactions(self.document, || Element::create(self.document, name, options.is.as_deref(), true, registry)))
+    }
+
+    // https://html.spec.whatwg.org/multipage/webappapis.html#perform-a-microtask-checkpoint
+    /// Perform a microtask checkpoint, the embedder should call this whenever the spec would run
+    /// microtasks, such as after running a script or a task.
+    pub fn perform_microtask_checkpoint(&mut self) {
+        custom_elements::process_backup_element_queue(self.document);
+
+        mutation_observer::notify_mutation_observers(self.document);
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-createdocumentfragment
+    pub fn create_document_fragment(&mut self) -> NodeId {
+        arena::insert(Node::new(NodeType::DocumentFragment(DocumentFragment::default()), self.document))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-nonelementparentnode-getelementbyid
+    /// Return the first element in the document with the id.
+    pub fn get_element_by_id(&self, id: &str) -> Option<NodeId> {
+        Document::get_element_by_id(self.document, id)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-getelementsbytagname
+    pub fn get_elements_by_tag_name(&self, qualified_name: &str) -> HtmlCollection {
+        Document::get_elements_by_tag_name(self.document, qualified_name)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-getelementsbytagnamens
+    pub fn get_elements_by_tag_name_ns(&self, namespace: Option<&str>, local_name: &str) -> HtmlCollection {
+        Document::get_elements_by_tag_name_ns(self.document, namespace, local_name)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-getelementsbyclassname
+    pub fn get_elements_by_class_name(&self, class_names: &str) -> HtmlCollection {
+        Document::get_elements_by_class_name(self.document, class_names)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-parentnode-queryselector
+    /// Return the first element in the document matching the selectors.
+    pub fn query_selector(&self, selectors: &str) -> Result<Option<NodeId>, DomException> {
+        Node::query_selector(self.document, selectors)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-parentnode-queryselectorall
+    /// Return every element in the document matching the selectors, in tree order.
+    pub fn query_selector_all(&self, selectors: &str) -> Result<Vec<NodeId>, DomException> {
+        Node::query_selector_all(self.document, selectors)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-createnodeiterator
+    pub fn create_node_iterator(&self, root: NodeId, what_to_show: u32, filter: Option<NodeFilter>) -> NodeIterator {
+        NodeIterator::new(root, what_to_show, filter)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-createtreewalker
+    pub fn create_tree_walker(&self, root: NodeId, what_to_show: u32, filter: Option<NodeFilter>) -> TreeWalker {
+        TreeWalker::new(root, what_to_show, filter)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-createtextnode
+    pub fn create_text_node(&mut self, data: String) -> NodeId {
+        arena::insert(Node::new(NodeType::Text(Text::new(data)), self.document))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-createcdatasection
+    // NOTE: the spec throws a NotSupportedError for html documents, this is not enforced so trees
+    // for xml based formats can be built.
+    pub fn create_cdata_section(&mut self, data: String) -> Result<NodeId, DomException> {
+        if data.contains("]]>") {
+            return Err(DomException::InvalidCharacterError);
+        }
+
+        Ok(arena::insert(Node::new(NodeType::CdataSection(CdataSection::new(data)), self.document)))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-createprocessinginstruction
+    pub fn create_processing_instruction(&mut self, target: String, data: String) -> Result<NodeId, DomException> {
+        if !names::is_xml_name(&target) || data.contains("?>") {
+            return Err(DomException::InvalidCharacterError);
+        }
+
+        let processing_instruction = ProcessingInstruction::new(target, data);
+
+        Ok(arena::insert(Node::new(NodeType::ProcessingInstruction(processing_instruction), self.document)))
+    }
+
     pub fn create_comment(&mut self, content: String) -> NodeId {
         arena::insert(Node::new(NodeType::Comment(Comment::new(content)), self.document))
     }
+
+    /// Create a doctype and append it to the document.
+    pub fn append_doctype(&mut self, name: String, public_id: String, system_id: String) -> NodeId {
+        let doctype = arena::insert(Node::new(NodeType::DocumentType(DocumentType::new(name, public_id, system_id)), self.document));
+
+        Node::insert(self.document, doctype, None, false);
+
+        doctype
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-adoptnode
+    /// Adopt the node into the document and return it, a node of another dom is moved into this dom
+    /// and returned with its new id.
+    pub fn adopt_node(&mut self, node: NodeId) -> Result<NodeId, DomException> {
+        match arena::get(node).borrow().node_type {
+            NodeType::Document(_) => return Err(DomException::NotSupportedError),
+            NodeType::ShadowRoot(_) => return Err(DomException::HierarchyRequestError),
+            _ => {},
+        }
+
+        let document = self.document;
+
+        Ok(custom_elements::ce_reactions(document, || Document::adopt(document, node)))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-node-appendchild
+    /// Append the node as the last child of the parent and return it, a node of another dom is
+    /// adopted and returned with its new id.
+    pub fn append_child(&mut self, parent: NodeId, node: NodeId) -> Result<NodeId, DomException> {
+        custom_elements::ce_reactions(self.document, || Node::pre_insert(node, parent, None))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-node-insertbefore
+    /// Insert the node before the child of the parent and return it, the node is appended when the child is none.
+    /// A node of another dom is adopted and returned with its new id.
+    pub fn insert_before(&mut self, parent: NodeId, node: NodeId, child: Option<NodeId>) -> Result<NodeId, DomException> {
+        custom_elements::ce_reactions(self.document, || Node::pre_insert(node, parent, child))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-node-replacechild
+    /// Replace the child of the parent with the node and return the child.
+    pub fn replace_child(&mut self, parent: NodeId, node: NodeId, child: NodeId) -> Result<NodeId, DomException> {
+        custom_elements::ce_reactions(self.document, || Node::replace(child, node, parent))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-childnode-remove
+    /// Remove the node from its parent, this does nothing if the node has no parent.
+    pub fn remove(&mut self, node: NodeId) {
+        custom_elements::ce_reactions(self.document, || Node::remove(node, false));
+    }
+
+    // https://dom.spec.whatwg.org/#dom-node-removechild
+    // https://dom.spec.whatwg.org/#concept-node-pre-remove
+    /// Remove the child from the parent and return it, fails if the child is not a child of the parent.
+    pub fn remove_child(&mut self, parent: NodeId, child: NodeId) -> Result<NodeId, DomException> {
+        if arena::get(child).borrow().parent != Some(parent) {
+            return Err(DomException::NotFoundError);
+        }
+
+        custom_elements::ce_reactions(self.document, || Node::remove(child, false));
+
+        Ok(child)
+    }
+
+    /// Set the quirks mode of the document.
+    pub fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        arena::with_mut(self.document, |document| document.downcast_mut::<Document>().quirks_mode = mode);
+    }
+
+    /// Set if scripting is enabled for the document, noscript text is then serialized as is.
+    pub fn set_scripting(&mut self, scripting: bool) {
+        arena::with_mut(self.document, |document| document.downcast_mut::<Document>().scripting = scripting);
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detached(dom: &mut Dom) -> NodeId {
+        let element = dom.create_element("div", ElementCreationOptions::default()).expect("valid name");
+        let text = dom.create_text_node(String::from("x"));
+
+        dom.append_child(element, text).expect("append");
+
+        element
+    }
+
+    #[test]
+    fn unreachable_nodes_are_freed() {
+        let mut dom = Dom::new();
+
+        let kept = detached(&mut dom);
+        let freed = detached(&mut dom);
+
+        let handle = NodeHandle::new(kept);
+
+        assert_eq!(dom.node_count(), 5);
+        assert_eq!(dom.collect_garbage(), 2);
+        assert_eq!(dom.node_count(), 3);
+
+        assert!(dom.contains(kept));
+        assert!(!dom.contains(freed));
+
+        let reused = dom.create_comment(String::new());
+
+        assert!(dom.contains(reused));
+        assert!(!dom.contains(freed));
+
+        drop(handle);
+
+        assert_eq!(dom.collect_garbage(), 3);
+        assert!(!dom.contains(kept));
+    }
+
+    #[test]
+    fn ids_of_other_and_dropped_doms_are_not_resolved() {
+        let mut dom = Dom::new();
+        let other = Dom::new();
+
+        let node = detached(&mut dom);
+
+        let dropped = {
+            let mut dropped = Dom::new();
+
+            detached(&mut dropped)
+        };
+
+        assert!(dom.try_get(node).is_some());
+        assert!(other.try_get(node).is_none());
+        assert!(dom.try_get(dropped).is_none());
+        assert!(arena::try_get(dropped).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "has been freed")]
+    fn freed_ids_panic() {
+        let mut dom = Dom::new();
+
+        let freed = detached(&mut dom);
+
+        dom.collect_garbage();
+
+        Element::id(freed);
+    }
+
+    #[test]
+    fn ids_captured_by_listeners_are_freed_unless_held_by_a_handle() {
+        use event::{AddEventListenerOptions, EventListenerOptions, EventTarget};
+
+        let mut dom = Dom::new();
 
+        let captured = detached(&mut dom);
+        let held = detached(&mut dom);
+
+        let handle = NodeHandle::new(held);
+
+        let callback: event::EventListenerCallback = Rc::new({
+            let handle = handle.clone();
+
+            move |_| {
+                let _ = (captured, handle.id());
+            }
+        });
+
+        dom.document().add_event_listener("click", callback.clone(), AddEventListenerOptions::default());
+
+        drop(handle);
+
+        assert_eq!(dom.collect_garbage(), 2);
+
+        assert!(!dom.contains(captured));
+        assert!(dom.contains(held));
+
+        dom.document().remove_event_listener("click", &callback, EventListenerOptions::default());
+
+        drop(callback);
+
+        assert_eq!(dom.collect_garbage(), 2);
+        assert!(!dom.contains(held));
+    }
+
+    #[test]
+    fn handles_are_remapped_by_compaction() {
+        let mut dom = Dom::new();
+
+        let freed = detached(&mut dom);
+        let held = detached(&mut dom);
+
+        let handle = NodeHandle::new(held);
+
+        dom.collect_garbage();
+
+        let ids = dom.compact();
+
+        assert!(!ids.contains_key(&freed));
+        assert!(!dom.contains(held));
+        assert_eq!(handle.id(), ids[&held]);
+        assert!(arena::get(handle.id()).borrow().first_child.is_some_and(|child| dom.contains(child)));
+
+        drop(handle);
+
+        assert_eq!(dom.collect_garbage(), 2);
+    }
+
+    #[test]
+    fn walkers_and_collections_hold_their_nodes() {
+        let mut dom = Dom::new();
+
+        let root = detached(&mut dom);
+
+        let mut walker = dom.create_tree_walker(root, traversal::NodeFilter::SHOW_ALL, None);
+        let children = Node::child_nodes(root);
+
+        walker.first_child();
+
+        dom.collect_garbage();
+        dom.compact();
+
+        assert!(dom.contains(walker.root()));
+        assert_eq!(walker.current_node(), children.item(0).expect("child"));
+        assert_eq!(walker.parent_node(), Some(walker.root()));
+
+        drop((walker, children));
+
+        assert_eq!(dom.collect_garbage(), 2);
+    }
+
+    #[test]
+    fn handles_follow_nodes_into_another_dom() {
+        let mut dom = Dom::new();
+        let mut other = Dom::new();
+
+        let node = detached(&mut other);
+        let handle = NodeHandle::new(node);
+
+        let parent = detached(&mut dom);
+        let adopted = dom.append_child(parent, node).expect("adopted");
+
+        assert_eq!(handle.id(), adopted);
+
+        dom.remove(adopted);
+
+        assert_eq!(dom.collect_garbage(), 2);
+        assert!(dom.contains(handle.id()));
+    }
+
+    #[test]
+    #[should_panic(expected = "has been freed")]
+    fn compacted_ids_panic() {
+        let mut dom = crate::parse_html("<p id=a></p>");
+
+        let element = dom.get_element_by_id("a").expect("element with id");
+
+        dom.compact();
+
+        Element::id(element);
+    }
+}
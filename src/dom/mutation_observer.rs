@@ -0,0 +1,617 @@
+use crate::dom::arena::{self, NodeId};
+use crate::dom::iterators::NodeIterator;
+use crate::dom::exception::DomException;
+use crate::dom::event::{self, EventInit};
+use crate::dom::node::document::Document;
+use crate::names::{LocalName, Namespace};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// https://dom.spec.whatwg.org/#callbackdef-mutationcallback
+pub type MutationCallback = Rc<dyn Fn(Vec<MutationRecord>, &Rc<RefCell<MutationObserver>>)>;
+
+// https://dom.spec.whatwg.org/#dom-mutationrecord-type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationRecordType {
+    Attributes,
+    CharacterData,
+    ChildList,
+}
+
+// https://dom.spec.whatwg.org/#interface-mutationrecord
+#[derive(Debug, Clone, PartialEq)]
+pub struct MutationRecord {
+    pub record_type: MutationRecordType,
+
+    /// The element whose attribute changed, the character data node whose data changed or the
+    /// parent whose children changed.
+    pub target: NodeId,
+
+    pub added_nodes: Vec<NodeId>,
+    pub removed_nodes: Vec<NodeId>,
+    pub previous_sibling: Option<NodeId>,
+    pub next_sibling: Option<NodeId>,
+    pub attribute_name: Option<LocalName>,
+    pub attribute_namespace: Option<Namespace>,
+
+    /// The value before the mutation, only set when the observer asked for old values.
+    pub old_value: Option<String>,
+}
+
+impl MutationRecord {
+    fn new(record_type: MutationRecordType, target: NodeId) -> MutationRecord {
+        MutationRecord {
+            record_type,
+            target,
+            added_nodes: Vec::new(),
+            removed_nodes: Vec::new(),
+            previous_sibling: None,
+            next_sibling: None,
+            attribute_name: None,
+            attribute_namespace: None,
+            old_value: None,
+        }
+    }
+}
+
+// https://dom.spec.whatwg.org/#dictdef-mutationobserverinit
+/// The options to observe a node with, the optional members are implied by the members that
+/// depend on them when none.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MutationObserverInit {
+    pub child_list: bool,
+    pub attributes: Option<bool>,
+    pub character_data: Option<bool>,
+    pub subtree: bool,
+    pub attribute_old_value: Option<bool>,
+    pub character_data_old_value: Option<bool>,
+    pub attribute_filter: Option<Vec<String>>,
+}
+
+/// The options of a registered observer, after the implied members were filled in.
+#[derive(Debug, Clone, PartialEq)]
+struct ObserveOptions {
+    child_list: bool,
+    attributes: bool,
+    character_data: bool,
+    subtree: bool,
+    attribute_old_value: bool,
+    character_data_old_value: bool,
+    attribute_filter: Option<Vec<String>>,
+}
+
+impl TryFrom<MutationObserverInit> for ObserveOptions {
+    type Error = DomException;
+
+    fn try_from(options: MutationObserverInit) -> Result<ObserveOptions, DomException> {
+        let attributes = options.attributes
+            .unwrap_or(options.attribute_old_value.is_some() || options.attribute_filter.is_some());
+
+        let character_data = options.character_data.unwrap_or(options.character_data_old_value.is_some());
+
+        let attribute_old_value = options.attribute_old_value.unwrap_or_default();
+        let character_data_old_value = options.character_data_old_value.unwrap_or_default();
+
+        if !options.child_list && !attributes && !character_data {
+            return Err(DomException::TypeError);
+        }
+
+        if (attribute_old_value || options.attribute_filter.is_some()) && !attributes {
+            return Err(DomException::TypeError);
+        }
+
+        if character_data_old_value && !character_data {
+            return Err(DomException::TypeError);
+        }
+
+        Ok(ObserveOptions {
+            child_list: options.child_list,
+            attributes,
+            character_data,
+            subtree: options.subtree,
+            attribute_old_value,
+            character_data_old_value,
+            attribute_filter: options.attribute_filter,
+        })
+    }
+}
+
+// https://dom.spec.whatwg.org/#registered-observer
+#[derive(Clone)]
+pub struct RegisteredObserver {
+    observer: Rc<RefCell<MutationObserver>>,
+    options: ObserveOptions,
+
+    // https://dom.spec.whatwg.org/#transient-registered-observer
+    /// The node the source registered observer is registered on, for transient registered observers.
+    source: Option<NodeId>,
+}
+
+impl RegisteredObserver {
+    /// Visit the source of the registered observer and the nodes and records of its observer.
+    pub(crate) fn for_each_reference(&mut self, f: &mut impl FnMut(&mut NodeId)) {
+        self.source.iter_mut().for_each(&mut *f);
+
+        self.observer.borrow_mut().for_each_reference(f);
+    }
+
+    fn is_transient_of(&self, observer: &Rc<RefCell<MutationObserver>>) -> bool {
+        self.source.is_some() && Rc::ptr_eq(&self.observer, observer)
+    }
+}
+
+// https://dom.spec.whatwg.org/#interface-mutationobserver
+pub struct MutationObserver {
+    callback: MutationCallback,
+
+    /// The nodes the observer has a registered observer on, including transient ones.
+    node_list: Vec<NodeId>,
+
+    record_queue: Vec<MutationRecord>,
+}
+
+impl MutationObserver {
+    // https://dom.spec.whatwg.org/#dom-mutationobserver-mutationobserver
+    pub fn new(callback: impl Fn(Vec<MutationRecord>, &Rc<RefCell<MutationObserver>>) + 'static) -> Rc<RefCell<MutationObserver>> {
+        Rc::new(RefCell::new(MutationObserver {
+            callback: Rc::new(callback),
+            node_list: Vec::new(),
+            record_queue: Vec::new(),
+        }))
+    }
+
+    pub(crate) fn for_each_reference(&mut self, f: &mut impl FnMut(&mut NodeId)) {
+        self.node_list.iter_mut().for_each(&mut *f);
+
+        for record in self.record_queue.iter_mut() {
+            f(&mut record.target);
+
+            record.added_nodes.iter_mut()
+                .chain(record.removed_nodes.iter_mut())
+                .chain(record.previous_sibling.iter_mut())
+                .chain(record.next_sibling.iter_mut())
+                .for_each(&mut *f);
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-mutationobserver-observe
+    /// Observe the target, observing a node again replaces the options it was observed with.
+    pub fn observe(observer: &Rc<RefCell<MutationObserver>>, target: NodeId, options: MutationObserverInit) -> Result<(), DomException> {
+        let options = ObserveOptions::try_from(options)?;
+
+        let registered = arena::get(target).borrow().registered_observers.iter()
+            .any(|registered| registered.source.is_none() && Rc::ptr_eq(&registered.observer, observer));
+
+        if registered {
+            let nodes = observer.borrow().node_list.clone();
+
+            for node in nodes.into_iter().filter(|node| arena::contains(*node)) {
+                arena::with_mut(node, |node| {
+                    node.registered_observers.retain(|registered| !(registered.is_transient_of(observer) && registered.source == Some(target)));
+                });
+            }
+
+            arena::with_mut(target, |node| {
+                node.registered_observers.iter_mut()
+                    .filter(|registered| registered.source.is_none() && Rc::ptr_eq(&registered.observer, observer))
+                    .for_each(|registered| registered.options = options.clone());
+            });
+        } else {
+            arena::with_mut(target, |node| {
+                node.registered_observers.push(RegisteredObserver {
+                    observer: observer.clone(),
+                    options,
+                    source: None,
+                });
+            });
+
+            let mut observer = observer.borrow_mut();
+
+            if !observer.node_list.contains(&target) {
+                observer.node_list.push(target);
+            }
+        }
+
+        Ok(())
+    }
+
+    // https://dom.spec.whatwg.org/#dom-mutationobserver-disconnect
+    /// Stop observing every node and drop the queued records.
+    pub fn disconnect(observer: &Rc<RefCell<MutationObserver>>) {
+        let nodes = std::mem::take(&mut observer.borrow_mut().node_list);
+
+        for node in nodes.into_iter().filter(|node| arena::contains(*node)) {
+            arena::with_mut(node, |node| {
+                node.registered_observers.retain(|registered| !Rc::ptr_eq(&registered.observer, observer));
+            });
+        }
+
+        observer.borrow_mut().record_queue.clear();
+    }
+
+    // https://dom.spec.whatwg.org/#dom-mutationobserver-takerecords
+    /// Return the queued records and empty the queue.
+    pub fn take_records(&mut self) -> Vec<MutationRecord> {
+        std::mem::take(&mut self.record_queue)
+    }
+}
+
+/// Add transient registered observers to the node for the observers of the inclusive ancestors of
+/// the parent that observe the subtree, so mutations in a removed subtree are still observed until
+/// the observers are notified.
+pub(crate) fn add_transient_observers(parent: NodeId, node: NodeId) {
+    for ancestor in NodeIterator::new(Some(parent), |node| node.parent) {
+        let observers = arena::get(ancestor).borrow().registered_observers.iter()
+            .filter(|registered| registered.options.subtree)
+            .cloned()
+            .collect::<Vec<RegisteredObserver>>();
+
+        for registered in observers {
+            arena::with_mut(node, |node| {
+                node.registered_observers.push(RegisteredObserver {
+                    source: Some(ancestor),
+                    ..registered.clone()
+                });
+            });
+
+            let mut observer = registered.observer.borrow_mut();
+
+            if !observer.node_list.contains(&node) {
+                observer.node_list.push(node);
+            }
+        }
+    }
+}
+
+/// Run the closure with the document of the node, which holds the pending observers and the slots
+/// to signal of its dom.
+fn with_document<R>(node: NodeId, f: impl FnOnce(&mut Document) -> R) -> R {
+    let document = arena::get(node).borrow().node_document;
+
+    arena::with_mut(document, |document| f(document.downcast_mut::<Document>()))
+}
+
+// https://dom.spec.whatwg.org/#queue-a-mutation-record
+/// Queue the record for every interested observer of the target or its ancestors, the old value of
+/// the record is only kept for observers that asked for it.
+fn queue_mutation_record(record: MutationRecord) {
+    let mut interested = Vec::<(Rc<RefCell<MutationObserver>>, Option<String>)>::new();
+
+    for node in NodeIterator::new(Some(record.target), |node| node.parent) {
+        for registered in arena::get(node).borrow().registered_observers.iter() {
+            let options = &registered.options;
+
+            let skip = (node != record.target && !options.subtree)
+                || match record.record_type {
+                    MutationRecordType::Attributes => {
+                        !options.attributes
+                            || options.attribute_filter.as_ref().is_some_and(|filter| {
+                                record.attribute_namespace.is_some()
+                                    || !filter.iter().any(|name| record.attribute_name.as_ref().is_some_and(|attribute_name| attribute_name == name.as_str()))
+                            })
+                    },
+                    MutationRecordType::CharacterData => !options.character_data,
+                    MutationRecordType::ChildList => !options.child_list,
+                };
+
+            if skip {
+                continue;
+            }
+
+            let index = match interested.iter().position(|(observer, _)| Rc::ptr_eq(observer, &registered.observer)) {
+                Some(index) => index,
+                None => {
+                    interested.push((registered.observer.clone(), None));
+
+                    interested.len() - 1
+                },
+            };
+
+            let old_value = match record.record_type {
+                MutationRecordType::Attributes => options.attribute_old_value,
+                MutationRecordType::CharacterData => options.character_data_old_value,
+                MutationRecordType::ChildList => false,
+            };
+
+            if old_value {
+                interested[index].1 = record.old_value.clone();
+            }
+        }
+    }
+
+    for (observer, old_value) in interested {
+        observer.borrow_mut().record_queue.push(MutationRecord {
+            old_value,
+            ..record.clone()
+        });
+
+        with_document(record.target, |document| {
+            let pending = &mut document.pending_mutation_observers;
+
+            if !pending.iter().any(|pending| Rc::ptr_eq(pending, &observer)) {
+                pending.push(observer);
+            }
+        });
+    }
+}
+
+// https://dom.spec.whatwg.org/#queue-a-tree-mutation-record
+pub(crate) fn queue_tree_mutation_record(
+    target: NodeId,
+    added_nodes: Vec<NodeId>,
+    removed_nodes: Vec<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+) {
+    debug_assert!(!added_nodes.is_empty() || !removed_nodes.is_empty());
+
+    queue_mutation_record(MutationRecord {
+        added_nodes,
+        removed_nodes,
+        previous_sibling,
+        next_sibling,
+        ..MutationRecord::new(MutationRecordType::ChildList, target)
+    });
+}
+
+pub(crate) fn queue_attribute_mutation_record(target: NodeId, name: LocalName, namespace: Option<Namespace>, old_value: Option<String>) {
+    queue_mutation_record(MutationRecord {
+        attribute_name: Some(name),
+        attribute_namespace: namespace,
+        old_value,
+        ..MutationRecord::new(MutationRecordType::Attributes, target)
+    });
+}
+
+pub(crate) fn queue_character_data_mutation_record(target: NodeId, old_value: String) {
+    queue_mutation_record(MutationRecord {
+        old_value: Some(old_value),
+        ..MutationRecord::new(MutationRecordType::CharacterData, target)
+    });
+}
+
+// https://dom.spec.whatwg.org/#signal-a-slot-change
+/// Add the slot to the signal slots, a slotchange event is fired at it when the observers are notified.
+pub(crate) fn signal_slot_change(slot: NodeId) {
+    with_document(slot, |document| {
+        let signal_slots = &mut document.signal_slots;
+
+        if !signal_slots.contains(&slot) {
+            signal_slots.push(slot);
+        }
+    });
+}
+
+// https://dom.spec.whatwg.org/#notify-mutation-observers
+/// Deliver the queued records of every pending observer of the document to its callback.
+pub fn notify_mutation_observers(document: NodeId) {
+    let notify_set = with_document(document, |document| std::mem::take(&mut document.pending_mutation_observers));
+
+    let signal_set = with_document(document, |document| std::mem::take(&mut document.signal_slots));
+
+    for observer in notify_set {
+        let records = observer.borrow_mut().take_records();
+        let nodes = observer.borrow().node_list.clone();
+
+        for node in nodes {
+            let observed = arena::contains(node) && arena::with_mut(node, |node| {
+                node.registered_observers.retain(|registered| !registered.is_transient_of(&observer));
+
+                node.registered_observers.iter().any(|registered| Rc::ptr_eq(&registered.observer, &observer))
+            });
+
+            if !observed {
+                observer.borrow_mut().node_list.retain(|other| *other != node);
+            }
+        }
+
+        if !records.is_empty() {
+            let callback = observer.borrow().callback.clone();
+
+            callback(records, &observer);
+        }
+    }
+
+    for slot in signal_set.into_iter().filter(|slot| arena::contains(*slot)) {
+        event::fire_event(slot, "slotchange", EventInit {
+            bubbles: true,
+            ..EventInit::default()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_html;
+    use crate::dom::Dom;
+    use crate::dom::node::character_data::CharacterData;
+    use crate::dom::node::element::Element;
+    use crate::dom::testing::element;
+
+    /// Create an observer which collects the records delivered to its callback.
+    fn observer() -> (Rc<RefCell<MutationObserver>>, Rc<RefCell<Vec<MutationRecord>>>) {
+        let delivered = Rc::new(RefCell::new(Vec::new()));
+
+        let observer = MutationObserver::new({
+            let delivered = delivered.clone();
+
+            move |records, _| delivered.borrow_mut().extend(records)
+        });
+
+        (observer, delivered)
+    }
+
+    #[test]
+    fn invalid_options() {
+        let dom = Dom::new();
+        let (observer, _) = observer();
+
+        let observe = |options| MutationObserver::observe(&observer, dom.document(), options);
+
+        assert_eq!(observe(MutationObserverInit::default()), Err(DomException::TypeError));
+        assert_eq!(observe(MutationObserverInit { attributes: Some(false), attribute_old_value: Some(true), ..Default::default() }), Err(DomException::TypeError));
+        assert_eq!(observe(MutationObserverInit { character_data: Some(false), character_data_old_value: Some(true), ..Default::default() }), Err(DomException::TypeError));
+        assert_eq!(observe(MutationObserverInit { attribute_filter: Some(Vec::new()), ..Default::default() }), Ok(()));
+    }
+
+    #[test]
+    fn child_list_records() {
+        let mut dom = parse_html("<div id=a><p id=b></p><p id=c></p></div>");
+
+        let (a, b, c) = (element(&dom, "a"), element(&dom, "b"), element(&dom, "c"));
+        let (observer, delivered) = observer();
+
+        MutationObserver::observe(&observer, a, MutationObserverInit { child_list: true, ..Default::default() }).expect("valid");
+
+        let text = dom.create_text_node(String::from("x"));
+
+        dom.insert_before(a, text, Some(c)).expect("insert");
+        dom.remove(b);
+
+        assert!(delivered.borrow().is_empty());
+
+        dom.perform_microtask_checkpoint();
+
+        let records = delivered.take();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].record_type, MutationRecordType::ChildList);
+        assert_eq!(records[0].added_nodes, vec![text]);
+        assert_eq!((records[0].previous_sibling, records[0].next_sibling), (Some(b), Some(c)));
+        assert_eq!(records[1].removed_nodes, vec![b]);
+        assert_eq!((records[1].previous_sibling, records[1].next_sibling), (None, Some(text)));
+
+        dom.perform_microtask_checkpoint();
+
+        assert!(delivered.borrow().is_empty());
+    }
+
+    #[test]
+    fn attribute_records() {
+        let mut dom = parse_html("<div id=a title=x></div>");
+
+        let a = element(&dom, "a");
+        let (observer, delivered) = observer();
+
+        let options = MutationObserverInit {
+            attribute_old_value: Some(true),
+            attribute_filter: Some(vec![String::from("title")]),
+            ..Default::default()
+        };
+
+        MutationObserver::observe(&observer, a, options).expect("valid");
+
+        Element::set_attribute(a, "title", "y").expect("valid");
+        Element::set_attribute(a, "lang", "en").expect("valid");
+        Element::remove_attribute(a, "title");
+
+        dom.perform_microtask_checkpoint();
+
+        let records = delivered.take();
+
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|record| record.record_type == MutationRecordType::Attributes && record.target == a));
+        assert_eq!(records[0].attribute_name.as_ref().map(|name| name.as_str()), Some("title"));
+        assert_eq!(records[0].old_value.as_deref(), Some("x"));
+        assert_eq!(records[1].old_value.as_deref(), Some("y"));
+    }
+
+    #[test]
+    fn character_data_records_in_subtree() {
+        let mut dom = parse_html("<div id=a><p>x</p></div>");
+
+        let a = element(&dom, "a");
+        let text = dom.query_selector("p").expect("valid").and_then(|p| arena::get(p).borrow().first_child).expect("text");
+
+        let (with_old_value, old_values) = observer();
+        let (without_old_value, values) = observer();
+
+        MutationObserver::observe(&with_old_value, a, MutationObserverInit { character_data_old_value: Some(true), subtree: true, ..Default::default() }).expect("valid");
+        MutationObserver::observe(&without_old_value, a, MutationObserverInit { character_data: Some(true), subtree: true, ..Default::default() }).expect("valid");
+
+        CharacterData::set_data(text, "y");
+
+        dom.perform_microtask_checkpoint();
+
+        assert_eq!(old_values.borrow()[0].target, text);
+        assert_eq!(old_values.borrow()[0].old_value.as_deref(), Some("x"));
+        assert_eq!(values.borrow()[0].old_value, None);
+    }
+
+    #[test]
+    fn removed_subtrees_are_observed_until_notified() {
+        let mut dom = parse_html("<div id=a><p id=b><i id=c></i></p></div>");
+
+        let (a, b, c) = (element(&dom, "a"), element(&dom, "b"), element(&dom, "c"));
+        let (observer, delivered) = observer();
+
+        MutationObserver::observe(&observer, a, MutationObserverInit { attributes: Some(true), subtree: true, ..Default::default() }).expect("valid");
+
+        dom.remove(b);
+
+        Element::set_attribute(c, "title", "x").expect("valid");
+
+        dom.perform_microtask_checkpoint();
+
+        assert_eq!(delivered.take().iter().map(|record| record.target).collect::<Vec<_>>(), vec![c]);
+
+        Element::set_attribute(c, "title", "y").expect("valid");
+
+        dom.perform_microtask_checkpoint();
+
+        assert!(delivered.borrow().is_empty());
+    }
+
+    #[test]
+    fn take_records_and_disconnect() {
+        let mut dom = parse_html("<div id=a></div>");
+
+        let a = element(&dom, "a");
+        let (observer, delivered) = observer();
+
+        MutationObserver::observe(&observer, a, MutationObserverInit { attributes: Some(true), ..Default::default() }).expect("valid");
+
+        Element::set_attribute(a, "title", "x").expect("valid");
+
+        assert_eq!(observer.borrow_mut().take_records().len(), 1);
+
+        Element::set_attribute(a, "title", "y").expect("valid");
+
+        MutationObserver::disconnect(&observer);
+
+        Element::set_attribute(a, "title", "z").expect("valid");
+
+        dom.perform_microtask_checkpoint();
+
+        assert!(delivered.borrow().is_empty());
+        assert!(observer.borrow_mut().take_records().is_empty());
+    }
+
+    #[test]
+    fn each_dom_notifies_its_own_observers() {
+        let mut first = parse_html("<div id=a></div>");
+        let mut second = parse_html("<div id=a></div>");
+
+        let (first_observer, first_delivered) = observer();
+        let (second_observer, second_delivered) = observer();
+
+        for (dom, observer) in [(&first, &first_observer), (&second, &second_observer)] {
+            MutationObserver::observe(observer, element(dom, "a"), MutationObserverInit { attributes: Some(true), ..Default::default() }).expect("valid");
+
+            Element::set_attribute(element(dom, "a"), "title", "x").expect("valid");
+        }
+
+        second.perform_microtask_checkpoint();
+
+        assert!(first_delivered.borrow().is_empty());
+        assert_eq!(second_delivered.take().len(), 1);
+
+        drop(second);
+
+        first.perform_microtask_checkpoint();
+
+        assert_eq!(first_delivered.take().len(), 1);
+    }
+}
@@ -1,12 +1,55 @@
 use crate::dom::node::QualifiedName;
 use crate::dom::arena::NodeId;
+use crate::names::{Namespace, Prefix, LocalName};
 
 
 /// An attribute node, the spec wants Attribute to extend Node, however its not needed.
+#[derive(Clone)]
 pub struct Attribute {
     pub(crate) node_document: NodeId,
     pub(crate) name: QualifiedName,
     pub(crate) value: String,
 }
 
+impl Attribute {
+    pub fn new(node_document: NodeId, name: QualifiedName, value: String) -> Attribute {
+        Attribute {
+            node_document,
+            name,
+            value,
+        }
+    }
 
+    /// The qualified name of the attribute, including the namespace.
+    #[inline]
+    pub fn name(&self) -> QualifiedName {
+        self.name.clone()
+    }
+
+    #[inline]
+    pub fn namespace(&self) -> Option<Namespace> {
+        self.name.namespace.clone()
+    }
+
+    #[inline]
+    pub fn prefix(&self) -> Option<Prefix> {
+        self.name.prefix.clone()
+    }
+
+    #[inline]
+    pub fn local_name(&self) -> LocalName {
+        self.name.local_name.clone()
+    }
+
+    // https://dom.spec.whatwg.org/#concept-attribute-qualified-name
+    /// The local name prefixed by the namespace prefix, if there is one.
+    #[inline]
+    pub fn qualified_name(&self) -> String {
+        self.name.qualified()
+    }
+
+    #[inline]
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
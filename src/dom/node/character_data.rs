@@ -0,0 +1,199 @@
+use crate::dom::arena::{self, NodeId};
+use crate::dom::exception::DomException;
+use crate::dom::mutation_observer;
+use crate::dom::inheritance::{private, Downcast};
+use crate::dom::node::{Node, NodeType};
+use crate::dom::node::document::Document;
+
+
+/// The data shared by text, cdata section, processing instruction and comment nodes.
+///
+/// Offsets and counts are in UTF-16 code units as the spec requires, an offset that falls inside a
+/// surrogate pair is moved back to the start of the pair since the data is stored as utf-8.
+pub struct CharacterData {
+    pub(crate) data: String,
+}
+
+impl private::Sealed for CharacterData {}
+
+impl Downcast<Node> for CharacterData {
+    fn downcast_ref(node: &Node) -> &CharacterData {
+        match &node.node_type {
+            NodeType::Text(text) => &text.character_data,
+            NodeType::CdataSection(cdata_section) => &cdata_section.text.character_data,
+            NodeType::ProcessingInstruction(processing_instruction) => &processing_instruction.character_data,
+            NodeType::Comment(comment) => &comment.character_data,
+            _ => panic!("expected character data"),
+        }
+    }
+
+    fn downcast_mut(node: &mut Node) -> &mut CharacterData {
+        match &mut node.node_type {
+            NodeType::Text(text) => &mut text.character_data,
+            NodeType::CdataSection(cdata_section) => &mut cdata_section.text.character_data,
+            NodeType::ProcessingInstruction(processing_instruction) => &mut processing_instruction.character_data,
+            NodeType::Comment(comment) => &mut comment.character_data,
+            _ => panic!("expected character data"),
+        }
+    }
+}
+
+/// Return the byte index of the UTF-16 offset in the string.
+fn byte_index(data: &str, offset: usize) -> usize {
+    let mut units = 0;
+
+    for (index, character) in data.char_indices() {
+        units += character.len_utf16();
+
+        if units > offset {
+            return index;
+        }
+    }
+
+    data.len()
+}
+
+impl CharacterData {
+    pub fn new(data: String) -> CharacterData {
+        CharacterData {
+            data,
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-characterdata-data
+    pub fn data(node: NodeId) -> String {
+        arena::get(node).borrow().downcast_ref::<CharacterData>().data.clone()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-characterdata-data
+    pub fn set_data(node: NodeId, data: &str) {
+        let length = CharacterData::length(node);
+
+        let _ = CharacterData::replace_data(node, 0, length, data);
+    }
+
+    // https://dom.spec.whatwg.org/#concept-node-length
+    /// The length of the data in UTF-16 code units.
+    pub fn length(node: NodeId) -> usize {
+        arena::get(node).borrow().downcast_ref::<CharacterData>().data.encode_utf16().count()
+    }
+
+    // https://dom.spec.whatwg.org/#concept-cd-substring
+    pub fn substring_data(node: NodeId, offset: usize, count: usize) -> Result<String, DomException> {
+        let node = arena::get(node);
+        let node = node.borrow();
+        let data = &node.downcast_ref::<CharacterData>().data;
+
+        let length = data.encode_utf16().count();
+
+        if offset > length {
+            return Err(DomException::IndexSizeError);
+        }
+
+        let start = byte_index(data, offset);
+        let end = byte_index(data, offset.saturating_add(count).min(length));
+
+        Ok(data[start..end].to_string())
+    }
+
+    // https://dom.spec.whatwg.org/#dom-characterdata-appenddata
+    pub fn append_data(node: NodeId, data: &str) {
+        let length = CharacterData::length(node);
+
+        let _ = CharacterData::replace_data(node, length, 0, data);
+    }
+
+    // https://dom.spec.whatwg.org/#dom-characterdata-insertdata
+    pub fn insert_data(node: NodeId, offset: usize, data: &str) -> Result<(), DomException> {
+        CharacterData::replace_data(node, offset, 0, data)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-characterdata-deletedata
+    pub fn delete_data(node: NodeId, offset: usize, count: usize) -> Result<(), DomException> {
+        CharacterData::replace_data(node, offset, count, "")
+    }
+
+    // https://dom.spec.whatwg.org/#concept-cd-replace
+    pub fn replace_data(node: NodeId, offset: usize, count: usize, data: &str) -> Result<(), DomException> {
+        let length = CharacterData::length(node);
+
+        if offset > length {
+            return Err(DomException::IndexSizeError);
+        }
+
+        let count = count.min(length - offset);
+
+        mutation_observer::queue_character_data_mutation_record(node, CharacterData::data(node));
+
+        arena::with_mut(node, |node| {
+            let character_data = node.downcast_mut::<CharacterData>();
+
+            let start = byte_index(&character_data.data, offset);
+            let end = byte_index(&character_data.data, offset + count);
+
+            character_data.data.replace_range(start..end, data);
+        });
+
+        let node_document = arena::get(node).borrow().node_document;
+        let data_length = data.encode_utf16().count();
+
+        Document::with_ranges(node_document, |ranges| {
+            for range in ranges.iter_mut() {
+                range.adjust_replace_data(node, offset, count, data_length);
+            }
+        });
+
+        // TODO: run the children changed steps for the parent
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::Dom;
+
+    #[test]
+    fn editing_data() {
+        let mut dom = Dom::new();
+        let text = dom.create_text_node(String::from("hello"));
+
+        CharacterData::append_data(text, " world");
+        assert_eq!(CharacterData::data(text), "hello world");
+
+        assert_eq!(CharacterData::insert_data(text, 5, ","), Ok(()));
+        assert_eq!(CharacterData::delete_data(text, 0, 1), Ok(()));
+        assert_eq!(CharacterData::replace_data(text, 0, 4, "Jell"), Ok(()));
+        assert_eq!(CharacterData::data(text), "Jell, world");
+
+        assert_eq!(CharacterData::substring_data(text, 6, 100).as_deref(), Ok("world"));
+        assert_eq!(CharacterData::delete_data(text, 4, 100), Ok(()));
+        assert_eq!(CharacterData::data(text), "Jell");
+
+        assert_eq!(CharacterData::substring_data(text, 5, 1), Err(DomException::IndexSizeError));
+        assert_eq!(CharacterData::insert_data(text, 5, "x"), Err(DomException::IndexSizeError));
+        assert_eq!(CharacterData::data(text), "Jell");
+
+        let comment = dom.create_comment(String::from("a"));
+
+        CharacterData::set_data(comment, "b");
+        assert_eq!(CharacterData::data(comment), "b");
+    }
+
+    #[test]
+    fn offsets_are_utf16_code_units() {
+        let mut dom = Dom::new();
+        let text = dom.create_text_node(String::from("a\u{1f600}b\u{e9}"));
+
+        assert_eq!(CharacterData::length(text), 5);
+        assert_eq!(CharacterData::substring_data(text, 1, 2).as_deref(), Ok("\u{1f600}"));
+        assert_eq!(CharacterData::substring_data(text, 3, 2).as_deref(), Ok("b\u{e9}"));
+
+        // NOTE: an offset inside the surrogate pair is moved back to the start of the pair.
+        assert_eq!(CharacterData::substring_data(text, 2, 2).as_deref(), Ok("\u{1f600}b"));
+
+        assert_eq!(CharacterData::delete_data(text, 1, 2), Ok(()));
+        assert_eq!(CharacterData::data(text), "ab\u{e9}");
+    }
+}
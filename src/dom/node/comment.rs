@@ -1,9 +1,10 @@
 use crate::dom::inheritance::{private, Downcast};
 use crate::dom::node::{Node, NodeType};
+use crate::dom::node::character_data::CharacterData;
 
 
 pub struct Comment {
-    data: String,
+    pub(crate) character_data: CharacterData,
 }
 
 impl private::Sealed for Comment {}
@@ -27,7 +28,7 @@ impl Downcast<Node> for Comment {
 impl Comment {
     pub fn new(data: String) -> Comment {
         Comment {
-            data,
+            character_data: CharacterData::new(data),
         }
     }
 }
@@ -1,8 +1,18 @@
 use crate::dom::arena::{self, NodeId};
 use crate::dom::iterators::TreeIterator;
 use crate::dom::node::{Node, NodeType};
-use crate::dom::node::element::NullOrCustomElementRegistry;
+use crate::dom::node::element::Element;
+use crate::dom::custom_elements::{self, NullOrCustomElementRegistry, LifecycleReaction, CustomElementReactionsStack};
+use crate::dom::mutation_observer::MutationObserver;
+use crate::dom::traversal::IteratorPosition;
 use crate::dom::inheritance::{private, Downcast};
+use crate::dom::collection::HtmlCollection;
+use crate::parser::quirks::QuirksMode;
+use crate::names::atoms;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
 
 
 pub struct Boundary {
@@ -32,12 +42,58 @@ impl Range {
         }
     }
 
+    // https://dom.spec.whatwg.org/#concept-node-remove
+    /// Adjust the boundaries for the node at the index being removed from the parent.
+    pub fn adjust_removal(&mut self, node: NodeId, parent: NodeId, index: usize) {
+        for boundary in [&mut self.start, &mut self.end] {
+            if Node::is_inclusive_ancestor(node, boundary.container) {
+                *boundary = Boundary::new(parent, index);
+            } else if boundary.container == parent && boundary.offset > index {
+                boundary.offset -= 1;
+            }
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-cd-replace
+    /// Adjust the boundaries for count code units at the offset of the node being replaced with data.
+    pub fn adjust_replace_data(&mut self, node: NodeId, offset: usize, count: usize, data_length: usize) {
+        for boundary in [&mut self.start, &mut self.end] {
+            if boundary.container != node {
+                continue;
+            }
+
+            if boundary.offset > offset && boundary.offset <= offset + count {
+                boundary.offset = offset;
+            } else if boundary.offset > offset + count {
+                boundary.offset = boundary.offset + data_length - count;
+            }
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-text-split
+    /// Adjust the boundaries for the node at the index in the parent being split at the offset.
+    pub fn adjust_split(&mut self, node: NodeId, new_node: NodeId, offset: usize, parent: NodeId, index: usize) {
+        for boundary in [&mut self.start, &mut self.end] {
+            if boundary.container == node && boundary.offset > offset {
+                *boundary = Boundary::new(new_node, boundary.offset - offset);
+            } else if boundary.container == parent && boundary.offset == index + 1 {
+                boundary.offset += 1;
+            }
+        }
+    }
+
+    pub(crate) fn for_each_reference(&mut self, f: &mut impl FnMut(&mut NodeId)) {
+        f(&mut self.start.container);
+
+        f(&mut self.end.container);
+    }
+
     pub fn adjust_offset(&mut self, parent: NodeId, child: NodeId, count: usize) {
-        if self.start.container == parent && self.start.offset > arena::get(child).index() {
+        if self.start.container == parent && self.start.offset > arena::get(child).borrow().index() {
             self.start.offset += count;
         }
 
-        if self.end.container == parent && self.end.offset > arena::get(child).index() {
+        if self.end.container == parent && self.end.offset > arena::get(child).borrow().index() {
             self.end.offset += count;
         }
     }
@@ -47,6 +103,31 @@ impl Range {
 pub struct Document {
     pub custom_element_registry: NullOrCustomElementRegistry,
     pub ranges: Vec<Range>,
+    pub quirks_mode: QuirksMode,
+
+    /// Whether scripting is enabled, set by the parser from its options.
+    pub scripting: bool,
+
+    /// The connected elements with an id, keyed by the id.
+    pub(crate) ids: HashMap<String, Vec<NodeId>>,
+
+    /// Incremented whenever a node of the document is inserted, removed or has an attribute changed,
+    /// live collections are collected again when it changes.
+    pub(crate) mutation_count: u64,
+
+    /// The positions of the live node iterators of the document, adjusted by the pre-removing steps.
+    pub(crate) node_iterators: Vec<Weak<RefCell<IteratorPosition>>>,
+
+    // NOTE: the reactions stack, the mutation observers and the signal slots belong to the agent in
+    // the spec. Every dom has one document and is its own agent, so they are kept here and a dom
+    // never runs the reactions or notifies the observers of another dom.
+    pub(crate) custom_element_reactions: CustomElementReactionsStack,
+
+    // https://dom.spec.whatwg.org/#mutation-observer-list
+    pub(crate) pending_mutation_observers: Vec<Rc<RefCell<MutationObserver>>>,
+
+    // https://dom.spec.whatwg.org/#signal-slot-list
+    pub(crate) signal_slots: Vec<NodeId>,
 }
 
 impl private::Sealed for Document {}
@@ -68,41 +149,260 @@ impl Downcast<Node> for Document {
 }
 
 impl Document {
-    pub fn adopt(document: NodeId, node: NodeId) {
-        let old_document = arena::get(node).node_document;
+    pub(crate) fn for_each_reference(&mut self, f: &mut impl FnMut(&mut NodeId)) {
+        self.custom_element_registry.for_each_reference(f);
 
-        if let Some(parent) = arena::get(node).parent {
-            arena::with_mut(parent, |parent| parent.remove(node));
+        for range in self.ranges.iter_mut() {
+            range.for_each_reference(f);
         }
 
+        self.ids.values_mut().flatten().for_each(&mut *f);
+
+        for position in self.node_iterators.iter().filter_map(Weak::upgrade) {
+            position.borrow_mut().for_each_reference(f);
+        }
+
+        self.custom_element_reactions.for_each_reference(f);
+
+        for observer in self.pending_mutation_observers.iter() {
+            observer.borrow_mut().for_each_reference(f);
+        }
+
+        self.signal_slots.iter_mut().for_each(f);
+    }
+
+    pub(crate) fn add_id(document: NodeId, id: String, element: NodeId) {
+        arena::with_mut(document, |document| {
+            document.downcast_mut::<Document>().ids.entry(id).or_default().push(element);
+        });
+    }
+
+    pub(crate) fn remove_id(document: NodeId, id: &str, element: NodeId) {
+        arena::with_mut(document, |document| {
+            let ids = &mut document.downcast_mut::<Document>().ids;
+
+            if let Some(elements) = ids.get_mut(id) {
+                elements.retain(|other| *other != element);
+
+                if elements.is_empty() {
+                    ids.remove(id);
+                }
+            }
+        });
+    }
+
+    /// Invalidate the live collections of the document.
+    pub(crate) fn mutated(document: NodeId) {
+        arena::with_mut(document, |document| document.downcast_mut::<Document>().mutation_count += 1);
+    }
+
+    // https://dom.spec.whatwg.org/#dom-nonelementparentnode-getelementbyid
+    /// Return the first element in tree order with the id.
+    pub fn get_element_by_id(document: NodeId, id: &str) -> Option<NodeId> {
+        let elements = arena::get(document).borrow().downcast_ref::<Document>().ids.get(id).cloned()?;
+
+        elements.into_iter().min_by(|a, b| Node::compare_tree_order(*a, *b))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-getelementsbytagname
+    pub fn get_elements_by_tag_name(document: NodeId, qualified_name: &str) -> HtmlCollection {
+        HtmlCollection::with_qualified_name(document, qualified_name)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-getelementsbytagnamens
+    pub fn get_elements_by_tag_name_ns(document: NodeId, namespace: Option<&str>, local_name: &str) -> HtmlCollection {
+        HtmlCollection::with_namespace_and_local_name(document, namespace, local_name)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-document-getelementsbyclassname
+    pub fn get_elements_by_class_name(document: NodeId, class_names: &str) -> HtmlCollection {
+        HtmlCollection::with_class_names(document, class_names)
+    }
+
+    /// Return the doctype of the document, if it has one.
+    pub fn doctype(document: NodeId) -> Option<NodeId> {
+        arena::get(document).borrow().children()
+            .find(|child| matches!(arena::get(child).borrow().node_type, NodeType::DocumentType(_)))
+    }
+
+    // https://dom.spec.whatwg.org/#document-element
+    /// Return the element child of the document, if it has one.
+    pub fn document_element(document: NodeId) -> Option<NodeId> {
+        arena::get(document).borrow().children()
+            .find(|child| matches!(arena::get(child).borrow().node_type, NodeType::Element(_)))
+    }
+
+    // https://html.spec.whatwg.org/multipage/dom.html#the-body-element-2
+    /// Return the first body or frameset child of the html document element, if there is one.
+    pub fn body(document: NodeId) -> Option<NodeId> {
+        let document_element = Document::document_element(document)
+            .filter(|element| arena::get(element).borrow().downcast_ref::<Element>().name.is_html(atoms::HTML))?;
+
+        arena::get(document_element).borrow().children().find(|child| {
+            matches!(&arena::get(child).borrow().node_type, NodeType::Element(element) if element.name.is_html(atoms::BODY) || element.name.is_html(atoms::FRAMESET))
+        })
+    }
+
+    /// Run the closure with the live ranges of the document, the ranges are taken out of the document
+    /// while it runs so the closure is free to access any node.
+    pub fn with_ranges<T>(document: NodeId, f: impl FnOnce(&mut Vec<Range>) -> T) -> T {
+        let mut ranges = arena::with_mut(document, |document| std::mem::take(&mut document.downcast_mut::<Document>().ranges));
+
+        let result = f(&mut ranges);
+
+        arena::with_mut(document, |document| document.downcast_mut::<Document>().ranges.append(&mut ranges));
+
+        result
+    }
+
+    // https://dom.spec.whatwg.org/#concept-node-adopt
+    /// Adopt the node into the document and return its id, a node of another dom is moved into the
+    /// arena of the document together with its shadow-including descendants and gets a new id.
+    pub fn adopt(document: NodeId, node: NodeId) -> NodeId {
+        let old_document = arena::get(node).borrow().node_document;
+
+        Node::remove(node, false);
+
+        let node = match arena::is_same_arena(document, node) {
+            true => node,
+            false => Document::import(document, node),
+        };
+
         if document != old_document {
-            // TODO: this will have to be shadow-inclusive
-            for descendant in TreeIterator::new(Some(node)) {
+            let registry = arena::get(document).borrow().downcast_ref::<Document>().custom_element_registry.effective_global_custom_element_registry();
+
+            for descendant in TreeIterator::shadow_including(node) {
                 arena::with_mut(descendant, |descendant| {
                     descendant.node_document = document;
 
-                    // TODO: step 2: shadow root thing
+                    match &mut descendant.node_type {
+                        NodeType::ShadowRoot(shadow_root) => {
+                            if shadow_root.custom_element_registry.is_global_custom_element_registry() {
+                                shadow_root.custom_element_registry = registry.clone();
+                            }
+                        },
+                        NodeType::Element(element) => {
+                            for attribute in element.attributes.iter_mut() {
+                                attribute.node_document = document;
+                            }
+
+                            if element.custom_element_registry.is_global_custom_element_registry() {
+                                element.custom_element_registry = registry.clone();
+                            }
+                        },
+                        _ => {},
+                    }
+                });
+            }
+
+            for descendant in TreeIterator::shadow_including(node) {
+                let custom = match &arena::get(descendant).borrow().node_type {
+                    NodeType::Element(element) => element.is_custom(),
+                    _ => false,
+                };
+
+                if custom {
+                    custom_elements::enqueue_callback_reaction(descendant, LifecycleReaction::Adopted {
+                        old_document,
+                        new_document: document,
+                    });
+                }
+            }
+        }
+
+        node
+    }
 
-                    if let NodeType::Element(element) = &mut descendant.node_type {
-                        for attribute in element.attributes.iter_mut() {
-                            attribute.node_document = document;
-                        }
+    /// Move the shadow-including subtree of the node into the arena of the document and return the
+    /// new id of the node, the references between the moved nodes are updated.
+    fn import(document: NodeId, node: NodeId) -> NodeId {
+        let ids = TreeIterator::shadow_including(node)
+            .collect::<Vec<NodeId>>()
+            .into_iter()
+            .map(|old| (old, arena::transfer(old, document)))
+            .collect::<HashMap<NodeId, NodeId>>();
 
-                        if element.custom_element_registry.is_global_custom_element_registry() {
-                            let registry = arena::get(document).downcast_ref::<Document>().custom_element_registry.clone();
+        let moved = |id: &NodeId| arena::is_same_arena(*id, document);
 
-                            element.custom_element_registry = registry.effective_global_custom_element_registry();
-                        }
+        for id in ids.values() {
+            arena::with_mut(id, |node| {
+                node.for_each_reference(&mut |id| {
+                    if let Some(new) = ids.get(id) {
+                        *id = *new;
                     }
                 });
-            }
 
-            // TODO: custom element callback reaction
+                // NOTE: the slots and form owners outside of the subtree stay behind in the old dom.
+                node.assigned_slot = node.assigned_slot.filter(moved);
+                node.manual_slot_assignment = node.manual_slot_assignment.filter(moved);
 
-            // TODO: currently we dont have shadow root elements, when we implement shadow root
-            // elements we will have to iterate over them here and run the adopting steps
+                if let NodeType::Element(element) = &mut node.node_type {
+                    element.form_owner = element.form_owner.filter(moved);
+                    element.assigned_nodes.retain(moved);
+                    element.manually_assigned_nodes.retain(moved);
+                }
+            });
         }
+
+        ids[&node]
     }
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_html;
+    use crate::dom::arena;
+    use crate::dom::node::element::Element;
+    use crate::dom::testing::create_element;
+
+    #[test]
+    fn id_index() {
+        let mut dom = parse_html("<div id=a><p id=b></p></div><span id=b></span>");
+
+        let a = dom.get_element_by_id("a").expect("a");
+        let p = arena::get(a).borrow().first_child.expect("p");
+        let span = arena::get(a).borrow().next_sibling.expect("span");
+
+        assert_eq!(dom.get_element_by_id("b"), Some(p));
+        assert_eq!(dom.get_element_by_id(""), None);
+
+        dom.remove(p);
+
+        assert_eq!(dom.get_element_by_id("b"), Some(span));
+
+        dom.insert_before(a, p, None).expect("insert p");
+        Element::set_attribute(span, "id", "c").expect("valid");
+
+        assert_eq!(dom.get_element_by_id("b"), Some(p));
+        assert_eq!(dom.get_element_by_id("c"), Some(span));
+
+        Element::remove_attribute(p, "id");
+        Element::set_attribute(span, "id", "").expect("valid");
+
+        assert_eq!(dom.get_element_by_id("b"), None);
+        assert_eq!(dom.get_element_by_id("c"), None);
+    }
+
+    #[test]
+    fn disconnected_elements_are_not_indexed() {
+        let mut dom = parse_html("<div id=a></div>");
+
+        let a = dom.get_element_by_id("a").expect("a");
+        let element = create_element(&mut dom, "p");
+
+        Element::set_attribute(element, "id", "b").expect("valid");
+
+        assert_eq!(dom.get_element_by_id("b"), None);
+
+        dom.append_child(a, element).expect("append");
+
+        assert_eq!(dom.get_element_by_id("b"), Some(element));
+
+        dom.remove(a);
+
+        assert_eq!(dom.get_element_by_id("a"), None);
+        assert_eq!(dom.get_element_by_id("b"), None);
+    }
+}
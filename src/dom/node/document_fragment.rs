@@ -1,6 +1,37 @@
+use crate::dom::arena::NodeId;
+use crate::dom::inheritance::{private, Downcast};
+use crate::dom::iterators::TreeIterator;
+use crate::dom::node::{Node, NodeType};
+use crate::dom::node::element::Element;
 
 
+#[derive(Default)]
 pub struct DocumentFragment {
 }
 
+impl private::Sealed for DocumentFragment {}
 
+impl Downcast<Node> for DocumentFragment {
+    fn downcast_ref(node: &Node) -> &DocumentFragment {
+        match &node.node_type {
+            NodeType::DocumentFragment(document_fragment) => document_fragment,
+            _ => panic!("expected document fragment"),
+        }
+    }
+
+    fn downcast_mut(node: &mut Node) -> &mut DocumentFragment {
+        match &mut node.node_type {
+            NodeType::DocumentFragment(document_fragment) => document_fragment,
+            _ => panic!("expected document fragment"),
+        }
+    }
+}
+
+impl DocumentFragment {
+    // https://dom.spec.whatwg.org/#dom-nonelementparentnode-getelementbyid
+    /// Return the first element in tree order with the id, fragments are not indexed so this walks
+    /// the descendants.
+    pub fn get_element_by_id(document_fragment: NodeId, id: &str) -> Option<NodeId> {
+        TreeIterator::descendants(document_fragment).find(|node| Element::id(*node).as_deref() == Some(id))
+    }
+}
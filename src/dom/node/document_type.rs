@@ -0,0 +1,39 @@
+
+use crate::dom::inheritance::{private, Downcast};
+use crate::dom::node::{Node, NodeType};
+
+
+/// A doctype node, the name and identifiers are empty strings when missing.
+pub struct DocumentType {
+    pub name: String,
+    pub public_id: String,
+    pub system_id: String,
+}
+
+impl private::Sealed for DocumentType {}
+
+impl Downcast<Node> for DocumentType {
+    fn downcast_ref(node: &Node) -> &DocumentType {
+        match &node.node_type {
+            NodeType::DocumentType(document_type) => document_type,
+            _ => panic!("expected document type"),
+        }
+    }
+
+    fn downcast_mut(node: &mut Node) -> &mut DocumentType {
+        match &mut node.node_type {
+            NodeType::DocumentType(document_type) => document_type,
+            _ => panic!("expected document type"),
+        }
+    }
+}
+
+impl DocumentType {
+    pub fn new(name: String, public_id: String, system_id: String) -> DocumentType {
+        DocumentType {
+            name,
+            public_id,
+            system_id,
+        }
+    }
+}
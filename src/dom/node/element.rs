@@ -1,38 +1,115 @@
 use crate::dom::node::attribute::Attribute;
 use crate::dom::node::{Node, NodeType, QualifiedName};
-use crate::dom::arena::NodeId;
+use crate::dom::node::document::Document;
+use crate::dom::node::shadow_root::{ShadowRoot, ShadowRootInit, ShadowRootMode, SlotAssignmentMode};
+use crate::dom::node::slot::{self, AssignedNodesOptions};
+use crate::dom::arena::{self, NodeId};
 use crate::dom::inheritance::{private, Downcast};
+use crate::dom::custom_elements::{self, CustomElementDefinition, CustomElementReaction, LifecycleReaction, NullOrCustomElementRegistry};
+use crate::dom::exception::DomException;
+use crate::dom::mutation_observer;
+use crate::dom::collection::HtmlCollection;
+use crate::dom::iterators::NodeIterator;
+use crate::dom::serializer::{self, GetHtmlOptions};
+use crate::names::{self, Namespace, Atom, atoms};
+use crate::selectors::{SelectorList, MatchingContext};
 
+use std::collections::VecDeque;
 use std::rc::Rc;
-use std::cell::RefCell;
 
 
-struct CustomElementRegistry {
-    scoped: bool,
-}
+// https://dom.spec.whatwg.org/#valid-shadow-host-name
+/// Local names of html elements that can host a shadow root, besides custom elements.
+const SHADOW_HOST_NAMES: &[&str] = &[
+    "article", "aside", "blockquote", "body", "div", "footer", "h1", "h2", "h3", "h4", "h5", "h6", "header", "main", "nav",
+    "p", "section", "span",
+];
 
-#[derive(Clone, Default)]
-pub struct NullOrCustomElementRegistry {
-    registry: Option<Rc<RefCell<CustomElementRegistry>>>,
+// https://dom.spec.whatwg.org/#concept-attribute-qualified-name
+fn is_qualified_name(name: &QualifiedName, qualified_name: &str) -> bool {
+    match &name.prefix {
+        Some(prefix) => {
+            qualified_name.strip_prefix(prefix.as_str())
+                .and_then(|rest| rest.strip_prefix(':'))
+                .is_some_and(|local_name| name.local_name == local_name)
+        },
+        None => name.local_name == qualified_name,
+    }
 }
 
-impl NullOrCustomElementRegistry {
-    pub fn is_global_custom_element_registry(&self) -> bool {
-        self.registry.as_ref()
-            .map(|registry| !registry.borrow().scoped).unwrap_or_default()
+// https://dom.spec.whatwg.org/#validate-and-extract
+/// Validate the qualified name in the namespace and return the qualified name it describes.
+fn validate_and_extract(namespace: Option<&str>, qualified_name: &str, element: bool) -> Result<QualifiedName, DomException> {
+    let namespace = namespace.filter(|namespace| !namespace.is_empty()).map(Namespace::from);
+
+    let (prefix, local_name) = match qualified_name.split_once(':') {
+        Some((prefix, local_name)) => {
+            if !names::is_valid_namespace_prefix(prefix) {
+                return Err(DomException::InvalidCharacterError);
+            }
+
+            (Some(prefix), local_name)
+        },
+        None => (None, qualified_name),
+    };
+
+    let valid = match element {
+        true => names::is_valid_element_local_name(local_name),
+        false => names::is_valid_attribute_local_name(local_name),
+    };
+
+    if !valid {
+        return Err(DomException::InvalidCharacterError);
     }
 
-    pub fn effective_global_custom_element_registry(&self) -> NullOrCustomElementRegistry {
-        NullOrCustomElementRegistry {
-            registry: self.registry.clone().filter(|registry| !registry.borrow().scoped)
-        }
+    let is_xmlns = qualified_name == "xmlns" || prefix == Some("xmlns");
+
+    if (prefix.is_some() && namespace.is_none())
+        || (prefix == Some("xml") && namespace != Some(Namespace::Xml))
+        || (is_xmlns && namespace != Some(Namespace::Xmlns))
+        || (namespace == Some(Namespace::Xmlns) && !is_xmlns)
+    {
+        return Err(DomException::NamespaceError);
     }
+
+    Ok(QualifiedName::new_with_prefix(prefix.map(Atom::from), namespace, Atom::from(local_name)))
+}
+
+// https://dom.spec.whatwg.org/#concept-element-custom-element-state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomElementState {
+    Undefined,
+    Failed,
+    Uncustomized,
+    Custom,
 }
 
 pub struct Element {
     pub name: QualifiedName,
     pub custom_element_registry: NullOrCustomElementRegistry,
     pub attributes: Vec<Attribute>,
+
+    /// The is value the element was created with, for customized built-in elements.
+    pub is_value: Option<String>,
+
+    pub custom_element_state: CustomElementState,
+    pub custom_element_definition: Option<Rc<CustomElementDefinition>>,
+    pub custom_element_reaction_queue: VecDeque<CustomElementReaction>,
+
+    /// The form owner of a form associated element.
+    pub form_owner: Option<NodeId>,
+
+    /// Set when a form associated element was associated with a form by the parser.
+    pub parser_inserted: bool,
+
+    pub shadow_root: Option<NodeId>,
+
+    /// The slottables assigned to a slot element.
+    pub assigned_nodes: Vec<NodeId>,
+
+    /// The slottables assigned to a slot element with [`Element::assign`], for shadow trees with
+    /// manual slot assignment.
+    pub manually_assigned_nodes: Vec<NodeId>,
 }
 
 impl private::Sealed for Element {}
@@ -54,6 +131,751 @@ impl Downcast<Node> for Element {
 }
 
 impl Element {
+    pub fn new(name: QualifiedName, is_value: Option<String>, custom_element_registry: NullOrCustomElementRegistry) -> Element {
+        Element {
+            name,
+            custom_element_registry,
+            attributes: Vec::new(),
+            is_value,
+            custom_element_state: CustomElementState::Uncustomized,
+            custom_element_definition: None,
+            custom_element_reaction_queue: VecDeque::new(),
+            form_owner: None,
+            parser_inserted: false,
+            shadow_root: None,
+            assigned_nodes: Vec::new(),
+            manually_assigned_nodes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn for_each_reference(&mut self, f: &mut impl FnMut(&mut NodeId)) {
+        self.custom_element_registry.for_each_reference(f);
+
+        for attribute in self.attributes.iter_mut() {
+            f(&mut attribute.node_document);
+        }
+
+        self.form_owner.iter_mut()
+            .chain(self.shadow_root.iter_mut())
+            .chain(self.assigned_nodes.iter_mut())
+            .chain(self.manually_assigned_nodes.iter_mut())
+            .for_each(f);
+    }
+
+    /// Return the attribute with the qualified name, if it exists.
+    pub fn attribute(&self, name: QualifiedName) -> Option<&Attribute> {
+        self.attributes.iter().find(|attribute| attribute.name == name)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-element-defined
+    /// Check if the element is defined, meaning it is not waiting for an upgrade and did not fail one.
+    #[inline]
+    pub fn is_defined(&self) -> bool {
+        matches!(self.custom_element_state, CustomElementState::Uncustomized | CustomElementState::Custom)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-element-custom
+    #[inline]
+    pub fn is_custom(&self) -> bool {
+        self.custom_element_state == CustomElementState::Custom
+    }
+
+    // https://dom.spec.whatwg.org/#concept-create-element
+    /// Create an element in the document, a null registry uses the registry of the document.
+    pub fn create(
+        document: NodeId,
+        name: QualifiedName,
+        is: Option<&str>,
+        synchronous_custom_elements: bool,
+        registry: NullOrCustomElementRegistry,
+    ) -> NodeId {
+        let registry = match registry.is_null() {
+            true => arena::get(document).borrow().downcast_ref::<Document>().custom_element_registry.clone(),
+            false => registry,
+        };
+
+        if let Some(registry) = registry.registry() {
+            registry.borrow_mut().add_scoped_document(document);
+        }
+
+        let definition = registry.look_up(name.clone(), is);
+
+        let is_value = is.map(|is| is.to_string());
+
+        match definition {
+            Some(definition) => {
+                let mut element = Element::new(name.clone(), is_value, registry);
+
+                element.custom_element_state = CustomElementState::Undefined;
+
+                let element = arena::insert(Node::new(NodeType::Element(element), document));
+
+                // NOTE: for an autonomous custom element the spec constructs the element directly,
+                // upgrading the undefined element gives the same result as the constructor is only
+                // handed the element.
+                if synchronous_custom_elements {
+                    let _ = Element::upgrade(element, definition);
+                } else {
+                    custom_elements::enqueue_upgrade_reaction(element, definition);
+                }
+
+                element
+            },
+            None => {
+                let mut element = Element::new(name.clone(), is_value, registry);
+
+                if name.is_namespace(Namespace::Html) && (custom_elements::is_valid_custom_element_name(name.local_name.as_str()) || is.is_some()) {
+                    element.custom_element_state = CustomElementState::Undefined;
+                }
+
+                arena::insert(Node::new(NodeType::Element(element), document))
+            },
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/custom-elements.html#concept-upgrade-an-element
+    /// Upgrade the element with the definition, when the constructor fails the element is left in
+    /// the failed state and the exception is returned.
+    pub fn upgrade(element: NodeId, definition: Rc<CustomElementDefinition>) -> Result<(), DomException> {
+        let state = arena::get(element).borrow().downcast_ref::<Element>().custom_element_state;
+
+        if !matches!(state, CustomElementState::Undefined | CustomElementState::Uncustomized) {
+            return Ok(());
+        }
+
+        arena::with_mut(element, |node| {
+            let element = node.downcast_mut::<Element>();
+
+            element.custom_element_definition = Some(definition.clone());
+
+            element.custom_element_state = CustomElementState::Failed;
+        });
+
+        let attributes = arena::get(element).borrow().downcast_ref::<Element>().attributes.iter()
+            .map(|attribute| (attribute.name.clone(), attribute.value.clone()))
+            .collect::<Vec<_>>();
+
+        for (name, value) in attributes {
+            custom_elements::enqueue_callback_reaction(element, LifecycleReaction::AttributeChanged {
+                local_name: name.local_name,
+                old_value: None,
+                value: Some(value),
+                namespace: name.namespace,
+            });
+        }
+
+        if Node::is_connected(element) {
+            custom_elements::enqueue_callback_reaction(element, LifecycleReaction::Connected);
+        }
+
+        let result = match definition.disable_shadow && arena::get(element).borrow().downcast_ref::<Element>().shadow_root.is_some() {
+            true => Err(DomException::NotSupportedError),
+            false => (definition.constructor)(element),
+        };
+
+        arena::with_mut(element, |node| {
+            let element = node.downcast_mut::<Element>();
+
+            match result {
+                Ok(()) => element.custom_element_state = CustomElementState::Custom,
+                Err(_) => {
+                    element.custom_element_definition = None;
+
+                    element.custom_element_reaction_queue.clear();
+                },
+            }
+        });
+
+        result
+    }
+
+    // https://html.spec.whatwg.org/multipage/custom-elements.html#concept-try-upgrade
+    pub fn try_upgrade(element: NodeId) {
+        let definition = {
+            let node = arena::get(element);
+            let node = node.borrow();
+            let element = node.downcast_ref::<Element>();
+
+            element.custom_element_registry.look_up(element.name.clone(), element.is_value.as_deref())
+        };
+
+        if let Some(definition) = definition {
+            custom_elements::enqueue_upgrade_reaction(element, definition);
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#reset-the-form-owner
+    // NOTE: the form content attribute is not supported, so the form owner is the nearest form ancestor.
+    pub fn reset_form_owner(element: NodeId) {
+        let form_owner = arena::with_mut(element, |node| {
+            let element = node.downcast_mut::<Element>();
+
+            element.parser_inserted = false;
+
+            element.form_owner
+        });
+
+        if form_owner.is_some_and(|form| Node::is_inclusive_ancestor(form, element)) {
+            return;
+        }
+
+        let parent = arena::get(element).borrow().parent;
+
+        let form = parent.and_then(|parent| {
+            NodeIterator::new(Some(parent), |node| node.parent)
+                .find(|node| matches!(&arena::get(node).borrow().node_type, NodeType::Element(element) if element.name.is_html(atoms::FORM)))
+        });
+
+        arena::with_mut(element, |node| node.downcast_mut::<Element>().form_owner = form);
+    }
+
+    // NOTE: the dom only builds html documents, so names are lowercased for every html element.
+    fn adjust_attribute_name(element: NodeId, qualified_name: &str) -> String {
+        match arena::get(element).borrow().downcast_ref::<Element>().name.is_namespace(Namespace::Html) {
+            true => qualified_name.to_ascii_lowercase(),
+            false => qualified_name.to_string(),
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-element-attributes-get-by-name
+    fn attribute_by_name(element: NodeId, qualified_name: &str) -> Option<QualifiedName> {
+        let qualified_name = Element::adjust_attribute_name(element, qualified_name);
+
+        arena::get(element).borrow().downcast_ref::<Element>().attributes.iter()
+            .find(|attribute| is_qualified_name(&attribute.name, &qualified_name))
+            .map(|attribute| attribute.name.clone())
+    }
+
+    // https://dom.spec.whatwg.org/#concept-element-attributes-get-by-namespace
+    fn attribute_by_namespace(element: NodeId, namespace: Option<&str>, local_name: &str) -> Option<QualifiedName> {
+        let namespace = namespace.filter(|namespace| !namespace.is_empty()).map(Namespace::from);
+
+        arena::get(element).borrow().downcast_ref::<Element>().attributes.iter()
+            .find(|attribute| attribute.name.namespace == namespace && attribute.name.local_name == local_name)
+            .map(|attribute| attribute.name.clone())
+    }
+
+    fn attribute_value(element: NodeId, name: QualifiedName) -> Option<String> {
+        arena::get(element).borrow().downcast_ref::<Element>().attribute(name).map(|attribute| attribute.value.clone())
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-getattribute
+    pub fn get_attribute(element: NodeId, qualified_name: &str) -> Option<String> {
+        Element::attribute_by_name(element, qualified_name).and_then(|name| Element::attribute_value(element, name))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-getattributens
+    pub fn get_attribute_ns(element: NodeId, namespace: Option<&str>, local_name: &str) -> Option<String> {
+        Element::attribute_by_namespace(element, namespace, local_name).and_then(|name| Element::attribute_value(element, name))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-hasattribute
+    pub fn has_attribute(element: NodeId, qualified_name: &str) -> bool {
+        Element::attribute_by_name(element, qualified_name).is_some()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-hasattributens
+    pub fn has_attribute_ns(element: NodeId, namespace: Option<&str>, local_name: &str) -> bool {
+        Element::attribute_by_namespace(element, namespace, local_name).is_some()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-hasattributes
+    pub fn has_attributes(element: NodeId) -> bool {
+        !arena::get(element).borrow().downcast_ref::<Element>().attributes.is_empty()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-getattributenames
+    /// The qualified names of the attributes, in order.
+    pub fn attribute_names(element: NodeId) -> Vec<String> {
+        arena::get(element).borrow().downcast_ref::<Element>().attributes.iter()
+            .map(|attribute| attribute.qualified_name())
+            .collect()
+    }
+
+    /// A copy of the attributes of the element, in order.
+    pub fn attributes(element: NodeId) -> Vec<Attribute> {
+        arena::get(element).borrow().downcast_ref::<Element>().attributes.clone()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-setattribute
+    pub fn set_attribute(element: NodeId, qualified_name: &str, value: &str) -> Result<(), DomException> {
+        if !names::is_valid_attribute_local_name(qualified_name) {
+            return Err(DomException::InvalidCharacterError);
+        }
+
+        let qualified_name = Element::adjust_attribute_name(element, qualified_name);
+
+        custom_elements::ce_reactions(element, || {
+            match Element::attribute_by_name(element, &qualified_name) {
+                Some(name) => Element::change_attribute(element, name, value.to_string()),
+                None => Element::append_attribute(element, QualifiedName::new(None, Atom::from(qualified_name.as_str())), value.to_string()),
+            }
+        });
+
+        Ok(())
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-setattributens
+    pub fn set_attribute_ns(element: NodeId, namespace: Option<&str>, qualified_name: &str, value: &str) -> Result<(), DomException> {
+        let name = validate_and_extract(namespace, qualified_name, false)?;
+
+        custom_elements::ce_reactions(element, || {
+            let existing = arena::get(element).borrow().downcast_ref::<Element>().attributes.iter()
+                .find(|attribute| attribute.name.namespace == name.namespace && attribute.name.local_name == name.local_name)
+                .map(|attribute| attribute.name.clone());
+
+            match existing {
+                Some(existing) => Element::change_attribute(element, existing, value.to_string()),
+                None => Element::append_attribute(element, name, value.to_string()),
+            }
+        });
+
+        Ok(())
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-removeattribute
+    pub fn remove_attribute(element: NodeId, qualified_name: &str) {
+        if let Some(name) = Element::attribute_by_name(element, qualified_name) {
+            custom_elements::ce_reactions(element, || Element::remove_attribute_by_name(element, name));
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-removeattributens
+    pub fn remove_attribute_ns(element: NodeId, namespace: Option<&str>, local_name: &str) {
+        if let Some(name) = Element::attribute_by_namespace(element, namespace, local_name) {
+            custom_elements::ce_reactions(element, || Element::remove_attribute_by_name(element, name));
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-toggleattribute
+    /// Toggle the attribute, or force it to be present or absent, and return whether it is present.
+    pub fn toggle_attribute(element: NodeId, qualified_name: &str, force: Option<bool>) -> Result<bool, DomException> {
+        if !names::is_valid_attribute_local_name(qualified_name) {
+            return Err(DomException::InvalidCharacterError);
+        }
+
+        let qualified_name = Element::adjust_attribute_name(element, qualified_name);
+
+        let present = custom_elements::ce_reactions(element, || {
+            match Element::attribute_by_name(element, &qualified_name) {
+                None if force != Some(false) => {
+                    Element::append_attribute(element, QualifiedName::new(None, Atom::from(qualified_name.as_str())), String::new());
+
+                    true
+                },
+                None => false,
+                Some(name) if force != Some(true) => {
+                    Element::remove_attribute_by_name(element, name);
+
+                    false
+                },
+                Some(_) => true,
+            }
+        });
+
+        Ok(present)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-element-attributes-change
+    fn change_attribute(element: NodeId, name: QualifiedName, value: String) {
+        let old_value = arena::with_mut(element, |node| {
+            node.downcast_mut::<Element>().attributes.iter_mut()
+                .find(|attribute| attribute.name == name)
+                .map(|attribute| std::mem::replace(&mut attribute.value, value.clone()))
+        });
+
+        Element::handle_attribute_changes(element, name, old_value, Some(value));
+    }
+
+    // https://dom.spec.whatwg.org/#concept-element-attributes-remove
+    fn remove_attribute_by_name(element: NodeId, name: QualifiedName) {
+        let old_value = arena::with_mut(element, |node| {
+            let attributes = &mut node.downcast_mut::<Element>().attributes;
+
+            attributes.iter()
+                .position(|attribute| attribute.name == name)
+                .map(|index| attributes.remove(index).value)
+        });
+
+        if old_value.is_some() {
+            Element::handle_attribute_changes(element, name, old_value, None);
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-element-attributes-append
+    pub fn append_attribute(element: NodeId, name: QualifiedName, value: String) {
+        arena::with_mut(element, |node| {
+            let node_document = node.node_document;
+
+            node.downcast_mut::<Element>().attributes.push(Attribute::new(node_document, name.clone(), value.clone()));
+        });
+
+        Element::handle_attribute_changes(element, name, None, Some(value));
+    }
+
+    // https://dom.spec.whatwg.org/#handle-attribute-changes
+    pub fn handle_attribute_changes(element: NodeId, name: QualifiedName, old_value: Option<String>, value: Option<String>) {
+        mutation_observer::queue_attribute_mutation_record(element, name.local_name.clone(), name.namespace.clone(), old_value.clone());
+
+        Document::mutated(arena::get(element).borrow().node_document);
+
+        if arena::get(element).borrow().downcast_ref::<Element>().is_custom() {
+            custom_elements::enqueue_callback_reaction(element, LifecycleReaction::AttributeChanged {
+                local_name: name.local_name.clone(),
+                old_value: old_value.clone(),
+                value: value.clone(),
+                namespace: name.namespace.clone(),
+            });
+        }
+
+        Element::attribute_change_steps(element, name, old_value.as_deref(), value.as_deref());
+    }
+
+    // https://dom.spec.whatwg.org/#concept-element-attributes-change-ext
+    fn attribute_change_steps(element: NodeId, name: QualifiedName, old_value: Option<&str>, value: Option<&str>) {
+        let changed = old_value.unwrap_or_default() != value.unwrap_or_default();
+
+        // https://dom.spec.whatwg.org/#slot-name
+        if name.namespace.is_none() && name.local_name == atoms::NAME && slot::is_slot(element) && changed {
+            slot::assign_slottables_for_tree(Node::root(element));
+        }
+
+        // https://dom.spec.whatwg.org/#slotable-name
+        if name.namespace.is_none() && name.local_name == atoms::SLOT && changed {
+            let assigned_slot = arena::get(element).borrow().assigned_slot;
+
+            if let Some(assigned_slot) = assigned_slot {
+                slot::assign_slottables(assigned_slot);
+            }
+
+            slot::assign_slot(element);
+        }
+
+        if name.namespace.is_none() && name.local_name == atoms::ID && Node::is_in_document_tree(element) {
+            let document = arena::get(element).borrow().node_document;
+
+            if let Some(old_value) = old_value.filter(|old_value| !old_value.is_empty()) {
+                Document::remove_id(document, old_value, element);
+            }
+
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                Document::add_id(document, value.to_string(), element);
+            }
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-matches
+    /// Check if the element matches the selectors, with the element as the scoping root.
+    pub fn matches(element: NodeId, selectors: &str) -> Result<bool, DomException> {
+        Ok(SelectorList::parse(selectors)?.matches(element))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-closest
+    /// Return the closest inclusive ancestor element matching the selectors.
+    pub fn closest(element: NodeId, selectors: &str) -> Result<Option<NodeId>, DomException> {
+        let selectors = SelectorList::parse(selectors)?;
+
+        let context = MatchingContext::new(element, Some(element));
+
+        Ok(NodeIterator::new(Some(element), |node| node.parent)
+            .take_while(|node| matches!(arena::get(node).borrow().node_type, NodeType::Element(_)))
+            .find(|node| selectors.matches_with_context(*node, &context)))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-shadowroot
+    /// Return the shadow root of the element, closed shadow roots are not returned.
+    pub fn shadow_root(element: NodeId) -> Option<NodeId> {
+        arena::get(element).borrow().downcast_ref::<Element>().shadow_root
+            .filter(|shadow_root| ShadowRoot::mode(*shadow_root) == ShadowRootMode::Open)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-attachshadow
+    /// Attach a shadow root to the element and return it, the registry of the element is used when
+    /// the init has none.
+    pub fn attach_shadow(element: NodeId, init: ShadowRootInit) -> Result<NodeId, DomException> {
+        let (document, registry) = {
+            let node = arena::get(element);
+            let node = node.borrow();
+
+            (node.node_document, node.downcast_ref::<Element>().custom_element_registry.clone())
+        };
+
+        let registry = match init.custom_element_registry {
+            Some(registry) => {
+                let is_document_registry = arena::get(document).borrow().downcast_ref::<Document>().custom_element_registry.registry()
+                    .is_some_and(|document_registry| Rc::ptr_eq(document_registry, &registry));
+
+                if !registry.borrow().is_scoped() && !is_document_registry {
+                    return Err(DomException::NotSupportedError);
+                }
+
+                NullOrCustomElementRegistry::new(registry)
+            },
+            None => registry,
+        };
+
+        Element::attach_shadow_root(element, init.mode, init.clonable, init.serializable, init.delegates_focus, init.slot_assignment, registry)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-attach-a-shadow-root
+    fn attach_shadow_root(
+        element: NodeId,
+        mode: ShadowRootMode,
+        clonable: bool,
+        serializable: bool,
+        delegates_focus: bool,
+        slot_assignment: SlotAssignmentMode,
+        registry: NullOrCustomElementRegistry,
+    ) -> Result<NodeId, DomException> {
+        let (document, name, is_value, current, custom) = {
+            let node = arena::get(element);
+            let node = node.borrow();
+            let element = node.downcast_ref::<Element>();
+
+            (node.node_document, element.name.clone(), element.is_value.clone(), element.shadow_root, element.is_custom())
+        };
+
+        if !name.is_namespace(Namespace::Html) {
+            return Err(DomException::NotSupportedError);
+        }
+
+        let is_custom_element_name = custom_elements::is_valid_custom_element_name(name.local_name.as_str());
+
+        if !is_custom_element_name && !SHADOW_HOST_NAMES.contains(&name.local_name.as_str()) {
+            return Err(DomException::NotSupportedError);
+        }
+
+        if is_custom_element_name || is_value.is_some() {
+            let definition = arena::get(element).borrow().downcast_ref::<Element>().custom_element_registry
+                .look_up(name, is_value.as_deref());
+
+            if definition.is_some_and(|definition| definition.disable_shadow) {
+                return Err(DomException::NotSupportedError);
+            }
+        }
+
+        if let Some(current) = current {
+            let (declarative, current_mode) = {
+                let node = arena::get(current);
+                let node = node.borrow();
+                let shadow_root = node.downcast_ref::<ShadowRoot>();
+
+                (shadow_root.declarative, shadow_root.mode)
+            };
+
+            if !declarative || current_mode != mode {
+                return Err(DomException::NotSupportedError);
+            }
+
+            while let Some(child) = arena::get(current).borrow().first_child {
+                Node::remove(child, false);
+            }
+
+            arena::with_mut(current, |node| node.downcast_mut::<ShadowRoot>().declarative = false);
+
+            return Ok(current);
+        }
+
+        if let Some(registry) = registry.registry() {
+            registry.borrow_mut().add_scoped_document(document);
+        }
+
+        let mut shadow_root = ShadowRoot::new(element, mode, registry);
+
+        shadow_root.delegates_focus = delegates_focus;
+        shadow_root.available_to_element_internals = custom;
+        shadow_root.slot_assignment = slot_assignment;
+        shadow_root.clonable = clonable;
+        shadow_root.serializable = serializable;
+
+        let shadow_root = arena::insert(Node::new(NodeType::ShadowRoot(shadow_root), document));
+
+        arena::with_mut(element, |node| node.downcast_mut::<Element>().shadow_root = Some(shadow_root));
+
+        Ok(shadow_root)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-slotable-assignedslot
+    /// Return the slot the element is assigned to, slots in closed shadow trees are not returned.
+    pub fn assigned_slot(element: NodeId) -> Option<NodeId> {
+        slot::find_slot(element, true)
+    }
+
+    // https://html.spec.whatwg.org/multipage/scripting.html#dom-slot-assignednodes
+    /// Return the slottables assigned to the slot element, flattening assigned slots when asked to.
+    pub fn assigned_nodes(slot: NodeId, options: AssignedNodesOptions) -> Vec<NodeId> {
+        match options.flatten {
+            true => slot::find_flattened_slottables(slot),
+            false => slot::assigned_nodes(slot),
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/scripting.html#dom-slot-assignedelements
+    pub fn assigned_elements(slot: NodeId, options: AssignedNodesOptions) -> Vec<NodeId> {
+        Element::assigned_nodes(slot, options).into_iter()
+            .filter(|node| matches!(arena::get(node).borrow().node_type, NodeType::Element(_)))
+            .collect()
+    }
+
+    // https://html.spec.whatwg.org/multipage/scripting.html#dom-slot-assign
+    /// Manually assign the slottables to the slot element, taking them from the slots they were
+    /// manually assigned to before.
+    pub fn assign(slot: NodeId, nodes: &[NodeId]) -> Result<(), DomException> {
+        if !nodes.iter().all(|node| slot::is_slottable(*node)) {
+            return Err(DomException::TypeError);
+        }
+
+        let manually_assigned_nodes = arena::with_mut(slot, |node| std::mem::take(&mut node.downcast_mut::<Element>().manually_assigned_nodes));
+
+        for node in manually_assigned_nodes.into_iter().filter_map(arena::try_get) {
+            node.borrow_mut().manual_slot_assignment = None;
+        }
+
+        let mut nodes_set = Vec::<NodeId>::new();
+
+        for node in nodes.iter().copied() {
+            let previous = arena::get(node).borrow().manual_slot_assignment;
+
+            if let Some(previous) = previous {
+                arena::with_mut(previous, |previous| {
+                    previous.downcast_mut::<Element>().manually_assigned_nodes.retain(|other| *other != node);
+                });
+            }
+
+            arena::with_mut(node, |node| node.manual_slot_assignment = Some(slot));
+
+            if !nodes_set.contains(&node) {
+                nodes_set.push(node);
+            }
+        }
+
+        arena::with_mut(slot, |node| node.downcast_mut::<Element>().manually_assigned_nodes = nodes_set);
+
+        slot::assign_slottables_for_tree(Node::root(slot));
+
+        Ok(())
+    }
+
+    // https://dom.spec.whatwg.org/#dom-parentnode-children
+    /// A live collection of the element children of the element.
+    pub fn children(element: NodeId) -> HtmlCollection {
+        HtmlCollection::children(element)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-getelementsbytagname
+    pub fn get_elements_by_tag_name(element: NodeId, qualified_name: &str) -> HtmlCollection {
+        HtmlCollection::with_qualified_name(element, qualified_name)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-getelementsbytagnamens
+    pub fn get_elements_by_tag_name_ns(element: NodeId, namespace: Option<&str>, local_name: &str) -> HtmlCollection {
+        HtmlCollection::with_namespace_and_local_name(element, namespace, local_name)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-element-getelementsbyclassname
+    pub fn get_elements_by_class_name(element: NodeId, class_names: &str) -> HtmlCollection {
+        HtmlCollection::with_class_names(element, class_names)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-id
+    /// The id of the element, this is none for other nodes and when the id attribute is empty.
+    pub fn id(node: NodeId) -> Option<String> {
+        match &arena::get(node).borrow().node_type {
+            NodeType::Element(element) => {
+                element.attribute(QualifiedName::new(None, atoms::ID))
+                    .map(|attribute| attribute.value.clone())
+                    .filter(|id| !id.is_empty())
+            },
+            _ => None,
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-innerhtml
+    /// Serialize the children of the element.
+    pub fn inner_html(element: NodeId) -> String {
+        serializer::serialize_children(element, &GetHtmlOptions::default())
+    }
+
+    // https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-outerhtml
+    /// Serialize the element together with its children.
+    pub fn outer_html(element: NodeId) -> String {
+        serializer::serialize_node(element, &GetHtmlOptions::default())
+    }
+
+    // https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-gethtml
+    /// Serialize the children of the element, including the shadow root when the options select it.
+    pub fn get_html(element: NodeId, options: &GetHtmlOptions) -> String {
+        serializer::serialize_children(element, options)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::Dom;
+    use crate::dom::testing::create_element;
+
+    const XLINK: &str = "http://www.w3.org/1999/xlink";
+
+    #[test]
+    fn attributes() {
+        let mut dom = Dom::new();
+        let element = create_element(&mut dom, "div");
+
+        assert!(!Element::has_attributes(element));
+
+        assert_eq!(Element::set_attribute(element, "Title", "a"), Ok(()));
+        assert_eq!(Element::set_attribute(element, "id", "b"), Ok(()));
+        assert_eq!(Element::set_attribute(element, "TITLE", "c"), Ok(()));
+
+        assert_eq!(Element::get_attribute(element, "title").as_deref(), Some("c"));
+        assert_eq!(Element::attribute_names(element), vec!["title", "id"]);
+        assert_eq!(Element::get_attribute(element, "id").as_deref(), Some("b"));
 
+        assert_eq!(Element::set_attribute(element, "a b", "x"), Err(DomException::InvalidCharacterError));
+        assert_eq!(Element::set_attribute(element, "", "x"), Err(DomException::InvalidCharacterError));
+
+        Element::remove_attribute(element, "TiTle");
+        Element::remove_attribute(element, "missing");
+
+        assert!(!Element::has_attribute(element, "title"));
+        assert_eq!(Element::attribute_names(element), vec!["id"]);
+    }
+
+    #[test]
+    fn toggling_attributes() {
+        let mut dom = Dom::new();
+        let element = create_element(&mut dom, "input");
+
+        assert_eq!(Element::toggle_attribute(element, "Hidden", None), Ok(true));
+        assert_eq!(Element::get_attribute(element, "hidden").as_deref(), Some(""));
+        assert_eq!(Element::toggle_attribute(element, "hidden", Some(true)), Ok(true));
+        assert_eq!(Element::toggle_attribute(element, "hidden", None), Ok(false));
+        assert_eq!(Element::toggle_attribute(element, "hidden", Some(false)), Ok(false));
+        assert_eq!(Element::toggle_attribute(element, "a=b", None), Err(DomException::InvalidCharacterError));
+
+        assert!(!Element::has_attributes(element));
+    }
+
+    #[test]
+    fn namespaced_attributes() {
+        let mut dom = Dom::new();
+        let element = create_element(&mut dom, "a");
+
+        assert_eq!(Element::set_attribute_ns(element, Some(XLINK), "xlink:href", "#a"), Ok(()));
+        assert_eq!(Element::set_attribute_ns(element, Some(XLINK), "other:href", "#b"), Ok(()));
+
+        assert_eq!(Element::attribute_names(element), vec!["xlink:href"]);
+        assert_eq!(Element::get_attribute(element, "xlink:href").as_deref(), Some("#b"));
+        assert_eq!(Element::get_attribute_ns(element, Some(XLINK), "href").as_deref(), Some("#b"));
+        assert_eq!(Element::get_attribute(element, "href"), None);
+
+        assert_eq!(Element::set_attribute_ns(element, None, "a:b", "x"), Err(DomException::NamespaceError));
+        assert_eq!(Element::set_attribute_ns(element, Some(XLINK), "xmlns", "x"), Err(DomException::NamespaceError));
+        assert_eq!(Element::set_attribute_ns(element, Some(XLINK), "a b:c", "x"), Err(DomException::InvalidCharacterError));
+
+        Element::remove_attribute_ns(element, Some(XLINK), "href");
+
+        assert!(!Element::has_attribute_ns(element, Some(XLINK), "href"));
+    }
+}
@@ -1,42 +1,57 @@
 pub mod document_fragment;
+pub mod document_type;
 pub mod document;
 pub mod element;
 pub mod comment;
-mod attribute;
-
-use crate::dom::iterators::{NodeIterator, TreeIterator};
+pub mod character_data;
+pub mod text;
+pub mod processing_instruction;
+pub mod attribute;
+pub mod shadow_root;
+pub mod slot;
+
+use crate::dom::iterators::{AxisIterator, NodeIterator, TreeIterator};
 use crate::dom::arena::{self, NodeId};
+use crate::dom::custom_elements::{self, LifecycleReaction};
+use crate::dom::exception::DomException;
+use crate::dom::collection::NodeList;
+use crate::dom::mutation_observer::{self, RegisteredObserver};
+use crate::dom::event::EventListener;
+use crate::dom::traversal;
+use crate::selectors::{SelectorList, MatchingContext};
+
+use std::cmp::Ordering;
+use std::iter::Rev;
+use std::rc::Rc;
 
 use document_fragment::DocumentFragment;
+use document_type::DocumentType;
 use document::Document;
 use element::Element;
 use comment::Comment;
+use text::{Text, CdataSection};
+use processing_instruction::ProcessingInstruction;
+use shadow_root::{ShadowRoot, SlotAssignmentMode};
 
+pub use crate::names::QualifiedName;
 
-/// The local name, namespace and namespace prefix of a node in the DOM tree.
-#[derive(Clone)]
-pub struct QualifiedName {
-    pub namespace: Option<String>,
-    pub namespace_prefix: Option<String>,
-    pub local_name: String,
-}
 
-impl QualifiedName {
-    /// Create a new qualified name with a local_name, namespace and namespace prefix.
-    pub fn new_with_ns(local_name: String, namespace: String, namespace_prefix: Option<String>) -> QualifiedName {
-        QualifiedName {
-            namespace: Some(namespace),
-            namespace_prefix,
-            local_name,
-        }
-    }
+// https://dom.spec.whatwg.org/#dictdef-getrootnodeoptions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GetRootNodeOptions {
+    pub composed: bool,
 }
 
 pub enum NodeType {
     Element(Element),
     Document(Document),
+    DocumentType(DocumentType),
     DocumentFragment(DocumentFragment),
+    Text(Text),
+    CdataSection(CdataSection),
+    ProcessingInstruction(ProcessingInstruction),
     Comment(Comment),
+    ShadowRoot(ShadowRoot),
 }
 
 pub struct Node {
@@ -63,6 +78,21 @@ pub struct Node {
 
     /// The count of children of the node.
     pub(crate) child_count: usize,
+
+    // https://dom.spec.whatwg.org/#registered-observer-list
+    /// The mutation observers observing the node.
+    pub(crate) registered_observers: Vec<RegisteredObserver>,
+
+    // https://dom.spec.whatwg.org/#slotable-assigned-slot
+    /// The slot a slottable is assigned to.
+    pub(crate) assigned_slot: Option<NodeId>,
+
+    // https://dom.spec.whatwg.org/#slottable-manual-slot-assignment
+    /// The slot a slottable was manually assigned to.
+    pub(crate) manual_slot_assignment: Option<NodeId>,
+
+    // https://dom.spec.whatwg.org/#eventtarget-event-listener-list
+    pub(crate) event_listener_list: Vec<Rc<EventListener>>,
 }
 
 impl Node {
@@ -76,119 +106,843 @@ impl Node {
             first_child: None,
             last_child: None,
             child_count: 0,
+            registered_observers: Vec::new(),
+            assigned_slot: None,
+            manual_slot_assignment: None,
+            event_listener_list: Vec::new(),
+        }
+    }
+
+    /// Visit the ids of the nodes the node refers to, these nodes are kept alive as long as the node
+    /// is and the ids are updated when the arena is compacted.
+    pub(crate) fn for_each_reference(&mut self, f: &mut impl FnMut(&mut NodeId)) {
+        f(&mut self.node_document);
+
+        let links = [
+            &mut self.parent,
+            &mut self.previous_sibling,
+            &mut self.next_sibling,
+            &mut self.first_child,
+            &mut self.last_child,
+            &mut self.assigned_slot,
+            &mut self.manual_slot_assignment,
+        ];
+
+        for id in links.into_iter().flatten() {
+            f(id);
+        }
+
+        for registered in self.registered_observers.iter_mut() {
+            registered.for_each_reference(f);
+        }
+
+        match &mut self.node_type {
+            NodeType::Element(element) => element.for_each_reference(f),
+            NodeType::Document(document) => document.for_each_reference(f),
+            NodeType::ShadowRoot(shadow_root) => shadow_root.for_each_reference(f),
+            _ => {},
         }
     }
 
     pub fn first_descendant(node: NodeId) -> NodeId {
-        arena::get(node).first_child.map(|child| Node::first_descendant(child)).unwrap_or(node)
+        arena::get(node).borrow().first_child.map(Node::first_descendant).unwrap_or(node)
     }
 
     pub fn root(node: NodeId) -> NodeId {
-        arena::get(node).parent.map(|parent| Node::root(parent)).unwrap_or(node)
+        arena::get(node).borrow().parent.map(Node::root).unwrap_or(node)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-shadow-including-root
+    /// The root of the node, continuing from the host when the root is a shadow root.
+    pub fn shadow_including_root(node: NodeId) -> NodeId {
+        let root = Node::root(node);
+
+        match &arena::get(root).borrow().node_type {
+            NodeType::ShadowRoot(shadow_root) => Node::shadow_including_root(shadow_root.host),
+            _ => root,
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-node-getrootnode
+    /// The root of the node, or its shadow-including root when composed.
+    pub fn get_root_node(node: NodeId, options: GetRootNodeOptions) -> NodeId {
+        match options.composed {
+            true => Node::shadow_including_root(node),
+            false => Node::root(node),
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#connected
+    /// Check if the shadow-including root of the node is a document.
+    pub fn is_connected(node: NodeId) -> bool {
+        matches!(arena::get(Node::shadow_including_root(node)).borrow().node_type, NodeType::Document(_))
+    }
+
+    // https://dom.spec.whatwg.org/#in-a-document-tree
+    /// Check if the root of the node is a document, nodes in shadow trees are connected but not in
+    /// the document tree.
+    pub fn is_in_document_tree(node: NodeId) -> bool {
+        matches!(arena::get(Node::root(node)).borrow().node_type, NodeType::Document(_))
     }
 
     pub fn descendants(&self) -> TreeIterator {
-        TreeIterator::new(self.first_child.map(|child| Node::first_descendant(child)))
+        match self.first_child.and_then(|child| arena::get(child).borrow().parent) {
+            Some(parent) => TreeIterator::descendants(parent),
+            None => TreeIterator::new(None),
+        }
+    }
+
+    pub fn children(&self) -> AxisIterator {
+        AxisIterator::children(self)
+    }
+
+    /// Iterate over the children of the node, last child first.
+    pub fn reverse_children(node: NodeId) -> Rev<AxisIterator> {
+        arena::get(node).borrow().children().rev()
+    }
+
+    // https://dom.spec.whatwg.org/#concept-tree-inclusive-ancestor
+    /// Iterate over the node and its ancestors, from the node up to its root.
+    pub fn inclusive_ancestors(node: NodeId) -> AxisIterator {
+        AxisIterator::inclusive_ancestors(node)
     }
 
-    pub fn children(&self) -> NodeIterator {
-        NodeIterator::new(self.first_child, |node| node.next_sibling)
+    /// Iterate over the siblings after the node, nearest first.
+    pub fn following_siblings(node: NodeId) -> AxisIterator {
+        AxisIterator::following_siblings(node)
+    }
+
+    /// Iterate over the siblings before the node, nearest first.
+    pub fn preceding_siblings(node: NodeId) -> AxisIterator {
+        AxisIterator::preceding_siblings(node)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-tree-following
+    /// Iterate over the nodes after the node in tree order, including its descendants.
+    pub fn following(node: NodeId) -> AxisIterator {
+        AxisIterator::following(node)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-tree-preceding
+    /// Iterate over the nodes before the node in tree order, nearest first, including its ancestors.
+    pub fn preceding(node: NodeId) -> AxisIterator {
+        AxisIterator::preceding(node)
+    }
+
+    /// Iterate over the descendants of the node in post-order, every node comes after its descendants.
+    pub fn post_order_descendants(node: NodeId) -> AxisIterator {
+        AxisIterator::post_order_descendants(node)
     }
 
     pub fn index(&self) -> usize {
         NodeIterator::new(self.previous_sibling, |node| node.previous_sibling).count()
     }
 
-    pub fn insert(&mut self, new_node: NodeId, child: Option<NodeId>) {
-        let nodes = matches!(arena::get(new_node).node_type, NodeType::DocumentFragment(_))
-            .then(|| arena::get(new_node).children().collect::<Vec<NodeId>>())
-            .unwrap_or_else(|| vec![new_node]);
+    // https://dom.spec.whatwg.org/#dom-node-childnodes
+    /// A live list of the children of the node.
+    pub fn child_nodes(node: NodeId) -> NodeList {
+        NodeList::children(node)
+    }
 
-        if nodes.len() > 0  {
-            arena::with_mut(new_node, |new_node| {
-                if let NodeType::DocumentFragment(_) = new_node.node_type {
-                    for node in &nodes {
-                        new_node.remove(*node);
-                    }
+    // https://dom.spec.whatwg.org/#concept-node-insert
+    /// Insert the node into the parent before the child, no mutation record is queued for the parent
+    /// when the observers are suppressed.
+    pub fn insert(parent: NodeId, new_node: NodeId, child: Option<NodeId>, suppress_observers: bool) {
+        let is_fragment = matches!(arena::get(new_node).borrow().node_type, NodeType::DocumentFragment(_));
+
+        let nodes = if is_fragment {
+            arena::get(new_node).borrow().children().collect::<Vec<NodeId>>()
+        } else {
+            vec![new_node]
+        };
+
+        if !nodes.is_empty() {
+            if is_fragment {
+                for node in &nodes {
+                    Node::remove(*node, true);
                 }
-            });
 
-            if let Some(parent) = self.parent && let Some(child) = child {
-                arena::with_mut(self.node_document, |node_document| {
-                    for range in node_document.downcast_mut::<Document>().ranges.iter_mut() {
+                mutation_observer::queue_tree_mutation_record(new_node, Vec::new(), nodes.clone(), None, None);
+            }
+
+            let node_document = arena::get(parent).borrow().node_document;
+
+            if let Some(child) = child {
+                Document::with_ranges(node_document, |ranges| {
+                    for range in ranges.iter_mut() {
                         range.adjust_offset(parent, child, nodes.len());
                     }
                 });
             }
 
-            // NOTE: previous sibling is only used with the shadow root related steps
-            // let previous_sibling = child.map(|node| arena::get(node).previous_sibling)
-            //    .unwrap_or_else(|| self.last_child.clone());
+            let previous_sibling = match child {
+                Some(child) => arena::get(child).borrow().previous_sibling,
+                None => arena::get(parent).borrow().last_child,
+            };
 
-            for node in nodes {
-                Document::adopt(self.node_document, node);
+            for node in nodes.iter().copied() {
+                Document::adopt(node_document, node);
 
                 if let Some(child) = child {
-                    self.insert_before(node, child);
+                    Node::insert_before(parent, node, child);
                 } else {
-                    self.append(node);
+                    Node::append(parent, node);
                 }
 
-                // TODO: implement step 4, 5, 6, and 7 once we have shadow root elements
+                Document::mutated(node_document);
+
+                let named_host = match &arena::get(parent).borrow().node_type {
+                    NodeType::Element(element) => element.shadow_root
+                        .is_some_and(|shadow_root| ShadowRoot::slot_assignment(shadow_root) == SlotAssignmentMode::Named),
+                    _ => false,
+                };
+
+                if named_host && slot::is_slottable(node) {
+                    slot::assign_slot(node);
+                }
+
+                if slot::is_unassigned_slot_in_shadow_tree(parent) {
+                    mutation_observer::signal_slot_change(parent);
+                }
+
+                // NOTE: the slots outside of the inserted node keep their assigned nodes, so the
+                // tree only has to be assigned when a slot was inserted.
+                if TreeIterator::new(Some(node)).any(slot::is_slot) {
+                    slot::assign_slottables_for_tree(Node::root(node));
+                }
+
+                let connected = Node::is_connected(node);
+
+                for descendant in TreeIterator::shadow_including(node) {
+                    Node::insertion_steps(descendant, connected);
+
+                    if !connected {
+                        continue;
+                    }
+
+                    let custom = match &arena::get(descendant).borrow().node_type {
+                        NodeType::Element(element) => Some(element.is_custom()),
+                        _ => None,
+                    };
+
+                    match custom {
+                        Some(true) => custom_elements::enqueue_callback_reaction(descendant, LifecycleReaction::Connected),
+                        Some(false) => Element::try_upgrade(descendant),
+                        None => {},
+                    }
+                }
 
                 // TODO: children changed steps will have to mark the children as dirty when we are
                 // to render the next layout tree
 
-                // TODO: implement step 10, 11, and 12 once we have shadow root elements
+                // NOTE: step 10, 11, and 12 run the post-connection steps, none of the nodes we
+                // implement define any.
+            }
+
+            if !suppress_observers {
+                mutation_observer::queue_tree_mutation_record(parent, nodes, Vec::new(), previous_sibling, child);
             }
         }
     }
 
-    fn append(&mut self, node: NodeId) {
-        if let Some(first_child) = self.first_child {
-            let previous = self.last_child.unwrap_or(first_child);
+    fn append(parent: NodeId, node: NodeId) {
+        let last_child = arena::get(parent).borrow().last_child;
+
+        if let Some(last_child) = last_child {
+            arena::with_mut(last_child, |last_child| last_child.next_sibling = Some(node));
+        }
+
+        arena::with_mut(node, |node| {
+            node.parent = Some(parent);
+
+            node.previous_sibling = last_child;
 
-            arena::with_mut(previous, |previous| previous.next_sibling = Some(node));
+            node.next_sibling = None;
+        });
 
-            arena::with_mut(node, |node| node.previous_sibling = Some(previous));
+        arena::with_mut(parent, |parent| {
+            parent.first_child.get_or_insert(node);
 
-            self.last_child = Some(node);
+            parent.last_child = Some(node);
+
+            parent.child_count += 1;
+        });
+    }
+
+    fn insert_before(parent: NodeId, node: NodeId, before: NodeId) {
+        let previous_sibling = arena::get(before).borrow().previous_sibling;
+
+        if let Some(previous_sibling) = previous_sibling {
+            arena::with_mut(previous_sibling, |previous_sibling| previous_sibling.next_sibling = Some(node));
         } else {
-            self.first_child = Some(node);
+            arena::with_mut(parent, |parent| parent.first_child = Some(node));
         }
 
-        self.child_count += 1;
+        arena::with_mut(node, |node| {
+            node.parent = Some(parent);
+
+            node.previous_sibling = previous_sibling;
+
+            node.next_sibling = Some(before);
+        });
+
+        arena::with_mut(before, |before| before.previous_sibling = Some(node));
+
+        arena::with_mut(parent, |parent| parent.child_count += 1);
     }
 
-    fn insert_before(&mut self, node: NodeId, before: NodeId) {
-        if let Some(previous_sibling) = arena::get(before).previous_sibling {
-            arena::with_mut(previous_sibling, |previous_sibling| previous_sibling.next_sibling = Some(node));
+    // https://dom.spec.whatwg.org/#concept-node-ensure-pre-insertion-validity
+    fn ensure_pre_insertion_validity(node: NodeId, parent: NodeId, child: Option<NodeId>) -> Result<(), DomException> {
+        Node::ensure_validity(node, parent, child)?;
 
-            arena::with_mut(node, |node| {
-                node.previous_sibling = Some(previous_sibling);
+        if !Node::is_document(parent) {
+            return Ok(());
+        }
 
-                node.next_sibling = Some(before);
-            });
+        let doctype_following = |child: NodeId| {
+            NodeIterator::new(arena::get(child).borrow().next_sibling, |node| node.next_sibling).any(Node::is_doctype)
+        };
+
+        let invalid = match &arena::get(node).borrow().node_type {
+            NodeType::DocumentFragment(_) => {
+                let elements = arena::get(node).borrow().children().filter(|child| Node::is_element(*child)).count();
+
+                elements > 1
+                    || arena::get(node).borrow().children().any(Text::is_text)
+                    || (elements == 1 && (Node::has_element_child(parent) || child.is_some_and(|child| Node::is_doctype(child) || doctype_following(child))))
+            },
+            NodeType::Element(_) => {
+                Node::has_element_child(parent) || child.is_some_and(|child| Node::is_doctype(child) || doctype_following(child))
+            },
+            NodeType::DocumentType(_) => {
+                let element_preceding = |child: NodeId| {
+                    NodeIterator::new(arena::get(child).borrow().previous_sibling, |node| node.previous_sibling).any(Node::is_element)
+                };
+
+                arena::get(parent).borrow().children().any(Node::is_doctype)
+                    || child.map(element_preceding).unwrap_or_else(|| Node::has_element_child(parent))
+            },
+            _ => false,
+        };
+
+        match invalid {
+            true => Err(DomException::HierarchyRequestError),
+            false => Ok(()),
+        }
+    }
 
-            arena::with_mut(before, |before| before.previous_sibling = Some(node));
-        } else {
-            self.first_child = Some(node);
+    // https://dom.spec.whatwg.org/#concept-node-replace
+    fn ensure_replace_validity(child: NodeId, node: NodeId, parent: NodeId) -> Result<(), DomException> {
+        Node::ensure_validity(node, parent, Some(child))?;
 
-            arena::with_mut(node, |node| node.next_sibling = Some(before));
+        if !Node::is_document(parent) {
+            return Ok(());
+        }
+
+        let other_element_child = || arena::get(parent).borrow().children().any(|other| other != child && Node::is_element(other));
+
+        let doctype_following = || NodeIterator::new(arena::get(child).borrow().next_sibling, |node| node.next_sibling).any(Node::is_doctype);
+
+        let invalid = match &arena::get(node).borrow().node_type {
+            NodeType::DocumentFragment(_) => {
+                let elements = arena::get(node).borrow().children().filter(|child| Node::is_element(*child)).count();
+
+                elements > 1 || arena::get(node).borrow().children().any(Text::is_text) || (elements == 1 && (other_element_child() || doctype_following()))
+            },
+            NodeType::Element(_) => other_element_child() || doctype_following(),
+            NodeType::DocumentType(_) => {
+                arena::get(parent).borrow().children().any(|other| other != child && Node::is_doctype(other))
+                    || NodeIterator::new(arena::get(child).borrow().previous_sibling, |node| node.previous_sibling).any(Node::is_element)
+            },
+            _ => false,
+        };
+
+        match invalid {
+            true => Err(DomException::HierarchyRequestError),
+            false => Ok(()),
+        }
+    }
+
+    /// The checks shared by pre-insertion and replace validity, everything but the document child rules.
+    fn ensure_validity(node: NodeId, parent: NodeId, child: Option<NodeId>) -> Result<(), DomException> {
+        if !matches!(arena::get(parent).borrow().node_type, NodeType::Document(_) | NodeType::DocumentFragment(_) | NodeType::ShadowRoot(_) | NodeType::Element(_)) {
+            return Err(DomException::HierarchyRequestError);
+        }
+
+        if Node::is_host_including_inclusive_ancestor(node, parent) {
+            return Err(DomException::HierarchyRequestError);
+        }
+
+        if let Some(child) = child && arena::get(child).borrow().parent != Some(parent) {
+            return Err(DomException::NotFoundError);
+        }
+
+        match arena::get(node).borrow().node_type {
+            NodeType::Document(_) | NodeType::ShadowRoot(_) => Err(DomException::HierarchyRequestError),
+            NodeType::Text(_) | NodeType::CdataSection(_) if Node::is_document(parent) => Err(DomException::HierarchyRequestError),
+            NodeType::DocumentType(_) if !Node::is_document(parent) => Err(DomException::HierarchyRequestError),
+            _ => Ok(()),
+        }
+    }
 
-            if self.last_child.is_none() {
-                self.last_child = Some(before);
+    /// Adopt a node of another dom into the node document of the parent before it is inserted, the
+    /// node is moved into the arena of the parent and the new id is returned.
+    fn adopt_from_other_dom(node: NodeId, parent: NodeId) -> NodeId {
+        match arena::is_same_arena(node, parent) {
+            true => node,
+            false => Document::adopt(arena::get(parent).borrow().node_document, node),
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-node-pre-insert
+    /// Insert the node into the parent before the child, or as the last child when the child is none.
+    pub fn pre_insert(node: NodeId, parent: NodeId, child: Option<NodeId>) -> Result<NodeId, DomException> {
+        Node::ensure_pre_insertion_validity(node, parent, child)?;
+
+        let node = Node::adopt_from_other_dom(node, parent);
+
+        let reference_child = match child == Some(node) {
+            true => arena::get(node).borrow().next_sibling,
+            false => child,
+        };
+
+        Node::insert(parent, node, reference_child, false);
+
+        Ok(node)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-node-replace
+    /// Replace the child of the parent with the node and return the child.
+    pub fn replace(child: NodeId, node: NodeId, parent: NodeId) -> Result<NodeId, DomException> {
+        Node::ensure_replace_validity(child, node, parent)?;
+
+        let node = Node::adopt_from_other_dom(node, parent);
+
+        let mut reference_child = arena::get(child).borrow().next_sibling;
+
+        if reference_child == Some(node) {
+            reference_child = arena::get(node).borrow().next_sibling;
+        }
+
+        let mut previous_sibling = arena::get(child).borrow().previous_sibling;
+
+        if previous_sibling == Some(node) {
+            previous_sibling = arena::get(node).borrow().previous_sibling;
+        }
+
+        let mut removed_nodes = Vec::new();
+
+        if arena::get(child).borrow().parent.is_some() {
+            removed_nodes.push(child);
+
+            Node::remove(child, true);
+        }
+
+        let nodes = match arena::get(node).borrow().node_type {
+            NodeType::DocumentFragment(_) => arena::get(node).borrow().children().collect(),
+            _ => vec![node],
+        };
+
+        Node::insert(parent, node, reference_child, true);
+
+        mutation_observer::queue_tree_mutation_record(parent, nodes, removed_nodes, previous_sibling, reference_child);
+
+        Ok(child)
+    }
+
+    #[inline]
+    fn is_document(node: NodeId) -> bool {
+        matches!(arena::get(node).borrow().node_type, NodeType::Document(_))
+    }
+
+    #[inline]
+    fn is_doctype(node: NodeId) -> bool {
+        matches!(arena::get(node).borrow().node_type, NodeType::DocumentType(_))
+    }
+
+    #[inline]
+    fn is_element(node: NodeId) -> bool {
+        matches!(arena::get(node).borrow().node_type, NodeType::Element(_))
+    }
+
+    fn has_element_child(node: NodeId) -> bool {
+        arena::get(node).borrow().children().any(Node::is_element)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-node-remove
+    /// Remove the node from its parent, this does nothing if the node has no parent.
+    pub fn remove(node: NodeId, suppress_observers: bool) {
+        let Some(parent) = arena::get(node).borrow().parent else {
+            return;
+        };
+
+        let index = arena::get(node).borrow().index();
+        let node_document = arena::get(node).borrow().node_document;
+
+        let (old_previous_sibling, old_next_sibling) = {
+            let node = arena::get(node);
+            let node = node.borrow();
+
+            (node.previous_sibling, node.next_sibling)
+        };
+
+        Document::with_ranges(node_document, |ranges| {
+            for range in ranges.iter_mut() {
+                range.adjust_removal(node, parent, index);
+            }
+        });
+
+        traversal::pre_removing_steps(node);
+
+        Node::unlink(parent, node);
+
+        Document::mutated(node_document);
+
+        let assigned_slot = arena::get(node).borrow().assigned_slot;
+
+        if let Some(assigned_slot) = assigned_slot {
+            slot::assign_slottables(assigned_slot);
+        }
+
+        if slot::is_unassigned_slot_in_shadow_tree(parent) {
+            mutation_observer::signal_slot_change(parent);
+        }
+
+        if TreeIterator::new(Some(node)).any(slot::is_slot) {
+            slot::assign_slottables_for_tree(Node::root(parent));
+
+            slot::assign_slottables_for_tree(node);
+        }
+
+        let is_parent_connected = Node::is_connected(parent);
+
+        Node::removing_steps(node, is_parent_connected);
+
+        let is_custom = |node: NodeId| match &arena::get(node).borrow().node_type {
+            NodeType::Element(element) => element.is_custom(),
+            _ => false,
+        };
+
+        if is_custom(node) && is_parent_connected {
+            custom_elements::enqueue_callback_reaction(node, LifecycleReaction::Disconnected);
+        }
+
+        for descendant in TreeIterator::shadow_including_descendants(node) {
+            Node::removing_steps(descendant, is_parent_connected);
+
+            if is_custom(descendant) && is_parent_connected {
+                custom_elements::enqueue_callback_reaction(descendant, LifecycleReaction::Disconnected);
             }
         }
 
-        self.child_count += 1;
+        mutation_observer::add_transient_observers(parent, node);
+
+        if !suppress_observers {
+            mutation_observer::queue_tree_mutation_record(parent, Vec::new(), vec![node], old_previous_sibling, old_next_sibling);
+        }
     }
 
-    fn pre_insert(&mut self, node: Node, child: NodeId) {
+    fn unlink(parent: NodeId, node: NodeId) {
+        let (previous_sibling, next_sibling) = {
+            let node = arena::get(node);
+            let node = node.borrow();
+
+            (node.previous_sibling, node.next_sibling)
+        };
+
+        match previous_sibling {
+            Some(previous_sibling) => arena::with_mut(previous_sibling, |previous_sibling| previous_sibling.next_sibling = next_sibling),
+            None => arena::with_mut(parent, |parent| parent.first_child = next_sibling),
+        }
+
+        match next_sibling {
+            Some(next_sibling) => arena::with_mut(next_sibling, |next_sibling| next_sibling.previous_sibling = previous_sibling),
+            None => arena::with_mut(parent, |parent| parent.last_child = previous_sibling),
+        }
+
+        arena::with_mut(node, |node| {
+            node.parent = None;
+
+            node.previous_sibling = None;
+
+            node.next_sibling = None;
+        });
+
+        arena::with_mut(parent, |parent| parent.child_count -= 1);
     }
 
-    fn remove(&mut self, node: NodeId) {
+    // https://dom.spec.whatwg.org/#concept-node-insert-ext
+    fn insertion_steps(node: NodeId, connected: bool) {
+        if connected && Node::is_in_document_tree(node) && let Some(id) = Element::id(node) {
+            Document::add_id(arena::get(node).borrow().node_document, id, node);
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-node-remove-ext
+    fn removing_steps(node: NodeId, was_connected: bool) {
+        if was_connected && let Some(id) = Element::id(node) {
+            Document::remove_id(arena::get(node).borrow().node_document, &id, node);
+        }
+
+        let has_form_owner = matches!(&arena::get(node).borrow().node_type, NodeType::Element(element) if element.form_owner.is_some());
+
+        if has_form_owner {
+            Element::reset_form_owner(node);
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-tree-order
+    /// Compare the position of two nodes in tree order, nodes in different trees compare by their roots.
+    pub fn compare_tree_order(a: NodeId, b: NodeId) -> Ordering {
+        if a == b {
+            return Ordering::Equal;
+        }
+
+        let ancestors = |node: NodeId| {
+            let mut ancestors = NodeIterator::new(Some(node), |node| node.parent).collect::<Vec<NodeId>>();
+
+            ancestors.reverse();
+
+            ancestors
+        };
+
+        let (a_ancestors, b_ancestors) = (ancestors(a), ancestors(b));
+
+        if a_ancestors[0] != b_ancestors[0] {
+            return a_ancestors[0].cmp(&b_ancestors[0]);
+        }
+
+        let common = a_ancestors.iter().zip(b_ancestors.iter()).take_while(|(a, b)| a == b).count();
+
+        match (a_ancestors.get(common), b_ancestors.get(common)) {
+            (None, _) => Ordering::Less,
+            (_, None) => Ordering::Greater,
+            (Some(a), Some(b)) => arena::get(a).borrow().index().cmp(&arena::get(b).borrow().index()),
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-tree-inclusive-ancestor
+    pub fn is_inclusive_ancestor(ancestor: NodeId, node: NodeId) -> bool {
+        NodeIterator::new(Some(node), |node| node.parent).any(|node| node == ancestor)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-shadow-including-inclusive-ancestor
+    /// Check if the ancestor is an inclusive ancestor of the node, following shadow roots to their hosts.
+    pub fn is_shadow_including_inclusive_ancestor(ancestor: NodeId, node: NodeId) -> bool {
+        let mut node = node;
+
+        loop {
+            if node == ancestor {
+                return true;
+            }
+
+            let current = arena::get(node);
+
+            let current = current.borrow();
+
+            node = match (&current.node_type, current.parent) {
+                (_, Some(parent)) => parent,
+                (NodeType::ShadowRoot(shadow_root), None) => shadow_root.host,
+                (_, None) => return false,
+            };
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#concept-tree-host-including-inclusive-ancestor
+    /// Check if the ancestor is an inclusive ancestor of the node, or of the host of its root.
+    pub fn is_host_including_inclusive_ancestor(ancestor: NodeId, node: NodeId) -> bool {
+        if Node::is_inclusive_ancestor(ancestor, node) {
+            return true;
+        }
+
+        match &arena::get(Node::root(node)).borrow().node_type {
+            NodeType::ShadowRoot(shadow_root) => Node::is_host_including_inclusive_ancestor(ancestor, shadow_root.host),
+            _ => false,
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#scope-match-a-selectors-string
+    /// Return the element descendants of the node matching the selectors, in tree order.
+    fn scope_match(node: NodeId, selectors: &str) -> Result<impl Iterator<Item = NodeId>, DomException> {
+        let selectors = SelectorList::parse(selectors)?;
+
+        let context = MatchingContext::new(node, Some(node));
+
+        Ok(TreeIterator::descendants(node).filter(move |descendant| {
+            Node::is_element(*descendant) && selectors.matches_with_context(*descendant, &context)
+        }))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-parentnode-queryselector
+    /// Return the first element descendant matching the selectors.
+    pub fn query_selector(node: NodeId, selectors: &str) -> Result<Option<NodeId>, DomException> {
+        Ok(Node::scope_match(node, selectors)?.next())
+    }
+
+    // https://dom.spec.whatwg.org/#dom-parentnode-queryselectorall
+    /// Return every element descendant matching the selectors, in tree order.
+    pub fn query_selector_all(node: NodeId, selectors: &str) -> Result<Vec<NodeId>, DomException> {
+        Ok(Node::scope_match(node, selectors)?.collect())
     }
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_html;
+    use crate::dom::{Dom, DomException};
+    use crate::dom::testing::{element, create_element};
+
+    #[test]
+    fn pre_insertion_validity() {
+        let mut dom = Dom::new();
+        let document = dom.document();
+
+        let html = create_element(&mut dom, "html");
+        let body = create_element(&mut dom, "body");
+        let text = dom.create_text_node(String::from("x"));
+
+        assert_eq!(dom.append_child(document, html), Ok(html));
+        assert_eq!(dom.append_child(html, body), Ok(body));
+
+        assert_eq!(dom.append_child(body, html), Err(DomException::HierarchyRequestError));
+        assert_eq!(dom.append_child(body, body), Err(DomException::HierarchyRequestError));
+        assert_eq!(dom.append_child(text, body), Err(DomException::HierarchyRequestError));
+        assert_eq!(dom.append_child(body, document), Err(DomException::HierarchyRequestError));
+        assert_eq!(dom.append_child(document, text), Err(DomException::HierarchyRequestError));
+
+        let other = create_element(&mut dom, "div");
+
+        assert_eq!(dom.append_child(document, other), Err(DomException::HierarchyRequestError));
+        assert_eq!(dom.insert_before(html, other, Some(text)), Err(DomException::NotFoundError));
+        assert_eq!(dom.remove_child(html, text), Err(DomException::NotFoundError));
+
+        let other_dom = Dom::new();
+
+        assert_eq!(dom.append_child(body, other_dom.document()), Err(DomException::HierarchyRequestError));
+
+        assert_eq!(arena::get(body).borrow().children().count(), 0);
+        assert_eq!(arena::get(document).borrow().children().collect::<Vec<NodeId>>(), vec![html]);
+    }
+
+    #[test]
+    fn document_children() {
+        let mut dom = Dom::new();
+        let document = dom.document();
+
+        let doctype = dom.append_doctype(String::from("html"), String::new(), String::new());
+        let html = create_element(&mut dom, "html");
+
+        dom.append_child(document, html).expect("document element");
+
+        let second = dom.append_doctype(String::from("html"), String::new(), String::new());
+
+        assert_eq!(dom.remove_child(document, second), Ok(second));
+        assert_eq!(dom.insert_before(document, second, Some(html)), Err(DomException::HierarchyRequestError));
+        assert_eq!(dom.insert_before(document, html, Some(doctype)), Err(DomException::HierarchyRequestError));
+
+        let comment = dom.create_comment(String::from("c"));
+
+        assert_eq!(dom.insert_before(document, comment, Some(html)), Ok(comment));
+
+        let fragment = dom.create_document_fragment();
+        let a = create_element(&mut dom, "a");
+        let b = create_element(&mut dom, "b");
+
+        dom.append_child(fragment, a).expect("append to fragment");
+        dom.append_child(fragment, b).expect("append to fragment");
+
+        assert_eq!(dom.replace_child(document, fragment, html), Err(DomException::HierarchyRequestError));
+
+        dom.remove_child(fragment, b).expect("child of fragment");
+
+        assert_eq!(dom.replace_child(document, fragment, html), Ok(html));
+        assert_eq!(arena::get(document).borrow().children().collect::<Vec<NodeId>>(), vec![doctype, comment, a]);
+        assert_eq!(arena::get(html).borrow().parent, None);
+    }
+
+    #[test]
+    fn appending_a_fragment_moves_its_children() {
+        let mut dom = Dom::new();
+
+        let parent = create_element(&mut dom, "div");
+        let fragment = dom.create_document_fragment();
+
+        let a = create_element(&mut dom, "a");
+        let b = dom.create_text_node(String::from("b"));
+        let c = create_element(&mut dom, "i");
+
+        for node in [a, b, c] {
+            dom.append_child(fragment, node).expect("append to fragment");
+        }
+
+        assert_eq!(dom.append_child(parent, fragment), Ok(fragment));
+
+        assert_eq!(arena::get(fragment).borrow().children().count(), 0);
+        assert_eq!(arena::get(parent).borrow().children().collect::<Vec<NodeId>>(), vec![a, b, c]);
+
+        for node in [a, b, c] {
+            assert_eq!(arena::get(node).borrow().parent, Some(parent));
+        }
+
+        assert_eq!(Element::outer_html(parent), "<div><a></a>b<i></i></div>");
+    }
+
+    #[test]
+    fn inserting_a_node_of_another_dom() {
+        let mut dom = parse_html("<div id=a></div>");
+        let mut other = parse_html("<p id=b title=x><i>y</i></p><span id=c></span>");
+
+        let (a, b) = (element(&dom, "a"), element(&other, "b"));
+
+        let adopted = dom.append_child(a, b).expect("adopted");
+
+        assert_ne!(adopted, b);
+        assert!(dom.contains(adopted));
+        assert!(dom.try_get(b).is_none());
+        assert!(other.try_get(b).is_none());
+
+        assert_eq!(Element::outer_html(a), "<div id=\"a\"><p id=\"b\" title=\"x\"><i>y</i></p></div>");
+        assert_eq!(dom.get_element_by_id("b"), Some(adopted));
+        assert_eq!(other.get_element_by_id("b"), None);
+
+        for node in arena::get(adopted).borrow().descendants() {
+            assert_eq!(arena::get(node).borrow().node_document, dom.document());
+        }
+
+        let fragment = other.create_document_fragment();
+        let c = element(&other, "c");
+
+        other.append_child(fragment, c).expect("append to fragment");
+
+        assert_eq!(dom.insert_before(a, fragment, Some(adopted)).map(|fragment| dom.contains(fragment)), Ok(true));
+        assert_eq!(Element::inner_html(a), "<span id=\"c\"></span><p id=\"b\" title=\"x\"><i>y</i></p>");
+        assert_eq!(Element::inner_html(Document::body(other.document()).expect("body")), "");
+
+        assert_eq!(dom.adopt_node(other.document()), Err(DomException::NotSupportedError));
+    }
+
+    #[test]
+    fn removing_a_child() {
+        let mut dom = Dom::new();
+
+        let parent = create_element(&mut dom, "div");
+
+        let (a, b, c) = (create_element(&mut dom, "a"), create_element(&mut dom, "b"), create_element(&mut dom, "i"));
+
+        for node in [a, b, c] {
+            dom.append_child(parent, node).expect("append");
+        }
+
+        assert_eq!(dom.remove_child(parent, a), Ok(a));
+        assert_eq!(dom.remove_child(parent, a), Err(DomException::NotFoundError));
+
+        dom.remove(c);
+
+        assert_eq!(arena::get(parent).borrow().children().collect::<Vec<NodeId>>(), vec![b]);
+        assert_eq!(arena::get(parent).borrow().child_count, 1);
+        assert_eq!((arena::get(b).borrow().previous_sibling, arena::get(b).borrow().next_sibling), (None, None));
+        assert_eq!(arena::get(a).borrow().parent, None);
+    }
+}
@@ -0,0 +1,36 @@
+use crate::dom::inheritance::{private, Downcast};
+use crate::dom::node::{Node, NodeType};
+use crate::dom::node::character_data::CharacterData;
+
+
+pub struct ProcessingInstruction {
+    pub target: String,
+    pub(crate) character_data: CharacterData,
+}
+
+impl private::Sealed for ProcessingInstruction {}
+
+impl Downcast<Node> for ProcessingInstruction {
+    fn downcast_ref(node: &Node) -> &ProcessingInstruction {
+        match &node.node_type {
+            NodeType::ProcessingInstruction(processing_instruction) => processing_instruction,
+            _ => panic!("expected processing instruction"),
+        }
+    }
+
+    fn downcast_mut(node: &mut Node) -> &mut ProcessingInstruction {
+        match &mut node.node_type {
+            NodeType::ProcessingInstruction(processing_instruction) => processing_instruction,
+            _ => panic!("expected processing instruction"),
+        }
+    }
+}
+
+impl ProcessingInstruction {
+    pub fn new(target: String, data: String) -> ProcessingInstruction {
+        ProcessingInstruction {
+            target,
+            character_data: CharacterData::new(data),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::dom::arena::{self, NodeId};
+use crate::dom::inheritance::{private, Downcast};
+use crate::dom::node::{Node, NodeType};
+use crate::dom::custom_elements::{CustomElementRegistry, NullOrCustomElementRegistry};
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+
+// https://dom.spec.whatwg.org/#enumdef-shadowrootmode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowRootMode {
+    Open,
+    Closed,
+}
+
+// https://dom.spec.whatwg.org/#enumdef-slotassignmentmode
+/// How the slottables of a shadow host are assigned to the slots of its shadow tree, by name or
+/// by the slots they were assigned to with [`Element::assign`](crate::dom::Element::assign).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SlotAssignmentMode {
+    #[default]
+    Named,
+    Manual,
+}
+
+// https://dom.spec.whatwg.org/#dictdef-shadowrootinit
+#[derive(Clone)]
+pub struct ShadowRootInit {
+    pub mode: ShadowRootMode,
+    pub delegates_focus: bool,
+    pub slot_assignment: SlotAssignmentMode,
+    pub clonable: bool,
+    pub serializable: bool,
+
+    /// The registry of the shadow root, the global registry of the document is used when none.
+    pub custom_element_registry: Option<Rc<RefCell<CustomElementRegistry>>>,
+}
+
+impl ShadowRootInit {
+    pub fn new(mode: ShadowRootMode) -> ShadowRootInit {
+        ShadowRootInit {
+            mode,
+            delegates_focus: false,
+            slot_assignment: SlotAssignmentMode::Named,
+            clonable: false,
+            serializable: false,
+            custom_element_registry: None,
+        }
+    }
+}
+
+// https://dom.spec.whatwg.org/#interface-shadowroot
+/// The root of a shadow tree, the shadow root has no parent and is reached through its host.
+pub struct ShadowRoot {
+    pub(crate) host: NodeId,
+    pub(crate) mode: ShadowRootMode,
+    pub(crate) delegates_focus: bool,
+    pub(crate) slot_assignment: SlotAssignmentMode,
+    pub(crate) available_to_element_internals: bool,
+    pub(crate) declarative: bool,
+    pub(crate) clonable: bool,
+    pub(crate) serializable: bool,
+    pub(crate) custom_element_registry: NullOrCustomElementRegistry,
+}
+
+impl private::Sealed for ShadowRoot {}
+
+impl Downcast<Node> for ShadowRoot {
+    fn downcast_ref(node: &Node) -> &ShadowRoot {
+        match &node.node_type {
+            NodeType::ShadowRoot(shadow_root) => shadow_root,
+            _ => panic!("expected shadow root"),
+        }
+    }
+
+    fn downcast_mut(node: &mut Node) -> &mut ShadowRoot {
+        match &mut node.node_type {
+            NodeType::ShadowRoot(shadow_root) => shadow_root,
+            _ => panic!("expected shadow root"),
+        }
+    }
+}
+
+impl ShadowRoot {
+    pub fn new(host: NodeId, mode: ShadowRootMode, custom_element_registry: NullOrCustomElementRegistry) -> ShadowRoot {
+        ShadowRoot {
+            host,
+            mode,
+            delegates_focus: false,
+            slot_assignment: SlotAssignmentMode::Named,
+            available_to_element_internals: false,
+            declarative: false,
+            clonable: false,
+            serializable: false,
+            custom_element_registry,
+        }
+    }
+
+    pub(crate) fn for_each_reference(&mut self, f: &mut impl FnMut(&mut NodeId)) {
+        f(&mut self.host);
+
+        self.custom_element_registry.for_each_reference(f);
+    }
+
+    /// Check if the node is a shadow root.
+    pub fn is_shadow_root(node: NodeId) -> bool {
+        matches!(arena::get(node).borrow().node_type, NodeType::ShadowRoot(_))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-shadowroot-host
+    pub fn host(shadow_root: NodeId) -> NodeId {
+        arena::get(shadow_root).borrow().downcast_ref::<ShadowRoot>().host
+    }
+
+    // https://dom.spec.whatwg.org/#dom-shadowroot-mode
+    pub fn mode(shadow_root: NodeId) -> ShadowRootMode {
+        arena::get(shadow_root).borrow().downcast_ref::<ShadowRoot>().mode
+    }
+
+    // https://dom.spec.whatwg.org/#dom-shadowroot-delegatesfocus
+    pub fn delegates_focus(shadow_root: NodeId) -> bool {
+        arena::get(shadow_root).borrow().downcast_ref::<ShadowRoot>().delegates_focus
+    }
+
+    // https://dom.spec.whatwg.org/#dom-shadowroot-slotassignment
+    pub fn slot_assignment(shadow_root: NodeId) -> SlotAssignmentMode {
+        arena::get(shadow_root).borrow().downcast_ref::<ShadowRoot>().slot_assignment
+    }
+
+    // https://dom.spec.whatwg.org/#dom-shadowroot-clonable
+    pub fn clonable(shadow_root: NodeId) -> bool {
+        arena::get(shadow_root).borrow().downcast_ref::<ShadowRoot>().clonable
+    }
+
+    // https://dom.spec.whatwg.org/#dom-shadowroot-serializable
+    pub fn serializable(shadow_root: NodeId) -> bool {
+        arena::get(shadow_root).borrow().downcast_ref::<ShadowRoot>().serializable
+    }
+
+    // https://dom.spec.whatwg.org/#dom-shadowroot-customelementregistry
+    pub fn custom_element_registry(shadow_root: NodeId) -> NullOrCustomElementRegistry {
+        arena::get(shadow_root).borrow().downcast_ref::<ShadowRoot>().custom_element_registry.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_html;
+    use crate::dom::{Dom, GetRootNodeOptions};
+    use crate::dom::exception::DomException;
+    use crate::dom::iterators::TreeIterator;
+    use crate::dom::node::element::Element;
+    use crate::dom::testing::{element, create_element};
+
+    #[test]
+    fn attaching_shadow_roots() {
+        let mut dom = Dom::new();
+
+        let (div, img, custom) = (create_element(&mut dom, "div"), create_element(&mut dom, "img"), create_element(&mut dom, "my-element"));
+
+        let shadow_root = Element::attach_shadow(div, ShadowRootInit::new(ShadowRootMode::Open)).expect("div is a valid host");
+
+        assert!(ShadowRoot::is_shadow_root(shadow_root));
+        assert_eq!(ShadowRoot::host(shadow_root), div);
+        assert_eq!(Element::shadow_root(div), Some(shadow_root));
+        assert_eq!(arena::get(shadow_root).borrow().parent, None);
+
+        assert_eq!(Element::attach_shadow(div, ShadowRootInit::new(ShadowRootMode::Open)), Err(DomException::NotSupportedError));
+        assert_eq!(Element::attach_shadow(img, ShadowRootInit::new(ShadowRootMode::Open)), Err(DomException::NotSupportedError));
+
+        let closed = Element::attach_shadow(custom, ShadowRootInit::new(ShadowRootMode::Closed)).expect("custom elements are valid hosts");
+
+        assert_eq!(ShadowRoot::mode(closed), ShadowRootMode::Closed);
+        assert_eq!(Element::shadow_root(custom), None);
+    }
+
+    #[test]
+    fn shadow_trees_are_connected_but_not_in_the_document_tree() {
+        let mut dom = parse_html("<div id=host></div><p id=after></p>");
+
+        let host = element(&dom, "host");
+        let shadow_root = Element::attach_shadow(host, ShadowRootInit::new(ShadowRootMode::Open)).expect("valid host");
+
+        let inner = create_element(&mut dom, "span");
+
+        Element::set_attribute(inner, "id", "inner").expect("valid");
+
+        dom.append_child(shadow_root, inner).expect("append to shadow root");
+
+        assert!(Node::is_connected(inner));
+        assert!(!Node::is_in_document_tree(inner));
+        assert_eq!(Node::get_root_node(inner, GetRootNodeOptions { composed: false }), shadow_root);
+        assert_eq!(Node::get_root_node(inner, GetRootNodeOptions { composed: true }), dom.document());
+
+        assert_eq!(dom.get_element_by_id("inner"), None);
+        assert_eq!(dom.query_selector("span").expect("valid"), None);
+        assert_eq!(Node::query_selector(shadow_root, "span").expect("valid"), Some(inner));
+
+        assert_eq!(dom.append_child(inner, host), Err(DomException::HierarchyRequestError));
+    }
+
+    #[test]
+    fn shadow_including_tree_order() {
+        let mut dom = parse_html("<div id=host><i id=light></i></div><p id=after></p>");
+
+        let host = element(&dom, "host");
+        let light = element(&dom, "light");
+        let after = element(&dom, "after");
+
+        let shadow_root = Element::attach_shadow(host, ShadowRootInit::new(ShadowRootMode::Closed)).expect("valid host");
+        let inner = create_element(&mut dom, "span");
+
+        dom.append_child(shadow_root, inner).expect("append to shadow root");
+
+        let body = arena::get(host).borrow().parent.expect("body");
+
+        assert_eq!(TreeIterator::shadow_including(body).collect::<Vec<_>>(), vec![body, host, shadow_root, inner, light, after]);
+        assert_eq!(TreeIterator::descendants(body).collect::<Vec<_>>(), vec![host, light, after]);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::dom::arena::{self, NodeId};
+use crate::dom::iterators::{NodeIterator, TreeIterator};
+use crate::dom::mutation_observer;
+use crate::dom::node::{Node, NodeType, QualifiedName};
+use crate::dom::node::element::Element;
+use crate::dom::node::text::Text;
+use crate::dom::node::shadow_root::{ShadowRoot, ShadowRootMode, SlotAssignmentMode};
+use crate::names::atoms;
+
+
+// https://html.spec.whatwg.org/multipage/scripting.html#assignednodesoptions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AssignedNodesOptions {
+    pub flatten: bool,
+}
+
+// https://dom.spec.whatwg.org/#concept-slot
+/// Check if the node is a slot element.
+pub fn is_slot(node: NodeId) -> bool {
+    matches!(&arena::get(node).borrow().node_type, NodeType::Element(element) if element.name.is_html(atoms::SLOT))
+}
+
+// https://dom.spec.whatwg.org/#concept-slotable
+/// Check if the node is a slottable, elements and text nodes are slottables.
+pub fn is_slottable(node: NodeId) -> bool {
+    matches!(arena::get(node).borrow().node_type, NodeType::Element(_)) || Text::is_text(node)
+}
+
+// https://dom.spec.whatwg.org/#slot-name
+fn slot_name(slot: NodeId) -> String {
+    arena::get(slot).borrow().downcast_ref::<Element>().attribute(QualifiedName::new(None, atoms::NAME))
+        .map(|attribute| attribute.value.clone())
+        .unwrap_or_default()
+}
+
+// https://dom.spec.whatwg.org/#slotable-name
+fn slottable_name(slottable: NodeId) -> String {
+    match &arena::get(slottable).borrow().node_type {
+        NodeType::Element(element) => {
+            element.attribute(QualifiedName::new(None, atoms::SLOT))
+                .map(|attribute| attribute.value.clone())
+                .unwrap_or_default()
+        },
+        _ => String::new(),
+    }
+}
+
+/// The slottables assigned to the slot.
+pub(crate) fn assigned_nodes(slot: NodeId) -> Vec<NodeId> {
+    arena::get(slot).borrow().downcast_ref::<Element>().assigned_nodes.clone()
+}
+
+/// Check if the node is a slot in a shadow tree with no assigned nodes.
+pub(crate) fn is_unassigned_slot_in_shadow_tree(node: NodeId) -> bool {
+    is_slot(node)
+        && matches!(arena::get(Node::root(node)).borrow().node_type, NodeType::ShadowRoot(_))
+        && arena::get(node).borrow().downcast_ref::<Element>().assigned_nodes.is_empty()
+}
+
+// https://dom.spec.whatwg.org/#find-a-slot
+/// Find the slot the slottable is assigned to in the shadow tree of its parent, closed shadow
+/// trees are skipped when open is set.
+pub(crate) fn find_slot(slottable: NodeId, open: bool) -> Option<NodeId> {
+    let parent = arena::get(slottable).borrow().parent?;
+
+    let shadow_root = match &arena::get(parent).borrow().node_type {
+        NodeType::Element(element) => element.shadow_root?,
+        _ => return None,
+    };
+
+    let (mode, slot_assignment) = {
+        let node = arena::get(shadow_root);
+        let node = node.borrow();
+        let shadow_root = node.downcast_ref::<ShadowRoot>();
+
+        (shadow_root.mode, shadow_root.slot_assignment)
+    };
+
+    if open && mode != ShadowRootMode::Open {
+        return None;
+    }
+
+    let mut slots = TreeIterator::descendants(shadow_root).filter(|node| is_slot(*node));
+
+    match slot_assignment {
+        SlotAssignmentMode::Manual => {
+            slots.find(|slot| arena::get(slot).borrow().downcast_ref::<Element>().manually_assigned_nodes.contains(&slottable))
+        },
+        SlotAssignmentMode::Named => {
+            let name = slottable_name(slottable);
+
+            slots.find(|slot| slot_name(*slot) == name)
+        },
+    }
+}
+
+// https://dom.spec.whatwg.org/#find-slotables
+fn find_slottables(slot: NodeId) -> Vec<NodeId> {
+    let (host, slot_assignment) = match &arena::get(Node::root(slot)).borrow().node_type {
+        NodeType::ShadowRoot(shadow_root) => (shadow_root.host, shadow_root.slot_assignment),
+        _ => return Vec::new(),
+    };
+
+    match slot_assignment {
+        SlotAssignmentMode::Manual => {
+            // NOTE: a manually assigned node is freed here when it is adopted into another dom.
+            arena::get(slot).borrow().downcast_ref::<Element>().manually_assigned_nodes.iter()
+                .copied()
+                .filter(|slottable| arena::try_get(slottable).is_some_and(|slottable| slottable.borrow().parent == Some(host)))
+                .collect()
+        },
+        SlotAssignmentMode::Named => {
+            NodeIterator::new(arena::get(host).borrow().first_child, |node| node.next_sibling)
+                .filter(|node| is_slottable(*node) && find_slot(*node, false) == Some(slot))
+                .collect()
+        },
+    }
+}
+
+// https://dom.spec.whatwg.org/#find-flattened-slotables
+/// The slottables of the slot with the slots among them replaced by their own flattened
+/// slottables, a slot without slottables falls back to its children.
+pub(crate) fn find_flattened_slottables(slot: NodeId) -> Vec<NodeId> {
+    if !matches!(arena::get(Node::root(slot)).borrow().node_type, NodeType::ShadowRoot(_)) {
+        return Vec::new();
+    }
+
+    let mut slottables = find_slottables(slot);
+
+    if slottables.is_empty() {
+        slottables.extend(NodeIterator::new(arena::get(slot).borrow().first_child, |node| node.next_sibling).filter(|node| is_slottable(*node)));
+    }
+
+    let mut result = Vec::new();
+
+    for node in slottables {
+        if is_slot(node) && matches!(arena::get(Node::root(node)).borrow().node_type, NodeType::ShadowRoot(_)) {
+            result.extend(find_flattened_slottables(node));
+        } else {
+            result.push(node);
+        }
+    }
+
+    result
+}
+
+// https://dom.spec.whatwg.org/#assign-slotables
+/// Recompute the assigned nodes of the slot, signalling a slot change when they differ.
+pub(crate) fn assign_slottables(slot: NodeId) {
+    let slottables = find_slottables(slot);
+    let assigned_nodes = assigned_nodes(slot);
+
+    if slottables != assigned_nodes {
+        mutation_observer::signal_slot_change(slot);
+    }
+
+    for node in assigned_nodes.iter().copied().filter(|node| !slottables.contains(node)) {
+        arena::with_mut(node, |node| {
+            if node.assigned_slot == Some(slot) {
+                node.assigned_slot = None;
+            }
+        });
+    }
+
+    for slottable in slottables.iter().copied() {
+        arena::with_mut(slottable, |node| node.assigned_slot = Some(slot));
+    }
+
+    arena::with_mut(slot, |node| node.downcast_mut::<Element>().assigned_nodes = slottables);
+}
+
+// https://dom.spec.whatwg.org/#assign-slotables-for-a-tree
+pub(crate) fn assign_slottables_for_tree(root: NodeId) {
+    let slots = TreeIterator::new(Some(root))
+        .filter(|node| is_slot(*node))
+        .collect::<Vec<NodeId>>();
+
+    for slot in slots {
+        assign_slottables(slot);
+    }
+}
+
+// https://dom.spec.whatwg.org/#assign-a-slot
+pub(crate) fn assign_slot(slottable: NodeId) {
+    if let Some(slot) = find_slot(slottable, false) {
+        assign_slottables(slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_html;
+    use crate::dom::{Dom, ElementCreationOptions, ShadowRootInit};
+    use crate::dom::exception::DomException;
+    use crate::dom::testing::element;
+    use crate::dom::event::{EventTarget, EventListenerCallback, AddEventListenerOptions};
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Attach a shadow root to the host and append slots with the names to it, none is the default slot.
+    fn shadow_tree(dom: &mut Dom, host: NodeId, init: ShadowRootInit, names: &[Option<&str>]) -> Vec<NodeId> {
+        let shadow_root = Element::attach_shadow(host, init).expect("valid host");
+
+        names.iter().map(|name| {
+            let slot = dom.create_element("slot", ElementCreationOptions::default()).expect("slot");
+
+            if let Some(name) = name {
+                Element::set_attribute(slot, "name", name).expect("valid");
+            }
+
+            dom.append_child(shadow_root, slot).expect("append slot");
+
+            slot
+        }).collect()
+    }
+
+    #[test]
+    fn named_assignment() {
+        let mut dom = parse_html("<div id=host><i id=a slot=a></i>text<b id=b></b><!--c--></div>");
+
+        let host = element(&dom, "host");
+        let (a, b) = (element(&dom, "a"), element(&dom, "b"));
+        let text = arena::get(a).borrow().next_sibling.expect("text");
+
+        let slots = shadow_tree(&mut dom, host, ShadowRootInit::new(ShadowRootMode::Open), &[Some("a"), None]);
+
+        assert_eq!(Element::assigned_nodes(slots[0], AssignedNodesOptions::default()), vec![a]);
+        assert_eq!(Element::assigned_nodes(slots[1], AssignedNodesOptions::default()), vec![text, b]);
+        assert_eq!(Element::assigned_elements(slots[1], AssignedNodesOptions::default()), vec![b]);
+        assert_eq!(Element::assigned_slot(a), Some(slots[0]));
+        assert_eq!(Text::assigned_slot(text), Some(slots[1]));
+
+        Element::set_attribute(b, "slot", "a").expect("valid");
+        Element::set_attribute(a, "slot", "none").expect("valid");
+
+        assert_eq!(Element::assigned_nodes(slots[0], AssignedNodesOptions::default()), vec![b]);
+        assert_eq!(Element::assigned_slot(a), None);
+
+        dom.remove(b);
+
+        assert!(Element::assigned_nodes(slots[0], AssignedNodesOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn closed_slots_are_hidden() {
+        let mut dom = parse_html("<div id=host><i id=a></i></div>");
+
+        let host = element(&dom, "host");
+        let a = element(&dom, "a");
+
+        let slots = shadow_tree(&mut dom, host, ShadowRootInit::new(ShadowRootMode::Closed), &[None]);
+
+        assert_eq!(Element::assign(slots[0], &[a]), Ok(()));
+        assert_eq!(Element::assigned_nodes(slots[0], AssignedNodesOptions::default()), vec![a]);
+        assert_eq!(Element::assigned_slot(a), None);
+        assert_eq!(find_slot(a, false), Some(slots[0]));
+    }
+
+    #[test]
+    fn flattened_assigned_nodes() {
+        let mut dom = parse_html("<div id=host><i id=a></i></div>");
+
+        let host = element(&dom, "host");
+        let a = element(&dom, "a");
+
+        let slots = shadow_tree(&mut dom, host, ShadowRootInit::new(ShadowRootMode::Open), &[Some("x")]);
+
+        let fallback = dom.create_element("em", ElementCreationOptions::default()).expect("em");
+
+        dom.append_child(slots[0], fallback).expect("append fallback");
+
+        let flatten = AssignedNodesOptions { flatten: true };
+
+        assert!(Element::assigned_nodes(slots[0], AssignedNodesOptions::default()).is_empty());
+        assert_eq!(Element::assigned_nodes(slots[0], flatten), vec![fallback]);
+
+        Element::set_attribute(a, "slot", "x").expect("valid");
+
+        assert_eq!(Element::assigned_nodes(slots[0], flatten), vec![a]);
+    }
+
+    #[test]
+    fn manual_assignment() {
+        let mut dom = parse_html("<div id=host><i id=a></i><b id=b></b><!--c--></div><p id=outside></p>");
+
+        let host = element(&dom, "host");
+        let (a, b, outside) = (element(&dom, "a"), element(&dom, "b"), element(&dom, "outside"));
+        let comment = arena::get(host).borrow().last_child.expect("comment");
+
+        let init = ShadowRootInit {
+            slot_assignment: SlotAssignmentMode::Manual,
+            ..ShadowRootInit::new(ShadowRootMode::Open)
+        };
+
+        let slots = shadow_tree(&mut dom, host, init, &[None, None]);
+
+        assert!(Element::assigned_nodes(slots[0], AssignedNodesOptions::default()).is_empty());
+
+        assert_eq!(Element::assign(slots[0], &[b, a, outside]), Ok(()));
+        assert_eq!(Element::assigned_nodes(slots[0], AssignedNodesOptions::default()), vec![b, a]);
+
+        assert_eq!(Element::assign(slots[1], &[a]), Ok(()));
+        assert_eq!(Element::assigned_nodes(slots[0], AssignedNodesOptions::default()), vec![b]);
+        assert_eq!(Element::assigned_nodes(slots[1], AssignedNodesOptions::default()), vec![a]);
+
+        assert_eq!(Element::assign(slots[0], &[comment]), Err(DomException::TypeError));
+
+        dom.remove(b);
+
+        assert!(Element::assigned_nodes(slots[0], AssignedNodesOptions::default()).is_empty());
+        assert_eq!(Element::assigned_slot(b), None);
+    }
+
+    #[test]
+    fn manually_assigned_nodes_adopted_into_another_dom() {
+        let mut dom = parse_html("<div id=host><i id=a></i></div>");
+        let mut other = Dom::new();
+
+        let host = element(&dom, "host");
+        let a = element(&dom, "a");
+
+        let init = ShadowRootInit {
+            slot_assignment: SlotAssignmentMode::Manual,
+            ..ShadowRootInit::new(ShadowRootMode::Open)
+        };
+
+        let slots = shadow_tree(&mut dom, host, init, &[None]);
+
+        Element::assign(slots[0], &[a]).expect("slottable");
+
+        let adopted = other.adopt_node(a).expect("adopted");
+
+        assert_eq!(Element::assigned_slot(adopted), None);
+        assert!(Element::assigned_nodes(slots[0], AssignedNodesOptions::default()).is_empty());
+        assert_eq!(Element::assign(slots[0], &[]), Ok(()));
+    }
+
+    #[test]
+    fn slot_change_events() {
+        let mut dom = parse_html("<div id=host></div>");
+
+        let host = element(&dom, "host");
+        let slots = shadow_tree(&mut dom, host, ShadowRootInit::new(ShadowRootMode::Open), &[None]);
+
+        let fired = Rc::new(Cell::new(0));
+
+        let callback: EventListenerCallback = Rc::new({
+            let fired = fired.clone();
+
+            move |_| fired.set(fired.get() + 1)
+        });
+
+        slots[0].add_event_listener("slotchange", callback, AddEventListenerOptions::default());
+
+        let a = dom.create_element("i", ElementCreationOptions::default()).expect("i");
+        let b = dom.create_element("b", ElementCreationOptions::default()).expect("b");
+
+        dom.append_child(host, a).expect("append");
+        dom.append_child(host, b).expect("append");
+
+        assert_eq!(fired.get(), 0);
+
+        dom.perform_microtask_checkpoint();
+
+        assert_eq!(fired.get(), 1);
+
+        dom.perform_microtask_checkpoint();
+
+        assert_eq!(fired.get(), 1);
+    }
+}
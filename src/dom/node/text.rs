@@ -0,0 +1,183 @@
+use crate::dom::arena::{self, NodeId};
+use crate::dom::exception::DomException;
+use crate::dom::inheritance::{private, Downcast};
+use crate::dom::iterators::NodeIterator;
+use crate::dom::node::{Node, NodeType};
+use crate::dom::node::character_data::CharacterData;
+use crate::dom::node::document::Document;
+use crate::dom::node::slot;
+
+
+pub struct Text {
+    pub(crate) character_data: CharacterData,
+}
+
+impl private::Sealed for Text {}
+
+impl Downcast<Node> for Text {
+    fn downcast_ref(node: &Node) -> &Text {
+        match &node.node_type {
+            NodeType::Text(text) => text,
+            NodeType::CdataSection(cdata_section) => &cdata_section.text,
+            _ => panic!("expected text"),
+        }
+    }
+
+    fn downcast_mut(node: &mut Node) -> &mut Text {
+        match &mut node.node_type {
+            NodeType::Text(text) => text,
+            NodeType::CdataSection(cdata_section) => &mut cdata_section.text,
+            _ => panic!("expected text"),
+        }
+    }
+}
+
+impl Text {
+    pub fn new(data: String) -> Text {
+        Text {
+            character_data: CharacterData::new(data),
+        }
+    }
+
+    /// Check if the node is a text node, cdata sections are text nodes too.
+    pub fn is_text(node: NodeId) -> bool {
+        matches!(arena::get(node).borrow().node_type, NodeType::Text(_) | NodeType::CdataSection(_))
+    }
+
+    // https://dom.spec.whatwg.org/#dom-slotable-assignedslot
+    /// Return the slot the text node is assigned to, slots in closed shadow trees are not returned.
+    pub fn assigned_slot(node: NodeId) -> Option<NodeId> {
+        slot::find_slot(node, true)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-text-split
+    /// Split the node at the offset and return the new node holding the data after the offset.
+    pub fn split_text(node: NodeId, offset: usize) -> Result<NodeId, DomException> {
+        let length = CharacterData::length(node);
+
+        if offset > length {
+            return Err(DomException::IndexSizeError);
+        }
+
+        let count = length - offset;
+        let new_data = CharacterData::substring_data(node, offset, count)?;
+
+        let node_document = arena::get(node).borrow().node_document;
+        let new_node = arena::insert(Node::new(NodeType::Text(Text::new(new_data)), node_document));
+
+        let (parent, next_sibling) = {
+            let node = arena::get(node);
+            let node = node.borrow();
+
+            (node.parent, node.next_sibling)
+        };
+
+        if let Some(parent) = parent {
+            Node::insert(parent, new_node, next_sibling, false);
+
+            let index = arena::get(node).borrow().index();
+
+            Document::with_ranges(node_document, |ranges| {
+                for range in ranges.iter_mut() {
+                    range.adjust_split(node, new_node, offset, parent, index);
+                }
+            });
+        }
+
+        CharacterData::replace_data(node, offset, count, "")?;
+
+        Ok(new_node)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-text-wholetext
+    /// The data of the contiguous text nodes around the node, in tree order.
+    pub fn whole_text(node: NodeId) -> String {
+        let first = NodeIterator::new(Some(node), |node| node.previous_sibling)
+            .take_while(|sibling| Text::is_text(*sibling))
+            .last()
+            .unwrap_or(node);
+
+        NodeIterator::new(Some(first), |node| node.next_sibling)
+            .take_while(|sibling| Text::is_text(*sibling))
+            .map(|sibling| arena::get(sibling).borrow().downcast_ref::<CharacterData>().data.clone())
+            .collect()
+    }
+}
+
+pub struct CdataSection {
+    pub(crate) text: Text,
+}
+
+impl private::Sealed for CdataSection {}
+
+impl Downcast<Node> for CdataSection {
+    fn downcast_ref(node: &Node) -> &CdataSection {
+        match &node.node_type {
+            NodeType::CdataSection(cdata_section) => cdata_section,
+            _ => panic!("expected cdata section"),
+        }
+    }
+
+    fn downcast_mut(node: &mut Node) -> &mut CdataSection {
+        match &mut node.node_type {
+            NodeType::CdataSection(cdata_section) => cdata_section,
+            _ => panic!("expected cdata section"),
+        }
+    }
+}
+
+impl CdataSection {
+    pub fn new(data: String) -> CdataSection {
+        CdataSection {
+            text: Text::new(data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::Dom;
+    use crate::dom::testing::create_element;
+
+    #[test]
+    fn split_and_whole_text() {
+        let mut dom = Dom::new();
+        let parent = create_element(&mut dom, "p");
+
+        let text = dom.create_text_node(String::from("hello world"));
+        let cdata = dom.create_cdata_section(String::from("!")).expect("cdata");
+
+        dom.append_child(parent, text).expect("append text");
+        dom.append_child(parent, cdata).expect("append cdata");
+
+        let new_node = Text::split_text(text, 5).expect("split");
+
+        assert_eq!(CharacterData::data(text), "hello");
+        assert_eq!(CharacterData::data(new_node), " world");
+        assert_eq!(arena::get(parent).borrow().children().collect::<Vec<_>>(), vec![text, new_node, cdata]);
+
+        assert_eq!(Text::whole_text(new_node), "hello world!");
+        assert_eq!(Text::split_text(text, 6), Err(DomException::IndexSizeError));
+
+        let comment = dom.create_comment(String::from("c"));
+
+        dom.insert_before(parent, comment, Some(new_node)).expect("insert comment");
+
+        assert_eq!(Text::whole_text(text), "hello");
+        assert_eq!(Text::whole_text(cdata), " world!");
+    }
+
+    #[test]
+    fn invalid_data() {
+        let mut dom = Dom::new();
+
+        assert_eq!(dom.create_cdata_section(String::from("a]]>b")), Err(DomException::InvalidCharacterError));
+        assert_eq!(dom.create_processing_instruction(String::from("1x"), String::new()), Err(DomException::InvalidCharacterError));
+        assert_eq!(dom.create_processing_instruction(String::from("x"), String::from("?>")), Err(DomException::InvalidCharacterError));
+
+        let processing_instruction = dom.create_processing_instruction(String::from("xml-stylesheet"), String::from("href=a")).expect("valid");
+
+        assert_eq!(CharacterData::data(processing_instruction), "href=a");
+    }
+}
@@ -0,0 +1,291 @@
+use crate::dom::arena::{self, NodeId};
+use crate::dom::node::NodeType;
+use crate::dom::node::document::Document;
+use crate::dom::node::character_data::CharacterData;
+use crate::dom::node::shadow_root::{ShadowRoot, ShadowRootMode};
+use crate::names::{atoms, LocalName, Namespace, QualifiedName};
+
+
+/// Elements that serialize as void, neither their children nor their end tag are serialized.
+const VOID_ELEMENTS: &[LocalName] = &[
+    atoms::AREA, atoms::BASE, atoms::BASEFONT, atoms::BGSOUND, atoms::BR, atoms::COL, atoms::EMBED, atoms::FRAME, atoms::HR,
+    atoms::IMG, atoms::INPUT, atoms::KEYGEN, atoms::LINK, atoms::META, atoms::PARAM, atoms::SOURCE, atoms::TRACK, atoms::WBR,
+];
+
+/// Elements whose text is serialized as is, noscript is one of them when scripting is enabled.
+const RAW_TEXT_ELEMENTS: &[LocalName] = &[
+    atoms::STYLE, atoms::SCRIPT, atoms::XMP, atoms::IFRAME, atoms::NOEMBED, atoms::NOFRAMES, atoms::PLAINTEXT,
+];
+
+// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#gethtmloptions
+/// The shadow roots to serialize, shadow roots are left out by default.
+#[derive(Debug, Clone, Default)]
+pub struct GetHtmlOptions {
+    /// Serialize the shadow roots that are serializable.
+    pub serializable_shadow_roots: bool,
+
+    /// Shadow roots to serialize even if they are not serializable.
+    pub shadow_roots: Vec<NodeId>,
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#serializes-as-void
+fn serializes_as_void(name: &QualifiedName) -> bool {
+    name.is_namespace(Namespace::Html) && VOID_ELEMENTS.contains(&name.local_name)
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#escapingString
+fn escape(output: &mut String, text: &str, attribute_mode: bool) {
+    for character in text.chars() {
+        match character {
+            '&' => output.push_str("&amp;"),
+            '\u{a0}' => output.push_str("&nbsp;"),
+            '"' if attribute_mode => output.push_str("&quot;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            _ => output.push(character),
+        }
+    }
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#attribute's-serialized-name
+fn attribute_name(name: &QualifiedName) -> String {
+    match &name.namespace {
+        None => name.local_name.to_string(),
+        Some(Namespace::Xml) => format!("xml:{}", name.local_name),
+        Some(Namespace::Xmlns) if name.local_name == atoms::XMLNS => name.local_name.to_string(),
+        Some(Namespace::Xmlns) => format!("xmlns:{}", name.local_name),
+        Some(Namespace::XLink) => format!("xlink:{}", name.local_name),
+        Some(_) => name.qualified(),
+    }
+}
+
+/// Check if the text children of the node are serialized as is.
+fn is_raw_text_parent(node: NodeId) -> bool {
+    let node = arena::get(node);
+    let node = node.borrow();
+
+    match &node.node_type {
+        NodeType::Element(element) if element.name.is_namespace(Namespace::Html) => {
+            RAW_TEXT_ELEMENTS.contains(&element.name.local_name)
+                || (element.name.local_name == atoms::NOSCRIPT && arena::get(node.node_document).borrow().downcast_ref::<Document>().scripting)
+        },
+        _ => false,
+    }
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#html-fragment-serialisation-algorithm
+/// Serialize the children of the node, preceded by the shadow root of a host when the options ask
+/// for it.
+pub(crate) fn serialize_children(node: NodeId, options: &GetHtmlOptions) -> String {
+    let mut output = String::new();
+
+    serialize_children_into(&mut output, node, options);
+
+    output
+}
+
+/// Serialize the node as it appears in the serialization of its parent.
+pub(crate) fn serialize_node(node: NodeId, options: &GetHtmlOptions) -> String {
+    let mut output = String::new();
+
+    serialize_node_into(&mut output, node, options);
+
+    output
+}
+
+// NOTE: the children of a template are its contents in this dom, so templates need no special case.
+fn serialize_children_into(output: &mut String, node: NodeId, options: &GetHtmlOptions) {
+    let (name, shadow_root) = match &arena::get(node).borrow().node_type {
+        NodeType::Element(element) => (Some(element.name.clone()), element.shadow_root),
+        _ => (None, None),
+    };
+
+    if name.as_ref().is_some_and(serializes_as_void) {
+        return;
+    }
+
+    let shadow_root = shadow_root.filter(|shadow_root| {
+        (options.serializable_shadow_roots && ShadowRoot::serializable(*shadow_root)) || options.shadow_roots.contains(shadow_root)
+    });
+
+    if let Some(shadow_root) = shadow_root {
+        let mode = match ShadowRoot::mode(shadow_root) {
+            ShadowRootMode::Open => "open",
+            ShadowRootMode::Closed => "closed",
+        };
+
+        output.push_str(&format!("<template shadowrootmode=\"{mode}\""));
+
+        if ShadowRoot::delegates_focus(shadow_root) {
+            output.push_str(" shadowrootdelegatesfocus=\"\"");
+        }
+
+        if ShadowRoot::serializable(shadow_root) {
+            output.push_str(" shadowrootserializable=\"\"");
+        }
+
+        if ShadowRoot::clonable(shadow_root) {
+            output.push_str(" shadowrootclonable=\"\"");
+        }
+
+        output.push('>');
+
+        serialize_children_into(output, shadow_root, options);
+
+        output.push_str("</template>");
+    }
+
+    for child in arena::get(node).borrow().children() {
+        serialize_node_into(output, child, options);
+    }
+}
+
+fn serialize_node_into(output: &mut String, node: NodeId, options: &GetHtmlOptions) {
+    let current = arena::get(node);
+    let current = current.borrow();
+
+    match &current.node_type {
+        NodeType::Element(element) => {
+            let tag_name = match element.name.namespace {
+                Some(Namespace::Html | Namespace::MathMl | Namespace::Svg) => element.name.local_name.to_string(),
+                _ => element.name.qualified(),
+            };
+
+            output.push('<');
+
+            output.push_str(&tag_name);
+
+            if let Some(is) = &element.is_value && element.attribute(QualifiedName::new(None, atoms::IS)).is_none() {
+                output.push_str(" is=\"");
+
+                escape(output, is, true);
+
+                output.push('"');
+            }
+
+            for attribute in element.attributes.iter() {
+                output.push(' ');
+
+                output.push_str(&attribute_name(&attribute.name));
+
+                output.push_str("=\"");
+
+                escape(output, &attribute.value, true);
+
+                output.push('"');
+            }
+
+            output.push('>');
+
+            if serializes_as_void(&element.name) {
+                return;
+            }
+
+            // NOTE: the parser drops a newline directly after the start tag of these elements, so a
+            // leading newline of the text has to be doubled to survive parsing.
+            let leading_newline = [atoms::PRE, atoms::TEXTAREA, atoms::LISTING].iter().any(|name| element.name.is_html(name.clone()))
+                && current.first_child.is_some_and(|child| {
+                    matches!(&arena::get(child).borrow().node_type, NodeType::Text(text) if text.character_data.data.starts_with('\n'))
+                });
+
+            if leading_newline {
+                output.push('\n');
+            }
+
+            serialize_children_into(output, node, options);
+
+            output.push_str(&format!("</{tag_name}>"));
+        },
+        NodeType::Text(_) | NodeType::CdataSection(_) => {
+            let data = &current.downcast_ref::<CharacterData>().data;
+
+            match current.parent.is_some_and(is_raw_text_parent) {
+                true => output.push_str(data),
+                false => escape(output, data, false),
+            }
+        },
+        NodeType::Comment(comment) => output.push_str(&format!("<!--{}-->", comment.character_data.data)),
+        NodeType::ProcessingInstruction(processing_instruction) => {
+            output.push_str(&format!("<?{} {}>", processing_instruction.target, processing_instruction.character_data.data));
+        },
+        NodeType::DocumentType(document_type) => output.push_str(&format!("<!DOCTYPE {}>", document_type.name)),
+        NodeType::Document(_) | NodeType::DocumentFragment(_) | NodeType::ShadowRoot(_) => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_html;
+    use crate::dom::Dom;
+    use crate::dom::testing::{element, create_element};
+    use crate::dom::node::element::Element;
+
+    #[test]
+    fn round_trip() {
+        let html = parse_html(include_str!("../../data/small-fragment.html")).to_html();
+
+        assert_eq!(html, concat!(
+            "<!DOCTYPE html><html><head>\n",
+            "        <title>Quick Example Site</title>\n",
+            "    </head>\n",
+            "    <body>\n",
+            "        <h1 style=\"background-color: blue;\">Blue Header</h1>\n",
+            "        <img src=\"https://github.githubassets.com/assets/GitHub-Mark-ea2971cee799.png\" alt=\"github logo\">\n",
+            "    \n\n\n\n</body></html>",
+        ));
+
+        assert_eq!(parse_html(&html).to_html(), html);
+    }
+
+    #[test]
+    fn void_elements() {
+        let mut dom = parse_html("<div id=a><br><img alt=x><input value=y><hr/></div><svg id=b><input/></svg>");
+
+        let a = element(&dom, "a");
+
+        assert_eq!(Element::inner_html(a), "<br><img alt=\"x\"><input value=\"y\"><hr>");
+
+        let br = create_element(&mut dom, "br");
+        let text = dom.create_text_node(String::from("x"));
+
+        dom.append_child(br, text).expect("append");
+
+        assert_eq!(Element::outer_html(br), "<br>");
+        assert_eq!(Element::inner_html(br), "");
+        assert_eq!(Element::inner_html(element(&dom, "b")), "<input></input>");
+    }
+
+    #[test]
+    fn escaping() {
+        let dom = parse_html("<p id=a title='a\"b&amp;c<d>&nbsp;'>x &amp; &lt;&gt;&nbsp;\"</p>");
+
+        assert_eq!(
+            Element::outer_html(element(&dom, "a")),
+            "<p id=\"a\" title=\"a&quot;b&amp;c&lt;d&gt;&nbsp;\">x &amp; &lt;&gt;&nbsp;\"</p>",
+        );
+
+        let mut dom = Dom::new();
+
+        let comment = dom.create_comment(String::from("a < b & c"));
+
+        assert_eq!(serialize_node(comment, &GetHtmlOptions::default()), "<!--a < b & c-->");
+    }
+
+    #[test]
+    fn raw_text() {
+        let mut dom = parse_html(concat!(
+            "<script id=a>if (a < b && c > d) {}</script>",
+            "<style id=b>a > b { content: \"&amp;\" }</style>",
+            "<div id=c><textarea>a < b &amp; c</textarea><noscript>a &lt; b</noscript><pre>\n\nx</pre></div>",
+        ));
+
+        assert_eq!(Element::inner_html(element(&dom, "a")), "if (a < b && c > d) {}");
+        assert_eq!(Element::inner_html(element(&dom, "b")), "a > b { content: \"&amp;\" }");
+        assert_eq!(Element::inner_html(element(&dom, "c")), "<textarea>a &lt; b &amp; c</textarea><noscript>a &lt; b</noscript><pre>\n\nx</pre>");
+
+        dom.set_scripting(false);
+
+        assert_eq!(Element::inner_html(element(&dom, "c")), "<textarea>a &lt; b &amp; c</textarea><noscript>a &amp;lt; b</noscript><pre>\n\nx</pre>");
+    }
+}
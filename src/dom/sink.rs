@@ -0,0 +1,167 @@
+use crate::dom::Dom;
+use crate::dom::arena::{self, NodeId};
+use crate::dom::node::{Node, NodeType, QualifiedName};
+use crate::dom::node::element::Element;
+use crate::dom::node::character_data::CharacterData;
+use crate::dom::custom_elements::{self, CustomElementDefinition, NullOrCustomElementRegistry};
+use crate::parser::interface::{self, TreeSink};
+use crate::parser::error::TreeBuilderError;
+use crate::parser::quirks::QuirksMode;
+use crate::tokenizer::{Doctype, Position};
+
+use std::rc::Rc;
+
+
+impl interface::Node for NodeId {
+    type CustomElementRegistry = NullOrCustomElementRegistry;
+
+    fn node_document(&self) -> NodeId {
+        arena::get(self).borrow().node_document
+    }
+
+    fn root(&self) -> NodeId {
+        Node::root(*self)
+    }
+
+    fn element_name(&self) -> QualifiedName {
+        arena::get(self).borrow().downcast_ref::<Element>().name.clone()
+    }
+
+    fn custom_element_registry(&self) -> Option<NullOrCustomElementRegistry> {
+        match &arena::get(self).borrow().node_type {
+            NodeType::Element(element) => Some(element.custom_element_registry.clone()),
+            NodeType::Document(document) => Some(document.custom_element_registry.clone()),
+            NodeType::ShadowRoot(shadow_root) => Some(shadow_root.custom_element_registry.clone()),
+            _ => None,
+        }
+    }
+
+    fn parent(&self) -> Option<NodeId> {
+        arena::get(self).borrow().parent
+    }
+
+    fn last_child(&self) -> Option<NodeId> {
+        arena::get(self).borrow().last_child
+    }
+
+    fn previous_sibling(&self) -> Option<NodeId> {
+        arena::get(self).borrow().previous_sibling
+    }
+
+    fn append(&mut self, child: &NodeId) {
+        custom_elements::ce_reactions(*self, || Node::insert(*self, *child, None, false));
+    }
+
+    fn append_before(&mut self, before: &NodeId, child: &NodeId) {
+        custom_elements::ce_reactions(*self, || Node::insert(*self, *child, Some(*before), false));
+    }
+
+    fn remove_from_parent(&mut self) {
+        custom_elements::ce_reactions(*self, || Node::remove(*self, false));
+    }
+
+    fn reparent_children(&mut self, new_parent: &NodeId) {
+        let children = arena::get(*self).borrow().children().collect::<Vec<NodeId>>();
+
+        custom_elements::ce_reactions(*self, || {
+            for child in children {
+                Node::remove(child, false);
+
+                Node::insert(*new_parent, child, None, false);
+            }
+        });
+    }
+
+    // NOTE: the data is appended directly as the parser never has live ranges to adjust.
+    fn append_text(&mut self, text: &str) {
+        arena::with_mut(*self, |node| node.downcast_mut::<CharacterData>().data.push_str(text));
+    }
+
+    fn append_attribute(&mut self, name: QualifiedName, value: &str) {
+        custom_elements::ce_reactions(*self, || Element::append_attribute(*self, name, value.to_string()));
+    }
+
+    fn has_attribute(&self, name: QualifiedName) -> bool {
+        arena::get(self).borrow().downcast_ref::<Element>().attribute(name).is_some()
+    }
+
+    fn set_parser_inserted(&self) {
+        arena::with_mut(*self, |node| node.downcast_mut::<Element>().parser_inserted = true);
+    }
+
+    fn set_associated_form(&self, form: NodeId) {
+        arena::with_mut(*self, |node| node.downcast_mut::<Element>().form_owner = Some(form));
+    }
+
+    fn is_node(&self, kind: &str) -> bool {
+        match &arena::get(self).borrow().node_type {
+            NodeType::Element(_) => kind == "element",
+            NodeType::Document(_) => kind == "document",
+            NodeType::DocumentType(_) => kind == "document-type",
+            NodeType::DocumentFragment(_) | NodeType::ShadowRoot(_) => kind == "document-fragment",
+            NodeType::Text(_) | NodeType::CdataSection(_) => kind == "text",
+            NodeType::ProcessingInstruction(_) => kind == "processing-instruction",
+            NodeType::Comment(_) => kind == "comment",
+        }
+    }
+}
+
+impl TreeSink for Dom {
+    type CustomElementDefinition = Rc<CustomElementDefinition>;
+
+    type CustomElementRegistry = NullOrCustomElementRegistry;
+
+    type Handle = NodeId;
+
+    fn document(&self) -> NodeId {
+        self.document
+    }
+
+    fn custom_element_definition(
+        &self,
+        registry: &Option<NullOrCustomElementRegistry>,
+        name: QualifiedName,
+        is: Option<&str>
+    ) -> Option<Rc<CustomElementDefinition>> {
+        registry.as_ref().and_then(|registry| registry.look_up(name, is))
+    }
+
+    fn parse_error(&mut self, error: TreeBuilderError, position: Option<Position>) {
+        self.errors.push((error, position));
+    }
+
+    fn create_element(
+        &mut self,
+        document: &NodeId,
+        name: QualifiedName,
+        is: Option<&str>,
+        sync: bool,
+        registry: &Option<NullOrCustomElementRegistry>
+    ) -> NodeId {
+        Element::create(*document, name, is, sync, registry.clone().unwrap_or_default())
+    }
+
+    fn create_text(&mut self, data: &str) -> NodeId {
+        Dom::create_text_node(self, data.to_string())
+    }
+
+    fn create_comment(&mut self, content: &str) -> NodeId {
+        Dom::create_comment(self, content.to_string())
+    }
+
+    fn append_doctype(&mut self, doctype: &Doctype) {
+        let name = doctype.name.map(|name| name.to_string()).unwrap_or_default();
+        let public_id = doctype.public_id.map(|public_id| public_id.to_string()).unwrap_or_default();
+        let system_id = doctype.system_id.map(|system_id| system_id.to_string()).unwrap_or_default();
+
+        Dom::append_doctype(self, name, public_id, system_id);
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        Dom::set_quirks_mode(self, mode);
+    }
+
+    fn set_scripting(&mut self, scripting: bool) {
+        Dom::set_scripting(self, scripting);
+    }
+}
@@ -0,0 +1,12 @@
+use crate::dom::{Dom, NodeId, ElementCreationOptions};
+
+
+/// The element with the id in the document of the dom.
+pub(crate) fn element(dom: &Dom, id: &str) -> NodeId {
+    dom.get_element_by_id(id).expect("element with id")
+}
+
+/// Create a html element with the local name and default options.
+pub(crate) fn create_element(dom: &mut Dom, local_name: &str) -> NodeId {
+    dom.create_element(local_name, ElementCreationOptions::default()).expect("valid name")
+}
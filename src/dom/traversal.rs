@@ -0,0 +1,683 @@
+use crate::dom::arena::{self, NodeId, NodeHandle};
+use crate::dom::iterators::last_inclusive_descendant;
+use crate::dom::node::{Node, NodeType};
+
+use crate::dom::node::document::Document;
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+
+// https://dom.spec.whatwg.org/#dom-nodefilter-filter_accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterResult {
+    Accept,
+    Reject,
+    Skip,
+}
+
+// https://dom.spec.whatwg.org/#callbackdef-nodefilter
+/// The filter of a traversal, called with each node that is shown by the what to show bitmask.
+///
+/// A rejected node is skipped together with its children by a [`TreeWalker`], a [`NodeIterator`]
+/// treats it like a skipped node.
+#[derive(Clone)]
+pub struct NodeFilter {
+    accept_node: Rc<dyn Fn(NodeId) -> FilterResult>,
+}
+
+impl NodeFilter {
+    pub const SHOW_ALL: u32 = 0xffffffff;
+    pub const SHOW_ELEMENT: u32 = 0x1;
+    pub const SHOW_ATTRIBUTE: u32 = 0x2;
+    pub const SHOW_TEXT: u32 = 0x4;
+    pub const SHOW_CDATA_SECTION: u32 = 0x8;
+    pub const SHOW_PROCESSING_INSTRUCTION: u32 = 0x40;
+    pub const SHOW_COMMENT: u32 = 0x80;
+    pub const SHOW_DOCUMENT: u32 = 0x100;
+    pub const SHOW_DOCUMENT_TYPE: u32 = 0x200;
+    pub const SHOW_DOCUMENT_FRAGMENT: u32 = 0x400;
+
+    pub fn new(accept_node: impl Fn(NodeId) -> FilterResult + 'static) -> NodeFilter {
+        NodeFilter {
+            accept_node: Rc::new(accept_node),
+        }
+    }
+}
+
+/// Return the what to show bit of the node, shadow roots are shown as document fragments.
+fn what_to_show_bit(node: NodeId) -> u32 {
+    match arena::get(node).borrow().node_type {
+        NodeType::Element(_) => NodeFilter::SHOW_ELEMENT,
+        NodeType::Text(_) => NodeFilter::SHOW_TEXT,
+        NodeType::CdataSection(_) => NodeFilter::SHOW_CDATA_SECTION,
+        NodeType::ProcessingInstruction(_) => NodeFilter::SHOW_PROCESSING_INSTRUCTION,
+        NodeType::Comment(_) => NodeFilter::SHOW_COMMENT,
+        NodeType::Document(_) => NodeFilter::SHOW_DOCUMENT,
+        NodeType::DocumentType(_) => NodeFilter::SHOW_DOCUMENT_TYPE,
+        NodeType::DocumentFragment(_) | NodeType::ShadowRoot(_) => NodeFilter::SHOW_DOCUMENT_FRAGMENT,
+    }
+}
+
+// https://dom.spec.whatwg.org/#concept-node-filter
+// NOTE: the filter can not throw and can not reach the traverser it belongs to, so there is no
+// need for the active flag.
+fn filter(node: NodeId, what_to_show: u32, filter: &Option<NodeFilter>) -> FilterResult {
+    if what_to_show & what_to_show_bit(node) == 0 {
+        return FilterResult::Skip;
+    }
+
+    match filter {
+        Some(filter) => (filter.accept_node)(node),
+        None => FilterResult::Accept,
+    }
+}
+
+/// Return the node following the node in tree order, stopping at the root.
+fn following(root: NodeId, node: NodeId) -> Option<NodeId> {
+    if let Some(first_child) = arena::get(node).borrow().first_child {
+        return Some(first_child);
+    }
+
+    let mut node = node;
+
+    loop {
+        if node == root {
+            return None;
+        }
+
+        let current = arena::get(node);
+
+        let current = current.borrow();
+
+        if let Some(next_sibling) = current.next_sibling {
+            return Some(next_sibling);
+        }
+
+        node = current.parent?;
+    }
+}
+
+/// Return the node preceding the node in tree order, stopping at the root.
+fn preceding(root: NodeId, node: NodeId) -> Option<NodeId> {
+    if node == root {
+        return None;
+    }
+
+    let current = arena::get(node);
+
+    let current = current.borrow();
+
+    match current.previous_sibling {
+        Some(previous_sibling) => Some(last_inclusive_descendant(previous_sibling)),
+        None => current.parent,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Next,
+    Previous,
+}
+
+// https://dom.spec.whatwg.org/#interface-treewalker
+/// A walker over the nodes of the subtree of a root, moving from its current node in any direction.
+///
+/// The root and the current node are held by handles, they are kept alive and followed through
+/// compaction.
+pub struct TreeWalker {
+    root: NodeHandle,
+    what_to_show: u32,
+    filter: Option<NodeFilter>,
+    current: NodeHandle,
+}
+
+impl Clone for TreeWalker {
+    /// Clone the walker with its own current node, moving the clone does not move the walker.
+    fn clone(&self) -> TreeWalker {
+        TreeWalker {
+            root: self.root.clone(),
+            what_to_show: self.what_to_show,
+            filter: self.filter.clone(),
+            current: NodeHandle::new(self.current.id()),
+        }
+    }
+}
+
+impl TreeWalker {
+    // https://dom.spec.whatwg.org/#dom-document-createtreewalker
+    pub fn new(root: NodeId, what_to_show: u32, filter: Option<NodeFilter>) -> TreeWalker {
+        TreeWalker {
+            root: NodeHandle::new(root),
+            what_to_show,
+            filter,
+            current: NodeHandle::new(root),
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-root
+    pub fn root(&self) -> NodeId {
+        self.root.id()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-whattoshow
+    pub fn what_to_show(&self) -> u32 {
+        self.what_to_show
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-currentnode
+    pub fn current_node(&self) -> NodeId {
+        self.current.id()
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-currentnode
+    pub fn set_current_node(&mut self, node: NodeId) {
+        self.current.set(node);
+    }
+
+    fn filter(&self, node: NodeId) -> FilterResult {
+        filter(node, self.what_to_show, &self.filter)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-parentnode
+    pub fn parent_node(&mut self) -> Option<NodeId> {
+        let mut node = self.current.id();
+
+        while node != self.root.id() {
+            node = arena::get(node).borrow().parent?;
+
+            if self.filter(node) == FilterResult::Accept {
+                self.current.set(node);
+
+                return Some(node);
+            }
+        }
+
+        None
+    }
+
+    // https://dom.spec.whatwg.org/#concept-traverse-children
+    fn traverse_children(&mut self, direction: Direction) -> Option<NodeId> {
+        let first = |node: NodeId| match direction {
+            Direction::Next => arena::get(node).borrow().first_child,
+            Direction::Previous => arena::get(node).borrow().last_child,
+        };
+
+        let sibling = |node: NodeId| match direction {
+            Direction::Next => arena::get(node).borrow().next_sibling,
+            Direction::Previous => arena::get(node).borrow().previous_sibling,
+        };
+
+        let mut node = first(self.current.id())?;
+
+        loop {
+            match self.filter(node) {
+                FilterResult::Accept => {
+                    self.current.set(node);
+
+                    return Some(node);
+                },
+                FilterResult::Skip => {
+                    if let Some(child) = first(node) {
+                        node = child;
+
+                        continue;
+                    }
+                },
+                FilterResult::Reject => {},
+            }
+
+            loop {
+                if let Some(sibling) = sibling(node) {
+                    node = sibling;
+
+                    break;
+                }
+
+                let parent = arena::get(node).borrow().parent.filter(|parent| *parent != self.root.id() && *parent != self.current.id())?;
+
+                node = parent;
+            }
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-firstchild
+    pub fn first_child(&mut self) -> Option<NodeId> {
+        self.traverse_children(Direction::Next)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-lastchild
+    pub fn last_child(&mut self) -> Option<NodeId> {
+        self.traverse_children(Direction::Previous)
+    }
+
+    // https://dom.spec.whatwg.org/#concept-traverse-siblings
+    fn traverse_siblings(&mut self, direction: Direction) -> Option<NodeId> {
+        let mut node = self.current.id();
+
+        if node == self.root.id() {
+            return None;
+        }
+
+        let sibling_of = |node: NodeId| match direction {
+            Direction::Next => arena::get(node).borrow().next_sibling,
+            Direction::Previous => arena::get(node).borrow().previous_sibling,
+        };
+
+        loop {
+            let mut sibling = sibling_of(node);
+
+            while let Some(current) = sibling {
+                node = current;
+
+                let result = self.filter(node);
+
+                if result == FilterResult::Accept {
+                    self.current.set(node);
+
+                    return Some(node);
+                }
+
+                sibling = match direction {
+                    Direction::Next => arena::get(node).borrow().first_child,
+                    Direction::Previous => arena::get(node).borrow().last_child,
+                };
+
+                if result == FilterResult::Reject || sibling.is_none() {
+                    sibling = sibling_of(node);
+                }
+            }
+
+            node = arena::get(node).borrow().parent.filter(|parent| *parent != self.root.id())?;
+
+            if self.filter(node) == FilterResult::Accept {
+                return None;
+            }
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-nextsibling
+    pub fn next_sibling(&mut self) -> Option<NodeId> {
+        self.traverse_siblings(Direction::Next)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-previoussibling
+    pub fn previous_sibling(&mut self) -> Option<NodeId> {
+        self.traverse_siblings(Direction::Previous)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-previousnode
+    pub fn previous_node(&mut self) -> Option<NodeId> {
+        let mut node = self.current.id();
+
+        while node != self.root.id() {
+            let mut sibling = arena::get(node).borrow().previous_sibling;
+
+            while let Some(current) = sibling {
+                node = current;
+
+                let mut result = self.filter(node);
+
+                while result != FilterResult::Reject && let Some(last_child) = arena::get(node).borrow().last_child {
+                    node = last_child;
+
+                    result = self.filter(node);
+                }
+
+                if result == FilterResult::Accept {
+                    self.current.set(node);
+
+                    return Some(node);
+                }
+
+                sibling = arena::get(node).borrow().previous_sibling;
+            }
+
+            if node == self.root.id() {
+                return None;
+            }
+
+            node = arena::get(node).borrow().parent?;
+
+            if self.filter(node) == FilterResult::Accept {
+                self.current.set(node);
+
+                return Some(node);
+            }
+        }
+
+        None
+    }
+
+    // https://dom.spec.whatwg.org/#dom-treewalker-nextnode
+    pub fn next_node(&mut self) -> Option<NodeId> {
+        let mut node = self.current.id();
+        let mut result = FilterResult::Accept;
+
+        loop {
+            while result != FilterResult::Reject && let Some(first_child) = arena::get(node).borrow().first_child {
+                node = first_child;
+
+                result = self.filter(node);
+
+                if result == FilterResult::Accept {
+                    self.current.set(node);
+
+                    return Some(node);
+                }
+            }
+
+            let mut temporary = node;
+
+            loop {
+                if temporary == self.root.id() {
+                    return None;
+                }
+
+                let current = arena::get(temporary);
+
+                let current = current.borrow();
+
+                if let Some(next_sibling) = current.next_sibling {
+                    node = next_sibling;
+
+                    break;
+                }
+
+                temporary = current.parent?;
+            }
+
+            result = self.filter(node);
+
+            if result == FilterResult::Accept {
+                self.current.set(node);
+
+                return Some(node);
+            }
+        }
+    }
+}
+
+/// The reference of a node iterator, shared with the node iterators of the document of its root so
+/// the pre-removing steps can adjust it.
+pub(crate) struct IteratorPosition {
+    root: NodeId,
+    reference: NodeId,
+    pointer_before_reference: bool,
+}
+
+impl IteratorPosition {
+    pub(crate) fn for_each_reference(&mut self, f: &mut impl FnMut(&mut NodeId)) {
+        f(&mut self.root);
+
+        f(&mut self.reference);
+    }
+}
+
+// https://dom.spec.whatwg.org/#interface-nodeiterator
+/// An iterator over the inclusive descendants of a root in tree order, which keeps its position
+/// when the nodes around it are removed.
+#[derive(Clone)]
+pub struct NodeIterator {
+    position: Rc<RefCell<IteratorPosition>>,
+    what_to_show: u32,
+    filter: Option<NodeFilter>,
+}
+
+impl NodeIterator {
+    // https://dom.spec.whatwg.org/#dom-document-createnodeiterator
+    pub fn new(root: NodeId, what_to_show: u32, filter: Option<NodeFilter>) -> NodeIterator {
+        let position = Rc::new(RefCell::new(IteratorPosition {
+            root,
+            reference: root,
+            pointer_before_reference: true,
+        }));
+
+        let document = arena::get(root).borrow().node_document;
+
+        arena::with_mut(document, |document| {
+            document.downcast_mut::<Document>().node_iterators.push(Rc::downgrade(&position));
+        });
+
+        NodeIterator {
+            position,
+            what_to_show,
+            filter,
+        }
+    }
+
+    // https://dom.spec.whatwg.org/#dom-nodeiterator-root
+    pub fn root(&self) -> NodeId {
+        self.position.borrow().root
+    }
+
+    // https://dom.spec.whatwg.org/#dom-nodeiterator-referencenode
+    pub fn reference_node(&self) -> NodeId {
+        self.position.borrow().reference
+    }
+
+    // https://dom.spec.whatwg.org/#dom-nodeiterator-pointerbeforereferencenode
+    pub fn pointer_before_reference_node(&self) -> bool {
+        self.position.borrow().pointer_before_reference
+    }
+
+    // https://dom.spec.whatwg.org/#dom-nodeiterator-whattoshow
+    pub fn what_to_show(&self) -> u32 {
+        self.what_to_show
+    }
+
+    // https://dom.spec.whatwg.org/#concept-nodeiterator-traverse
+    fn traverse(&self, direction: Direction) -> Option<NodeId> {
+        let (root, mut node, mut before_node) = {
+            let position = self.position.borrow();
+
+            (position.root, position.reference, position.pointer_before_reference)
+        };
+
+        loop {
+            match direction {
+                Direction::Next => {
+                    if !before_node {
+                        node = following(root, node)?;
+                    }
+
+                    before_node = false;
+                },
+                Direction::Previous => {
+                    if before_node {
+                        node = preceding(root, node)?;
+                    }
+
+                    before_node = true;
+                },
+            }
+
+            if filter(node, self.what_to_show, &self.filter) == FilterResult::Accept {
+                break;
+            }
+        }
+
+        let mut position = self.position.borrow_mut();
+
+        position.reference = node;
+        position.pointer_before_reference = before_node;
+
+        Some(node)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-nodeiterator-nextnode
+    pub fn next_node(&self) -> Option<NodeId> {
+        self.traverse(Direction::Next)
+    }
+
+    // https://dom.spec.whatwg.org/#dom-nodeiterator-previousnode
+    pub fn previous_node(&self) -> Option<NodeId> {
+        self.traverse(Direction::Previous)
+    }
+}
+
+// https://dom.spec.whatwg.org/#nodeiterator-pre-removing-steps
+/// Move the reference of the node iterators in the document of the node out of the node before it
+/// is removed, the iterators that were dropped are forgotten.
+pub(crate) fn pre_removing_steps(node: NodeId) {
+    let document = arena::get(node).borrow().node_document;
+
+    let iterators = arena::with_mut(document, |document| {
+        let iterators = &mut document.downcast_mut::<Document>().node_iterators;
+
+        iterators.retain(|iterator| iterator.strong_count() > 0);
+
+        iterators.iter().filter_map(Weak::upgrade).collect::<Vec<_>>()
+    });
+
+    for iterator in iterators {
+        let mut position = iterator.borrow_mut();
+
+        if !Node::is_inclusive_ancestor(node, position.reference) || node == position.root
+        {
+            continue;
+        }
+
+        if position.pointer_before_reference {
+            let mut following = node;
+
+            let next = loop {
+                if following == position.root {
+                    break None;
+                }
+
+                let current = arena::get(following);
+
+                let current = current.borrow();
+
+                if let Some(next_sibling) = current.next_sibling {
+                    break Some(next_sibling);
+                }
+
+                match current.parent {
+                    Some(parent) => following = parent,
+                    None => break None,
+                }
+            };
+
+            if let Some(next) = next.filter(|next| Node::is_inclusive_ancestor(position.root, *next)) {
+                position.reference = next;
+
+                continue;
+            }
+
+            position.pointer_before_reference = false;
+        }
+
+        let current = arena::get(node);
+
+        let current = current.borrow();
+
+        position.reference = match current.previous_sibling {
+            Some(previous_sibling) => last_inclusive_descendant(previous_sibling),
+            None => current.parent.expect("the removed node has a parent"),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_html;
+    use crate::dom::Dom;
+    use crate::dom::node::element::Element;
+    use crate::dom::node::text::Text;
+
+    /// A document with a subtree rooted at #root: `root(a(b, "text"), c(d), e)`.
+    fn document() -> (Dom, NodeId) {
+        let dom = parse_html("<div id=root><p id=a><i id=b></i>text</p><p id=c><i id=d></i></p><p id=e></p></div>");
+
+        let root = dom.get_element_by_id("root").expect("root");
+
+        (dom, root)
+    }
+
+    fn ids(nodes: impl IntoIterator<Item = Option<NodeId>>) -> Vec<String> {
+        nodes.into_iter()
+            .map(|node| node.and_then(Element::id).unwrap_or_else(|| String::from("-")))
+            .collect()
+    }
+
+    fn reject(id: &'static str) -> NodeFilter {
+        NodeFilter::new(move |node| match Element::id(node).as_deref() == Some(id) {
+            true => FilterResult::Reject,
+            false => FilterResult::Accept,
+        })
+    }
+
+    #[test]
+    fn tree_walker_moves_in_every_direction() {
+        let (dom, root) = document();
+
+        let mut walker = dom.create_tree_walker(root, NodeFilter::SHOW_ELEMENT, None);
+
+        assert_eq!(ids((0..6).map(|_| walker.next_node())), vec!["a", "b", "c", "d", "e", "-"]);
+        assert_eq!(walker.current_node(), dom.get_element_by_id("e").expect("e"));
+
+        assert_eq!(ids([walker.previous_sibling(), walker.first_child(), walker.parent_node(), walker.parent_node(), walker.parent_node()]), vec!["c", "d", "c", "root", "-"]);
+        assert_eq!(ids([walker.last_child(), walker.previous_node(), walker.previous_node(), walker.next_sibling()]), vec!["e", "d", "c", "e"]);
+    }
+
+    #[test]
+    fn tree_walker_filters() {
+        let (dom, root) = document();
+
+        let mut walker = dom.create_tree_walker(root, NodeFilter::SHOW_ALL, Some(reject("c")));
+
+        assert_eq!(ids((0..6).map(|_| walker.next_node())), vec!["a", "b", "-", "e", "-", "-"]);
+        assert!(walker.previous_node().is_some_and(Text::is_text));
+
+        let skip_c = NodeFilter::new(|node| match Element::id(node).as_deref() {
+            Some("c") => FilterResult::Skip,
+            _ => FilterResult::Accept,
+        });
+
+        let mut walker = dom.create_tree_walker(root, NodeFilter::SHOW_ELEMENT, Some(skip_c));
+
+        assert_eq!(ids([walker.first_child(), walker.next_sibling(), walker.next_sibling()]), vec!["a", "d", "e"]);
+    }
+
+    #[test]
+    fn node_iterator() {
+        let (dom, root) = document();
+
+        let iterator = dom.create_node_iterator(root, NodeFilter::SHOW_ELEMENT, Some(reject("c")));
+
+        assert_eq!(ids((0..6).map(|_| iterator.next_node())), vec!["root", "a", "b", "d", "e", "-"]);
+        assert_eq!(ids([iterator.previous_node(), iterator.previous_node()]), vec!["e", "d"]);
+        assert!(iterator.pointer_before_reference_node());
+        assert_eq!(iterator.root(), root);
+    }
+
+    #[test]
+    fn node_iterator_survives_removal() {
+        let (mut dom, root) = document();
+
+        let iterator = dom.create_node_iterator(root, NodeFilter::SHOW_ELEMENT, None);
+
+        assert_eq!(ids([iterator.next_node(), iterator.next_node(), iterator.next_node()]), vec!["root", "a", "b"]);
+
+        let a = dom.get_element_by_id("a").expect("a");
+
+        dom.remove(a);
+
+        assert_eq!(ids([Some(iterator.reference_node())]), vec!["root"]);
+        assert!(!iterator.pointer_before_reference_node());
+        assert_eq!(ids([iterator.next_node(), iterator.next_node()]), vec!["c", "d"]);
+
+        let c = dom.get_element_by_id("c").expect("c");
+
+        assert_eq!(ids([iterator.previous_node()]), vec!["d"]);
+
+        dom.remove(c);
+
+        assert_eq!(ids([Some(iterator.reference_node())]), vec!["e"]);
+        assert!(iterator.pointer_before_reference_node());
+        assert_eq!(ids([iterator.next_node(), iterator.next_node()]), vec!["e", "-"]);
+    }
+}
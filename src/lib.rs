@@ -0,0 +1,68 @@
+pub mod tokenizer;
+pub mod parser;
+pub mod dom;
+pub mod names;
+pub mod selectors;
+
+use parser::ParserOptions;
+use dom::Dom;
+
+
+/// Parse a html document into a dom with the default parser options.
+pub fn parse_html(input: &str) -> Dom {
+    parser::parse_document(input, ParserOptions::default(), Dom::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_html;
+    use crate::dom::node::element::Element;
+
+    fn body(input: &str) -> String {
+        let html = parse_html(input).to_html();
+
+        html.split_once("<body>")
+            .and_then(|(_, body)| body.strip_suffix("</body></html>"))
+            .unwrap_or(&html)
+            .to_string()
+    }
+
+    #[test]
+    fn builds_a_document() {
+        assert_eq!(
+            parse_html("<!DOCTYPE html><title>t</title><p id=a>x").to_html(),
+            "<!DOCTYPE html><html><head><title>t</title></head><body><p id=\"a\">x</p></body></html>",
+        );
+    }
+
+    #[test]
+    fn character_references_in_text() {
+        assert_eq!(body("<p>a &amp; b &lt; c&gt;</p>"), "<p>a &amp; b &lt; c&gt;</p>");
+        assert_eq!(body("<p>&copy;&#169;&#xA9;&copy</p>"), "<p>\u{a9}\u{a9}\u{a9}\u{a9}</p>");
+        assert_eq!(body("<p>&notit; &notin;</p>"), "<p>\u{ac}it; \u{2209}</p>");
+        assert_eq!(body("<p>&#0;&#x110000;&#128;&#xD800;</p>"), "<p>\u{fffd}\u{fffd}\u{20ac}\u{fffd}</p>");
+        assert_eq!(body("<p>&nosuchref; &# &#x; a&b</p>"), "<p>&amp;nosuchref; &amp;# &amp;#x; a&amp;b</p>");
+    }
+
+    #[test]
+    fn character_references_in_raw_text() {
+        assert_eq!(body("<textarea>&amp;</textarea>"), "<textarea>&amp;</textarea>");
+        assert_eq!(parse_html("<style>&amp;</style>").to_html(), "<html><head><style>&amp;</style></head><body></body></html>");
+    }
+
+    #[test]
+    fn character_references_in_attributes() {
+        let dom = parse_html("<a id=a href=\"?a=1&b=2&copy=3&amp;x\" title='&copy &amp' rel=x&lt;y></a>");
+        let a = dom.get_element_by_id("a").expect("a");
+
+        assert_eq!(Element::get_attribute(a, "href").as_deref(), Some("?a=1&b=2&copy=3&x"));
+        assert_eq!(Element::get_attribute(a, "title").as_deref(), Some("\u{a9} &"));
+        assert_eq!(Element::get_attribute(a, "rel").as_deref(), Some("x<y"));
+    }
+
+    #[test]
+    fn frameset_and_template() {
+        assert_eq!(parse_html("<frameset></frameset>").to_html(), "<html><head></head><frameset></frameset></html>");
+        assert_eq!(body("<div><template>&amp;</template></div>"), "<div><template>&amp;</template></div>");
+    }
+}
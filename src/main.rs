@@ -1,11 +1,5 @@
-#![feature(box_into_inner)]
-
-pub mod tokenizer;
-pub mod parser;
-pub mod dom;
 
 
 fn main() {
 }
 
-
@@ -0,0 +1,309 @@
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use std::fmt;
+
+
+/// Declares the static names, every name gets a constant in [`atoms`] whose index is known at
+/// compile time.
+macro_rules! static_atoms {
+    ($($ident:ident => $name:literal,)*) => {
+        #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+        #[repr(u32)]
+        enum StaticAtom {
+            $($ident,)*
+        }
+
+        const STATIC_ATOMS: &[&str] = &[$($name,)*];
+
+        /// Static names, comparing against these compares their index.
+        pub mod atoms {
+            use super::{Atom, StaticAtom};
+
+            $(pub const $ident: Atom = Atom(super::Repr::Static(StaticAtom::$ident as u32));)*
+        }
+    };
+}
+
+static_atoms! {
+    EMPTY => "",
+    A => "a",
+    ADDRESS => "address",
+    ANNOTATION_XML => "annotation-xml",
+    APPLET => "applet",
+    AREA => "area",
+    ARTICLE => "article",
+    AS => "as",
+    ASIDE => "aside",
+    B => "b",
+    BASE => "base",
+    BASEFONT => "basefont",
+    BGSOUND => "bgsound",
+    BIG => "big",
+    BLOCKQUOTE => "blockquote",
+    BODY => "body",
+    BR => "br",
+    BUTTON => "button",
+    CAPTION => "caption",
+    CENTER => "center",
+    CHECKED => "checked",
+    CLASS => "class",
+    CODE => "code",
+    COL => "col",
+    COLGROUP => "colgroup",
+    COLOR => "color",
+    DD => "dd",
+    DESC => "desc",
+    DETAILS => "details",
+    DIALOG => "dialog",
+    DIR => "dir",
+    DISABLED => "disabled",
+    DIV => "div",
+    DL => "dl",
+    DT => "dt",
+    EM => "em",
+    EMBED => "embed",
+    FACE => "face",
+    FIELDSET => "fieldset",
+    FIGCAPTION => "figcaption",
+    FIGURE => "figure",
+    FONT => "font",
+    FOOTER => "footer",
+    FOREIGNOBJECT => "foreignObject",
+    FORM => "form",
+    FRAME => "frame",
+    FRAMESET => "frameset",
+    H1 => "h1",
+    H2 => "h2",
+    H3 => "h3",
+    H4 => "h4",
+    H5 => "h5",
+    H6 => "h6",
+    HEAD => "head",
+    HEADER => "header",
+    HGROUP => "hgroup",
+    HR => "hr",
+    HREF => "href",
+    HTML => "html",
+    I => "i",
+    ID => "id",
+    IFRAME => "iframe",
+    IMAGE => "image",
+    IMAGESRCSET => "imagesrcset",
+    IMG => "img",
+    INPUT => "input",
+    IS => "is",
+    KEYGEN => "keygen",
+    LEGEND => "legend",
+    LI => "li",
+    LINK => "link",
+    LISTING => "listing",
+    MAIN => "main",
+    MALIGNMARK => "malignmark",
+    MARQUEE => "marquee",
+    MATH => "math",
+    MENU => "menu",
+    META => "meta",
+    MGLYPH => "mglyph",
+    MI => "mi",
+    MN => "mn",
+    MO => "mo",
+    MS => "ms",
+    MTEXT => "mtext",
+    NAME => "name",
+    NAV => "nav",
+    NOBR => "nobr",
+    NOEMBED => "noembed",
+    NOFRAMES => "noframes",
+    NOSCRIPT => "noscript",
+    OBJECT => "object",
+    OL => "ol",
+    OPTGROUP => "optgroup",
+    OPTION => "option",
+    OUTPUT => "output",
+    P => "p",
+    PARAM => "param",
+    PLAINTEXT => "plaintext",
+    PRE => "pre",
+    RB => "rb",
+    REL => "rel",
+    REQUIRED => "required",
+    RP => "rp",
+    RT => "rt",
+    RTC => "rtc",
+    RUBY => "ruby",
+    S => "s",
+    SCRIPT => "script",
+    SEARCH => "search",
+    SECTION => "section",
+    SELECT => "select",
+    SELECTED => "selected",
+    SIZE => "size",
+    SLOT => "slot",
+    SMALL => "small",
+    SOURCE => "source",
+    SPAN => "span",
+    SRC => "src",
+    SRCSET => "srcset",
+    STRIKE => "strike",
+    STRONG => "strong",
+    STYLE => "style",
+    SUB => "sub",
+    SUMMARY => "summary",
+    SUP => "sup",
+    SVG => "svg",
+    TABLE => "table",
+    TBODY => "tbody",
+    TD => "td",
+    TEMPLATE => "template",
+    TEXTAREA => "textarea",
+    TFOOT => "tfoot",
+    TH => "th",
+    THEAD => "thead",
+    TITLE => "title",
+    TR => "tr",
+    TRACK => "track",
+    TT => "tt",
+    TYPE => "type",
+    U => "u",
+    UL => "ul",
+    VAR => "var",
+    WBR => "wbr",
+    XLINK => "xlink",
+    XML => "xml",
+    XMLNS => "xmlns",
+    XMP => "xmp",
+}
+
+/// The index of every static atom, keyed by its name.
+static STATIC_INDICES: LazyLock<HashMap<&'static str, u32>> = LazyLock::new(|| {
+    STATIC_ATOMS.iter().enumerate().map(|(index, name)| (*name, index as u32)).collect()
+});
+
+/// The longest name that is stored inside the atom itself.
+const INLINE_CAPACITY: usize = 22;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Repr {
+    Static(u32),
+    Inline { len: u8, bytes: [u8; INLINE_CAPACITY] },
+    Dynamic(Arc<str>),
+}
+
+/// A name that is cheap to clone and compare.
+///
+/// Static names are compared by their index, short names are stored inline and longer names are
+/// reference counted, so names from untrusted input are freed together with the last atom holding
+/// them. A name always has the same representation, which keeps equality and hashing consistent.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Atom(Repr);
+
+impl Atom {
+    /// Create the atom of a string.
+    pub fn new(string: &str) -> Atom {
+        if let Some(index) = STATIC_INDICES.get(string) {
+            return Atom(Repr::Static(*index));
+        }
+
+        if string.len() <= INLINE_CAPACITY {
+            let mut bytes = [0; INLINE_CAPACITY];
+
+            bytes[..string.len()].copy_from_slice(string.as_bytes());
+
+            return Atom(Repr::Inline { len: string.len() as u8, bytes });
+        }
+
+        Atom(Repr::Dynamic(Arc::from(string)))
+    }
+
+    /// Return the string of the atom.
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            Repr::Static(index) => STATIC_ATOMS[*index as usize],
+            // NOTE: the bytes were copied from a str, so they are valid utf-8 up to the length.
+            Repr::Inline { len, bytes } => std::str::from_utf8(&bytes[..*len as usize]).expect("inline atom is utf-8"),
+            Repr::Dynamic(string) => string,
+        }
+    }
+
+    /// Return the atom of the ascii lowercase version of this atom.
+    pub fn to_ascii_lowercase(&self) -> Atom {
+        let string = self.as_str();
+
+        if string.bytes().any(|byte| byte.is_ascii_uppercase()) {
+            Atom::new(&string.to_ascii_lowercase())
+        } else {
+            self.clone()
+        }
+    }
+}
+
+impl Default for Atom {
+    fn default() -> Atom { atoms::EMPTY }
+}
+
+impl From<&str> for Atom {
+    fn from(string: &str) -> Atom { Atom::new(string) }
+}
+
+impl From<&String> for Atom {
+    fn from(string: &String) -> Atom { Atom::new(string) }
+}
+
+impl PartialEq<str> for Atom {
+    fn eq(&self, other: &str) -> bool { self.as_str() == other }
+}
+
+impl PartialEq<&str> for Atom {
+    fn eq(&self, other: &&str) -> bool { self.as_str() == *other }
+}
+
+impl fmt::Debug for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_names_use_their_index() {
+        assert_eq!(Atom::new("div"), atoms::DIV);
+        assert!(matches!(Atom::new("div").0, Repr::Static(_)));
+        assert_eq!(atoms::FOREIGNOBJECT.as_str(), "foreignObject");
+    }
+
+    #[test]
+    fn short_names_are_inline() {
+        let atom = Atom::new("my-element");
+
+        assert!(matches!(atom.0, Repr::Inline { .. }));
+        assert_eq!(atom, Atom::new("my-element"));
+        assert_eq!(atom.as_str(), "my-element");
+    }
+
+    #[test]
+    fn long_names_are_reference_counted() {
+        let name = "a-custom-element-with-a-long-name";
+        let atom = Atom::new(name);
+
+        assert!(matches!(&atom.0, Repr::Dynamic(string) if Arc::strong_count(string) == 1));
+        assert_eq!(atom, Atom::new(name));
+        assert_eq!(atom.as_str(), name);
+    }
+
+    #[test]
+    fn lowercase_keeps_the_representation_consistent() {
+        assert_eq!(Atom::new("DIV").to_ascii_lowercase(), atoms::DIV);
+        assert_eq!(Atom::new("My-Element").to_ascii_lowercase(), Atom::new("my-element"));
+        assert_eq!(Atom::new("x".repeat(30).as_str()), Atom::new("X".repeat(30).as_str()).to_ascii_lowercase());
+    }
+}
@@ -0,0 +1,175 @@
+
+mod atom;
+mod namespace;
+
+pub use atom::{Atom, atoms};
+pub use namespace::Namespace;
+
+
+const SPECIAL_HTML: &[LocalName] = &[
+    atoms::ADDRESS, atoms::APPLET, atoms::AREA, atoms::ARTICLE, atoms::ASIDE, atoms::BASE, atoms::BASEFONT, atoms::BGSOUND,
+    atoms::BLOCKQUOTE, atoms::BODY, atoms::BR, atoms::BUTTON, atoms::CAPTION, atoms::CENTER, atoms::COL, atoms::COLGROUP,
+    atoms::DD, atoms::DETAILS, atoms::DIR, atoms::DIV, atoms::DL, atoms::DT, atoms::EMBED, atoms::FIELDSET,
+    atoms::FIGCAPTION, atoms::FIGURE, atoms::FOOTER, atoms::FORM, atoms::FRAME, atoms::FRAMESET, atoms::H1, atoms::H2,
+    atoms::H3, atoms::H4, atoms::H5, atoms::H6, atoms::HEAD, atoms::HEADER, atoms::HGROUP, atoms::HR, atoms::HTML,
+    atoms::IFRAME, atoms::IMG, atoms::INPUT, atoms::KEYGEN, atoms::LI, atoms::LINK, atoms::LISTING, atoms::MAIN,
+    atoms::MARQUEE, atoms::MENU, atoms::META, atoms::NAV, atoms::NOEMBED, atoms::NOFRAMES, atoms::NOSCRIPT,
+    atoms::OBJECT, atoms::OL, atoms::P, atoms::PARAM, atoms::PLAINTEXT, atoms::PRE, atoms::SCRIPT, atoms::SEARCH,
+    atoms::SECTION, atoms::SELECT, atoms::SOURCE, atoms::STYLE, atoms::SUMMARY, atoms::TABLE, atoms::TBODY, atoms::TD,
+    atoms::TEMPLATE, atoms::TEXTAREA, atoms::TFOOT, atoms::TH, atoms::THEAD, atoms::TITLE, atoms::TR, atoms::TRACK,
+    atoms::UL, atoms::WBR, atoms::XMP,
+];
+
+/// The local name of an element or attribute.
+pub type LocalName = Atom;
+
+/// The namespace prefix of an element or attribute.
+pub type Prefix = Atom;
+
+/// The local name, namespace and namespace prefix of an element or attribute.
+///
+/// Qualified names are cheap to clone and compare, see [`Atom`] for how the names are stored.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct QualifiedName {
+    pub namespace: Option<Namespace>,
+    pub prefix: Option<Prefix>,
+    pub local_name: LocalName,
+}
+
+impl QualifiedName {
+    /// Create a new qualified name with a local name and namespace.
+    pub fn new(namespace: Option<Namespace>, local_name: LocalName) -> QualifiedName {
+        QualifiedName {
+            namespace,
+            prefix: None,
+            local_name,
+        }
+    }
+
+    /// Create a new qualified name with a local name, namespace and namespace prefix.
+    pub fn new_with_prefix(prefix: Option<Prefix>, namespace: Option<Namespace>, local_name: LocalName) -> QualifiedName {
+        QualifiedName {
+            namespace,
+            prefix,
+            local_name,
+        }
+    }
+
+    /// Create a new qualified name in the html namespace.
+    pub fn html(local_name: LocalName) -> QualifiedName {
+        QualifiedName::new(Some(Namespace::Html), local_name)
+    }
+
+    /// Check if the name is in a namespace.
+    #[inline]
+    pub fn is_namespace(&self, namespace: Namespace) -> bool {
+        self.namespace == Some(namespace)
+    }
+
+    /// Check if the name is a html element with the local name.
+    #[inline]
+    pub fn is_html(&self, local_name: LocalName) -> bool {
+        self.is_namespace(Namespace::Html) && self.local_name == local_name
+    }
+
+    /// Return the qualified name as it appears in markup, prefixed with the namespace prefix if there is one.
+    pub fn qualified(&self) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}:{}", prefix, self.local_name),
+            None => self.local_name.to_string(),
+        }
+    }
+
+    /// Check if the element is a mathml text integration point.
+    pub fn is_mathml_text_integration_point(&self) -> bool {
+        self.is_namespace(Namespace::MathMl)
+            && [atoms::MI, atoms::MO, atoms::MN, atoms::MS, atoms::MTEXT].contains(&self.local_name)
+    }
+
+    /// Check if the element is a mathml annotation xml element
+    pub fn is_mathml_annotation_xml(&self) -> bool {
+        self.is_namespace(Namespace::MathMl) && self.local_name == atoms::ANNOTATION_XML
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#html-integration-point
+    // NOTE: the encoding attribute of annotation-xml is not checked as only the name is known here.
+    /// Check if the element is a html integration point.
+    pub fn is_html_integration_point(&self) -> bool {
+        self.is_mathml_annotation_xml()
+            || (self.is_namespace(Namespace::Svg) && [atoms::FOREIGNOBJECT, atoms::DESC, atoms::TITLE].contains(&self.local_name))
+    }
+
+    // TODO: form associated custom elements
+    /// Check if the element is a form associated element.
+    pub fn is_form_associated(&self) -> bool {
+        self.is_namespace(Namespace::Html)
+            && [atoms::BUTTON, atoms::FIELDSET, atoms::INPUT, atoms::OBJECT, atoms::OUTPUT, atoms::SELECT, atoms::TEXTAREA, atoms::IMG].contains(&self.local_name)
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#special
+    /// Check if the element is in the special parsing category.
+    pub fn is_special(&self) -> bool {
+        match self.namespace {
+            Some(Namespace::Html) => SPECIAL_HTML.contains(&self.local_name),
+            Some(Namespace::MathMl) => [atoms::MI, atoms::MO, atoms::MN, atoms::MS, atoms::MTEXT, atoms::ANNOTATION_XML].contains(&self.local_name),
+            Some(Namespace::Svg) => [atoms::FOREIGNOBJECT, atoms::DESC, atoms::TITLE].contains(&self.local_name),
+            _ => false,
+        }
+    }
+
+    /// Check if the element is a listed element.
+    pub fn is_listed(&self) -> bool {
+        self.is_namespace(Namespace::Html)
+            && [atoms::BUTTON, atoms::FIELDSET, atoms::INPUT, atoms::OBJECT, atoms::OUTPUT, atoms::SELECT, atoms::TEXTAREA].contains(&self.local_name)
+    }
+}
+
+// https://dom.spec.whatwg.org/#valid-element-local-name
+/// Check if the name is a valid local name for an element.
+pub fn is_valid_element_local_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() => {
+            !chars.any(|character| character.is_ascii_whitespace() || ['\0', '/', '>'].contains(&character))
+        },
+        Some(first) if [':', '_'].contains(&first) || first >= '\u{80}' => {
+            chars.all(|character| character.is_ascii_alphanumeric() || ['-', '.', ':', '_'].contains(&character) || character >= '\u{80}')
+        },
+        _ => false,
+    }
+}
+
+// https://www.w3.org/TR/xml/#NT-NameStartChar
+fn is_xml_name_start_char(character: char) -> bool {
+    matches!(character,
+        ':' | 'A'..='Z' | '_' | 'a'..='z' | '\u{c0}'..='\u{d6}' | '\u{d8}'..='\u{f6}' | '\u{f8}'..='\u{2ff}'
+            | '\u{370}'..='\u{37d}' | '\u{37f}'..='\u{1fff}' | '\u{200c}'..='\u{200d}' | '\u{2070}'..='\u{218f}'
+            | '\u{2c00}'..='\u{2fef}' | '\u{3001}'..='\u{d7ff}' | '\u{f900}'..='\u{fdcf}' | '\u{fdf0}'..='\u{fffd}'
+            | '\u{10000}'..='\u{effff}'
+    )
+}
+
+// https://www.w3.org/TR/xml/#NT-Name
+/// Check if the name matches the xml Name production.
+pub fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars.next().is_some_and(is_xml_name_start_char)
+        && chars.all(|character| {
+            is_xml_name_start_char(character)
+                || matches!(character, '-' | '.' | '0'..='9' | '\u{b7}' | '\u{300}'..='\u{36f}' | '\u{203f}'..='\u{2040}')
+        })
+}
+
+// https://dom.spec.whatwg.org/#valid-attribute-local-name
+/// Check if the name is a valid local name for an attribute.
+pub fn is_valid_attribute_local_name(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|character| character.is_ascii_whitespace() || ['\0', '/', '=', '>'].contains(&character))
+}
+
+// https://dom.spec.whatwg.org/#valid-namespace-prefix
+/// Check if the prefix is a valid namespace prefix.
+pub fn is_valid_namespace_prefix(prefix: &str) -> bool {
+    !prefix.is_empty() && !prefix.chars().any(|character| character.is_ascii_whitespace() || ['\0', '/', '>'].contains(&character))
+}
@@ -0,0 +1,52 @@
+
+use super::atom::Atom;
+
+use std::fmt;
+
+
+/// A namespace an element or attribute can be in.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Namespace {
+    Html,
+    Svg,
+    MathMl,
+    XLink,
+    Xml,
+    Xmlns,
+    Other(Atom),
+}
+
+impl Namespace {
+    /// Return the namespace url.
+    pub fn url(&self) -> &str {
+        match self {
+            Namespace::Html => "http://www.w3.org/1999/xhtml",
+            Namespace::Svg => "http://www.w3.org/2000/svg",
+            Namespace::MathMl => "http://www.w3.org/1998/Math/MathML",
+            Namespace::XLink => "http://www.w3.org/1999/xlink",
+            Namespace::Xml => "http://www.w3.org/XML/1998/namespace",
+            Namespace::Xmlns => "http://www.w3.org/2000/xmlns/",
+            Namespace::Other(url) => url.as_str(),
+        }
+    }
+}
+
+impl From<&str> for Namespace {
+    fn from(url: &str) -> Namespace {
+        match url {
+            "http://www.w3.org/1999/xhtml" => Namespace::Html,
+            "http://www.w3.org/2000/svg" => Namespace::Svg,
+            "http://www.w3.org/1998/Math/MathML" => Namespace::MathMl,
+            "http://www.w3.org/1999/xlink" => Namespace::XLink,
+            "http://www.w3.org/XML/1998/namespace" => Namespace::Xml,
+            "http://www.w3.org/2000/xmlns/" => Namespace::Xmlns,
+            _ => Namespace::Other(Atom::new(url)),
+        }
+    }
+}
+
+impl fmt::Debug for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.url())
+    }
+}
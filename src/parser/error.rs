@@ -0,0 +1,130 @@
+use crate::tokenizer::{Token, TagKind};
+
+use super::state::InsertionMode;
+
+use std::fmt;
+
+
+/// An owned description of the token that caused a parse error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorToken {
+    StartTag(String),
+    EndTag(String),
+    Doctype(Option<String>),
+    Character(char),
+    Comment,
+    Eof,
+}
+
+impl<'a> From<&Token<'a>> for ErrorToken {
+    fn from(token: &Token<'a>) -> ErrorToken {
+        match token {
+            Token::Tag(tag) if tag.kind == TagKind::Start => ErrorToken::StartTag(tag.name.clone()),
+            Token::Tag(tag) => ErrorToken::EndTag(tag.name.clone()),
+            Token::Doctype(doctype) => ErrorToken::Doctype(doctype.name.map(|name| name.to_string())),
+            Token::Character(character) => ErrorToken::Character(*character),
+            Token::Comment(_) => ErrorToken::Comment,
+        }
+    }
+}
+
+impl fmt::Display for ErrorToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorToken::StartTag(name) => write!(f, "<{}>", name),
+            ErrorToken::EndTag(name) => write!(f, "</{}>", name),
+            ErrorToken::Doctype(Some(name)) => write!(f, "<!DOCTYPE {}>", name),
+            ErrorToken::Doctype(None) => write!(f, "<!DOCTYPE>"),
+            ErrorToken::Character(character) => write!(f, "{:?}", character),
+            ErrorToken::Comment => write!(f, "comment"),
+            ErrorToken::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+/// A parse error encountered during tree construction.
+///
+/// Every variant carries the offending token and the insertion mode the tree builder was in, the
+/// variant itself identifies the error condition and is stable across releases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeBuilderError {
+    /// The doctype is not `<!DOCTYPE html>` or the legacy compat doctype.
+    BadDoctype { token: ErrorToken, mode: InsertionMode },
+
+    /// The document does not start with a doctype and is not an iframe srcdoc document.
+    MissingDoctype { token: ErrorToken, mode: InsertionMode },
+
+    /// A doctype appeared after the initial insertion mode.
+    UnexpectedDoctype { token: ErrorToken, mode: InsertionMode },
+
+    /// A start tag that is not allowed in the current insertion mode.
+    UnexpectedStartTag { token: ErrorToken, mode: InsertionMode },
+
+    /// An end tag that is not allowed in the current insertion mode.
+    UnexpectedEndTag { token: ErrorToken, mode: InsertionMode },
+
+    /// Any other token that is not allowed in the current insertion mode.
+    UnexpectedToken { token: ErrorToken, mode: InsertionMode },
+
+    /// A U+0000 NULL character in the body.
+    UnexpectedNullCharacter { token: ErrorToken, mode: InsertionMode },
+
+    /// The token implicitly closed elements that were still open.
+    UnclosedElements { token: ErrorToken, mode: InsertionMode },
+
+    /// The end of the file was reached while elements were still open.
+    UnexpectedEof { token: ErrorToken, mode: InsertionMode },
+}
+
+impl TreeBuilderError {
+    /// The token that caused the error.
+    pub fn token(&self) -> &ErrorToken {
+        match self {
+            TreeBuilderError::BadDoctype { token, .. }
+                | TreeBuilderError::MissingDoctype { token, .. }
+                | TreeBuilderError::UnexpectedDoctype { token, .. }
+                | TreeBuilderError::UnexpectedStartTag { token, .. }
+                | TreeBuilderError::UnexpectedEndTag { token, .. }
+                | TreeBuilderError::UnexpectedToken { token, .. }
+                | TreeBuilderError::UnexpectedNullCharacter { token, .. }
+                | TreeBuilderError::UnclosedElements { token, .. }
+                | TreeBuilderError::UnexpectedEof { token, .. } => token,
+        }
+    }
+
+    /// The insertion mode the tree builder was in when the error occurred.
+    pub fn mode(&self) -> InsertionMode {
+        match self {
+            TreeBuilderError::BadDoctype { mode, .. }
+                | TreeBuilderError::MissingDoctype { mode, .. }
+                | TreeBuilderError::UnexpectedDoctype { mode, .. }
+                | TreeBuilderError::UnexpectedStartTag { mode, .. }
+                | TreeBuilderError::UnexpectedEndTag { mode, .. }
+                | TreeBuilderError::UnexpectedToken { mode, .. }
+                | TreeBuilderError::UnexpectedNullCharacter { mode, .. }
+                | TreeBuilderError::UnclosedElements { mode, .. }
+                | TreeBuilderError::UnexpectedEof { mode, .. } => *mode,
+        }
+    }
+
+    /// A stable identifier for the error condition, suitable for filtering.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TreeBuilderError::BadDoctype { .. } => "bad-doctype",
+            TreeBuilderError::MissingDoctype { .. } => "missing-doctype",
+            TreeBuilderError::UnexpectedDoctype { .. } => "unexpected-doctype",
+            TreeBuilderError::UnexpectedStartTag { .. } => "unexpected-start-tag",
+            TreeBuilderError::UnexpectedEndTag { .. } => "unexpected-end-tag",
+            TreeBuilderError::UnexpectedToken { .. } => "unexpected-token",
+            TreeBuilderError::UnexpectedNullCharacter { .. } => "unexpected-null-character",
+            TreeBuilderError::UnclosedElements { .. } => "unclosed-elements",
+            TreeBuilderError::UnexpectedEof { .. } => "unexpected-eof",
+        }
+    }
+}
+
+impl fmt::Display for TreeBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} in {:?}", self.code(), self.token(), self.mode())
+    }
+}
@@ -0,0 +1,127 @@
+use crate::tokenizer::Tag;
+use crate::names::{QualifiedName, LocalName};
+
+use super::interface::Node;
+
+
+/// An entry in the list of active formatting elements.
+pub enum FormattingEntry<Handle> {
+    Marker,
+    Element {
+        handle: Handle,
+        name: QualifiedName,
+        tag: Tag,
+    },
+}
+
+impl<Handle> FormattingEntry<Handle> {
+    /// The handle of the element, markers have no handle.
+    pub fn handle(&self) -> Option<&Handle> {
+        match self {
+            FormattingEntry::Marker => None,
+            FormattingEntry::Element { handle, .. } => Some(handle),
+        }
+    }
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#list-of-active-formatting-elements
+/// The list of active formatting elements, every element is stored together with the token it
+/// was created for so that it can be recreated.
+pub struct ActiveFormattingElements<Handle> {
+    entries: Vec<FormattingEntry<Handle>>,
+}
+
+impl<Handle: Node> ActiveFormattingElements<Handle> {
+    pub fn new() -> ActiveFormattingElements<Handle> {
+        ActiveFormattingElements {
+            entries: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&FormattingEntry<Handle>> {
+        self.entries.get(index)
+    }
+
+    #[inline]
+    pub fn push_marker(&mut self) {
+        self.entries.push(FormattingEntry::Marker);
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#push-onto-the-list-of-active-formatting-elements
+    /// Push an element, removing the earliest of three identical elements after the last marker.
+    pub fn push(&mut self, handle: Handle, name: QualifiedName, tag: Tag) {
+        let identical = self.entries.iter()
+            .enumerate()
+            .rev()
+            .take_while(|(_, entry)| !matches!(entry, FormattingEntry::Marker))
+            .filter(|(_, entry)| match entry {
+                FormattingEntry::Element { name: other_name, tag: other, .. } => *other_name == name && same_attributes(other, &tag),
+                FormattingEntry::Marker => false,
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<usize>>();
+
+        if identical.len() >= 3 && let Some(earliest) = identical.last() {
+            self.entries.remove(*earliest);
+        }
+
+        self.entries.push(FormattingEntry::Element { handle, name, tag });
+    }
+
+    /// Insert an element at the index.
+    pub fn insert(&mut self, index: usize, handle: Handle, name: QualifiedName, tag: Tag) {
+        self.entries.insert(index, FormattingEntry::Element { handle, name, tag });
+    }
+
+    /// Replace the element at the index with another element created for the same token.
+    pub fn replace(&mut self, index: usize, new_handle: Handle) {
+        if let Some(FormattingEntry::Element { handle, .. }) = self.entries.get_mut(index) {
+            *handle = new_handle;
+        }
+    }
+
+    pub fn position(&self, handle: &Handle) -> Option<usize> {
+        self.entries.iter().rposition(|entry| entry.handle() == Some(handle))
+    }
+
+    pub fn contains(&self, handle: &Handle) -> bool {
+        self.position(handle).is_some()
+    }
+
+    pub fn remove(&mut self, index: usize) -> FormattingEntry<Handle> {
+        self.entries.remove(index)
+    }
+
+    /// Return the index of the last html element with the local name after the last marker.
+    pub fn last_after_marker(&self, local_name: &LocalName) -> Option<usize> {
+        self.entries.iter()
+            .enumerate()
+            .rev()
+            .take_while(|(_, entry)| !matches!(entry, FormattingEntry::Marker))
+            .find(|(_, entry)| match entry {
+                FormattingEntry::Element { name, .. } => name.is_html(local_name.clone()),
+                FormattingEntry::Marker => false,
+            })
+            .map(|(index, _)| index)
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#clear-the-list-of-active-formatting-elements-up-to-the-last-marker
+    pub fn clear_to_last_marker(&mut self) {
+        while let Some(entry) = self.entries.pop() {
+            if let FormattingEntry::Marker = entry {
+                break;
+            }
+        }
+    }
+}
+
+fn same_attributes(a: &Tag, b: &Tag) -> bool {
+    a.attributes.len() == b.attributes.len()
+        && a.attributes.iter().all(|attribute| b.attributes.contains(attribute))
+}
@@ -1,83 +1,35 @@
-use crate::tokenizer::Doctype;
+use crate::tokenizer::{Doctype, Position};
+use crate::names::QualifiedName;
 
 use super::quirks::QuirksMode;
+use super::error::TreeBuilderError;
 
 
-/// The local name of an element and its namespace.
-#[derive(Clone, Copy)]
-pub struct QualifiedName<'a> {
-    pub namespace: Option<&'a str>,
-    pub namespace_prefix: Option<&'a str>,
-    pub local_name: &'a str,
-}
-
-impl<'a> QualifiedName<'a> {
-    /// Create a new element name with a local_name and namespace.
-    pub fn new_with_ns(local_name: &'a str, namespace: &'a str) -> QualifiedName<'a> {
-        QualifiedName {
-            namespace: Some(namespace),
-            namespace_prefix: None,
-            local_name,
-        }
-    }
-
-    /// Check if the element name is in a namespace.
-    pub fn is_namespace(&self, is: &str) -> bool {
-        self.namespace.as_ref().map(|namespace| *namespace == is)
-            .unwrap_or_default()
-    }
-
-    /// Check if the element is a mathml text integration point.
-    pub fn is_mathml_text_integration_point(&self) -> bool {
-        self.is_namespace("http://www.w3.org/1998/Math/MathML")
-            && ["mi", "mo", "mn", "ms", "mtext"].contains(&self.local_name)
-    }
-
-    /// Check if the element is a mathml annotation xml element
-    pub fn is_mathml_annotation_xml(&self) -> bool {
-        self.is_namespace("http://www.w3.org/1998/Math/MathML")
-            && self.local_name == "annotation-xml"
-    }
-
-    // TODO: implement html integration point, im not sure if we really need this
-    /// Check if the element is a html integration point.
-    pub fn is_html_integration_point(&self) -> bool {
-        self.is_mathml_annotation_xml()
-    }
-
-    // TODO: form associated custom elements
-    /// Check if the element is a form associated element.
-    pub fn is_form_associated(&self) -> bool {
-        self.is_namespace("http://www.w3.org/1999/xhtml")
-            && ["button", "fieldset", "input", "object", "output", "select", "textarea", "img"].contains(&self.local_name)
-    }
-
-    /// Check if the element is a listed element.
-    pub fn is_listed(&self) -> bool {
-        self.is_namespace("http://www.w3.org/1999/xhtml")
-            && ["button", "fieldset", "input", "object", "output", "select", "textarea"].contains(&self.local_name)
-    }
-}
-
 /// A reference to a node in the dom.
 pub trait Node: Clone + PartialEq {
     /// A custom element registry.
     type CustomElementRegistry;
 
     /// Given a node, return the associated node document handle.
-    fn node_document(&self) -> &Self;
+    fn node_document(&self) -> Self;
 
     /// Given a node, return its root node.
-    fn root(&self) -> &Self;
+    fn root(&self) -> Self;
 
     /// Given an element node, return the element name.
-    fn element_name<'a>(&self) -> QualifiedName<'a>;
+    fn element_name(&self) -> QualifiedName;
 
     /// Given an element, shadow root or document node, return its custom element registry.
     fn custom_element_registry(&self) -> Option<Self::CustomElementRegistry>;
 
     /// Given a handle to a node, return the parent of said node if it exists.
-    fn parent(&self) -> Option<&Self>;
+    fn parent(&self) -> Option<Self>;
+
+    /// Given a handle to a node, return the last child of said node if it exists.
+    fn last_child(&self) -> Option<Self>;
+
+    /// Given a handle to a node, return the previous sibling of said node if it exists.
+    fn previous_sibling(&self) -> Option<Self>;
 
     /// Given a parent and child node, append said child node into the dom as the last child of said parent node.
     fn append(&mut self, child: &Self);
@@ -85,6 +37,15 @@ pub trait Node: Clone + PartialEq {
     /// Append a child node before another node.
     fn append_before(&mut self, before: &Self, child: &Self);
 
+    /// Given a node, remove it from its parent if it has one.
+    fn remove_from_parent(&mut self);
+
+    /// Given a node, move all of its children to the end of the new parent.
+    fn reparent_children(&mut self, new_parent: &Self);
+
+    /// Given a text node, append the text to its data.
+    fn append_text(&mut self, text: &str);
+
     /// Given a node, qualified name, name and a value, append an attribute with those values.
     fn append_attribute(&mut self, name: QualifiedName, value: &str);
 
@@ -123,8 +84,8 @@ pub trait TreeSink {
         is: Option<&str>
     ) -> Option<Self::CustomElementDefinition>;
 
-    /// Called when a parse error is encountered.
-    fn parse_error<Message: AsRef<str>>(&mut self, message: Message);
+    /// Called when a parse error is encountered, with the source position of the offending token if known.
+    fn parse_error(&mut self, error: TreeBuilderError, position: Option<Position>);
 
     /// Given a name and attributes, create an element.
     fn create_element(
@@ -136,6 +97,9 @@ pub trait TreeSink {
         registry: &Option<Self::CustomElementRegistry>
     ) -> Self::Handle;
 
+    /// Given some data, create a text node.
+    fn create_text(&mut self, data: &str) -> Self::Handle;
+
     /// Given some content, create a comment.
     fn create_comment(&mut self, content: &str) -> Self::Handle;
 
@@ -144,6 +108,9 @@ pub trait TreeSink {
 
     /// Set the quirks mode.
     fn set_quirks_mode(&mut self, mode: QuirksMode);
+
+    /// Set whether scripting is enabled.
+    fn set_scripting(&mut self, scripting: bool);
 }
 
 
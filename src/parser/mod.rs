@@ -1,12 +1,24 @@
 pub mod interface;
 pub mod quirks;
+pub mod error;
+pub mod options;
+pub mod preload;
+mod open_elements;
+mod formatting;
+mod foreign;
 mod state;
 
-use crate::tokenizer::{TokenSink, Token, Tag, TagKind};
+use crate::tokenizer::{Tokenizer, TokenSink, TokenizerState, Token, Tag, TagKind, Position};
+use crate::names::{QualifiedName, Namespace, Atom, LocalName, atoms};
 
-use state::InsertionMode;
-use interface::{TreeSink, Node, QualifiedName};
+pub use state::InsertionMode;
+use interface::{TreeSink, Node};
 use quirks::QuirksMode;
+use error::{TreeBuilderError, ErrorToken};
+use open_elements::OpenElements;
+use formatting::{ActiveFormattingElements, FormattingEntry};
+
+pub use options::{ParserOptions, ErrorPolicy};
 
 
 enum InsertionPoint<Handle> {
@@ -15,7 +27,7 @@ enum InsertionPoint<Handle> {
 }
 
 impl<Handle> InsertionPoint<Handle> {
-    pub fn parent<'a>(&'a self) -> &'a Handle {
+    pub fn parent(&self) -> &Handle {
         match self {
             InsertionPoint::LastChild(handle) => handle,
             InsertionPoint::BeforeChild(_, handle) => handle,
@@ -37,77 +49,148 @@ impl<Handle> Default for ElementPointers<Handle> {
     }
 }
 
+fn is_whitespace(character: char) -> bool {
+    matches!(character, '\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ')
+}
+
+/// Check if the tag has a type attribute that is an ascii case-insensitive match for `hidden`.
+fn is_hidden_input(tag: &Tag) -> bool {
+    tag.attributes.iter().any(|attribute| attribute.name.as_str() == "type" && attribute.value.eq_ignore_ascii_case("hidden"))
+}
+
+/// Parse a whole document from the input into the sink, and return the sink.
+pub fn parse_document<Sink: TreeSink>(input: &str, options: ParserOptions, sink: Sink) -> Sink {
+    let mut tokenizer = Tokenizer::new(TreeBuilder::new(sink, options), input.chars(), options);
+
+    tokenizer.run();
+
+    tokenizer.finish().finish()
+}
+
 pub struct TreeBuilder<Sink: TreeSink> {
     sink: Sink,
+    options: ParserOptions,
     mode: InsertionMode,
+    original_mode: InsertionMode,
+    quirks_mode: QuirksMode,
     document: Sink::Handle,
     element_pointers: ElementPointers<Sink::Handle>,
-    open_elements: Vec<Sink::Handle>,
+    open_elements: OpenElements<Sink::Handle>,
+    active_formatting: ActiveFormattingElements<Sink::Handle>,
+    template_modes: Vec<InsertionMode>,
+    pending_table_characters: String,
     foster_parenting: bool,
+    frameset_ok: bool,
+    ignore_lf: bool,
+    position: Option<Position>,
+    next_state: Option<TokenizerState>,
+    errored: bool,
 }
 
 impl<Sink: TreeSink> TreeBuilder<Sink> {
-    pub fn new(sink: Sink) -> TreeBuilder<Sink> {
+    pub fn new(mut sink: Sink, options: ParserOptions) -> TreeBuilder<Sink> {
         let document = sink.document();
 
+        sink.set_scripting(options.scripting);
+
         TreeBuilder {
             sink,
+            options,
             mode: InsertionMode::Initial,
+            original_mode: InsertionMode::Initial,
+            quirks_mode: QuirksMode::NoQuirks,
             document,
             element_pointers: ElementPointers::default(),
-            open_elements: Vec::new(),
+            open_elements: OpenElements::new(),
+            active_formatting: ActiveFormattingElements::new(),
+            template_modes: Vec::new(),
+            pending_table_characters: String::new(),
             foster_parenting: false,
+            frameset_ok: true,
+            ignore_lf: false,
+            position: None,
+            next_state: None,
+            errored: false,
         }
     }
 
-    fn current_node(&self) -> &Sink::Handle {
-        self.open_elements.last().expect("no current node")
+    #[inline]
+    /// Moves the tree builder and returns the sink.
+    pub fn finish(self) -> Sink { self.sink }
+
+    fn parse_error(&mut self, error: TreeBuilderError) {
+        self.sink.parse_error(error, self.position);
+
+        self.errored = true;
     }
 
-    // NOTE: currently this assumes its not a html fragment parser.
-    fn adjusted_current_node(&self) -> &Sink::Handle {
-        self.current_node()
+    /// Returns true if the tree builder stopped at the first parse error.
+    fn aborted(&self) -> bool {
+        self.errored && self.options.error_policy == ErrorPolicy::Abort
+    }
+
+    fn resolve_quirks_mode(&self, mode: QuirksMode) -> QuirksMode {
+        self.options.quirks_mode.unwrap_or(mode)
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.quirks_mode = mode;
+
+        self.sink.set_quirks_mode(mode);
     }
 
-    fn last_open_element(&self, local_name: &str) -> (usize, Option<&Sink::Handle>) {
-        let index = self.open_elements.iter()
-            .enumerate()
-            .rev()
-            .find_map(|(index, handle)| (handle.element_name().local_name == local_name).then(|| index));
+    fn current_node(&self) -> &Sink::Handle {
+        self.open_elements.current().expect("no current node")
+    }
 
-        let element = self.open_elements.iter()
-            .filter(|handle| handle.element_name().local_name == local_name)
-            .last();
+    fn current_name(&self) -> &QualifiedName {
+        self.open_elements.current_name().expect("no current node")
+    }
 
-        (index.unwrap_or_default(), element)
+    // NOTE: currently this assumes its not a html fragment parser.
+    fn adjusted_current_name(&self) -> &QualifiedName {
+        self.current_name()
     }
 
     fn not_foreign(&self, token: &Token) -> bool {
-        let element_name = self.adjusted_current_node().element_name();
+        if self.open_elements.is_empty() {
+            return true;
+        }
+
+        let element_name = self.adjusted_current_name();
 
-        self.open_elements.is_empty()
-            || element_name.is_namespace("http://www.w3.org/1999/xhtml")
-            || (element_name.is_mathml_text_integration_point() && !(token.is_start_tag("mglyph") || token.is_start_tag("malignmark")))
+        element_name.is_namespace(Namespace::Html)
+            || (element_name.is_mathml_text_integration_point()
+                && matches!(token, Token::Tag(Tag { kind: TagKind::Start, .. }))
+                && !(token.is_start_tag("mglyph") || token.is_start_tag("malignmark")))
             || (element_name.is_mathml_text_integration_point() && matches!(token, Token::Character(_)))
             || (element_name.is_mathml_annotation_xml() && token.is_start_tag("svg"))
             || (element_name.is_html_integration_point() && matches!(token, Token::Tag(Tag { kind: TagKind::Start, .. }) | Token::Character(_)))
     }
 
     fn adjusted_insertion_location(&self, target: &Sink::Handle) -> InsertionPoint<Sink::Handle> {
-        let name = target.element_name();
-
-        if self.foster_parenting && ["table", "tbody", "tfoot", "thead", "tr"].contains(&name.local_name) {
-            let (template_index, template) = self.last_open_element("template");
-            let (table_index, table) = self.last_open_element("table");
-
-            if let Some(handle) = template && table_index < template_index {
-                InsertionPoint::LastChild(handle.clone())
-            } else if table.is_none() {
-                InsertionPoint::LastChild(self.open_elements[0].clone())
-            } else if let Some(handle) = table && let Some(parent) = handle.parent() {
-                InsertionPoint::BeforeChild(handle.clone(), parent.clone())
-            } else {
-                InsertionPoint::LastChild(self.open_elements[table_index - 1].clone())
+        let is_table_target = self.open_elements.position(target)
+            .and_then(|index| self.open_elements.name(index))
+            .map(|name| [atoms::TABLE, atoms::TBODY, atoms::TFOOT, atoms::THEAD, atoms::TR].iter().any(|local_name| name.is_html(local_name.clone())))
+            .unwrap_or_default();
+
+        if self.foster_parenting && is_table_target {
+            let template = self.open_elements.last_index_of(atoms::TEMPLATE);
+            let table = self.open_elements.last_index_of(atoms::TABLE);
+
+            match (template, table) {
+                (Some(template), table) if table.map(|table| table < template).unwrap_or(true) => {
+                    InsertionPoint::LastChild(self.open_elements.get(template).expect("template").clone())
+                },
+                (_, None) => InsertionPoint::LastChild(self.open_elements.get(0).expect("html element").clone()),
+                (_, Some(table)) => {
+                    let handle = self.open_elements.get(table).expect("table");
+
+                    match handle.parent() {
+                        Some(parent) => InsertionPoint::BeforeChild(handle.clone(), parent),
+                        None => InsertionPoint::LastChild(self.open_elements.get(table - 1).expect("table parent").clone()),
+                    }
+                },
             }
         } else {
             InsertionPoint::LastChild(target.clone())
@@ -117,12 +200,17 @@ impl<Sink: TreeSink> TreeBuilder<Sink> {
     fn appropriate_insertion_point(&self, override_: Option<&Sink::Handle>) -> InsertionPoint<Sink::Handle> {
         let target = override_.unwrap_or_else(|| self.current_node());
 
-        self.adjusted_insertion_location(&target)
+        self.adjusted_insertion_location(target)
     }
 
     // TODO: implement will_execute_script for javascript stuff
-    fn create_element_for(&mut self, tag: &Tag, namespace: &str, intended_parent: &Sink::Handle) -> Sink::Handle {
-        let name = QualifiedName::new_with_ns(&tag.name, namespace);
+    fn create_element_for(&mut self, tag: &Tag, namespace: Namespace, intended_parent: &Sink::Handle) -> Sink::Handle {
+        let local_name = match namespace {
+            Namespace::Svg => foreign::adjust_svg_tag_name(&tag.name),
+            _ => Atom::from(&tag.name),
+        };
+
+        let name = QualifiedName::new(Some(namespace.clone()), local_name);
 
         let is = tag.attributes.iter()
             .find(|attribute| attribute.name.as_str() == "is")
@@ -130,26 +218,25 @@ impl<Sink: TreeSink> TreeBuilder<Sink> {
 
         let registry = intended_parent.custom_element_registry();
 
-        let will_execute_script = self.sink.custom_element_definition(&registry, name, is).is_some();
+        let will_execute_script = self.sink.custom_element_definition(&registry, name.clone(), is).is_some();
 
-        let mut element = self.sink.create_element(intended_parent.node_document(), name, is, will_execute_script, &registry);
+        let mut element = self.sink.create_element(&intended_parent.node_document(), name, is, will_execute_script, &registry);
 
         for attribute in tag.attributes.iter() {
-            let name = QualifiedName::new_with_ns(attribute.name.as_str(), "");
+            let name = foreign::adjust_attribute_name(namespace.clone(), &attribute.name);
 
             element.append_attribute(name, attribute.value.as_str());
         }
 
-        if let Some(form) = &self.element_pointers.form {
-            if element.element_name().is_form_associated()
-                && !self.open_elements.iter().any(|handle| handle.element_name().local_name == "template")
-                && (!element.element_name().is_listed() || element.has_attribute(QualifiedName::new_with_ns("form", "")))
-                && intended_parent.root() == form.root()
-            {
-                element.set_associated_form(form.clone());
+        if let Some(form) = &self.element_pointers.form
+            && element.element_name().is_form_associated()
+            && !self.open_elements.contains_html(atoms::TEMPLATE)
+            && (!element.element_name().is_listed() || element.has_attribute(QualifiedName::new(None, atoms::FORM)))
+            && intended_parent.root() == form.root()
+        {
+            element.set_associated_form(form.clone());
 
-                element.set_parser_inserted();
-            }
+            element.set_parser_inserted();
         }
 
         element
@@ -158,11 +245,11 @@ impl<Sink: TreeSink> TreeBuilder<Sink> {
     fn insert_at(&mut self, element: &Sink::Handle, adjusted_insertion_location: InsertionPoint<Sink::Handle>) {
         match adjusted_insertion_location {
             InsertionPoint::LastChild(mut handle) => handle.append(element),
-            InsertionPoint::BeforeChild(mut handle, before) => handle.append_before(&before, element),
+            InsertionPoint::BeforeChild(before, mut parent) => parent.append_before(&before, element),
         }
     }
 
-    fn insert_foreign_element(&mut self, tag: &Tag, namespace: &str, only_add_to_element_stack: bool) -> Sink::Handle {
+    fn insert_foreign_element(&mut self, tag: &Tag, namespace: Namespace, only_add_to_element_stack: bool) -> Sink::Handle {
         let adjusted_insertion_location = self.appropriate_insertion_point(None);
 
         let element = self.create_element_for(tag, namespace, adjusted_insertion_location.parent());
@@ -182,119 +269,2117 @@ impl<Sink: TreeSink> TreeBuilder<Sink> {
         self.document.append(&comment);
     }
 
+    fn insert_comment(&mut self, content: &str, position: Option<&Sink::Handle>) {
+        let adjusted_insertion_location = self.appropriate_insertion_point(position);
+
+        let comment = self.sink.create_comment(content);
+
+        self.insert_at(&comment, adjusted_insertion_location);
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#insert-a-character
     fn append_character(&mut self, character: char) {
         let adjusted_insertion_location = self.appropriate_insertion_point(None);
 
-        if !adjusted_insertion_location.parent().is_node("document") {
+        if adjusted_insertion_location.parent().is_node("document") {
+            return;
+        }
+
+        let previous = match &adjusted_insertion_location {
+            InsertionPoint::LastChild(parent) => parent.last_child(),
+            InsertionPoint::BeforeChild(before, _) => before.previous_sibling(),
+        };
+
+        let mut buffer = [0; 4];
+
+        match previous {
+            Some(mut text) if text.is_node("text") => text.append_text(character.encode_utf8(&mut buffer)),
+            _ => {
+                let text = self.sink.create_text(character.encode_utf8(&mut buffer));
+
+                self.insert_at(&text, adjusted_insertion_location);
+            },
         }
     }
 
-    #[inline]
-    fn reprocess(&mut self, token: Token, mode: InsertionMode) {
-        self.mode = mode;
+    fn insert_html_element(&mut self, tag: &Tag) -> Sink::Handle {
+        self.insert_foreign_element(tag, Namespace::Html, false)
+    }
 
-        self.step(token);
+    fn pop(&mut self) -> Sink::Handle {
+        self.open_elements.pop().expect("no current node")
     }
 
-    fn step(&mut self, token: Token) {
-        match self.mode {
-            InsertionMode::Initial => match token {
-                Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') => {},
-                Token::Comment(content) => self.append_comment(content),
-                Token::Doctype(doctype) => {
-                    if doctype.is_parse_error() {
-                        self.sink.parse_error("bad doctype");
+    fn insert_implied_element(&mut self, name: &str) -> Sink::Handle {
+        let tag = Tag::new(TagKind::Start, String::from(name), false, Vec::new());
+
+        self.insert_html_element(&tag)
+    }
+
+    fn unexpected_tag(&mut self, token: Token) {
+        let error = match token {
+            Token::Tag(Tag { kind: TagKind::Start, .. }) => TreeBuilderError::UnexpectedStartTag { token: ErrorToken::from(&token), mode: self.mode },
+            Token::Tag(Tag { kind: TagKind::End, .. }) => TreeBuilderError::UnexpectedEndTag { token: ErrorToken::from(&token), mode: self.mode },
+            _ => TreeBuilderError::UnexpectedToken { token: ErrorToken::from(&token), mode: self.mode },
+        };
+
+        self.parse_error(error);
+    }
+
+    /// Add the attributes of the tag that are missing on the element.
+    fn merge_attributes(&mut self, tag: &Tag, element: &Sink::Handle) {
+        let mut element = element.clone();
+
+        for attribute in tag.attributes.iter() {
+            let name = QualifiedName::new(None, Atom::from(&attribute.name));
+
+            if !element.has_attribute(name.clone()) {
+                element.append_attribute(name, attribute.value.as_str());
+            }
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#close-a-p-element
+    fn close_p_element(&mut self, token: Token) {
+        self.open_elements.generate_implied_end_tags(Some(atoms::P));
+
+        if !self.current_name().is_html(atoms::P) {
+            self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+        }
+
+        self.open_elements.pop_until(atoms::P);
+    }
+
+    fn close_p_element_in_button_scope(&mut self, token: Token) {
+        if self.open_elements.has_in_button_scope(atoms::P) {
+            self.close_p_element(token);
+        }
+    }
+
+    /// Generate implied end tags and pop until an element with the local name has been popped.
+    fn close_element(&mut self, token: Token, local_name: Atom) {
+        self.open_elements.generate_implied_end_tags(Some(local_name.clone()));
+
+        if !self.current_name().is_html(local_name.clone()) {
+            self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+        }
+
+        self.open_elements.pop_until(local_name);
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody:current-node-23
+    fn close_list_item(&mut self, token: Token, local_names: &[Atom]) {
+        self.frameset_ok = false;
+
+        for index in (0..self.open_elements.len()).rev() {
+            let name = self.open_elements.name(index).expect("open element").clone();
+
+            if let Some(local_name) = local_names.iter().find(|local_name| name.is_html((*local_name).clone())) {
+                self.close_element(token, local_name.clone());
+
+                break;
+            } else if name.is_special() && ![atoms::ADDRESS, atoms::DIV, atoms::P].iter().any(|local_name| name.is_html(local_name.clone())) {
+                break;
+            }
+        }
+
+        self.close_p_element_in_button_scope(token);
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody:current-node-87
+    fn any_other_end_tag(&mut self, token: Token, tag: &Tag) {
+        let local_name = Atom::from(&tag.name);
+
+        for index in (0..self.open_elements.len()).rev() {
+            let name = self.open_elements.name(index).expect("open element").clone();
+
+            if name.is_html(local_name.clone()) {
+                let element = self.open_elements.get(index).expect("open element").clone();
+
+                self.open_elements.generate_implied_end_tags(Some(local_name.clone()));
+
+                if self.open_elements.current() != Some(&element) {
+                    self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+                }
+
+                self.open_elements.pop_until_element(&element);
+
+                return;
+            } else if name.is_special() {
+                self.unexpected_tag(token);
+
+                return;
+            }
+        }
+    }
+
+    /// Insert an html element for the tag and push it onto the list of active formatting elements.
+    fn insert_formatting_element(&mut self, tag: &Tag) {
+        let element = self.insert_html_element(tag);
+
+        self.active_formatting.push(element, QualifiedName::html(Atom::from(&tag.name)), tag.clone());
+    }
+
+    fn is_marker_or_open(&self, index: usize) -> bool {
+        self.active_formatting.get(index)
+            .and_then(FormattingEntry::handle)
+            .is_none_or(|handle| self.open_elements.contains(handle))
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#reconstruct-the-active-formatting-elements
+    fn reconstruct_active_formatting_elements(&mut self) {
+        let Some(last) = self.active_formatting.len().checked_sub(1) else {
+            return;
+        };
+
+        if self.is_marker_or_open(last) {
+            return;
+        }
+
+        let mut first = last;
+
+        while first > 0 && !self.is_marker_or_open(first - 1) {
+            first -= 1;
+        }
+
+        for index in first..=last {
+            let Some(FormattingEntry::Element { tag, .. }) = self.active_formatting.get(index) else {
+                continue;
+            };
+
+            let tag = tag.clone();
+
+            let element = self.insert_html_element(&tag);
+
+            self.active_formatting.replace(index, element);
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm
+    fn adoption_agency(&mut self, tag: &Tag) {
+        let token = Token::Tag(tag);
+        let subject = Atom::from(&tag.name);
+
+        if self.current_name().is_html(subject.clone()) && !self.active_formatting.contains(self.current_node()) {
+            self.pop();
+
+            return;
+        }
+
+        for _ in 0..8 {
+            let Some(formatting_index) = self.active_formatting.last_after_marker(&subject) else {
+                self.any_other_end_tag(token, tag);
+
+                return;
+            };
+
+            let formatting_element = self.active_formatting.get(formatting_index)
+                .and_then(FormattingEntry::handle)
+                .expect("formatting element")
+                .clone();
+
+            let Some(formatting_stack_index) = self.open_elements.position(&formatting_element) else {
+                self.unexpected_tag(token);
+
+                self.active_formatting.remove(formatting_index);
+
+                return;
+            };
+
+            if !self.open_elements.has_element_in_scope(&formatting_element) {
+                self.unexpected_tag(token);
+
+                return;
+            }
+
+            if self.current_node() != &formatting_element {
+                self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+            }
+
+            let furthest_block_index = (formatting_stack_index + 1..self.open_elements.len())
+                .find(|index| self.open_elements.is_special(*index));
+
+            let Some(furthest_block_index) = furthest_block_index else {
+                self.open_elements.pop_until_element(&formatting_element);
+
+                self.active_formatting.remove(formatting_index);
+
+                return;
+            };
+
+            let mut furthest_block = self.open_elements.get(furthest_block_index).expect("furthest block").clone();
+            let common_ancestor = self.open_elements.get(formatting_stack_index - 1).expect("common ancestor").clone();
+
+            let mut bookmark = formatting_index;
+            let mut node_index = furthest_block_index;
+            let mut last_node = furthest_block.clone();
+
+            for inner_loop_counter in 1.. {
+                node_index -= 1;
+
+                let node = self.open_elements.get(node_index).expect("open element").clone();
+
+                if node == formatting_element {
+                    break;
+                }
+
+                let mut list_index = self.active_formatting.position(&node);
+
+                if inner_loop_counter > 3 && let Some(index) = list_index {
+                    self.active_formatting.remove(index);
+
+                    if index < bookmark {
+                        bookmark -= 1;
                     }
 
-                    self.sink.append_doctype(&doctype);
+                    list_index = None;
+                }
 
-                    self.sink.set_quirks_mode(QuirksMode::from(doctype));
+                let Some(list_index) = list_index else {
+                    self.open_elements.remove(&node);
 
-                    self.mode = InsertionMode::BeforeHtml;
-                },
-                _ => {
-                    self.sink.parse_error("not an iframe srcdoc");
+                    continue;
+                };
 
-                    self.sink.set_quirks_mode(QuirksMode::Quirks);
+                let Some(FormattingEntry::Element { tag: node_tag, .. }) = self.active_formatting.get(list_index) else {
+                    unreachable!("the node is an element in the list");
+                };
 
-                    self.reprocess(token, InsertionMode::BeforeHtml);
-                },
-            },
-            InsertionMode::BeforeHtml => match token {
-                Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') => {},
-                Token::Doctype(doctype) => self.sink.parse_error(format!("unexpected: {:?}", doctype)),
-                Token::Comment(content) => self.append_comment(content),
-                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => {
-                    let element = self.create_element_for(tag, "http://www.w3.org/1999/xhtml", &self.document.clone());
+                let node_tag = node_tag.clone();
 
-                    self.document.append(&element);
+                let mut element = self.create_element_for(&node_tag, Namespace::Html, &common_ancestor);
 
-                    self.open_elements.push(element);
+                self.active_formatting.replace(list_index, element.clone());
 
-                    self.mode = InsertionMode::BeforeHead;
-                },
-                Token::Tag(tag) if tag.kind == TagKind::End && !["head", "body", "html", "br"].contains(&tag.name.as_str()) => {
-                    self.sink.parse_error(format!("unexpected: {:?}", tag));
-                },
-                _ => {
-                    let element = self.sink.create_element(&self.document, QualifiedName::new_with_ns("html", "http://www.w3.org/1999/xhtml"), None, false, &None);
+                self.open_elements.replace(node_index, element.clone());
 
-                    self.document.append(&element);
+                if last_node == furthest_block {
+                    bookmark = list_index + 1;
+                }
 
-                    self.open_elements.push(element);
+                last_node.remove_from_parent();
 
-                    self.reprocess(token, InsertionMode::BeforeHead);
-                },
-            },
-            InsertionMode::BeforeHead => match token {
-                Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') => {},
-                Token::Comment(content) => self.append_comment(content),
-                Token::Doctype(doctype) => self.sink.parse_error(format!("unexpected: {:?}", doctype)),
-                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => {
-                    self.reprocess(token, InsertionMode::InBody);
+                element.append(&last_node);
 
-                    self.mode = InsertionMode::BeforeHead;
-                },
-                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "head" => {
-                    let element = self.insert_foreign_element(tag, "http://www.w3.org/1999/xhtml", false);
+                last_node = element;
+            }
 
-                    self.element_pointers.head.replace(element);
+            last_node.remove_from_parent();
 
-                    self.mode = InsertionMode::InHead;
-                },
-                Token::Tag(tag) if tag.kind == TagKind::End && !["head", "body", "html", "br"].contains(&tag.name.as_str()) => {
-                    self.sink.parse_error(format!("unexpected: {:?}", tag));
-                },
-                _ => {
-                    let tag = Tag::new(TagKind::Start, String::from("head"), false, Vec::new());
-                    let element = self.insert_foreign_element(&tag, "http://www.w3.org/1999/xhtml", false);
+            let adjusted_insertion_location = self.appropriate_insertion_point(Some(&common_ancestor));
 
-                    self.element_pointers.head.replace(element);
+            self.insert_at(&last_node, adjusted_insertion_location);
 
-                    self.reprocess(token, InsertionMode::InHead);
-                },
-            },
-            InsertionMode::InHead => match token {
-                Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') => {},
-                _ => {
-                },
-            },
-            _ => todo!(),
+            let formatting_index = self.active_formatting.position(&formatting_element).expect("formatting element");
+
+            let FormattingEntry::Element { name, tag: formatting_tag, .. } = self.active_formatting.remove(formatting_index) else {
+                unreachable!("the formatting element is an element in the list");
+            };
+
+            let element = self.create_element_for(&formatting_tag, Namespace::Html, &furthest_block);
+
+            furthest_block.reparent_children(&element);
+
+            furthest_block.append(&element);
+
+            if formatting_index < bookmark {
+                bookmark -= 1;
+            }
+
+            self.active_formatting.insert(bookmark, element.clone(), name, formatting_tag);
+
+            self.open_elements.remove(&formatting_element);
+
+            let furthest_block_index = self.open_elements.position(&furthest_block).expect("furthest block");
+
+            self.open_elements.insert(furthest_block_index + 1, element);
         }
     }
-}
 
-impl<Sink: TreeSink> TokenSink for TreeBuilder<Sink> {
-    fn process(&mut self, token: Token) {
-        if self.not_foreign(&token) {
-            self.step(token);
+    // https://html.spec.whatwg.org/multipage/parsing.html#reset-the-insertion-mode-appropriately
+    // NOTE: currently this assumes its not a html fragment parser.
+    fn reset_insertion_mode(&mut self) {
+        for index in (0..self.open_elements.len()).rev() {
+            let last = index == 0;
+            let name = self.open_elements.name(index).expect("open element");
+
+            let mode = if name.is_html(atoms::SELECT) {
+                let in_table = !last && (0..index).rev()
+                    .map(|ancestor| self.open_elements.name(ancestor).expect("open element"))
+                    .take_while(|ancestor| !ancestor.is_html(atoms::TEMPLATE))
+                    .any(|ancestor| ancestor.is_html(atoms::TABLE));
+
+                if in_table { InsertionMode::InSelectInTable } else { InsertionMode::InSelect }
+            } else if (name.is_html(atoms::TD) || name.is_html(atoms::TH)) && !last {
+                InsertionMode::InCell
+            } else if name.is_html(atoms::TR) {
+                InsertionMode::InRow
+            } else if name.is_html(atoms::TBODY) || name.is_html(atoms::THEAD) || name.is_html(atoms::TFOOT) {
+                InsertionMode::InTableBody
+            } else if name.is_html(atoms::CAPTION) {
+                InsertionMode::InCaption
+            } else if name.is_html(atoms::COLGROUP) {
+                InsertionMode::InColumnGroup
+            } else if name.is_html(atoms::TABLE) {
+                InsertionMode::InTable
+            } else if name.is_html(atoms::TEMPLATE) {
+                *self.template_modes.last().expect("template insertion mode")
+            } else if name.is_html(atoms::HEAD) && !last {
+                InsertionMode::InHead
+            } else if name.is_html(atoms::BODY) {
+                InsertionMode::InBody
+            } else if name.is_html(atoms::FRAMESET) {
+                InsertionMode::InFrameset
+            } else if name.is_html(atoms::HTML) {
+                match self.element_pointers.head {
+                    Some(_) => InsertionMode::AfterHead,
+                    None => InsertionMode::BeforeHead,
+                }
+            } else if last {
+                InsertionMode::InBody
+            } else {
+                continue;
+            };
+
+            self.mode = mode;
+
+            return;
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-context
+    /// Pop elements until the current node is an html element with one of the local names.
+    fn clear_stack_back_to(&mut self, local_names: &[LocalName]) {
+        self.open_elements.pop_until_current(|name| local_names.iter().any(|local_name| name.is_html(local_name.clone())));
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#close-the-cell
+    fn close_cell(&mut self, token: Token) {
+        self.open_elements.generate_implied_end_tags(None);
+
+        if !self.current_name().is_html(atoms::TD) && !self.current_name().is_html(atoms::TH) {
+            self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+        }
+
+        self.open_elements.pop_until_one_of(&[atoms::TD, atoms::TH]);
+
+        self.active_formatting.clear_to_last_marker();
+
+        self.mode = InsertionMode::InRow;
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incaption
+    /// Close the caption if it is in table scope, and return whether it was closed.
+    fn close_caption(&mut self, token: Token) -> bool {
+        if !self.open_elements.has_in_table_scope(atoms::CAPTION) {
+            self.unexpected_tag(token);
+
+            return false;
+        }
+
+        self.open_elements.generate_implied_end_tags(None);
+
+        if !self.current_name().is_html(atoms::CAPTION) {
+            self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+        }
+
+        self.open_elements.pop_until(atoms::CAPTION);
+
+        self.active_formatting.clear_to_last_marker();
+
+        self.mode = InsertionMode::InTable;
+
+        true
+    }
+
+    /// Close the template, this pops the template insertion mode and resets the insertion mode.
+    fn close_template(&mut self, token: Token) {
+        self.open_elements.generate_implied_end_tags_thoroughly();
+
+        if !self.current_name().is_html(atoms::TEMPLATE) {
+            self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+        }
+
+        self.open_elements.pop_until(atoms::TEMPLATE);
+
+        self.active_formatting.clear_to_last_marker();
+
+        self.template_modes.pop();
+
+        self.reset_insertion_mode();
+    }
+
+    /// Close the select element and reset the insertion mode.
+    fn close_select(&mut self) {
+        self.open_elements.pop_until(atoms::SELECT);
+
+        self.reset_insertion_mode();
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intabletext
+    fn flush_pending_table_characters(&mut self) {
+        let characters = std::mem::take(&mut self.pending_table_characters);
+
+        if let Some(character) = characters.chars().find(|character| !is_whitespace(*character)) {
+            self.parse_error(TreeBuilderError::UnexpectedToken { token: ErrorToken::Character(character), mode: self.mode });
+
+            self.foster_parenting = true;
+
+            for character in characters.chars() {
+                self.process_using(InsertionMode::InBody, Token::Character(character));
+            }
+
+            self.foster_parenting = false;
         } else {
+            for character in characters.chars() {
+                self.append_character(character);
+            }
         }
     }
 
-    fn eof(&mut self) {
-        // TODO: end of file tokens are handled as not foreign
+    // https://html.spec.whatwg.org/multipage/parsing.html#stop-parsing
+    fn stop_parsing(&mut self) {
+        while self.open_elements.pop().is_some() {}
     }
-}
 
+    fn process_eof(&mut self) {
+        while self.eof_using(self.mode) {}
+
+        self.stop_parsing();
+    }
+
+    /// Process the end of the file using the rules of the insertion mode, and return whether it has to be
+    /// reprocessed in the new insertion mode.
+    fn eof_using(&mut self, mode: InsertionMode) -> bool {
+        match mode {
+            InsertionMode::Initial => {
+                if !self.options.iframe_srcdoc {
+                    self.parse_error(TreeBuilderError::MissingDoctype { token: ErrorToken::Eof, mode: self.mode });
+
+                    let mode = self.resolve_quirks_mode(QuirksMode::Quirks);
+
+                    self.set_quirks_mode(mode);
+                }
 
+                self.mode = InsertionMode::BeforeHtml;
+
+                true
+            },
+            InsertionMode::BeforeHtml => {
+                let element = self.sink.create_element(&self.document, QualifiedName::html(atoms::HTML), None, false, &None);
+
+                self.document.append(&element);
+
+                self.open_elements.push(element);
+
+                self.mode = InsertionMode::BeforeHead;
+
+                true
+            },
+            InsertionMode::BeforeHead => {
+                let element = self.insert_implied_element("head");
+
+                self.element_pointers.head.replace(element);
+
+                self.mode = InsertionMode::InHead;
+
+                true
+            },
+            InsertionMode::InHead => {
+                self.pop();
+
+                self.mode = InsertionMode::AfterHead;
+
+                true
+            },
+            InsertionMode::InHeadNoScript => {
+                self.parse_error(TreeBuilderError::UnexpectedEof { token: ErrorToken::Eof, mode: self.mode });
+
+                self.pop();
+
+                self.mode = InsertionMode::InHead;
+
+                true
+            },
+            InsertionMode::AfterHead => {
+                self.insert_implied_element("body");
+
+                self.mode = InsertionMode::InBody;
+
+                true
+            },
+            InsertionMode::Text => {
+                self.parse_error(TreeBuilderError::UnexpectedEof { token: ErrorToken::Eof, mode: self.mode });
+
+                self.pop();
+
+                self.mode = self.original_mode;
+
+                true
+            },
+            InsertionMode::InBody if !self.template_modes.is_empty() => self.eof_using(InsertionMode::InTemplate),
+            InsertionMode::InBody => {
+                let unclosed = self.open_elements.iter().any(|(_, name)| {
+                    !name.is_namespace(Namespace::Html) || ![
+                        atoms::DD, atoms::DT, atoms::LI, atoms::OPTGROUP, atoms::OPTION, atoms::P, atoms::RB, atoms::RP,
+                        atoms::RT, atoms::RTC, atoms::TBODY, atoms::TD, atoms::TFOOT, atoms::TH, atoms::THEAD, atoms::TR,
+                        atoms::BODY, atoms::HTML,
+                    ].contains(&name.local_name)
+                });
+
+                if unclosed {
+                    self.parse_error(TreeBuilderError::UnexpectedEof { token: ErrorToken::Eof, mode: self.mode });
+                }
+
+                false
+            },
+            InsertionMode::InTable
+                | InsertionMode::InCaption
+                | InsertionMode::InColumnGroup
+                | InsertionMode::InTableBody
+                | InsertionMode::InRow
+                | InsertionMode::InCell
+                | InsertionMode::InSelect
+                | InsertionMode::InSelectInTable => self.eof_using(InsertionMode::InBody),
+            InsertionMode::InTableText => {
+                self.flush_pending_table_characters();
+
+                self.mode = self.original_mode;
+
+                true
+            },
+            InsertionMode::InTemplate => {
+                if !self.open_elements.contains_html(atoms::TEMPLATE) {
+                    return false;
+                }
+
+                self.parse_error(TreeBuilderError::UnexpectedEof { token: ErrorToken::Eof, mode: self.mode });
+
+                self.open_elements.pop_until(atoms::TEMPLATE);
+
+                self.active_formatting.clear_to_last_marker();
+
+                self.template_modes.pop();
+
+                self.reset_insertion_mode();
+
+                true
+            },
+            InsertionMode::InFrameset => {
+                if !self.current_name().is_html(atoms::HTML) {
+                    self.parse_error(TreeBuilderError::UnexpectedEof { token: ErrorToken::Eof, mode: self.mode });
+                }
+
+                false
+            },
+            InsertionMode::AfterBody
+                | InsertionMode::AfterAfterBody
+                | InsertionMode::AfterFrameset
+                | InsertionMode::AfterAfterFrameset => false,
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#generic-raw-text-element-parsing-algorithm
+    fn parse_generic_text(&mut self, tag: &Tag, state: TokenizerState) {
+        self.insert_html_element(tag);
+
+        self.next_state = Some(state);
+
+        self.original_mode = self.mode;
+
+        self.mode = InsertionMode::Text;
+    }
+
+    #[inline]
+    fn reprocess(&mut self, token: Token, mode: InsertionMode) {
+        self.mode = mode;
+
+        self.step(token);
+    }
+
+    #[inline]
+    fn step(&mut self, token: Token) {
+        self.process_using(self.mode, token);
+    }
+
+    fn process_using(&mut self, mode: InsertionMode, token: Token) {
+        match mode {
+            InsertionMode::Initial => match token {
+                Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') => {},
+                Token::Comment(content) => self.append_comment(content),
+                Token::Doctype(ref doctype) => {
+                    if doctype.is_parse_error() {
+                        self.parse_error(TreeBuilderError::BadDoctype { token: ErrorToken::from(&token), mode: self.mode });
+                    }
+
+                    self.sink.append_doctype(doctype);
+
+                    if !self.options.iframe_srcdoc {
+                        let mode = self.resolve_quirks_mode(QuirksMode::from(*doctype));
+
+                        self.set_quirks_mode(mode);
+                    } else if let Some(mode) = self.options.quirks_mode {
+                        self.set_quirks_mode(mode);
+                    }
+
+                    self.mode = InsertionMode::BeforeHtml;
+                },
+                _ => {
+                    if !self.options.iframe_srcdoc {
+                        self.parse_error(TreeBuilderError::MissingDoctype { token: ErrorToken::from(&token), mode: self.mode });
+
+                        let mode = self.resolve_quirks_mode(QuirksMode::Quirks);
+
+                        self.set_quirks_mode(mode);
+                    } else if let Some(mode) = self.options.quirks_mode {
+                        self.set_quirks_mode(mode);
+                    }
+
+                    self.reprocess(token, InsertionMode::BeforeHtml);
+                },
+            },
+            InsertionMode::BeforeHtml => match token {
+                Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') => {},
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Comment(content) => self.append_comment(content),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => {
+                    let element = self.create_element_for(tag, Namespace::Html, &self.document.clone());
+
+                    self.document.append(&element);
+
+                    self.open_elements.push(element);
+
+                    self.mode = InsertionMode::BeforeHead;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && !["head", "body", "html", "br"].contains(&tag.name.as_str()) => {
+                    self.parse_error(TreeBuilderError::UnexpectedEndTag { token: ErrorToken::from(&token), mode: self.mode });
+                },
+                _ => {
+                    let element = self.sink.create_element(&self.document, QualifiedName::html(atoms::HTML), None, false, &None);
+
+                    self.document.append(&element);
+
+                    self.open_elements.push(element);
+
+                    self.reprocess(token, InsertionMode::BeforeHead);
+                },
+            },
+            InsertionMode::BeforeHead => match token {
+                Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') => {},
+                Token::Comment(content) => self.append_comment(content),
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "head" => {
+                    let element = self.insert_foreign_element(tag, Namespace::Html, false);
+
+                    self.element_pointers.head.replace(element);
+
+                    self.mode = InsertionMode::InHead;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && !["head", "body", "html", "br"].contains(&tag.name.as_str()) => {
+                    self.parse_error(TreeBuilderError::UnexpectedEndTag { token: ErrorToken::from(&token), mode: self.mode });
+                },
+                _ => {
+                    let tag = Tag::new(TagKind::Start, String::from("head"), false, Vec::new());
+                    let element = self.insert_foreign_element(&tag, Namespace::Html, false);
+
+                    self.element_pointers.head.replace(element);
+
+                    self.reprocess(token, InsertionMode::InHead);
+                },
+            },
+            InsertionMode::InHead => match token {
+                Token::Character(character @ ('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ')) => self.append_character(character),
+                Token::Comment(content) => self.append_comment(content),
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["base", "basefont", "bgsound", "link", "meta"].contains(&tag.name.as_str()) => {
+                    self.insert_html_element(tag);
+
+                    self.pop();
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "title" => self.parse_generic_text(tag, TokenizerState::RcData),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "noscript" && self.options.scripting => {
+                    self.parse_generic_text(tag, TokenizerState::RawText);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["noframes", "style"].contains(&tag.name.as_str()) => {
+                    self.parse_generic_text(tag, TokenizerState::RawText);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "noscript" => {
+                    self.insert_html_element(tag);
+
+                    self.mode = InsertionMode::InHeadNoScript;
+                },
+                // TODO: the script element should be marked as parser inserted and non blocking
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "script" => {
+                    self.parse_generic_text(tag, TokenizerState::ScriptData);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "head" => {
+                    self.pop();
+
+                    self.mode = InsertionMode::AfterHead;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "template" => {
+                    self.insert_html_element(tag);
+
+                    self.active_formatting.push_marker();
+
+                    self.frameset_ok = false;
+
+                    self.mode = InsertionMode::InTemplate;
+
+                    self.template_modes.push(InsertionMode::InTemplate);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "template" => {
+                    if !self.open_elements.contains_html(atoms::TEMPLATE) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.close_template(token);
+                    }
+                },
+                Token::Tag(tag) if (tag.kind == TagKind::Start && tag.name.as_str() == "head")
+                    || (tag.kind == TagKind::End && !["body", "html", "br"].contains(&tag.name.as_str())) =>
+                {
+                    self.unexpected_tag(token);
+                },
+                _ => {
+                    self.pop();
+
+                    self.reprocess(token, InsertionMode::AfterHead);
+                },
+            },
+            InsertionMode::InHeadNoScript => match token {
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "noscript" => {
+                    self.pop();
+
+                    self.mode = InsertionMode::InHead;
+                },
+                Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') | Token::Comment(_) => {
+                    self.process_using(InsertionMode::InHead, token);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["basefont", "bgsound", "link", "meta", "noframes", "style"].contains(&tag.name.as_str()) => {
+                    self.process_using(InsertionMode::InHead, token);
+                },
+                Token::Tag(tag) if (tag.kind == TagKind::Start && ["head", "noscript"].contains(&tag.name.as_str()))
+                    || (tag.kind == TagKind::End && tag.name.as_str() != "br") =>
+                {
+                    self.unexpected_tag(token);
+                },
+                _ => {
+                    self.parse_error(TreeBuilderError::UnexpectedToken { token: ErrorToken::from(&token), mode: self.mode });
+
+                    self.pop();
+
+                    self.reprocess(token, InsertionMode::InHead);
+                },
+            },
+            InsertionMode::Text => match token {
+                Token::Character(character) => self.append_character(character),
+                Token::Tag(tag) if tag.kind == TagKind::End => {
+                    // TODO: run the script element once we support scripting
+                    self.pop();
+
+                    self.mode = self.original_mode;
+                },
+                _ => {},
+            },
+            InsertionMode::AfterHead => match token {
+                Token::Character(character @ ('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ')) => self.append_character(character),
+                Token::Comment(content) => self.insert_comment(content, None),
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "body" => {
+                    self.insert_html_element(tag);
+
+                    self.frameset_ok = false;
+
+                    self.mode = InsertionMode::InBody;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "frameset" => {
+                    self.insert_html_element(tag);
+
+                    self.mode = InsertionMode::InFrameset;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && [
+                    "base", "basefont", "bgsound", "link", "meta", "noframes", "script", "style", "template", "title",
+                ].contains(&tag.name.as_str()) => {
+                    self.unexpected_tag(token);
+
+                    if let Some(head) = self.element_pointers.head.clone() {
+                        self.open_elements.push(head.clone());
+
+                        self.process_using(InsertionMode::InHead, token);
+
+                        self.open_elements.remove(&head);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "template" => self.process_using(InsertionMode::InHead, token),
+                Token::Tag(tag) if (tag.kind == TagKind::Start && tag.name.as_str() == "head")
+                    || (tag.kind == TagKind::End && !["body", "html", "br"].contains(&tag.name.as_str())) =>
+                {
+                    self.unexpected_tag(token);
+                },
+                _ => {
+                    self.insert_implied_element("body");
+
+                    self.reprocess(token, InsertionMode::InBody);
+                },
+            },
+            InsertionMode::InBody => match token {
+                Token::Character('\0') => self.parse_error(TreeBuilderError::UnexpectedNullCharacter { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Character(character @ ('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ')) => {
+                    self.reconstruct_active_formatting_elements();
+
+                    self.append_character(character);
+                },
+                Token::Character(character) => {
+                    self.reconstruct_active_formatting_elements();
+
+                    self.append_character(character);
+
+                    self.frameset_ok = false;
+                },
+                Token::Comment(content) => self.insert_comment(content, None),
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => {
+                    self.unexpected_tag(token);
+
+                    if !self.open_elements.contains_html(atoms::TEMPLATE) && let Some(html) = self.open_elements.get(0).cloned() {
+                        self.merge_attributes(tag, &html);
+                    }
+                },
+                Token::Tag(tag) if (tag.kind == TagKind::Start && [
+                    "base", "basefont", "bgsound", "link", "meta", "noframes", "script", "style", "template", "title",
+                ].contains(&tag.name.as_str())) || (tag.kind == TagKind::End && tag.name.as_str() == "template") => {
+                    self.process_using(InsertionMode::InHead, token);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "body" => {
+                    self.unexpected_tag(token);
+
+                    let body = self.open_elements.name(1).filter(|name| name.is_html(atoms::BODY))
+                        .and_then(|_| self.open_elements.get(1).cloned());
+
+                    if let Some(body) = body && !self.open_elements.contains_html(atoms::TEMPLATE) {
+                        self.frameset_ok = false;
+
+                        self.merge_attributes(tag, &body);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "frameset" => {
+                    self.unexpected_tag(token);
+
+                    let body = self.open_elements.name(1).filter(|name| name.is_html(atoms::BODY))
+                        .and_then(|_| self.open_elements.get(1).cloned());
+
+                    if let Some(mut body) = body && self.frameset_ok && !self.open_elements.contains_html(atoms::TEMPLATE) {
+                        body.remove_from_parent();
+
+                        self.open_elements.pop_until_current(|name| name.is_html(atoms::HTML));
+
+                        self.insert_html_element(tag);
+
+                        self.mode = InsertionMode::InFrameset;
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && ["body", "html"].contains(&tag.name.as_str()) => {
+                    if !self.open_elements.has_in_scope(atoms::BODY) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.mode = InsertionMode::AfterBody;
+
+                        if tag.name.as_str() == "html" {
+                            self.step(token);
+                        }
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && [
+                    "address", "article", "aside", "blockquote", "center", "details", "dialog", "dir", "div", "dl",
+                    "fieldset", "figcaption", "figure", "footer", "header", "hgroup", "main", "menu", "nav", "ol", "p",
+                    "search", "section", "summary", "ul",
+                ].contains(&tag.name.as_str()) => {
+                    self.close_p_element_in_button_scope(token);
+
+                    self.insert_html_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["h1", "h2", "h3", "h4", "h5", "h6"].contains(&tag.name.as_str()) => {
+                    self.close_p_element_in_button_scope(token);
+
+                    if [atoms::H1, atoms::H2, atoms::H3, atoms::H4, atoms::H5, atoms::H6].iter().any(|local_name| self.current_name().is_html(local_name.clone())) {
+                        self.unexpected_tag(token);
+
+                        self.pop();
+                    }
+
+                    self.insert_html_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["pre", "listing"].contains(&tag.name.as_str()) => {
+                    self.close_p_element_in_button_scope(token);
+
+                    self.insert_html_element(tag);
+
+                    self.ignore_lf = true;
+
+                    self.frameset_ok = false;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "form" => {
+                    if self.element_pointers.form.is_some() && !self.open_elements.contains_html(atoms::TEMPLATE) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.close_p_element_in_button_scope(token);
+
+                        let element = self.insert_html_element(tag);
+
+                        if !self.open_elements.contains_html(atoms::TEMPLATE) {
+                            self.element_pointers.form.replace(element);
+                        }
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "li" => {
+                    self.close_list_item(token, &[atoms::LI]);
+
+                    self.insert_html_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["dd", "dt"].contains(&tag.name.as_str()) => {
+                    self.close_list_item(token, &[atoms::DD, atoms::DT]);
+
+                    self.insert_html_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "plaintext" => {
+                    self.close_p_element_in_button_scope(token);
+
+                    self.insert_html_element(tag);
+
+                    self.next_state = Some(TokenizerState::Plaintext);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "button" => {
+                    if self.open_elements.has_in_scope(atoms::BUTTON) {
+                        self.unexpected_tag(token);
+
+                        self.open_elements.generate_implied_end_tags(None);
+
+                        self.open_elements.pop_until(atoms::BUTTON);
+                    }
+
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_html_element(tag);
+
+                    self.frameset_ok = false;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && [
+                    "address", "article", "aside", "blockquote", "button", "center", "details", "dialog", "dir", "div",
+                    "dl", "fieldset", "figcaption", "figure", "footer", "header", "hgroup", "listing", "main", "menu",
+                    "nav", "ol", "pre", "search", "section", "summary", "ul",
+                ].contains(&tag.name.as_str()) => {
+                    let local_name = Atom::from(&tag.name);
+
+                    if !self.open_elements.has_in_scope(local_name.clone()) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.open_elements.generate_implied_end_tags(None);
+
+                        if !self.current_name().is_html(local_name.clone()) {
+                            self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+                        }
+
+                        self.open_elements.pop_until(local_name);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "form" => {
+                    if self.open_elements.contains_html(atoms::TEMPLATE) {
+                        if !self.open_elements.has_in_scope(atoms::FORM) {
+                            self.unexpected_tag(token);
+                        } else {
+                            self.close_element(token, atoms::FORM);
+                        }
+                    } else {
+                        let form = self.element_pointers.form.take();
+
+                        match form {
+                            Some(form) if self.open_elements.has_element_in_scope(&form) => {
+                                self.open_elements.generate_implied_end_tags(None);
+
+                                if self.open_elements.current() != Some(&form) {
+                                    self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+                                }
+
+                                self.open_elements.remove(&form);
+                            },
+                            _ => self.unexpected_tag(token),
+                        }
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "p" => {
+                    if !self.open_elements.has_in_button_scope(atoms::P) {
+                        self.unexpected_tag(token);
+
+                        self.insert_implied_element("p");
+                    }
+
+                    self.close_p_element(token);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "li" => {
+                    if !self.open_elements.has_in_list_item_scope(atoms::LI) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.close_element(token, atoms::LI);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && ["dd", "dt"].contains(&tag.name.as_str()) => {
+                    let local_name = Atom::from(&tag.name);
+
+                    if !self.open_elements.has_in_scope(local_name.clone()) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.close_element(token, local_name);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && ["h1", "h2", "h3", "h4", "h5", "h6"].contains(&tag.name.as_str()) => {
+                    let headings = [atoms::H1, atoms::H2, atoms::H3, atoms::H4, atoms::H5, atoms::H6];
+
+                    if !self.open_elements.has_any_in_scope(|name| headings.iter().any(|local_name| name.is_html(local_name.clone()))) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.open_elements.generate_implied_end_tags(None);
+
+                        if !self.current_name().is_html(Atom::from(&tag.name)) {
+                            self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+                        }
+
+                        self.open_elements.pop_until_one_of(&headings);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "a" => {
+                    let active = self.active_formatting.last_after_marker(&atoms::A)
+                        .and_then(|index| self.active_formatting.get(index))
+                        .and_then(FormattingEntry::handle)
+                        .cloned();
+
+                    if let Some(element) = active {
+                        self.unexpected_tag(token);
+
+                        self.adoption_agency(&Tag::new(TagKind::End, String::from("a"), false, Vec::new()));
+
+                        if let Some(index) = self.active_formatting.position(&element) {
+                            self.active_formatting.remove(index);
+                        }
+
+                        self.open_elements.remove(&element);
+                    }
+
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_formatting_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && [
+                    "b", "big", "code", "em", "font", "i", "s", "small", "strike", "strong", "tt", "u",
+                ].contains(&tag.name.as_str()) => {
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_formatting_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "nobr" => {
+                    self.reconstruct_active_formatting_elements();
+
+                    if self.open_elements.has_in_scope(atoms::NOBR) {
+                        self.unexpected_tag(token);
+
+                        self.adoption_agency(&Tag::new(TagKind::End, String::from("nobr"), false, Vec::new()));
+
+                        self.reconstruct_active_formatting_elements();
+                    }
+
+                    self.insert_formatting_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && [
+                    "a", "b", "big", "code", "em", "font", "i", "nobr", "s", "small", "strike", "strong", "tt", "u",
+                ].contains(&tag.name.as_str()) => {
+                    self.adoption_agency(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["applet", "marquee", "object"].contains(&tag.name.as_str()) => {
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_html_element(tag);
+
+                    self.active_formatting.push_marker();
+
+                    self.frameset_ok = false;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && ["applet", "marquee", "object"].contains(&tag.name.as_str()) => {
+                    let local_name = Atom::from(&tag.name);
+
+                    if !self.open_elements.has_in_scope(local_name.clone()) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.open_elements.generate_implied_end_tags(None);
+
+                        if !self.current_name().is_html(local_name.clone()) {
+                            self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+                        }
+
+                        self.open_elements.pop_until(local_name);
+
+                        self.active_formatting.clear_to_last_marker();
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "table" => {
+                    if self.quirks_mode != QuirksMode::Quirks {
+                        self.close_p_element_in_button_scope(token);
+                    }
+
+                    self.insert_html_element(tag);
+
+                    self.frameset_ok = false;
+
+                    self.mode = InsertionMode::InTable;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "br" => {
+                    self.unexpected_tag(token);
+
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_implied_element("br");
+
+                    self.pop();
+
+                    self.frameset_ok = false;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["area", "br", "embed", "img", "keygen", "wbr"].contains(&tag.name.as_str()) => {
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_html_element(tag);
+
+                    self.pop();
+
+                    self.frameset_ok = false;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "input" => {
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_html_element(tag);
+
+                    self.pop();
+
+                    if !is_hidden_input(tag) {
+                        self.frameset_ok = false;
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["param", "source", "track"].contains(&tag.name.as_str()) => {
+                    self.insert_html_element(tag);
+
+                    self.pop();
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "hr" => {
+                    self.close_p_element_in_button_scope(token);
+
+                    self.insert_html_element(tag);
+
+                    self.pop();
+
+                    self.frameset_ok = false;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "image" => {
+                    self.unexpected_tag(token);
+
+                    let image = Tag::new(TagKind::Start, String::from("img"), tag.self_closing, tag.attributes.clone());
+
+                    self.step(Token::Tag(&image));
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "textarea" => {
+                    self.insert_html_element(tag);
+
+                    self.ignore_lf = true;
+
+                    self.next_state = Some(TokenizerState::RcData);
+
+                    self.original_mode = self.mode;
+
+                    self.frameset_ok = false;
+
+                    self.mode = InsertionMode::Text;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "xmp" => {
+                    self.close_p_element_in_button_scope(token);
+
+                    self.reconstruct_active_formatting_elements();
+
+                    self.frameset_ok = false;
+
+                    self.parse_generic_text(tag, TokenizerState::RawText);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "iframe" => {
+                    self.frameset_ok = false;
+
+                    self.parse_generic_text(tag, TokenizerState::RawText);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start
+                    && (tag.name.as_str() == "noembed" || (tag.name.as_str() == "noscript" && self.options.scripting)) =>
+                {
+                    self.parse_generic_text(tag, TokenizerState::RawText);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "select" => {
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_html_element(tag);
+
+                    self.frameset_ok = false;
+
+                    self.mode = match self.mode {
+                        InsertionMode::InTable
+                            | InsertionMode::InCaption
+                            | InsertionMode::InTableBody
+                            | InsertionMode::InRow
+                            | InsertionMode::InCell => InsertionMode::InSelectInTable,
+                        _ => InsertionMode::InSelect,
+                    };
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["optgroup", "option"].contains(&tag.name.as_str()) => {
+                    if self.current_name().is_html(atoms::OPTION) {
+                        self.pop();
+                    }
+
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_html_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["rb", "rtc"].contains(&tag.name.as_str()) => {
+                    if self.open_elements.has_in_scope(atoms::RUBY) {
+                        self.open_elements.generate_implied_end_tags(None);
+
+                        if !self.current_name().is_html(atoms::RUBY) {
+                            self.unexpected_tag(token);
+                        }
+                    }
+
+                    self.insert_html_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["rp", "rt"].contains(&tag.name.as_str()) => {
+                    if self.open_elements.has_in_scope(atoms::RUBY) {
+                        self.open_elements.generate_implied_end_tags(Some(atoms::RTC));
+
+                        if !self.current_name().is_html(atoms::RUBY) && !self.current_name().is_html(atoms::RTC) {
+                            self.unexpected_tag(token);
+                        }
+                    }
+
+                    self.insert_html_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["math", "svg"].contains(&tag.name.as_str()) => {
+                    let namespace = if tag.name.as_str() == "math" { Namespace::MathMl } else { Namespace::Svg };
+
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_foreign_element(tag, namespace, false);
+
+                    if tag.self_closing {
+                        self.pop();
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && [
+                    "caption", "col", "colgroup", "frame", "head", "tbody", "td", "tfoot", "th", "thead", "tr",
+                ].contains(&tag.name.as_str()) => {
+                    self.unexpected_tag(token);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start => {
+                    self.reconstruct_active_formatting_elements();
+
+                    self.insert_html_element(tag);
+                },
+                Token::Tag(tag) => self.any_other_end_tag(token, tag),
+            },
+            InsertionMode::AfterBody => match token {
+                Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') => self.process_using(InsertionMode::InBody, token),
+                Token::Comment(content) => {
+                    let html = self.open_elements.get(0).cloned();
+
+                    self.insert_comment(content, html.as_ref());
+                },
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "html" => self.mode = InsertionMode::AfterAfterBody,
+                _ => {
+                    self.unexpected_tag(token);
+
+                    self.reprocess(token, InsertionMode::InBody);
+                },
+            },
+            InsertionMode::AfterAfterBody => match token {
+                Token::Comment(content) => self.append_comment(content),
+                Token::Doctype(_) | Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                _ => {
+                    self.unexpected_tag(token);
+
+                    self.reprocess(token, InsertionMode::InBody);
+                },
+            },
+            InsertionMode::InTable => match token {
+                Token::Character(_) if [atoms::TABLE, atoms::TBODY, atoms::TEMPLATE, atoms::TFOOT, atoms::THEAD, atoms::TR]
+                    .into_iter()
+                    .any(|local_name| self.current_name().is_html(local_name)) =>
+                {
+                    self.pending_table_characters.clear();
+
+                    self.original_mode = self.mode;
+
+                    self.reprocess(token, InsertionMode::InTableText);
+                },
+                Token::Comment(content) => self.insert_comment(content, None),
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "caption" => {
+                    self.clear_stack_back_to(&[atoms::TABLE, atoms::TEMPLATE, atoms::HTML]);
+
+                    self.active_formatting.push_marker();
+
+                    self.insert_html_element(tag);
+
+                    self.mode = InsertionMode::InCaption;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "colgroup" => {
+                    self.clear_stack_back_to(&[atoms::TABLE, atoms::TEMPLATE, atoms::HTML]);
+
+                    self.insert_html_element(tag);
+
+                    self.mode = InsertionMode::InColumnGroup;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "col" => {
+                    self.clear_stack_back_to(&[atoms::TABLE, atoms::TEMPLATE, atoms::HTML]);
+
+                    self.insert_implied_element("colgroup");
+
+                    self.reprocess(token, InsertionMode::InColumnGroup);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["tbody", "tfoot", "thead"].contains(&tag.name.as_str()) => {
+                    self.clear_stack_back_to(&[atoms::TABLE, atoms::TEMPLATE, atoms::HTML]);
+
+                    self.insert_html_element(tag);
+
+                    self.mode = InsertionMode::InTableBody;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["td", "th", "tr"].contains(&tag.name.as_str()) => {
+                    self.clear_stack_back_to(&[atoms::TABLE, atoms::TEMPLATE, atoms::HTML]);
+
+                    self.insert_implied_element("tbody");
+
+                    self.reprocess(token, InsertionMode::InTableBody);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "table" => {
+                    self.unexpected_tag(token);
+
+                    if self.open_elements.has_in_table_scope(atoms::TABLE) {
+                        self.open_elements.pop_until(atoms::TABLE);
+
+                        self.reset_insertion_mode();
+
+                        self.step(token);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "table" => {
+                    if !self.open_elements.has_in_table_scope(atoms::TABLE) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.open_elements.pop_until(atoms::TABLE);
+
+                        self.reset_insertion_mode();
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && [
+                    "body", "caption", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead", "tr",
+                ].contains(&tag.name.as_str()) => {
+                    self.unexpected_tag(token);
+                },
+                Token::Tag(tag) if (tag.kind == TagKind::Start && ["style", "script", "template"].contains(&tag.name.as_str()))
+                    || (tag.kind == TagKind::End && tag.name.as_str() == "template") =>
+                {
+                    self.process_using(InsertionMode::InHead, token);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "input" && is_hidden_input(tag) => {
+                    self.unexpected_tag(token);
+
+                    self.insert_html_element(tag);
+
+                    self.pop();
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "form" => {
+                    self.unexpected_tag(token);
+
+                    if self.element_pointers.form.is_none() && !self.open_elements.contains_html(atoms::TEMPLATE) {
+                        let element = self.insert_html_element(tag);
+
+                        self.element_pointers.form.replace(element);
+
+                        self.pop();
+                    }
+                },
+                _ => {
+                    self.unexpected_tag(token);
+
+                    self.foster_parenting = true;
+
+                    self.process_using(InsertionMode::InBody, token);
+
+                    self.foster_parenting = false;
+                },
+            },
+            InsertionMode::InTableText => match token {
+                Token::Character('\0') => self.parse_error(TreeBuilderError::UnexpectedNullCharacter { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Character(character) => self.pending_table_characters.push(character),
+                _ => {
+                    self.flush_pending_table_characters();
+
+                    self.reprocess(token, self.original_mode);
+                },
+            },
+            InsertionMode::InCaption => match token {
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "caption" => {
+                    self.close_caption(token);
+                },
+                Token::Tag(tag) if (tag.kind == TagKind::Start && [
+                    "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+                ].contains(&tag.name.as_str())) || (tag.kind == TagKind::End && tag.name.as_str() == "table") => {
+                    if self.close_caption(token) {
+                        self.step(token);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && [
+                    "body", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead", "tr",
+                ].contains(&tag.name.as_str()) => {
+                    self.unexpected_tag(token);
+                },
+                _ => self.process_using(InsertionMode::InBody, token),
+            },
+            InsertionMode::InColumnGroup => match token {
+                Token::Character(character @ ('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ')) => self.append_character(character),
+                Token::Comment(content) => self.insert_comment(content, None),
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "col" => {
+                    self.insert_html_element(tag);
+
+                    self.pop();
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "colgroup" => {
+                    if !self.current_name().is_html(atoms::COLGROUP) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.pop();
+
+                        self.mode = InsertionMode::InTable;
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "col" => self.unexpected_tag(token),
+                Token::Tag(tag) if tag.name.as_str() == "template" => self.process_using(InsertionMode::InHead, token),
+                _ => {
+                    if !self.current_name().is_html(atoms::COLGROUP) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.pop();
+
+                        self.reprocess(token, InsertionMode::InTable);
+                    }
+                },
+            },
+            InsertionMode::InTableBody => match token {
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "tr" => {
+                    self.clear_stack_back_to(&[atoms::TBODY, atoms::TFOOT, atoms::THEAD, atoms::TEMPLATE, atoms::HTML]);
+
+                    self.insert_html_element(tag);
+
+                    self.mode = InsertionMode::InRow;
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["th", "td"].contains(&tag.name.as_str()) => {
+                    self.unexpected_tag(token);
+
+                    self.clear_stack_back_to(&[atoms::TBODY, atoms::TFOOT, atoms::THEAD, atoms::TEMPLATE, atoms::HTML]);
+
+                    self.insert_implied_element("tr");
+
+                    self.reprocess(token, InsertionMode::InRow);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && ["tbody", "tfoot", "thead"].contains(&tag.name.as_str()) => {
+                    if !self.open_elements.has_in_table_scope(Atom::from(&tag.name)) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.clear_stack_back_to(&[atoms::TBODY, atoms::TFOOT, atoms::THEAD, atoms::TEMPLATE, atoms::HTML]);
+
+                        self.pop();
+
+                        self.mode = InsertionMode::InTable;
+                    }
+                },
+                Token::Tag(tag) if (tag.kind == TagKind::Start && [
+                    "caption", "col", "colgroup", "tbody", "tfoot", "thead",
+                ].contains(&tag.name.as_str())) || (tag.kind == TagKind::End && tag.name.as_str() == "table") => {
+                    let in_scope = [atoms::TBODY, atoms::THEAD, atoms::TFOOT].into_iter()
+                        .any(|local_name| self.open_elements.has_in_table_scope(local_name));
+
+                    if !in_scope {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.clear_stack_back_to(&[atoms::TBODY, atoms::TFOOT, atoms::THEAD, atoms::TEMPLATE, atoms::HTML]);
+
+                        self.pop();
+
+                        self.reprocess(token, InsertionMode::InTable);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && [
+                    "body", "caption", "col", "colgroup", "html", "td", "th", "tr",
+                ].contains(&tag.name.as_str()) => {
+                    self.unexpected_tag(token);
+                },
+                _ => self.process_using(InsertionMode::InTable, token),
+            },
+            InsertionMode::InRow => match token {
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["th", "td"].contains(&tag.name.as_str()) => {
+                    self.clear_stack_back_to(&[atoms::TR, atoms::TEMPLATE, atoms::HTML]);
+
+                    self.insert_html_element(tag);
+
+                    self.mode = InsertionMode::InCell;
+
+                    self.active_formatting.push_marker();
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "tr" => {
+                    if !self.open_elements.has_in_table_scope(atoms::TR) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.clear_stack_back_to(&[atoms::TR, atoms::TEMPLATE, atoms::HTML]);
+
+                        self.pop();
+
+                        self.mode = InsertionMode::InTableBody;
+                    }
+                },
+                Token::Tag(tag) if (tag.kind == TagKind::Start && [
+                    "caption", "col", "colgroup", "tbody", "tfoot", "thead", "tr",
+                ].contains(&tag.name.as_str())) || (tag.kind == TagKind::End && tag.name.as_str() == "table") => {
+                    if !self.open_elements.has_in_table_scope(atoms::TR) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.clear_stack_back_to(&[atoms::TR, atoms::TEMPLATE, atoms::HTML]);
+
+                        self.pop();
+
+                        self.reprocess(token, InsertionMode::InTableBody);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && ["tbody", "tfoot", "thead"].contains(&tag.name.as_str()) => {
+                    if !self.open_elements.has_in_table_scope(Atom::from(&tag.name)) {
+                        self.unexpected_tag(token);
+                    } else if self.open_elements.has_in_table_scope(atoms::TR) {
+                        self.clear_stack_back_to(&[atoms::TR, atoms::TEMPLATE, atoms::HTML]);
+
+                        self.pop();
+
+                        self.reprocess(token, InsertionMode::InTableBody);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && [
+                    "body", "caption", "col", "colgroup", "html", "td", "th",
+                ].contains(&tag.name.as_str()) => {
+                    self.unexpected_tag(token);
+                },
+                _ => self.process_using(InsertionMode::InTable, token),
+            },
+            InsertionMode::InCell => match token {
+                Token::Tag(tag) if tag.kind == TagKind::End && ["td", "th"].contains(&tag.name.as_str()) => {
+                    let local_name = Atom::from(&tag.name);
+
+                    if !self.open_elements.has_in_table_scope(local_name.clone()) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.open_elements.generate_implied_end_tags(None);
+
+                        if !self.current_name().is_html(local_name.clone()) {
+                            self.parse_error(TreeBuilderError::UnclosedElements { token: ErrorToken::from(&token), mode: self.mode });
+                        }
+
+                        self.open_elements.pop_until(local_name);
+
+                        self.active_formatting.clear_to_last_marker();
+
+                        self.mode = InsertionMode::InRow;
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && [
+                    "caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr",
+                ].contains(&tag.name.as_str()) => {
+                    if !self.open_elements.has_in_table_scope(atoms::TD) && !self.open_elements.has_in_table_scope(atoms::TH) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.close_cell(token);
+
+                        self.step(token);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && ["body", "caption", "col", "colgroup", "html"].contains(&tag.name.as_str()) => {
+                    self.unexpected_tag(token);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && ["table", "tbody", "tfoot", "thead", "tr"].contains(&tag.name.as_str()) => {
+                    if !self.open_elements.has_in_table_scope(Atom::from(&tag.name)) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.close_cell(token);
+
+                        self.step(token);
+                    }
+                },
+                _ => self.process_using(InsertionMode::InBody, token),
+            },
+            InsertionMode::InSelect => match token {
+                Token::Character('\0') => self.parse_error(TreeBuilderError::UnexpectedNullCharacter { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Character(character) => self.append_character(character),
+                Token::Comment(content) => self.insert_comment(content, None),
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "option" => {
+                    if self.current_name().is_html(atoms::OPTION) {
+                        self.pop();
+                    }
+
+                    self.insert_html_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["optgroup", "hr"].contains(&tag.name.as_str()) => {
+                    if self.current_name().is_html(atoms::OPTION) {
+                        self.pop();
+                    }
+
+                    if self.current_name().is_html(atoms::OPTGROUP) {
+                        self.pop();
+                    }
+
+                    self.insert_html_element(tag);
+
+                    if tag.name.as_str() == "hr" {
+                        self.pop();
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "optgroup" => {
+                    let after_optgroup = self.open_elements.len().checked_sub(2)
+                        .and_then(|index| self.open_elements.name(index))
+                        .is_some_and(|name| name.is_html(atoms::OPTGROUP));
+
+                    if self.current_name().is_html(atoms::OPTION) && after_optgroup {
+                        self.pop();
+                    }
+
+                    if self.current_name().is_html(atoms::OPTGROUP) {
+                        self.pop();
+                    } else {
+                        self.unexpected_tag(token);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "option" => {
+                    if self.current_name().is_html(atoms::OPTION) {
+                        self.pop();
+                    } else {
+                        self.unexpected_tag(token);
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "select" => {
+                    if !self.open_elements.has_in_select_scope(atoms::SELECT) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.close_select();
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "select" => {
+                    self.unexpected_tag(token);
+
+                    if self.open_elements.has_in_select_scope(atoms::SELECT) {
+                        self.close_select();
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && ["input", "keygen", "textarea"].contains(&tag.name.as_str()) => {
+                    self.unexpected_tag(token);
+
+                    if self.open_elements.has_in_select_scope(atoms::SELECT) {
+                        self.close_select();
+
+                        self.step(token);
+                    }
+                },
+                Token::Tag(tag) if (tag.kind == TagKind::Start && ["script", "template"].contains(&tag.name.as_str()))
+                    || (tag.kind == TagKind::End && tag.name.as_str() == "template") =>
+                {
+                    self.process_using(InsertionMode::InHead, token);
+                },
+                _ => self.unexpected_tag(token),
+            },
+            InsertionMode::InSelectInTable => match token {
+                Token::Tag(tag) if ["caption", "table", "tbody", "tfoot", "thead", "tr", "td", "th"].contains(&tag.name.as_str()) => {
+                    self.unexpected_tag(token);
+
+                    if tag.kind == TagKind::Start || self.open_elements.has_in_table_scope(Atom::from(&tag.name)) {
+                        self.close_select();
+
+                        self.step(token);
+                    }
+                },
+                _ => self.process_using(InsertionMode::InSelect, token),
+            },
+            InsertionMode::InTemplate => match token {
+                Token::Character(_) | Token::Comment(_) | Token::Doctype(_) => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if (tag.kind == TagKind::Start && [
+                    "base", "basefont", "bgsound", "link", "meta", "noframes", "script", "style", "template", "title",
+                ].contains(&tag.name.as_str())) || (tag.kind == TagKind::End && tag.name.as_str() == "template") => {
+                    self.process_using(InsertionMode::InHead, token);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start => {
+                    let mode = match tag.name.as_str() {
+                        "caption" | "colgroup" | "tbody" | "tfoot" | "thead" => InsertionMode::InTable,
+                        "col" => InsertionMode::InColumnGroup,
+                        "tr" => InsertionMode::InTableBody,
+                        "td" | "th" => InsertionMode::InRow,
+                        _ => InsertionMode::InBody,
+                    };
+
+                    self.template_modes.pop();
+
+                    self.template_modes.push(mode);
+
+                    self.reprocess(token, mode);
+                },
+                Token::Tag(_) => self.unexpected_tag(token),
+            },
+            InsertionMode::InFrameset => match token {
+                Token::Character(character @ ('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ')) => self.append_character(character),
+                Token::Comment(content) => self.insert_comment(content, None),
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "frameset" => {
+                    self.insert_html_element(tag);
+                },
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "frameset" => {
+                    if self.current_name().is_html(atoms::HTML) {
+                        self.unexpected_tag(token);
+                    } else {
+                        self.pop();
+
+                        if !self.current_name().is_html(atoms::FRAMESET) {
+                            self.mode = InsertionMode::AfterFrameset;
+                        }
+                    }
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "frame" => {
+                    self.insert_html_element(tag);
+
+                    self.pop();
+                },
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "noframes" => self.process_using(InsertionMode::InHead, token),
+                _ => self.unexpected_tag(token),
+            },
+            InsertionMode::AfterFrameset => match token {
+                Token::Character(character @ ('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ')) => self.append_character(character),
+                Token::Comment(content) => self.insert_comment(content, None),
+                Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::End && tag.name.as_str() == "html" => self.mode = InsertionMode::AfterAfterFrameset,
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "noframes" => self.process_using(InsertionMode::InHead, token),
+                _ => self.unexpected_tag(token),
+            },
+            InsertionMode::AfterAfterFrameset => match token {
+                Token::Comment(content) => self.append_comment(content),
+                Token::Doctype(_) | Token::Character('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ') => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "html" => self.process_using(InsertionMode::InBody, token),
+                Token::Tag(tag) if tag.kind == TagKind::Start && tag.name.as_str() == "noframes" => self.process_using(InsertionMode::InHead, token),
+                _ => self.unexpected_tag(token),
+            },
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+    fn process_foreign(&mut self, token: Token) {
+        match token {
+            Token::Character('\0') => {
+                self.parse_error(TreeBuilderError::UnexpectedNullCharacter { token: ErrorToken::from(&token), mode: self.mode });
+
+                self.append_character('\u{fffd}');
+            },
+            Token::Character(character @ ('\u{0009}' | '\u{000a}' | '\u{000c}' | '\u{000d}' | ' ')) => self.append_character(character),
+            Token::Character(character) => {
+                self.append_character(character);
+
+                self.frameset_ok = false;
+            },
+            Token::Comment(content) => self.insert_comment(content, None),
+            Token::Doctype(_) => self.parse_error(TreeBuilderError::UnexpectedDoctype { token: ErrorToken::from(&token), mode: self.mode }),
+            Token::Tag(tag) if (tag.kind == TagKind::Start && foreign::BREAKOUT_TAGS.contains(&tag.name.as_str()))
+                || (tag.kind == TagKind::Start && tag.name.as_str() == "font" && tag.attributes.iter().any(|attribute| {
+                    ["color", "face", "size"].contains(&attribute.name.as_str())
+                }))
+                || (tag.kind == TagKind::End && ["br", "p"].contains(&tag.name.as_str())) =>
+            {
+                self.unexpected_tag(token);
+
+                self.open_elements.pop_until_current(|name| {
+                    name.is_namespace(Namespace::Html) || name.is_mathml_text_integration_point() || name.is_html_integration_point()
+                });
+
+                self.step(token);
+            },
+            Token::Tag(tag) if tag.kind == TagKind::Start => {
+                let namespace = self.adjusted_current_name().namespace.clone().unwrap_or(Namespace::Html);
+
+                self.insert_foreign_element(tag, namespace, false);
+
+                if tag.self_closing {
+                    self.pop();
+                }
+            },
+            Token::Tag(tag) => {
+                if tag.name.as_str() == "script" && self.current_name().is_namespace(Namespace::Svg) && self.current_name().local_name == atoms::SCRIPT {
+                    self.pop();
+
+                    return;
+                }
+
+                let mut index = self.open_elements.len() - 1;
+
+                if !self.current_name().local_name.as_str().eq_ignore_ascii_case(&tag.name) {
+                    self.unexpected_tag(token);
+                }
+
+                loop {
+                    if index == 0 {
+                        return;
+                    }
+
+                    let name = self.open_elements.name(index).expect("open element").clone();
+
+                    if name.local_name.as_str().eq_ignore_ascii_case(&tag.name) {
+                        let element = self.open_elements.get(index).expect("open element").clone();
+
+                        self.open_elements.pop_until_element(&element);
+
+                        return;
+                    }
+
+                    index -= 1;
+
+                    if self.open_elements.name(index).expect("open element").is_namespace(Namespace::Html) {
+                        self.step(token);
+
+                        return;
+                    }
+                }
+            },
+        }
+    }
+}
+
+impl<Sink: TreeSink> TokenSink for TreeBuilder<Sink> {
+    fn process(&mut self, token: Token) {
+        if self.aborted() {
+            return;
+        }
+
+        if std::mem::take(&mut self.ignore_lf) && token == Token::Character('\n') {
+            return;
+        }
+
+        if self.not_foreign(&token) {
+            self.step(token);
+        } else {
+            self.process_foreign(token);
+        }
+    }
+
+    fn eof(&mut self) {
+        if !self.aborted() {
+            self.process_eof();
+        }
+    }
+
+    fn set_position(&mut self, position: Position) {
+        self.position = Some(position);
+    }
+
+    fn next_state(&mut self) -> Option<TokenizerState> {
+        self.next_state.take()
+    }
+
+    fn errored(&self) -> bool {
+        self.errored
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_html;
+    use crate::dom::{arena, Dom};
+    use crate::dom::node::document::Document;
+
+    fn body(input: &str) -> String {
+        let html = parse_html(input).to_html();
+
+        html.split_once("<body>")
+            .and_then(|(_, body)| body.strip_suffix("</body></html>"))
+            .unwrap_or(&html)
+            .to_string()
+    }
+
+    fn parse(input: &str, options: ParserOptions) -> Dom {
+        parse_document(input, options, Dom::new())
+    }
+
+    fn quirks_mode(dom: &Dom) -> QuirksMode {
+        arena::get(dom.document()).borrow().downcast_ref::<Document>().quirks_mode
+    }
+
+    #[test]
+    fn template_in_head() {
+        assert_eq!(
+            parse_html("<template><p>x</p></template>").to_html(),
+            "<html><head><template><p>x</p></template></head><body></body></html>",
+        );
+
+        assert_eq!(
+            parse_html("<head></head><template><tr><td>a</td></tr></template>").to_html(),
+            "<html><head><template><tr><td>a</td></tr></template></head><body></body></html>",
+        );
+    }
+
+    #[test]
+    fn unclosed_template() {
+        assert_eq!(
+            parse_html("<template><div>x").to_html(),
+            "<html><head><template><div>x</div></template></head><body></body></html>",
+        );
+
+        assert_eq!(body("<p><template><td>x"), "<p><template><td>x</td></template></p>");
+    }
+
+    #[test]
+    fn frameset() {
+        assert_eq!(
+            parse_html("<frameset><frame></frameset>").to_html(),
+            "<html><head></head><frameset><frame></frameset></html>",
+        );
+
+        assert_eq!(
+            parse_html("<div><frameset><frame></frameset>").to_html(),
+            "<html><head></head><frameset><frame></frameset></html>",
+        );
+
+        assert_eq!(body("<p>a<frameset>"), "<p>a</p>");
+    }
+
+    #[test]
+    fn tables() {
+        assert_eq!(body("<table><tr><td>a</td></tr></table>"), "<table><tbody><tr><td>a</td></tr></tbody></table>");
+        assert_eq!(body("<table>x<tr><td>y</table>"), "x<table><tbody><tr><td>y</td></tr></tbody></table>");
+        assert_eq!(body("<table><caption>c</caption><col></table>"), "<table><caption>c</caption><colgroup><col></colgroup></table>");
+        assert_eq!(body("<table><td>a<td>b</table>"), "<table><tbody><tr><td>a</td><td>b</td></tr></tbody></table>");
+    }
+
+    #[test]
+    fn selects() {
+        assert_eq!(body("<select><option>a<option>b</select>x"), "<select><option>a</option><option>b</option></select>x");
+        assert_eq!(
+            body("<table><td><select><option>a</table>b"),
+            "<table><tbody><tr><td><select><option>a</option></select></td></tr></tbody></table>b",
+        );
+    }
+
+    #[test]
+    fn adoption_agency() {
+        assert_eq!(body("<a>1<a>2"), "<a>1</a><a>2</a>");
+        assert_eq!(body("<b><i>x</b>y</i>"), "<b><i>x</i></b><i>y</i>");
+        assert_eq!(body("<a><p>x</a>y"), "<a></a><p><a>x</a>y</p>");
+    }
+
+    #[test]
+    fn reconstructs_formatting_elements() {
+        assert_eq!(body("<p><b>x</p><p>y"), "<p><b>x</b></p><p><b>y</b></p>");
+        assert_eq!(body("<b>1<b>2<b>3<b>4</p>x"), "<b>1<b>2<b>3<b>4<p></p>x</b></b></b></b>");
+    }
+
+    #[test]
+    fn double_escaped_script_data() {
+        assert_eq!(body("<body><script><!--<script></script>x</script>y"), "<script><!--<script></script>x</script>y");
+        assert_eq!(body("<body><script><!--<script></script>--></script>y"), "<script><!--<script></script>--></script>y");
+    }
+
+    #[test]
+    fn malformed_input_does_not_panic() {
+        let inputs = [
+            "<table><template>", "<select><template><option>", "<table><caption><table>", "<table><colgroup>x",
+            "<frameset></frameset><frameset>", "<template><frameset>", "<table><tr><td><template></table>",
+            "<select><table>", "<svg><table>", "<math><mi><table><td>", "</template>", "<template><col><tr>",
+            "<b><table><td></b><i></table>x", "<a><table><a>", "<div><a><div><a><div><a>",
+        ];
+
+        for input in inputs {
+            parse_html(input);
+        }
+    }
+
+    #[test]
+    fn errors_are_reported_at_the_start_of_the_token() {
+        let dom = parse_html("<!DOCTYPE html><p>\n  </div  class=a>x");
+
+        assert_eq!(dom.errors().len(), 1);
+
+        let (error, position) = &dom.errors()[0];
+
+        assert_eq!(error.token(), &ErrorToken::EndTag(String::from("div")));
+        assert_eq!(*position, Some(Position { line: 2, column: 3 }));
+    }
+
+    #[test]
+    fn scripting_flag() {
+        let input = "<head><noscript><link rel=stylesheet href=a.css></noscript></head><body><noscript><p>x</p></noscript>";
+
+        let scripting = parse(input, ParserOptions::default());
+        let no_scripting = parse(input, ParserOptions { scripting: false, ..ParserOptions::default() });
+
+        assert_ne!(scripting.to_html(), no_scripting.to_html());
+
+        assert_eq!(scripting.query_selector("noscript > link, noscript > p"), Ok(None));
+        assert!(no_scripting.query_selector("head > noscript > link").expect("valid").is_some());
+        assert!(no_scripting.query_selector("body > noscript > p").expect("valid").is_some());
+    }
+
+    #[test]
+    fn iframe_srcdoc() {
+        let dom = parse("<p>x", ParserOptions::default());
+
+        assert_eq!(dom.errors()[0].0.token(), &ErrorToken::StartTag(String::from("p")));
+        assert_eq!(quirks_mode(&dom), QuirksMode::Quirks);
+
+        let dom = parse("<p>x", ParserOptions { iframe_srcdoc: true, ..ParserOptions::default() });
+
+        assert!(dom.errors().is_empty());
+        assert_eq!(quirks_mode(&dom), QuirksMode::NoQuirks);
+    }
+
+    #[test]
+    fn quirks_override() {
+        let options = ParserOptions { quirks_mode: Some(QuirksMode::LimitedQuirks), ..ParserOptions::default() };
+
+        assert_eq!(quirks_mode(&parse("<!DOCTYPE html><p>x", options)), QuirksMode::LimitedQuirks);
+        assert_eq!(quirks_mode(&parse("<p>x", options)), QuirksMode::LimitedQuirks);
+        assert_eq!(quirks_mode(&parse("<!DOCTYPE html><p>x", ParserOptions::default())), QuirksMode::NoQuirks);
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let input = "<!DOCTYPE html><p>a</div>b</span>c";
+
+        let recovered = parse(input, ParserOptions::default());
+
+        assert_eq!(recovered.errors().len(), 2);
+        assert_eq!(body(input), "<p>abc</p>");
+
+        let aborted = parse(input, ParserOptions { error_policy: ErrorPolicy::Abort, ..ParserOptions::default() });
+
+        assert_eq!(aborted.errors().len(), 1);
+        assert_eq!(aborted.to_html(), "<!DOCTYPE html><html><head></head><body><p>a</p></body></html>");
+    }
+}
@@ -0,0 +1,453 @@
+use crate::names::{QualifiedName, Namespace, LocalName, atoms};
+
+use super::interface::Node;
+
+
+const IMPLIED_END_TAGS: &[LocalName] = &[
+    atoms::DD, atoms::DT, atoms::LI, atoms::OPTGROUP, atoms::OPTION, atoms::P, atoms::RB, atoms::RP, atoms::RT, atoms::RTC,
+];
+
+const THOROUGHLY_IMPLIED_END_TAGS: &[LocalName] = &[
+    atoms::CAPTION, atoms::COLGROUP, atoms::DD, atoms::DT, atoms::LI, atoms::OPTGROUP, atoms::OPTION, atoms::P, atoms::RB,
+    atoms::RP, atoms::RT, atoms::RTC, atoms::TBODY, atoms::TD, atoms::TFOOT, atoms::TH, atoms::THEAD, atoms::TR,
+];
+
+// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-scope
+fn is_scope_boundary(name: &QualifiedName) -> bool {
+    match name.namespace {
+        Some(Namespace::Html) => [
+            atoms::APPLET, atoms::CAPTION, atoms::HTML, atoms::TABLE, atoms::TD, atoms::TH, atoms::MARQUEE, atoms::OBJECT,
+            atoms::TEMPLATE,
+        ].contains(&name.local_name),
+        Some(Namespace::MathMl) => [
+            atoms::MI, atoms::MO, atoms::MN, atoms::MS, atoms::MTEXT, atoms::ANNOTATION_XML,
+        ].contains(&name.local_name),
+        Some(Namespace::Svg) => [atoms::FOREIGNOBJECT, atoms::DESC, atoms::TITLE].contains(&name.local_name),
+        _ => false,
+    }
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-list-item-scope
+fn is_list_item_scope_boundary(name: &QualifiedName) -> bool {
+    is_scope_boundary(name) || name.is_html(atoms::OL) || name.is_html(atoms::UL)
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-button-scope
+fn is_button_scope_boundary(name: &QualifiedName) -> bool {
+    is_scope_boundary(name) || name.is_html(atoms::BUTTON)
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-table-scope
+fn is_table_scope_boundary(name: &QualifiedName) -> bool {
+    name.is_html(atoms::HTML) || name.is_html(atoms::TABLE) || name.is_html(atoms::TEMPLATE)
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-select-scope
+fn is_select_scope_boundary(name: &QualifiedName) -> bool {
+    !(name.is_html(atoms::OPTGROUP) || name.is_html(atoms::OPTION))
+}
+
+struct OpenElement<Handle> {
+    handle: Handle,
+    name: QualifiedName,
+}
+
+/// The stack of open elements, every element is stored together with its qualified name so
+/// that scope queries never have to go through the tree sink.
+pub struct OpenElements<Handle> {
+    elements: Vec<OpenElement<Handle>>,
+}
+
+impl<Handle: Node> OpenElements<Handle> {
+    pub fn new() -> OpenElements<Handle> {
+        OpenElements {
+            elements: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, handle: Handle) {
+        let name = handle.element_name();
+
+        self.elements.push(OpenElement { handle, name });
+    }
+
+    #[inline]
+    pub fn pop(&mut self) -> Option<Handle> {
+        self.elements.pop().map(|element| element.handle)
+    }
+
+    /// Insert an element at the index, moving the elements after it down the stack.
+    pub fn insert(&mut self, index: usize, handle: Handle) {
+        let name = handle.element_name();
+
+        self.elements.insert(index, OpenElement { handle, name });
+    }
+
+    /// Replace the element at the index.
+    pub fn replace(&mut self, index: usize, handle: Handle) {
+        let name = handle.element_name();
+
+        self.elements[index] = OpenElement { handle, name };
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// The current node, this is the bottommost node in the stack.
+    #[inline]
+    pub fn current(&self) -> Option<&Handle> {
+        self.elements.last().map(|element| &element.handle)
+    }
+
+    /// The qualified name of the current node.
+    #[inline]
+    pub fn current_name(&self) -> Option<&QualifiedName> {
+        self.elements.last().map(|element| &element.name)
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&Handle> {
+        self.elements.get(index).map(|element| &element.handle)
+    }
+
+    #[inline]
+    pub fn name(&self, index: usize) -> Option<&QualifiedName> {
+        self.elements.get(index).map(|element| &element.name)
+    }
+
+    /// Iterate over the handles and names from the topmost to the bottommost node.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&Handle, &QualifiedName)> {
+        self.elements.iter().map(|element| (&element.handle, &element.name))
+    }
+
+    pub fn position(&self, handle: &Handle) -> Option<usize> {
+        self.elements.iter().rposition(|element| element.handle == *handle)
+    }
+
+    pub fn contains(&self, handle: &Handle) -> bool {
+        self.position(handle).is_some()
+    }
+
+    pub fn remove(&mut self, handle: &Handle) {
+        if let Some(index) = self.position(handle) {
+            self.elements.remove(index);
+        }
+    }
+
+    /// Return the index of the bottommost html element with the local name.
+    pub fn last_index_of(&self, local_name: LocalName) -> Option<usize> {
+        self.elements.iter().rposition(|element| element.name.is_html(local_name.clone()))
+    }
+
+    /// Check if there is an html element with the local name anywhere in the stack.
+    pub fn contains_html(&self, local_name: LocalName) -> bool {
+        self.last_index_of(local_name).is_some()
+    }
+
+    fn has_in_specific_scope(&self, target: impl Fn(&QualifiedName) -> bool, boundary: fn(&QualifiedName) -> bool) -> bool {
+        for element in self.elements.iter().rev() {
+            if target(&element.name) {
+                return true;
+            } else if boundary(&element.name) {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// Check if any html element matching the predicate is in scope.
+    pub fn has_any_in_scope(&self, target: impl Fn(&QualifiedName) -> bool) -> bool {
+        self.has_in_specific_scope(target, is_scope_boundary)
+    }
+
+    /// Check if an html element with the local name is in scope.
+    pub fn has_in_scope(&self, local_name: LocalName) -> bool {
+        self.has_in_specific_scope(|name| name.is_html(local_name.clone()), is_scope_boundary)
+    }
+
+    /// Check if a specific element is in scope.
+    pub fn has_element_in_scope(&self, handle: &Handle) -> bool {
+        for element in self.elements.iter().rev() {
+            if element.handle == *handle {
+                return true;
+            } else if is_scope_boundary(&element.name) {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// Check if an html element with the local name is in list item scope.
+    pub fn has_in_list_item_scope(&self, local_name: LocalName) -> bool {
+        self.has_in_specific_scope(|name| name.is_html(local_name.clone()), is_list_item_scope_boundary)
+    }
+
+    /// Check if an html element with the local name is in button scope.
+    pub fn has_in_button_scope(&self, local_name: LocalName) -> bool {
+        self.has_in_specific_scope(|name| name.is_html(local_name.clone()), is_button_scope_boundary)
+    }
+
+    /// Check if an html element with the local name is in table scope.
+    pub fn has_in_table_scope(&self, local_name: LocalName) -> bool {
+        self.has_in_specific_scope(|name| name.is_html(local_name.clone()), is_table_scope_boundary)
+    }
+
+    /// Check if an html element with the local name is in select scope.
+    pub fn has_in_select_scope(&self, local_name: LocalName) -> bool {
+        self.has_in_specific_scope(|name| name.is_html(local_name.clone()), is_select_scope_boundary)
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#generate-implied-end-tags
+    /// Pop elements that have implied end tags, except html elements with the local name.
+    pub fn generate_implied_end_tags(&mut self, except: Option<LocalName>) {
+        while let Some(name) = self.current_name()
+            && name.is_namespace(Namespace::Html)
+            && IMPLIED_END_TAGS.contains(&name.local_name)
+            && Some(&name.local_name) != except.as_ref()
+        {
+            self.pop();
+        }
+    }
+
+    // https://html.spec.whatwg.org/multipage/parsing.html#generate-all-implied-end-tags-thoroughly
+    pub fn generate_implied_end_tags_thoroughly(&mut self) {
+        while let Some(name) = self.current_name()
+            && name.is_namespace(Namespace::Html)
+            && THOROUGHLY_IMPLIED_END_TAGS.contains(&name.local_name)
+        {
+            self.pop();
+        }
+    }
+
+    /// Pop elements until an html element with the local name has been popped.
+    pub fn pop_until(&mut self, local_name: LocalName) {
+        self.pop_until_one_of(&[local_name]);
+    }
+
+    /// Pop elements until an html element with one of the local names has been popped.
+    pub fn pop_until_one_of(&mut self, local_names: &[LocalName]) {
+        while let Some(element) = self.elements.pop() {
+            if element.name.is_namespace(Namespace::Html) && local_names.contains(&element.name.local_name) {
+                break;
+            }
+        }
+    }
+
+    /// Pop elements until the specific element has been popped.
+    pub fn pop_until_element(&mut self, handle: &Handle) {
+        while let Some(element) = self.elements.pop() {
+            if element.handle == *handle {
+                break;
+            }
+        }
+    }
+
+    /// Pop elements until the current node matches the predicate, the matching element is not popped.
+    pub fn pop_until_current(&mut self, f: impl Fn(&QualifiedName) -> bool) {
+        while let Some(name) = self.current_name() && !f(name) {
+            self.pop();
+        }
+    }
+
+    /// Check if the element at the index is in the special category.
+    pub fn is_special(&self, index: usize) -> bool {
+        self.name(index).map(|name| name.is_special()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::names::Atom;
+
+    #[derive(Clone, PartialEq)]
+    struct Element {
+        id: usize,
+        name: QualifiedName,
+    }
+
+    impl Node for Element {
+        type CustomElementRegistry = ();
+
+        fn node_document(&self) -> Element { self.clone() }
+
+        fn root(&self) -> Element { self.clone() }
+
+        fn element_name(&self) -> QualifiedName { self.name.clone() }
+
+        fn custom_element_registry(&self) -> Option<()> { None }
+
+        fn parent(&self) -> Option<Element> { None }
+
+        fn last_child(&self) -> Option<Element> { None }
+
+        fn previous_sibling(&self) -> Option<Element> { None }
+
+        fn append(&mut self, _: &Element) {}
+
+        fn append_before(&mut self, _: &Element, _: &Element) {}
+
+        fn remove_from_parent(&mut self) {}
+
+        fn reparent_children(&mut self, _: &Element) {}
+
+        fn append_text(&mut self, _: &str) {}
+
+        fn append_attribute(&mut self, _: QualifiedName, _: &str) {}
+
+        fn has_attribute(&self, _: QualifiedName) -> bool { false }
+
+        fn set_parser_inserted(&self) {}
+
+        fn set_associated_form(&self, _: Element) {}
+
+        fn is_node(&self, _: &str) -> bool { false }
+    }
+
+    /// A stack of html elements with the local names, from the topmost to the bottommost node.
+    fn stack(local_names: &[&str]) -> OpenElements<Element> {
+        let mut open_elements = OpenElements::new();
+
+        for (id, local_name) in local_names.iter().enumerate() {
+            open_elements.push(Element { id, name: QualifiedName::html(Atom::from(*local_name)) });
+        }
+
+        open_elements
+    }
+
+    fn local_names(open_elements: &OpenElements<Element>) -> Vec<String> {
+        open_elements.iter().map(|(_, name)| name.local_name.to_string()).collect()
+    }
+
+    #[test]
+    fn scopes() {
+        assert!(stack(&["html", "body", "p"]).has_in_scope(atoms::P));
+        assert!(!stack(&["html", "p", "table"]).has_in_scope(atoms::P));
+        assert!(!stack(&["html", "body"]).has_in_scope(atoms::P));
+
+        assert!(stack(&["html", "p", "span"]).has_in_button_scope(atoms::P));
+        assert!(!stack(&["html", "p", "button"]).has_in_button_scope(atoms::P));
+        assert!(stack(&["html", "p", "button"]).has_in_scope(atoms::P));
+
+        assert!(stack(&["html", "ul", "li", "div"]).has_in_list_item_scope(atoms::LI));
+        assert!(!stack(&["html", "li", "ol"]).has_in_list_item_scope(atoms::LI));
+
+        assert!(stack(&["html", "table", "tbody", "tr", "td"]).has_in_table_scope(atoms::TBODY));
+        assert!(stack(&["html", "table", "td", "p"]).has_in_table_scope(atoms::TD));
+        assert!(!stack(&["html", "tbody", "table"]).has_in_table_scope(atoms::TBODY));
+
+        assert!(stack(&["html", "select", "optgroup", "option"]).has_in_select_scope(atoms::SELECT));
+        assert!(!stack(&["html", "select", "div"]).has_in_select_scope(atoms::SELECT));
+    }
+
+    #[test]
+    fn foreign_scope_boundaries() {
+        let mut open_elements = stack(&["html", "p"]);
+
+        open_elements.push(Element { id: 2, name: QualifiedName::new(Some(Namespace::Svg), atoms::SVG) });
+
+        assert!(open_elements.has_in_scope(atoms::P));
+
+        open_elements.push(Element { id: 3, name: QualifiedName::new(Some(Namespace::Svg), atoms::TITLE) });
+
+        assert!(!open_elements.has_in_scope(atoms::P));
+        assert!(!open_elements.has_element_in_scope(open_elements.get(1).expect("p")));
+        assert!(open_elements.has_element_in_scope(open_elements.get(3).expect("title")));
+    }
+
+    #[test]
+    fn implied_end_tags() {
+        let mut open_elements = stack(&["html", "body", "ul", "li", "p", "dd"]);
+
+        open_elements.generate_implied_end_tags(Some(atoms::P));
+
+        assert_eq!(local_names(&open_elements), vec!["html", "body", "ul", "li", "p"]);
+
+        open_elements.generate_implied_end_tags(None);
+
+        assert_eq!(local_names(&open_elements), vec!["html", "body", "ul"]);
+
+        let mut open_elements = stack(&["html", "table", "tbody", "tr", "td", "p"]);
+
+        open_elements.generate_implied_end_tags(None);
+
+        assert_eq!(local_names(&open_elements), vec!["html", "table", "tbody", "tr", "td"]);
+
+        open_elements.generate_implied_end_tags_thoroughly();
+
+        assert_eq!(local_names(&open_elements), vec!["html", "table"]);
+    }
+
+    #[test]
+    fn popping() {
+        let mut open_elements = stack(&["html", "body", "div", "p", "span"]);
+
+        open_elements.pop_until(atoms::DIV);
+
+        assert_eq!(local_names(&open_elements), vec!["html", "body"]);
+
+        let mut open_elements = stack(&["html", "body", "h1", "div", "h2", "span"]);
+
+        open_elements.pop_until_one_of(&[atoms::H1, atoms::H2]);
+
+        assert_eq!(local_names(&open_elements), vec!["html", "body", "h1", "div"]);
+
+        let div = open_elements.get(3).expect("div").clone();
+
+        open_elements.push(Element { id: 6, name: QualifiedName::html(atoms::DIV) });
+        open_elements.pop_until_element(&div);
+
+        assert_eq!(local_names(&open_elements), vec!["html", "body", "h1"]);
+
+        open_elements.pop_until_current(|name| name.is_html(atoms::HTML));
+
+        assert_eq!(local_names(&open_elements), vec!["html"]);
+
+        open_elements.pop_until(atoms::P);
+
+        assert!(open_elements.is_empty());
+    }
+
+    #[test]
+    fn removal() {
+        let mut open_elements = stack(&["html", "body", "b", "i"]);
+
+        let b = open_elements.get(2).expect("b").clone();
+
+        assert_eq!(open_elements.position(&b), Some(2));
+        assert_eq!(open_elements.last_index_of(atoms::I), Some(3));
+
+        open_elements.remove(&b);
+
+        assert!(!open_elements.contains(&b));
+        assert!(!open_elements.contains_html(atoms::B));
+        assert_eq!(local_names(&open_elements), vec!["html", "body", "i"]);
+        assert_eq!(open_elements.current_name().map(|name| name.local_name.clone()), Some(atoms::I));
+
+        open_elements.remove(&b);
+
+        assert_eq!(open_elements.len(), 3);
+    }
+
+    #[test]
+    fn special_elements() {
+        let mut open_elements = stack(&["html", "body", "b"]);
+
+        open_elements.push(Element { id: 3, name: QualifiedName::new(Some(Namespace::Svg), atoms::TITLE) });
+
+        assert!(open_elements.is_special(1));
+        assert!(!open_elements.is_special(2));
+        assert!(open_elements.is_special(3));
+        assert!(!open_elements.is_special(4));
+    }
+}
@@ -0,0 +1,41 @@
+
+use super::quirks::QuirksMode;
+
+
+/// What the parser does after encountering a parse error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Report the error and recover as the spec describes.
+    #[default]
+    Recover,
+
+    /// Report the error and stop parsing, the tree is left as it was when the error occurred.
+    Abort,
+}
+
+/// Options that change how a document is parsed, shared by the tokenizer and the tree builder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserOptions {
+    /// The scripting flag, when set `noscript` is parsed as raw text instead of as markup.
+    pub scripting: bool,
+
+    /// Whether the document is an iframe srcdoc document, these never end up in quirks mode.
+    pub iframe_srcdoc: bool,
+
+    /// Force a quirks mode instead of deriving it from the doctype.
+    pub quirks_mode: Option<QuirksMode>,
+
+    /// What to do after a parse error.
+    pub error_policy: ErrorPolicy,
+}
+
+impl Default for ParserOptions {
+    fn default() -> ParserOptions {
+        ParserOptions {
+            scripting: true,
+            iframe_srcdoc: false,
+            quirks_mode: None,
+            error_policy: ErrorPolicy::Recover,
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use crate::tokenizer::{Tokenizer, TokenSink, TokenizerState, Token, Tag, TagKind, Attribute, Position};
+use crate::names::{LocalName, Atom, atoms};
+
+use super::options::ParserOptions;
+
+
+/// The kind of resource referenced by a preload request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceKind {
+    Image,
+    Stylesheet,
+    Script,
+
+    /// A `link rel=preload` resource, with the value of its `as` attribute.
+    Preload(Option<String>),
+
+    /// The document base url, urls after it should be resolved against it.
+    Base,
+}
+
+/// A resource url found by the preload scanner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreloadRequest {
+    /// The url exactly as it appears in the attribute, it is not resolved.
+    pub url: String,
+
+    pub kind: ResourceKind,
+
+    /// The local name of the element the url was found on.
+    pub element: LocalName,
+
+    /// The local name of the attribute the url was found in.
+    pub attribute: LocalName,
+
+    /// All attributes of the element, such as `media`, `type` or `crossorigin`.
+    pub attributes: Vec<Attribute>,
+
+    /// The position of the end of the start tag in the source.
+    pub position: Option<Position>,
+}
+
+/// The preload scanner runs the tokenizer over a document ahead of the tree builder and reports
+/// the resources it references, so they can be fetched before the tree is built.
+pub struct PreloadScanner<F: FnMut(PreloadRequest)> {
+    callback: F,
+    position: Option<Position>,
+    next_state: Option<TokenizerState>,
+    base: bool,
+}
+
+impl PreloadScanner<fn(PreloadRequest)> {
+    /// Scan the input and return every preload request in document order.
+    pub fn scan(input: &str, options: ParserOptions) -> Vec<PreloadRequest> {
+        let mut requests = Vec::new();
+
+        PreloadScanner::run(input, options, |request| requests.push(request));
+
+        requests
+    }
+
+    /// Scan the input and call the callback for every preload request in document order.
+    pub fn run<F: FnMut(PreloadRequest)>(input: &str, options: ParserOptions, callback: F) {
+        let mut tokenizer = Tokenizer::new(PreloadScanner::new(callback), input.chars(), options);
+
+        tokenizer.run();
+    }
+}
+
+impl<F: FnMut(PreloadRequest)> PreloadScanner<F> {
+    pub fn new(callback: F) -> PreloadScanner<F> {
+        PreloadScanner {
+            callback,
+            position: None,
+            next_state: None,
+            base: false,
+        }
+    }
+
+    fn request(&mut self, tag: &Tag, kind: ResourceKind, attribute: LocalName, url: &str) {
+        if url.is_empty() {
+            return;
+        }
+
+        (self.callback)(PreloadRequest {
+            url: url.to_string(),
+            kind,
+            element: Atom::from(&tag.name),
+            attribute,
+            attributes: tag.attributes.clone(),
+            position: self.position,
+        });
+    }
+
+    fn scan_tag(&mut self, tag: &Tag) {
+        match tag.name.as_str() {
+            "img" => {
+                if let Some(src) = attribute(tag, "src") {
+                    self.request(tag, ResourceKind::Image, atoms::SRC, src);
+                }
+
+                if let Some(srcset) = attribute(tag, "srcset") {
+                    for url in parse_srcset(srcset) {
+                        self.request(tag, ResourceKind::Image, atoms::SRCSET, url);
+                    }
+                }
+            },
+            "link" => {
+                let rel = attribute(tag, "rel").unwrap_or_default().to_ascii_lowercase();
+                let href = attribute(tag, "href");
+
+                for keyword in rel.split_ascii_whitespace() {
+                    match keyword {
+                        "stylesheet" => if let Some(href) = href {
+                            self.request(tag, ResourceKind::Stylesheet, atoms::HREF, href);
+                        },
+                        "preload" => {
+                            let as_ = attribute(tag, "as").map(|as_| as_.to_ascii_lowercase());
+
+                            if let Some(href) = href {
+                                self.request(tag, ResourceKind::Preload(as_.clone()), atoms::HREF, href);
+                            }
+
+                            if let Some(srcset) = attribute(tag, "imagesrcset") && as_.as_deref() == Some("image") {
+                                for url in parse_srcset(srcset) {
+                                    self.request(tag, ResourceKind::Preload(as_.clone()), atoms::IMAGESRCSET, url);
+                                }
+                            }
+                        },
+                        _ => {},
+                    }
+                }
+            },
+            "script" => {
+                if let Some(src) = attribute(tag, "src") {
+                    self.request(tag, ResourceKind::Script, atoms::SRC, src);
+                }
+            },
+            "base" if !self.base => {
+                if let Some(href) = attribute(tag, "href") {
+                    self.base = true;
+
+                    self.request(tag, ResourceKind::Base, atoms::HREF, href);
+                }
+            },
+            _ => {},
+        }
+
+        self.next_state = match tag.name.as_str() {
+            "title" | "textarea" => Some(TokenizerState::RcData),
+            "style" | "xmp" | "iframe" | "noembed" | "noframes" | "noscript" => Some(TokenizerState::RawText),
+            "script" => Some(TokenizerState::ScriptData),
+            "plaintext" => Some(TokenizerState::Plaintext),
+            _ => None,
+        };
+    }
+}
+
+impl<F: FnMut(PreloadRequest)> TokenSink for PreloadScanner<F> {
+    fn process(&mut self, token: Token) {
+        if let Token::Tag(tag) = token && tag.kind == TagKind::Start {
+            self.scan_tag(tag);
+        }
+    }
+
+    fn eof(&mut self) {}
+
+    fn set_position(&mut self, position: Position) {
+        self.position = Some(position);
+    }
+
+    fn next_state(&mut self) -> Option<TokenizerState> {
+        self.next_state.take()
+    }
+}
+
+fn attribute<'a>(tag: &'a Tag, name: &str) -> Option<&'a str> {
+    tag.attributes.iter()
+        .find(|attribute| attribute.name.as_str() == name)
+        .map(|attribute| attribute.value.trim())
+}
+
+// https://html.spec.whatwg.org/multipage/images.html#parsing-a-srcset-attribute
+/// Return the urls of the image candidates in a srcset attribute, descriptors are skipped.
+fn parse_srcset(srcset: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    let mut rest = srcset;
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+
+        if rest.is_empty() {
+            break urls;
+        }
+
+        let end = rest.find(|c: char| c.is_ascii_whitespace()).unwrap_or(rest.len());
+        let (candidate, remaining) = rest.split_at(end);
+
+        if let Some(url) = candidate.strip_suffix(',') {
+            urls.push(url.trim_end_matches(','));
+
+            rest = remaining;
+        } else {
+            urls.push(candidate);
+
+            let mut depth = 0;
+
+            let end = remaining.char_indices()
+                .find(|(_, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        ',' => return depth == 0,
+                        _ => {},
+                    }
+
+                    false
+                })
+                .map(|(index, _)| index + 1)
+                .unwrap_or(remaining.len());
+
+            rest = &remaining[end..];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(input: &str) -> Vec<(ResourceKind, String, String)> {
+        PreloadScanner::scan(input, ParserOptions::default())
+            .into_iter()
+            .map(|request| (request.kind, request.element.to_string(), request.url))
+            .collect()
+    }
+
+    #[test]
+    fn stylesheets_and_preloads() {
+        assert_eq!(
+            scan("<link rel=stylesheet href=a.css><link rel='Preload' as=Font href=b.woff2><link rel=icon href=c.ico>"),
+            vec![
+                (ResourceKind::Stylesheet, String::from("link"), String::from("a.css")),
+                (ResourceKind::Preload(Some(String::from("font"))), String::from("link"), String::from("b.woff2")),
+            ],
+        );
+
+        assert_eq!(
+            scan("<link rel=preload as=image imagesrcset='a.png 1x, b.png 2x'>"),
+            vec![
+                (ResourceKind::Preload(Some(String::from("image"))), String::from("link"), String::from("a.png")),
+                (ResourceKind::Preload(Some(String::from("image"))), String::from("link"), String::from("b.png")),
+            ],
+        );
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(
+            scan("<script src=a.js></script><script>document.write('<img src=b.png>')</script>"),
+            vec![(ResourceKind::Script, String::from("script"), String::from("a.js"))],
+        );
+    }
+
+    #[test]
+    fn images() {
+        assert_eq!(
+            scan("<img src=a.png srcset='b.png 2x, c.png 3x'><img src=''>"),
+            vec![
+                (ResourceKind::Image, String::from("img"), String::from("a.png")),
+                (ResourceKind::Image, String::from("img"), String::from("b.png")),
+                (ResourceKind::Image, String::from("img"), String::from("c.png")),
+            ],
+        );
+    }
+
+    #[test]
+    fn srcset_candidates() {
+        assert_eq!(parse_srcset("a.png 1x,b.png 480w, c.png (max-width: 1px, 2px) 2x, d.png,"), vec!["a.png", "b.png", "c.png", "d.png"]);
+        assert_eq!(parse_srcset("data:image/png;base64,xyz= 2x"), vec!["data:image/png;base64,xyz="]);
+    }
+
+    #[test]
+    fn only_the_first_base() {
+        assert_eq!(
+            scan("<base href=/a/><base href=/b/>"),
+            vec![(ResourceKind::Base, String::from("base"), String::from("/a/"))],
+        );
+    }
+
+    #[test]
+    fn urls_with_ampersands() {
+        let requests = PreloadScanner::scan("<img src=\"a.png?x=1&y=2&amp;z=3\">", ParserOptions::default());
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].url, "a.png?x=1&y=2&z=3");
+        assert_eq!(requests[0].attribute, atoms::SRC);
+        assert!(requests[0].position.is_some());
+    }
+
+    #[test]
+    fn noscript_follows_the_scripting_flag() {
+        let input = "<noscript><img src=a.png></noscript><img src=b.png>";
+
+        let urls = |options| PreloadScanner::scan(input, options).into_iter().map(|request| request.url).collect::<Vec<String>>();
+
+        assert_eq!(urls(ParserOptions::default()), vec!["b.png"]);
+        assert_eq!(urls(ParserOptions { scripting: false, ..ParserOptions::default() }), vec!["a.png", "b.png"]);
+    }
+}
@@ -74,7 +74,9 @@ const PUBLIC_ID_OTHER: &[&str] = &[
 
 const SYSTEM_ID_MATCH: &str = "http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd";
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum QuirksMode {
+    #[default]
     NoQuirks,
     Quirks,
     LimitedQuirks,
@@ -89,9 +91,10 @@ impl<'a> From<Doctype<'a>> for QuirksMode {
             Doctype { system_id, .. } if system_id == Some(UniCase::new(SYSTEM_ID_MATCH)) => QuirksMode::Quirks,
             Doctype { public_id, .. } if public_id.map(|string| PUBLIC_ID_PREFIX.iter().any(|prefix| string.starts_with(prefix))).unwrap_or_default() => QuirksMode::Quirks,
             Doctype { system_id, public_id, .. } if public_id.map(|string| PUBLIC_ID_OTHER.iter().any(|prefix| string.starts_with(prefix))).unwrap_or_default() => {
-                system_id.is_none()
-                    .then(|| QuirksMode::Quirks)
-                    .unwrap_or(QuirksMode::LimitedQuirks)
+                match system_id {
+                    None => QuirksMode::Quirks,
+                    Some(_) => QuirksMode::LimitedQuirks,
+                }
             },
             _ => QuirksMode::NoQuirks,
         }
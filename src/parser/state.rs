@@ -1,5 +1,6 @@
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InsertionMode {
     Initial,
     BeforeHtml,
@@ -16,9 +17,12 @@ pub enum InsertionMode {
     InTableBody,
     InRow,
     InCell,
+    InSelect,
+    InSelectInTable,
     InTemplate,
     AfterBody,
     InFrameset,
+    AfterFrameset,
     AfterAfterBody,
     AfterAfterFrameset,
 }
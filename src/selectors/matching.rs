@@ -0,0 +1,481 @@
+use super::{
+    SelectorList, ComplexSelector, CompoundSelector, SimpleSelector, Combinator, NamespaceConstraint, AttributeSelector,
+    AttributeOperator, PseudoClass, Nth,
+};
+
+use crate::dom::arena::{self, NodeId};
+use crate::dom::iterators::{NodeIterator, TreeIterator};
+use crate::dom::node::NodeType;
+use crate::dom::node::element::Element;
+use crate::dom::node::document::Document;
+use crate::names::{QualifiedName, LocalName, Namespace, atoms};
+use crate::parser::quirks::QuirksMode;
+
+
+/// The state which is shared by every element matched against a selector.
+#[derive(Debug, Clone, Copy)]
+pub struct MatchingContext {
+    // https://drafts.csswg.org/selectors-4/#scoping-root
+    /// The element `:scope` matches, `:scope` matches the root element when there is none.
+    pub scope: Option<NodeId>,
+
+    /// Ids and classes are matched ASCII case-insensitively in quirks mode.
+    pub quirks_mode: QuirksMode,
+}
+
+impl MatchingContext {
+    /// Create a context for matching nodes in the document of the node.
+    pub fn new(node: NodeId, scope: Option<NodeId>) -> MatchingContext {
+        let document = arena::get(node).borrow().node_document;
+
+        MatchingContext {
+            scope: scope.filter(|scope| is_element(*scope)),
+            quirks_mode: arena::get(document).borrow().downcast_ref::<Document>().quirks_mode,
+        }
+    }
+
+    fn case_sensitive_eq(&self, a: &str, b: &str) -> bool {
+        match self.quirks_mode {
+            QuirksMode::Quirks => a.eq_ignore_ascii_case(b),
+            _ => a == b,
+        }
+    }
+}
+
+fn is_element(node: NodeId) -> bool {
+    matches!(arena::get(node).borrow().node_type, NodeType::Element(_))
+}
+
+fn element_name(element: NodeId) -> QualifiedName {
+    arena::get(element).borrow().downcast_ref::<Element>().name.clone()
+}
+
+fn parent_element(node: NodeId) -> Option<NodeId> {
+    arena::get(node).borrow().parent.filter(|parent| is_element(*parent))
+}
+
+fn ancestor_elements(node: NodeId) -> impl Iterator<Item = NodeId> {
+    NodeIterator::new(parent_element(node), |node| node.parent).take_while(|node| is_element(*node))
+}
+
+fn previous_element_siblings(node: NodeId) -> impl Iterator<Item = NodeId> {
+    NodeIterator::new(arena::get(node).borrow().previous_sibling, |node| node.previous_sibling).filter(|node| is_element(*node))
+}
+
+fn next_element_siblings(node: NodeId) -> impl Iterator<Item = NodeId> {
+    NodeIterator::new(arena::get(node).borrow().next_sibling, |node| node.next_sibling).filter(|node| is_element(*node))
+}
+
+// https://html.spec.whatwg.org/multipage/semantics-other.html#case-sensitivity-of-selectors
+/// Check if the local name of a type selector matches the element, html elements are matched
+/// against the lowercased selector.
+fn type_matches(element: NodeId, selector: &LocalName) -> bool {
+    let name = element_name(element);
+
+    match name.is_namespace(Namespace::Html) {
+        true => name.local_name == selector.to_ascii_lowercase(),
+        false => name.local_name == *selector,
+    }
+}
+
+/// Check if the local name of an attribute selector matches the attribute, the names of attributes
+/// on html elements are matched ASCII case-insensitively.
+fn attribute_name_matches(element: NodeId, selector: &str, local_name: &LocalName) -> bool {
+    match element_name(element).is_namespace(Namespace::Html) {
+        true => local_name.as_str().eq_ignore_ascii_case(selector),
+        false => local_name == selector,
+    }
+}
+
+fn namespace_matches(constraint: &NamespaceConstraint, namespace: Option<&Namespace>) -> bool {
+    match constraint {
+        NamespaceConstraint::Any => true,
+        NamespaceConstraint::None => namespace.is_none(),
+        NamespaceConstraint::Specific(constraint) => namespace == Some(constraint),
+    }
+}
+
+fn has_attribute(element: NodeId, local_name: LocalName) -> bool {
+    arena::get(element).borrow().downcast_ref::<Element>().attribute(QualifiedName::new(None, local_name)).is_some()
+}
+
+fn attribute_value(element: NodeId, local_name: LocalName) -> Option<String> {
+    arena::get(element).borrow().downcast_ref::<Element>()
+        .attribute(QualifiedName::new(None, local_name))
+        .map(|attribute| attribute.value.clone())
+}
+
+// https://drafts.csswg.org/selectors-4/#match-a-complex-selector-against-an-element
+pub fn matches_complex(selector: &ComplexSelector, element: NodeId, context: &MatchingContext) -> bool {
+    matches_from(selector, selector.compounds.len() - 1, element, None, context)
+}
+
+/// Match the compound at the index against the element, then the compounds to the left of it
+/// against the elements reached through the combinators.
+///
+/// The anchor is the element a relative selector is relative to, the leftmost compound has to
+/// be reached from it through the leading combinator.
+fn matches_from(selector: &ComplexSelector, index: usize, element: NodeId, anchor: Option<NodeId>, context: &MatchingContext) -> bool {
+    if !matches_compound(&selector.compounds[index], element, context) {
+        return false;
+    }
+
+    if index == 0 {
+        return match (selector.relative, anchor) {
+            (Some(combinator), Some(anchor)) => is_reached_through(anchor, element, combinator),
+            _ => true,
+        };
+    }
+
+    let next = |candidate: NodeId| matches_from(selector, index - 1, candidate, anchor, context);
+
+    match selector.combinators[index - 1] {
+        Combinator::Descendant => ancestor_elements(element).any(next),
+        Combinator::Child => parent_element(element).is_some_and(next),
+        Combinator::NextSibling => previous_element_siblings(element).next().is_some_and(next),
+        Combinator::SubsequentSibling => previous_element_siblings(element).any(next),
+    }
+}
+
+/// Check if the element is reached from the anchor through the combinator.
+fn is_reached_through(anchor: NodeId, element: NodeId, combinator: Combinator) -> bool {
+    match combinator {
+        Combinator::Descendant => ancestor_elements(element).any(|ancestor| ancestor == anchor),
+        Combinator::Child => parent_element(element) == Some(anchor),
+        Combinator::NextSibling => previous_element_siblings(element).next() == Some(anchor),
+        Combinator::SubsequentSibling => previous_element_siblings(element).any(|sibling| sibling == anchor),
+    }
+}
+
+// https://drafts.csswg.org/selectors-4/#relational
+fn matches_has(selectors: &[ComplexSelector], element: NodeId, context: &MatchingContext) -> bool {
+    selectors.iter().any(|selector| {
+        let index = selector.compounds.len() - 1;
+
+        let matches = |candidate: NodeId| {
+            is_element(candidate) && matches_from(selector, index, candidate, Some(element), context)
+        };
+
+        match selector.relative {
+            Some(Combinator::NextSibling | Combinator::SubsequentSibling) => {
+                next_element_siblings(element).any(|sibling| TreeIterator::new(Some(sibling)).any(&matches))
+            },
+            _ => TreeIterator::descendants(element).any(&matches),
+        }
+    })
+}
+
+fn matches_compound(compound: &CompoundSelector, element: NodeId, context: &MatchingContext) -> bool {
+    compound.0.iter().all(|selector| matches_simple(selector, element, context))
+}
+
+fn matches_list(list: &SelectorList, element: NodeId, context: &MatchingContext) -> bool {
+    list.0.iter().any(|selector| matches_complex(selector, element, context))
+}
+
+fn matches_simple(selector: &SimpleSelector, element: NodeId, context: &MatchingContext) -> bool {
+    match selector {
+        SimpleSelector::Type { namespace, local_name } => {
+            let name = element_name(element);
+
+            namespace_matches(namespace, name.namespace.as_ref()) && local_name.as_ref().is_none_or(|local_name| type_matches(element, local_name))
+        },
+        SimpleSelector::Id(id) => {
+            attribute_value(element, atoms::ID).is_some_and(|value| context.case_sensitive_eq(&value, id))
+        },
+        SimpleSelector::Class(class) => {
+            attribute_value(element, atoms::CLASS)
+                .is_some_and(|value| value.split_ascii_whitespace().any(|value| context.case_sensitive_eq(value, class)))
+        },
+        SimpleSelector::Attribute(selector) => matches_attribute(selector, element),
+        SimpleSelector::PseudoClass(pseudo_class) => matches_pseudo_class(pseudo_class, element, context),
+    }
+}
+
+// https://drafts.csswg.org/selectors-4/#attribute-selectors
+fn matches_attribute(selector: &AttributeSelector, element: NodeId) -> bool {
+    let node = arena::get(element);
+    let node = node.borrow();
+
+    node.downcast_ref::<Element>().attributes.iter()
+        .filter(|attribute| {
+            namespace_matches(&selector.namespace, attribute.name.namespace.as_ref())
+                && attribute_name_matches(element, &selector.local_name, &attribute.name.local_name)
+        })
+        .any(|attribute| {
+            let Some((operator, value)) = &selector.operation else {
+                return true;
+            };
+
+            let (actual, value) = match selector.case_insensitive {
+                true => (attribute.value.to_ascii_lowercase(), value.to_ascii_lowercase()),
+                false => (attribute.value.clone(), value.clone()),
+            };
+
+            match operator {
+                AttributeOperator::Equal => actual == value,
+                AttributeOperator::Includes => {
+                    !value.is_empty()
+                        && !value.contains(|c: char| c.is_ascii_whitespace())
+                        && actual.split_ascii_whitespace().any(|actual| actual == value)
+                },
+                AttributeOperator::DashMatch => actual == value || actual.strip_prefix(&value).is_some_and(|rest| rest.starts_with('-')),
+                AttributeOperator::Prefix => !value.is_empty() && actual.starts_with(&value),
+                AttributeOperator::Suffix => !value.is_empty() && actual.ends_with(&value),
+                AttributeOperator::Substring => !value.is_empty() && actual.contains(&value),
+            }
+        })
+}
+
+/// The 1-based index of the element among the siblings it is counted with.
+fn nth_index(siblings: impl Iterator<Item = NodeId>, filter: impl Fn(NodeId) -> bool) -> i32 {
+    siblings.filter(|sibling| filter(*sibling)).count() as i32 + 1
+}
+
+fn matches_nth(nth: &Nth, element: NodeId, last: bool, filter: impl Fn(NodeId) -> bool) -> bool {
+    match last {
+        true => nth.matches(nth_index(next_element_siblings(element), filter)),
+        false => nth.matches(nth_index(previous_element_siblings(element), filter)),
+    }
+}
+
+fn matches_nth_child(nth: &Nth, of: Option<&SelectorList>, element: NodeId, last: bool, context: &MatchingContext) -> bool {
+    match of {
+        Some(of) => {
+            matches_list(of, element, context) && matches_nth(nth, element, last, |sibling| matches_list(of, sibling, context))
+        },
+        None => matches_nth(nth, element, last, |_| true),
+    }
+}
+
+fn matches_nth_of_type(nth: &Nth, element: NodeId, last: bool) -> bool {
+    let name = element_name(element);
+
+    matches_nth(nth, element, last, |sibling| {
+        let sibling = element_name(sibling);
+
+        sibling.namespace == name.namespace && sibling.local_name == name.local_name
+    })
+}
+
+// https://html.spec.whatwg.org/multipage/semantics-other.html#concept-element-disabled
+/// Check if a form control is disabled by its own attribute or by a disabled ancestor.
+fn is_disabled(element: NodeId) -> bool {
+    let name = element_name(element);
+
+    if name.is_html(atoms::OPTGROUP) {
+        return has_attribute(element, atoms::DISABLED);
+    }
+
+    if name.is_html(atoms::OPTION) {
+        return has_attribute(element, atoms::DISABLED)
+            || parent_element(element).is_some_and(|parent| element_name(parent).is_html(atoms::OPTGROUP) && has_attribute(parent, atoms::DISABLED));
+    }
+
+    has_attribute(element, atoms::DISABLED) || is_in_disabled_fieldset(element)
+}
+
+// https://html.spec.whatwg.org/multipage/form-elements.html#concept-fieldset-disabled
+fn is_in_disabled_fieldset(element: NodeId) -> bool {
+    let mut child = element;
+
+    for ancestor in ancestor_elements(element) {
+        if element_name(ancestor).is_html(atoms::FIELDSET) && has_attribute(ancestor, atoms::DISABLED) {
+            let first_legend = arena::get(ancestor).borrow().children()
+                .find(|child| is_element(*child) && element_name(*child).is_html(atoms::LEGEND));
+
+            if first_legend != Some(child) {
+                return true;
+            }
+        }
+
+        child = ancestor;
+    }
+
+    false
+}
+
+fn is_form_control(name: QualifiedName) -> bool {
+    [atoms::BUTTON, atoms::INPUT, atoms::SELECT, atoms::TEXTAREA, atoms::OPTGROUP, atoms::OPTION, atoms::FIELDSET]
+        .into_iter()
+        .any(|local_name| name.is_html(local_name))
+}
+
+fn is_requirable(name: QualifiedName) -> bool {
+    [atoms::INPUT, atoms::SELECT, atoms::TEXTAREA].into_iter().any(|local_name| name.is_html(local_name))
+}
+
+// https://html.spec.whatwg.org/multipage/semantics-other.html#pseudo-classes
+fn matches_pseudo_class(pseudo_class: &PseudoClass, element: NodeId, context: &MatchingContext) -> bool {
+    let first = Nth { a: 0, b: 1 };
+
+    match pseudo_class {
+        PseudoClass::Is(list) | PseudoClass::Where(list) => matches_list(list, element, context),
+        PseudoClass::Not(list) => !matches_list(list, element, context),
+        PseudoClass::Has(selectors) => matches_has(selectors, element, context),
+        PseudoClass::NthChild(nth, of) => matches_nth_child(nth, of.as_ref(), element, false, context),
+        PseudoClass::NthLastChild(nth, of) => matches_nth_child(nth, of.as_ref(), element, true, context),
+        PseudoClass::NthOfType(nth) => matches_nth_of_type(nth, element, false),
+        PseudoClass::NthLastOfType(nth) => matches_nth_of_type(nth, element, true),
+        PseudoClass::FirstChild => previous_element_siblings(element).next().is_none(),
+        PseudoClass::LastChild => next_element_siblings(element).next().is_none(),
+        PseudoClass::OnlyChild => previous_element_siblings(element).chain(next_element_siblings(element)).next().is_none(),
+        PseudoClass::FirstOfType => matches_nth_of_type(&first, element, false),
+        PseudoClass::LastOfType => matches_nth_of_type(&first, element, true),
+        PseudoClass::OnlyOfType => matches_nth_of_type(&first, element, false) && matches_nth_of_type(&first, element, true),
+        PseudoClass::Empty => {
+            arena::get(element).borrow().children().all(|child| match &arena::get(child).borrow().node_type {
+                NodeType::Comment(_) | NodeType::ProcessingInstruction(_) => true,
+                NodeType::Text(text) => text.character_data.data.is_empty(),
+                _ => false,
+            })
+        },
+        PseudoClass::Root => arena::get(element).borrow().parent.is_some_and(|parent| matches!(arena::get(parent).borrow().node_type, NodeType::Document(_))),
+        PseudoClass::Scope => match context.scope {
+            Some(scope) => scope == element,
+            None => matches_pseudo_class(&PseudoClass::Root, element, context),
+        },
+        PseudoClass::Defined => arena::get(element).borrow().downcast_ref::<Element>().is_defined(),
+        PseudoClass::AnyLink => {
+            let name = element_name(element);
+
+            (name.is_html(atoms::A) || name.is_html(atoms::AREA)) && has_attribute(element, atoms::HREF)
+        },
+        // NOTE: checkedness is not tracked separately from the content attributes yet.
+        PseudoClass::Checked => {
+            let name = element_name(element);
+
+            let checkable = attribute_value(element, atoms::TYPE)
+                .is_some_and(|value| value.eq_ignore_ascii_case("checkbox") || value.eq_ignore_ascii_case("radio"));
+
+            (name.is_html(atoms::INPUT) && checkable && has_attribute(element, atoms::CHECKED))
+                || (name.is_html(atoms::OPTION) && has_attribute(element, atoms::SELECTED))
+        },
+        PseudoClass::Disabled => is_form_control(element_name(element)) && is_disabled(element),
+        PseudoClass::Enabled => is_form_control(element_name(element)) && !is_disabled(element),
+        PseudoClass::Required => is_requirable(element_name(element)) && has_attribute(element, atoms::REQUIRED),
+        PseudoClass::Optional => is_requirable(element_name(element)) && !has_attribute(element, atoms::REQUIRED),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_html;
+    use crate::dom::Dom;
+    use crate::dom::node::element::Element;
+    use crate::dom::testing::{element, create_element};
+
+    const XLINK: &str = "http://www.w3.org/1999/xlink";
+
+    const DOCUMENT: &str = "<div id=a class='x y'>\
+        <p id=b lang=en-US title='one two'>1</p>\
+        <p id=c data-x=abc></p>\
+        <span id=d><em id=e>2</em></span>\
+        <p id=f></p>\
+        </div>\
+        <a id=g href=#></a><input id=h type=checkbox checked><input id=i disabled>";
+
+    fn ids(dom: &Dom, selectors: &str) -> Vec<String> {
+        dom.query_selector_all(selectors)
+            .expect("valid selectors")
+            .into_iter()
+            .filter_map(Element::id)
+            .collect()
+    }
+
+    #[test]
+    fn compound_and_combinators() {
+        let dom = parse_html(DOCUMENT);
+
+        assert_eq!(ids(&dom, "P"), vec!["b", "c", "f"]);
+        assert_eq!(ids(&dom, "div.x.y > p#c, em"), vec!["c", "e"]);
+        assert_eq!(ids(&dom, "div em"), vec!["e"]);
+        assert_eq!(ids(&dom, "div > em"), Vec::<String>::new());
+        assert_eq!(ids(&dom, "#b + p"), vec!["c"]);
+        assert_eq!(ids(&dom, "#c ~ *"), vec!["d", "f"]);
+        assert_eq!(ids(&dom, ".z"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn attributes() {
+        let dom = parse_html(DOCUMENT);
+
+        assert_eq!(ids(&dom, "[LANG|=en]"), vec!["b"]);
+        assert_eq!(ids(&dom, "[title~=two]"), vec!["b"]);
+        assert_eq!(ids(&dom, "[data-x^=a][data-x$=c][data-x*=b]"), vec!["c"]);
+        assert_eq!(ids(&dom, "[data-x=ABC]"), Vec::<String>::new());
+        assert_eq!(ids(&dom, "[data-x=ABC i]"), vec!["c"]);
+        assert_eq!(ids(&dom, "p[title]"), vec!["b"]);
+    }
+
+    #[test]
+    fn attributes_without_a_namespace() {
+        let dom = parse_html("<a id=a href=#a></a><svg><a id=b xlink:href=#b></a><a id=c href=#c></a></svg>");
+
+        assert_eq!(ids(&dom, "[href]"), vec!["a", "c"]);
+        assert_eq!(ids(&dom, "[href^='#']"), vec!["a", "c"]);
+        assert_eq!(ids(&dom, "[*|href]"), vec!["a", "b", "c"]);
+        assert_eq!(ids(&dom, "[|href]"), vec!["a", "c"]);
+
+        let mut dom = Dom::new();
+
+        let a = create_element(&mut dom, "a");
+
+        Element::set_attribute_ns(a, Some(XLINK), "xlink:href", "#a").expect("valid name");
+
+        assert_eq!(Element::matches(a, "[href]"), Ok(false));
+        assert_eq!(Element::matches(a, "[*|href]"), Ok(true));
+    }
+
+    #[test]
+    fn structural_pseudo_classes() {
+        let dom = parse_html(DOCUMENT);
+
+        assert_eq!(ids(&dom, "div > :first-child, div > :last-child"), vec!["b", "f"]);
+        assert_eq!(ids(&dom, "div > :nth-child(2n+1)"), vec!["b", "d"]);
+        assert_eq!(ids(&dom, "div > :nth-last-child(1 of p)"), vec!["f"]);
+        assert_eq!(ids(&dom, "p:nth-of-type(2), span:only-of-type"), vec!["c", "d"]);
+        assert_eq!(ids(&dom, "p:empty"), vec!["c", "f"]);
+        assert_eq!(ids(&dom, ":root").len(), 0);
+        assert_eq!(dom.query_selector_all(":root").expect("valid").len(), 1);
+    }
+
+    #[test]
+    fn logical_pseudo_classes() {
+        let dom = parse_html(DOCUMENT);
+
+        assert_eq!(ids(&dom, "div > :not(p)"), vec!["d"]);
+        assert_eq!(ids(&dom, ":is(span, #f, :unknown)"), vec!["d", "f"]);
+        assert_eq!(ids(&dom, ":where(em)"), vec!["e"]);
+        assert_eq!(ids(&dom, "div:has(> span em)"), vec!["a"]);
+        assert_eq!(ids(&dom, "p:has(+ span)"), vec!["c"]);
+    }
+
+    #[test]
+    fn state_pseudo_classes() {
+        let dom = parse_html(DOCUMENT);
+
+        assert_eq!(ids(&dom, ":any-link"), vec!["g"]);
+        assert_eq!(ids(&dom, ":checked"), vec!["h"]);
+        assert_eq!(ids(&dom, "input:disabled"), vec!["i"]);
+        assert_eq!(ids(&dom, "input:enabled"), vec!["h"]);
+    }
+
+    #[test]
+    fn matches_and_closest() {
+        let dom = parse_html(DOCUMENT);
+
+        let a = element(&dom, "a");
+        let e = element(&dom, "e");
+
+        assert_eq!(Element::matches(e, "div em"), Ok(true));
+        assert_eq!(Element::matches(e, ":scope"), Ok(true));
+        assert_eq!(Element::closest(e, "div"), Ok(Some(a)));
+        assert_eq!(Element::closest(e, "em"), Ok(Some(e)));
+        assert_eq!(Element::closest(e, "p"), Ok(None));
+        assert!(Element::matches(e, "em[").is_err());
+
+        assert_eq!(dom.query_selector("em").expect("valid"), Some(e));
+        assert_eq!(dom.query_selector("table").expect("valid"), None);
+    }
+}
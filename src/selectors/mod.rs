@@ -0,0 +1,183 @@
+mod parser;
+mod matching;
+
+pub use parser::SelectorParseError;
+pub use matching::MatchingContext;
+
+use crate::dom::NodeId;
+use crate::names::{Namespace, LocalName};
+
+
+// https://drafts.csswg.org/selectors-4/#selector-list
+/// A comma separated list of complex selectors, an element matches the list if it matches any of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorList(pub Vec<ComplexSelector>);
+
+impl SelectorList {
+    // https://drafts.csswg.org/selectors-4/#parse-a-selector
+    /// Parse a selector list, the input is not allowed to contain a relative selector or a pseudo-element.
+    pub fn parse(input: &str) -> Result<SelectorList, SelectorParseError> {
+        parser::Parser::new(input).parse()
+    }
+
+    // https://drafts.csswg.org/selectors-4/#match-a-selector-against-an-element
+    /// Check if the element matches any selector in the list, with the element as the scoping root.
+    pub fn matches(&self, element: NodeId) -> bool {
+        self.matches_with_context(element, &MatchingContext::new(element, Some(element)))
+    }
+
+    pub fn matches_with_context(&self, element: NodeId, context: &MatchingContext) -> bool {
+        self.0.iter().any(|selector| matching::matches_complex(selector, element, context))
+    }
+}
+
+// https://drafts.csswg.org/selectors-4/#complex
+/// A sequence of compound selectors separated by combinators.
+///
+/// The compounds are stored left to right, `combinators[i]` sits between `compounds[i]` and
+/// `compounds[i + 1]`, matching starts from the last compound which is the subject of the selector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComplexSelector {
+    pub compounds: Vec<CompoundSelector>,
+    pub combinators: Vec<Combinator>,
+
+    /// The leading combinator of a relative selector in `:has()`, relative to the anchor element.
+    pub relative: Option<Combinator>,
+}
+
+impl ComplexSelector {
+    /// The compound selector which is matched against the element itself.
+    pub fn subject(&self) -> &CompoundSelector {
+        &self.compounds[self.compounds.len() - 1]
+    }
+}
+
+// https://drafts.csswg.org/selectors-4/#combinators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combinator {
+    /// `A B`
+    Descendant,
+
+    /// `A > B`
+    Child,
+
+    /// `A + B`
+    NextSibling,
+
+    /// `A ~ B`
+    SubsequentSibling,
+}
+
+// https://drafts.csswg.org/selectors-4/#compound
+/// A sequence of simple selectors which all have to match the same element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompoundSelector(pub Vec<SimpleSelector>);
+
+// https://drafts.csswg.org/selectors-4/#simple
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimpleSelector {
+    /// A type selector, or the universal selector when there is no local name.
+    Type {
+        namespace: NamespaceConstraint,
+        local_name: Option<LocalName>,
+    },
+
+    Id(String),
+
+    Class(String),
+
+    Attribute(AttributeSelector),
+
+    PseudoClass(PseudoClass),
+}
+
+// https://drafts.csswg.org/selectors-4/#type-nmsp
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NamespaceConstraint {
+    /// No namespace prefix or `*|`, matches elements in any namespace.
+    Any,
+
+    /// `|`, matches elements without a namespace.
+    None,
+
+    Specific(Namespace),
+}
+
+// https://drafts.csswg.org/selectors-4/#attribute-selectors
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeSelector {
+    pub local_name: String,
+    pub namespace: NamespaceConstraint,
+    pub operation: Option<(AttributeOperator, String)>,
+
+    /// Set by the `i` flag, the value is compared ASCII case-insensitively.
+    pub case_insensitive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeOperator {
+    /// `[att=val]`
+    Equal,
+
+    /// `[att~=val]`
+    Includes,
+
+    /// `[att|=val]`
+    DashMatch,
+
+    /// `[att^=val]`
+    Prefix,
+
+    /// `[att$=val]`
+    Suffix,
+
+    /// `[att*=val]`
+    Substring,
+}
+
+// https://drafts.csswg.org/css-syntax-3/#anb-microsyntax
+/// The `An+B` argument of the child-indexed pseudo-classes, matches every index `A*n + B` for `n >= 0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nth {
+    pub a: i32,
+    pub b: i32,
+}
+
+impl Nth {
+    /// Check if the 1-based index matches the pattern.
+    pub fn matches(&self, index: i32) -> bool {
+        match self.a {
+            0 => index == self.b,
+            a => (index - self.b) % a == 0 && (index - self.b) / a >= 0,
+        }
+    }
+}
+
+// https://drafts.csswg.org/selectors-4/#pseudo-classes
+#[derive(Debug, Clone, PartialEq)]
+pub enum PseudoClass {
+    Is(SelectorList),
+    Where(SelectorList),
+    Not(SelectorList),
+    Has(Vec<ComplexSelector>),
+    NthChild(Nth, Option<SelectorList>),
+    NthLastChild(Nth, Option<SelectorList>),
+    NthOfType(Nth),
+    NthLastOfType(Nth),
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    FirstOfType,
+    LastOfType,
+    OnlyOfType,
+    Empty,
+    Root,
+    Scope,
+    Defined,
+    AnyLink,
+    Checked,
+    Disabled,
+    Enabled,
+    Required,
+    Optional,
+}
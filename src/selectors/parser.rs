@@ -0,0 +1,741 @@
+use super::{
+    SelectorList, ComplexSelector, CompoundSelector, SimpleSelector, Combinator, NamespaceConstraint, AttributeSelector,
+    AttributeOperator, PseudoClass, Nth,
+};
+
+use crate::names::Atom;
+
+use std::fmt;
+
+
+/// An error encountered while parsing a selector, the position is an offset in characters into the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorParseError {
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for SelectorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for SelectorParseError {}
+
+// https://drafts.csswg.org/css-syntax-3/#whitespace
+fn is_whitespace(character: char) -> bool {
+    matches!(character, ' ' | '\t' | '\n' | '\r' | '\x0C')
+}
+
+// https://drafts.csswg.org/css-syntax-3/#ident-start-code-point
+fn is_name_start(character: char) -> bool {
+    character.is_ascii_alphabetic() || character == '_' || !character.is_ascii()
+}
+
+// https://drafts.csswg.org/css-syntax-3/#ident-code-point
+fn is_name(character: char) -> bool {
+    is_name_start(character) || character.is_ascii_digit() || character == '-'
+}
+
+pub struct Parser {
+    chars: Vec<char>,
+    position: usize,
+
+    /// Set while parsing the argument of `:has()`, which is not allowed to nest.
+    in_has: bool,
+}
+
+impl Parser {
+    pub fn new(input: &str) -> Parser {
+        Parser {
+            chars: input.chars().collect(),
+            position: 0,
+            in_has: false,
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<SelectorList, SelectorParseError> {
+        let list = self.parse_selector_list()?;
+
+        match self.peek() {
+            Some(_) => Err(self.error("unexpected character")),
+            None => Ok(list),
+        }
+    }
+
+    fn error(&self, message: &'static str) -> SelectorParseError {
+        SelectorParseError {
+            position: self.position,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn consume(&mut self, character: char) -> bool {
+        let matches = self.peek() == Some(character);
+
+        if matches {
+            self.position += 1;
+        }
+
+        matches
+    }
+
+    fn expect(&mut self, character: char, message: &'static str) -> Result<(), SelectorParseError> {
+        match self.consume(character) {
+            true => Ok(()),
+            false => Err(self.error(message)),
+        }
+    }
+
+    /// Skip whitespace and comments, returning true if anything was skipped.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(character), _) if is_whitespace(character) => self.position += 1,
+                (Some('/'), Some('*')) => {
+                    self.position += 2;
+
+                    while self.peek().is_some() && !(self.peek() == Some('*') && self.peek_at(1) == Some('/')) {
+                        self.position += 1;
+                    }
+
+                    self.position = (self.position + 2).min(self.chars.len());
+                },
+                _ => break,
+            }
+        }
+
+        self.position != start
+    }
+
+    // https://drafts.csswg.org/css-syntax-3/#starts-with-a-valid-escape
+    fn is_valid_escape(&self, offset: usize) -> bool {
+        self.peek_at(offset) == Some('\\') && self.peek_at(offset + 1) != Some('\n')
+    }
+
+    // https://drafts.csswg.org/css-syntax-3/#would-start-an-identifier
+    fn starts_ident(&self) -> bool {
+        match self.peek() {
+            Some('-') => self.peek_at(1).is_some_and(|character| is_name_start(character) || character == '-') || self.is_valid_escape(1),
+            Some('\\') => self.is_valid_escape(0),
+            Some(character) => is_name_start(character),
+            None => false,
+        }
+    }
+
+    // https://drafts.csswg.org/css-syntax-3/#consume-escaped-code-point
+    fn consume_escape(&mut self) -> char {
+        let Some(character) = self.peek() else {
+            return '\u{FFFD}';
+        };
+
+        self.position += 1;
+
+        if !character.is_ascii_hexdigit() {
+            return character;
+        }
+
+        let mut value = character.to_digit(16).unwrap_or_default();
+
+        for _ in 0..5 {
+            match self.peek().and_then(|character| character.to_digit(16)) {
+                Some(digit) => {
+                    value = value * 16 + digit;
+
+                    self.position += 1;
+                },
+                None => break,
+            }
+        }
+
+        if self.peek().is_some_and(is_whitespace) {
+            self.position += 1;
+        }
+
+        match value {
+            0 => '\u{FFFD}',
+            _ => char::from_u32(value).unwrap_or('\u{FFFD}'),
+        }
+    }
+
+    // https://drafts.csswg.org/css-syntax-3/#consume-name
+    fn parse_ident(&mut self) -> Result<String, SelectorParseError> {
+        if !self.starts_ident() {
+            return Err(self.error("expected identifier"));
+        }
+
+        let mut ident = String::new();
+
+        loop {
+            match self.peek() {
+                Some(character) if is_name(character) => {
+                    ident.push(character);
+
+                    self.position += 1;
+                },
+                Some('\\') if self.is_valid_escape(0) => {
+                    self.position += 1;
+
+                    ident.push(self.consume_escape());
+                },
+                _ => return Ok(ident),
+            }
+        }
+    }
+
+    // https://drafts.csswg.org/css-syntax-3/#consume-string-token
+    fn parse_string(&mut self, quote: char) -> Result<String, SelectorParseError> {
+        let mut string = String::new();
+
+        loop {
+            match self.peek() {
+                Some(character) if character == quote => {
+                    self.position += 1;
+
+                    return Ok(string);
+                },
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some('\\') => {
+                    self.position += 1;
+
+                    match self.peek() {
+                        Some('\n') => self.position += 1,
+                        Some(_) => string.push(self.consume_escape()),
+                        None => {},
+                    }
+                },
+                Some(character) => {
+                    string.push(character);
+
+                    self.position += 1;
+                },
+            }
+        }
+    }
+
+    /// Parse a comma separated list of complex selectors, stopping before a closing parenthesis.
+    fn parse_selector_list(&mut self) -> Result<SelectorList, SelectorParseError> {
+        let mut selectors = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            selectors.push(self.parse_complex(false)?);
+
+            if !self.consume(',') {
+                return Ok(SelectorList(selectors));
+            }
+        }
+    }
+
+    // https://drafts.csswg.org/selectors-4/#forgiving-selector
+    /// Parse a selector list where invalid selectors are dropped instead of failing the whole list.
+    fn parse_forgiving_selector_list(&mut self) -> SelectorList {
+        let mut selectors = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            let start = self.position;
+
+            match self.parse_complex(false) {
+                Ok(selector) if matches!(self.peek(), Some(',' | ')') | None) => selectors.push(selector),
+                _ => {
+                    self.position = start;
+
+                    self.skip_argument();
+                },
+            }
+
+            if !self.consume(',') {
+                return SelectorList(selectors);
+            }
+        }
+    }
+
+    /// Skip to the next comma or closing parenthesis that is not nested in a block or string.
+    fn skip_argument(&mut self) {
+        let mut depth = 0usize;
+
+        while let Some(character) = self.peek() {
+            match character {
+                ',' | ')' if depth == 0 => return,
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth = depth.saturating_sub(1),
+                '\\' => self.position += 1,
+                '"' | '\'' => {
+                    self.position += 1;
+
+                    let _ = self.parse_string(character);
+
+                    continue;
+                },
+                _ => {},
+            }
+
+            self.position += 1;
+        }
+    }
+
+    // https://drafts.csswg.org/selectors-4/#typedef-relative-selector-list
+    fn parse_relative_selector_list(&mut self) -> Result<Vec<ComplexSelector>, SelectorParseError> {
+        let mut selectors = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            selectors.push(self.parse_complex(true)?);
+
+            if !self.consume(',') {
+                return Ok(selectors);
+            }
+        }
+    }
+
+    fn parse_combinator(&mut self) -> Option<Combinator> {
+        let combinator = match self.peek() {
+            Some('>') => Combinator::Child,
+            Some('+') => Combinator::NextSibling,
+            Some('~') => Combinator::SubsequentSibling,
+            _ => return None,
+        };
+
+        self.position += 1;
+
+        Some(combinator)
+    }
+
+    // https://drafts.csswg.org/selectors-4/#typedef-complex-selector
+    fn parse_complex(&mut self, relative: bool) -> Result<ComplexSelector, SelectorParseError> {
+        let relative = relative.then(|| {
+            let combinator = self.parse_combinator().unwrap_or(Combinator::Descendant);
+
+            self.skip_whitespace();
+
+            combinator
+        });
+
+        let mut compounds = vec![self.parse_compound()?];
+        let mut combinators = Vec::new();
+
+        loop {
+            let whitespace = self.skip_whitespace();
+
+            let combinator = match self.parse_combinator() {
+                Some(combinator) => combinator,
+                None if whitespace && !matches!(self.peek(), Some(',' | ')') | None) => Combinator::Descendant,
+                None => break,
+            };
+
+            self.skip_whitespace();
+
+            combinators.push(combinator);
+
+            compounds.push(self.parse_compound()?);
+        }
+
+        Ok(ComplexSelector {
+            compounds,
+            combinators,
+            relative,
+        })
+    }
+
+    // https://drafts.csswg.org/selectors-4/#typedef-compound-selector
+    fn parse_compound(&mut self) -> Result<CompoundSelector, SelectorParseError> {
+        let mut selectors = Vec::new();
+
+        if let Some(selector) = self.parse_type_selector()? {
+            selectors.push(selector);
+        }
+
+        loop {
+            let selector = match self.peek() {
+                Some('#') => {
+                    self.position += 1;
+
+                    SimpleSelector::Id(self.parse_ident()?)
+                },
+                Some('.') => {
+                    self.position += 1;
+
+                    SimpleSelector::Class(self.parse_ident()?)
+                },
+                Some('[') => {
+                    self.position += 1;
+
+                    SimpleSelector::Attribute(self.parse_attribute_selector()?)
+                },
+                Some(':') => {
+                    self.position += 1;
+
+                    SimpleSelector::PseudoClass(self.parse_pseudo_class()?)
+                },
+                _ => break,
+            };
+
+            selectors.push(selector);
+        }
+
+        match selectors.is_empty() {
+            true => Err(self.error("expected selector")),
+            false => Ok(CompoundSelector(selectors)),
+        }
+    }
+
+    /// Parse the namespace prefix of a type or attribute selector, if there is one.
+    ///
+    /// There is no way to declare namespace prefixes, so only `*|` and `|` are valid prefixes.
+    fn parse_namespace_prefix(&mut self) -> Result<Option<NamespaceConstraint>, SelectorParseError> {
+        let is_prefix = |parser: &Parser, offset: usize| {
+            parser.peek_at(offset) == Some('|') && parser.peek_at(offset + 1) != Some('=')
+        };
+
+        if self.peek() == Some('*') && is_prefix(self, 1) {
+            self.position += 2;
+
+            return Ok(Some(NamespaceConstraint::Any));
+        }
+
+        if is_prefix(self, 0) {
+            self.position += 1;
+
+            return Ok(Some(NamespaceConstraint::None));
+        }
+
+        if self.starts_ident() {
+            let start = self.position;
+
+            self.parse_ident()?;
+
+            let prefixed = is_prefix(self, 0);
+
+            self.position = start;
+
+            if prefixed {
+                return Err(self.error("undeclared namespace prefix"));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // https://drafts.csswg.org/selectors-4/#typedef-type-selector
+    fn parse_type_selector(&mut self) -> Result<Option<SimpleSelector>, SelectorParseError> {
+        let prefix = self.parse_namespace_prefix()?;
+
+        let local_name = match self.peek() {
+            Some('*') => {
+                self.position += 1;
+
+                None
+            },
+            _ if self.starts_ident() => Some(Atom::from(self.parse_ident()?.as_str())),
+            _ if prefix.is_some() => return Err(self.error("expected type selector")),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(SimpleSelector::Type {
+            namespace: prefix.unwrap_or(NamespaceConstraint::Any),
+            local_name,
+        }))
+    }
+
+    // https://drafts.csswg.org/selectors-4/#typedef-attribute-selector
+    fn parse_attribute_selector(&mut self) -> Result<AttributeSelector, SelectorParseError> {
+        self.skip_whitespace();
+
+        let namespace = self.parse_namespace_prefix()?.unwrap_or(NamespaceConstraint::None);
+        let local_name = self.parse_ident()?;
+
+        self.skip_whitespace();
+
+        let operator = match (self.peek(), self.peek_at(1)) {
+            (Some('='), _) => Some((AttributeOperator::Equal, 1)),
+            (Some('~'), Some('=')) => Some((AttributeOperator::Includes, 2)),
+            (Some('|'), Some('=')) => Some((AttributeOperator::DashMatch, 2)),
+            (Some('^'), Some('=')) => Some((AttributeOperator::Prefix, 2)),
+            (Some('$'), Some('=')) => Some((AttributeOperator::Suffix, 2)),
+            (Some('*'), Some('=')) => Some((AttributeOperator::Substring, 2)),
+            (Some(']'), _) => None,
+            _ => return Err(self.error("expected attribute operator")),
+        };
+
+        let mut case_insensitive = false;
+
+        let operation = match operator {
+            Some((operator, length)) => {
+                self.position += length;
+
+                self.skip_whitespace();
+
+                let value = match self.peek() {
+                    Some(quote @ ('"' | '\'')) => {
+                        self.position += 1;
+
+                        self.parse_string(quote)?
+                    },
+                    _ => self.parse_ident()?,
+                };
+
+                self.skip_whitespace();
+
+                if self.starts_ident() {
+                    case_insensitive = match self.parse_ident()?.to_ascii_lowercase().as_str() {
+                        "i" => true,
+                        "s" => false,
+                        _ => return Err(self.error("invalid attribute selector modifier")),
+                    };
+
+                    self.skip_whitespace();
+                }
+
+                Some((operator, value))
+            },
+            None => None,
+        };
+
+        self.expect(']', "expected ']'")?;
+
+        Ok(AttributeSelector {
+            local_name,
+            namespace,
+            operation,
+            case_insensitive,
+        })
+    }
+
+    // https://drafts.csswg.org/selectors-4/#pseudo-classes
+    fn parse_pseudo_class(&mut self) -> Result<PseudoClass, SelectorParseError> {
+        if self.peek() == Some(':') {
+            return Err(self.error("pseudo-elements are not supported"));
+        }
+
+        let name = self.parse_ident()?.to_ascii_lowercase();
+
+        if !self.consume('(') {
+            return match name.as_str() {
+                "first-child" => Ok(PseudoClass::FirstChild),
+                "last-child" => Ok(PseudoClass::LastChild),
+                "only-child" => Ok(PseudoClass::OnlyChild),
+                "first-of-type" => Ok(PseudoClass::FirstOfType),
+                "last-of-type" => Ok(PseudoClass::LastOfType),
+                "only-of-type" => Ok(PseudoClass::OnlyOfType),
+                "empty" => Ok(PseudoClass::Empty),
+                "root" => Ok(PseudoClass::Root),
+                "scope" => Ok(PseudoClass::Scope),
+                "defined" => Ok(PseudoClass::Defined),
+                "any-link" | "link" => Ok(PseudoClass::AnyLink),
+                "checked" => Ok(PseudoClass::Checked),
+                "disabled" => Ok(PseudoClass::Disabled),
+                "enabled" => Ok(PseudoClass::Enabled),
+                "required" => Ok(PseudoClass::Required),
+                "optional" => Ok(PseudoClass::Optional),
+                _ => Err(self.error("unknown pseudo-class")),
+            };
+        }
+
+        self.skip_whitespace();
+
+        let pseudo_class = match name.as_str() {
+            "is" => PseudoClass::Is(self.parse_forgiving_selector_list()),
+            "where" => PseudoClass::Where(self.parse_forgiving_selector_list()),
+            "not" => PseudoClass::Not(self.parse_selector_list()?),
+            "has" if self.in_has => return Err(self.error(":has() can not be nested")),
+            "has" => {
+                self.in_has = true;
+
+                let selectors = self.parse_relative_selector_list();
+
+                self.in_has = false;
+
+                PseudoClass::Has(selectors?)
+            },
+            "nth-child" => {
+                let nth = self.parse_nth()?;
+
+                PseudoClass::NthChild(nth, self.parse_of_selector()?)
+            },
+            "nth-last-child" => {
+                let nth = self.parse_nth()?;
+
+                PseudoClass::NthLastChild(nth, self.parse_of_selector()?)
+            },
+            "nth-of-type" => PseudoClass::NthOfType(self.parse_nth()?),
+            "nth-last-of-type" => PseudoClass::NthLastOfType(self.parse_nth()?),
+            _ => return Err(self.error("unknown pseudo-class")),
+        };
+
+        self.skip_whitespace();
+
+        self.expect(')', "expected ')'")?;
+
+        Ok(pseudo_class)
+    }
+
+    /// Parse the optional `of S` part of `:nth-child()` and `:nth-last-child()`.
+    fn parse_of_selector(&mut self) -> Result<Option<SelectorList>, SelectorParseError> {
+        let start = self.position;
+
+        if !self.skip_whitespace() || !self.starts_ident() {
+            self.position = start;
+
+            return Ok(None);
+        }
+
+        match self.parse_ident()?.eq_ignore_ascii_case("of") && self.skip_whitespace() {
+            true => self.parse_selector_list().map(Some),
+            false => Err(self.error("expected 'of'")),
+        }
+    }
+
+    fn parse_integer(&mut self) -> Option<i32> {
+        let start = self.position;
+
+        let mut value = 0i32;
+
+        while let Some(digit) = self.peek().and_then(|character| character.to_digit(10)) {
+            value = value.saturating_mul(10).saturating_add(digit as i32);
+
+            self.position += 1;
+        }
+
+        (self.position != start).then_some(value)
+    }
+
+    // https://drafts.csswg.org/css-syntax-3/#anb-microsyntax
+    fn parse_nth(&mut self) -> Result<Nth, SelectorParseError> {
+        if self.starts_ident() {
+            let start = self.position;
+
+            match self.parse_ident()?.to_ascii_lowercase().as_str() {
+                "odd" => return Ok(Nth { a: 2, b: 1 }),
+                "even" => return Ok(Nth { a: 2, b: 0 }),
+                _ => self.position = start,
+            }
+        }
+
+        let sign = match self.peek() {
+            Some('-') => {
+                self.position += 1;
+
+                -1
+            },
+            Some('+') => {
+                self.position += 1;
+
+                1
+            },
+            _ => 1,
+        };
+
+        let coefficient = self.parse_integer();
+
+        if !matches!(self.peek(), Some('n' | 'N')) {
+            return match coefficient {
+                Some(b) => Ok(Nth { a: 0, b: sign * b }),
+                None => Err(self.error("expected An+B")),
+            };
+        }
+
+        self.position += 1;
+
+        let a = sign * coefficient.unwrap_or(1);
+        let start = self.position;
+
+        self.skip_whitespace();
+
+        let sign = match self.peek() {
+            Some('-') => -1,
+            Some('+') => 1,
+            _ => {
+                self.position = start;
+
+                return Ok(Nth { a, b: 0 });
+            },
+        };
+
+        self.position += 1;
+
+        self.skip_whitespace();
+
+        match self.parse_integer() {
+            Some(b) => Ok(Nth { a, b: sign * b }),
+            None => Err(self.error("expected An+B")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nth(input: &str) -> Result<Nth, SelectorParseError> {
+        Parser::new(input).parse_nth()
+    }
+
+    #[test]
+    fn complex_selectors() {
+        let list = SelectorList::parse("div > p.a#b[c], *").expect("valid");
+
+        assert_eq!(list.0.len(), 2);
+        assert_eq!(list.0[0].combinators, vec![Combinator::Child]);
+        assert_eq!(list.0[0].subject().0.len(), 4);
+        assert_eq!(list.0[0].subject().0[1], SimpleSelector::Class(String::from("a")));
+        assert_eq!(list.0[0].subject().0[2], SimpleSelector::Id(String::from("b")));
+
+        let list = SelectorList::parse("a /* comment */ b ~ c + d").expect("valid");
+
+        assert_eq!(list.0[0].combinators, vec![Combinator::Descendant, Combinator::SubsequentSibling, Combinator::NextSibling]);
+    }
+
+    #[test]
+    fn attribute_selectors() {
+        let list = SelectorList::parse("[lang|='en' i]").expect("valid");
+
+        let SimpleSelector::Attribute(attribute) = &list.0[0].subject().0[0] else {
+            panic!("expected attribute selector");
+        };
+
+        assert_eq!(attribute.local_name, "lang");
+        assert_eq!(attribute.operation, Some((AttributeOperator::DashMatch, String::from("en"))));
+        assert!(attribute.case_insensitive);
+    }
+
+    #[test]
+    fn an_plus_b() {
+        assert_eq!(nth("odd"), Ok(Nth { a: 2, b: 1 }));
+        assert_eq!(nth("EVEN"), Ok(Nth { a: 2, b: 0 }));
+        assert_eq!(nth("3"), Ok(Nth { a: 0, b: 3 }));
+        assert_eq!(nth("-n+3"), Ok(Nth { a: -1, b: 3 }));
+        assert_eq!(nth("2n + 1"), Ok(Nth { a: 2, b: 1 }));
+        assert_eq!(nth("-2n-1"), Ok(Nth { a: -2, b: -1 }));
+        assert_eq!(nth("n"), Ok(Nth { a: 1, b: 0 }));
+        assert!(nth("x").is_err());
+
+        assert!(Nth { a: -1, b: 3 }.matches(3));
+        assert!(!Nth { a: -1, b: 3 }.matches(4));
+        assert!(Nth { a: 2, b: 1 }.matches(5));
+        assert!(!Nth { a: 2, b: 1 }.matches(-1));
+    }
+
+    #[test]
+    fn invalid_selectors() {
+        for input in ["", "div,", "p >", "a::before", "[a=", ":unknown", ":has(:has(a))", "#", ".1", ":nth-child(2n of)"] {
+            assert!(SelectorList::parse(input).is_err(), "{input:?} should not parse");
+        }
+
+        assert!(SelectorList::parse(":is(:unknown, a)").is_ok());
+    }
+}
@@ -0,0 +1,144 @@
+use super::Buffer;
+use super::entities::NAMED_CHARACTER_REFERENCES;
+
+
+/// The length of the longest named character reference, including the semicolon.
+const LONGEST_NAME: usize = 32;
+
+// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+const C1_REPLACEMENTS: &[(u32, char)] = &[
+    (0x80, '\u{20ac}'), (0x82, '\u{201a}'), (0x83, '\u{0192}'), (0x84, '\u{201e}'), (0x85, '\u{2026}'), (0x86, '\u{2020}'),
+    (0x87, '\u{2021}'), (0x88, '\u{02c6}'), (0x89, '\u{2030}'), (0x8a, '\u{0160}'), (0x8b, '\u{2039}'), (0x8c, '\u{0152}'),
+    (0x8e, '\u{017d}'), (0x91, '\u{2018}'), (0x92, '\u{2019}'), (0x93, '\u{201c}'), (0x94, '\u{201d}'), (0x95, '\u{2022}'),
+    (0x96, '\u{2013}'), (0x97, '\u{2014}'), (0x98, '\u{02dc}'), (0x99, '\u{2122}'), (0x9a, '\u{0161}'), (0x9b, '\u{203a}'),
+    (0x9c, '\u{0153}'), (0x9e, '\u{017e}'), (0x9f, '\u{0178}'),
+];
+
+// https://html.spec.whatwg.org/multipage/parsing.html#character-reference-state
+/// Consume a character reference after an ampersand and return the characters it refers to.
+///
+/// When there is no reference only the ampersand is returned and nothing is consumed, the characters
+/// after it are then tokenized as usual which gives the same result as flushing them.
+pub(super) fn consume(buffer: &mut Buffer, in_attribute: bool) -> String {
+    match buffer.chars.peek() {
+        Some('#') => consume_numeric(buffer),
+        Some(character) if character.is_ascii_alphanumeric() => consume_named(buffer, in_attribute),
+        _ => String::from("&"),
+    }
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#named-character-reference-state
+fn consume_named(buffer: &mut Buffer, in_attribute: bool) -> String {
+    let mut candidate = buffer.chars.clone()
+        .take_while(|character| character.is_ascii_alphanumeric())
+        .take(LONGEST_NAME)
+        .collect::<String>();
+
+    if buffer.chars.clone().nth(candidate.len()) == Some(';') {
+        candidate.push(';');
+    }
+
+    let found = (1..=candidate.len()).rev()
+        .find_map(|len| {
+            NAMED_CHARACTER_REFERENCES.binary_search_by_key(&&candidate[..len], |(name, _)| name)
+                .ok()
+                .map(|index| NAMED_CHARACTER_REFERENCES[index])
+        });
+
+    let Some((name, characters)) = found else {
+        return String::from("&");
+    };
+
+    let next = buffer.chars.clone().nth(name.len());
+
+    // NOTE: for historical reasons a reference without a semicolon in an attribute is not
+    // replaced when it is followed by an equals sign or an alphanumeric character.
+    if in_attribute && !name.ends_with(';') && next.is_some_and(|next| next == '=' || next.is_ascii_alphanumeric()) {
+        return String::from("&");
+    }
+
+    buffer.advance(name.len());
+
+    characters.to_string()
+}
+
+// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-state
+fn consume_numeric(buffer: &mut Buffer) -> String {
+    let mut lookahead = buffer.chars.clone().skip(1).peekable();
+
+    let hexadecimal = lookahead.next_if(|character| matches!(character, 'x' | 'X')).is_some();
+    let radix = if hexadecimal { 16 } else { 10 };
+
+    let digits = lookahead.clone()
+        .take_while(|character| character.is_digit(radix))
+        .collect::<String>();
+
+    if digits.is_empty() {
+        return String::from("&");
+    }
+
+    let semicolon = lookahead.nth(digits.len()) == Some(';');
+
+    buffer.advance(1 + hexadecimal as usize + digits.len() + semicolon as usize);
+
+    let code = digits.chars()
+        .filter_map(|digit| digit.to_digit(radix))
+        .fold(0u32, |code, digit| code.saturating_mul(radix).saturating_add(digit));
+
+    let character = match code {
+        0 => '\u{fffd}',
+        0x80..=0x9f => C1_REPLACEMENTS.iter()
+            .find(|(c1, _)| *c1 == code)
+            .map(|(_, replacement)| *replacement)
+            .unwrap_or_else(|| char::from_u32(code).expect("c1 control is a char")),
+        _ => char::from_u32(code).unwrap_or('\u{fffd}'),
+    };
+
+    character.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(input: &str, in_attribute: bool) -> (String, String) {
+        let mut buffer = Buffer::new(input.chars());
+
+        let characters = consume(&mut buffer, in_attribute);
+
+        (characters, buffer.collect())
+    }
+
+    #[test]
+    fn table_is_sorted() {
+        assert!(NAMED_CHARACTER_REFERENCES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(NAMED_CHARACTER_REFERENCES.iter().all(|(name, _)| name.len() <= LONGEST_NAME));
+    }
+
+    #[test]
+    fn named() {
+        assert_eq!(reference("amp;x", false), (String::from("&"), String::from("x")));
+        assert_eq!(reference("ampx", false), (String::from("&"), String::from("x")));
+        assert_eq!(reference("notin;", false), (String::from("\u{2209}"), String::new()));
+        assert_eq!(reference("notit;", false), (String::from("\u{ac}"), String::from("it;")));
+        assert_eq!(reference("nosuch;", false), (String::from("&"), String::from("nosuch;")));
+    }
+
+    #[test]
+    fn named_in_attribute() {
+        assert_eq!(reference("copy=1", true), (String::from("&"), String::from("copy=1")));
+        assert_eq!(reference("copyx", true), (String::from("&"), String::from("copyx")));
+        assert_eq!(reference("copy x", true), (String::from("\u{a9}"), String::from(" x")));
+        assert_eq!(reference("copy;=1", true), (String::from("\u{a9}"), String::from("=1")));
+    }
+
+    #[test]
+    fn numeric() {
+        assert_eq!(reference("#65;", false), (String::from("A"), String::new()));
+        assert_eq!(reference("#x41z", false), (String::from("A"), String::from("z")));
+        assert_eq!(reference("#X41;", false), (String::from("A"), String::new()));
+        assert_eq!(reference("#x;", false), (String::from("&"), String::from("#x;")));
+        assert_eq!(reference("#150;", false), (String::from("\u{2013}"), String::new()));
+        assert_eq!(reference("#99999999999999;", false), (String::from("\u{fffd}"), String::new()));
+    }
+}
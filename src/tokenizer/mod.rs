@@ -1,10 +1,14 @@
 mod state;
+mod character_reference;
+mod entities;
 pub mod token;
 
-pub use token::{Doctype, Tag, TagKind, Token, TokenSink, Attribute};
+pub use token::{Doctype, Tag, TagKind, Token, TokenSink, TokenizerState, Attribute, Position};
 
 use state::{IdentifierKind, DoctypeKind, EscapeKind, RawKind, State};
 
+use crate::parser::{ParserOptions, ErrorPolicy};
+
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::str::Chars;
@@ -91,16 +95,23 @@ impl Data {
 
 struct Buffer<'a> {
     chars: Peekable<Chars<'a>>,
-    last: Option<Peekable<Chars<'a>>>,
+    last: Option<(Peekable<Chars<'a>>, Position)>,
+    position: Position,
 }
 
 impl<'a> Iterator for Buffer<'a> {
     type Item = char;
 
     fn next(&mut self) -> Option<char> {
-        self.last.replace(self.chars.clone());
+        self.last.replace((self.chars.clone(), self.position));
+
+        let next = self.chars.next();
 
-        self.chars.next()
+        if let Some(character) = next {
+            self.position.advance(character);
+        }
+
+        next
     }
 }
 
@@ -109,12 +120,21 @@ impl<'a> Buffer<'a> {
         Buffer {
             chars: chars.peekable(),
             last: None,
+            position: Position::default(),
         }
     }
 
     fn reconsume(&mut self) {
-        if let Some(chars) = self.last.take() {
+        if let Some((chars, position)) = self.last.take() {
             self.chars = chars;
+
+            self.position = position;
+        }
+    }
+
+    fn advance(&mut self, count: usize) {
+        for _ in 0..count {
+            self.next();
         }
     }
 
@@ -124,12 +144,18 @@ impl<'a> Buffer<'a> {
 
     // NOTE: this is case insensitive, technically its not completely compliant with the HTML standard.
     fn peek_exact(&mut self, string: &str) -> bool {
-        self.chars.clone()
+        let found = self.chars.clone()
             .take(string.len())
             .map(|c| c.to_ascii_lowercase())
-            .eq(string.chars())
-            .then(|| self.chars.nth(string.len() - 1))
-            .is_some()
+            .eq(string.chars());
+
+        if found {
+            for character in self.chars.by_ref().take(string.len()) {
+                self.position.advance(character);
+            }
+        }
+
+        found
     }
 }
 
@@ -138,16 +164,22 @@ pub struct Tokenizer<'a, Sink: TokenSink> {
     buffer: Buffer<'a>,
     state: State,
     data: Data,
+    options: ParserOptions,
+    start: Position,
+    eof: bool,
 }
 
 impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
     /// Create a new tokenizer.
-    pub fn new(sink: Sink, chars: Chars<'a>) -> Tokenizer<'a, Sink> {
+    pub fn new(sink: Sink, chars: Chars<'a>, options: ParserOptions) -> Tokenizer<'a, Sink> {
         Tokenizer {
             sink,
             buffer: Buffer::new(chars),
             state: State::Data,
             data: Data::new(),
+            options,
+            start: Position::default(),
+            eof: false,
         }
     }
 
@@ -155,6 +187,28 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
     /// Moves the tokenizer and returns the sink.
     pub fn finish(self) -> Sink { self.sink }
 
+    #[inline]
+    /// Returns the options the tokenizer was created with.
+    pub fn options(&self) -> &ParserOptions { &self.options }
+
+    /// Step the tokenizer until the end of file has been emitted, or until the sink encounters a
+    /// parse error when the error policy is to abort.
+    pub fn run(&mut self) {
+        while !self.eof && !self.aborted() {
+            self.step();
+        }
+    }
+
+    fn aborted(&self) -> bool {
+        self.options.error_policy == ErrorPolicy::Abort && self.sink.errored()
+    }
+
+    fn emit_eof(&mut self) {
+        self.eof = true;
+
+        self.sink.eof();
+    }
+
     fn reconsume(&mut self, state: State) {
         self.buffer.reconsume();
 
@@ -180,7 +234,21 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
     fn emit_tag(&mut self) {
         self.data.last.replace(Rc::clone(&self.data.tag));
 
-        self.sink.emit([Token::Tag(&*self.data.tag.borrow())]);
+        self.sink.emit([Token::Tag(&self.data.tag.borrow())]);
+
+        let tag = self.data.tag.borrow();
+
+        // noscript is only raw text when scripting is enabled, otherwise its content is tokenized as markup.
+        let scripting = self.options.scripting || tag.name != "noscript";
+
+        if tag.kind == TagKind::Start && let Some(state) = self.sink.next_state() && scripting {
+            self.state = match state {
+                TokenizerState::RcData => State::RawData(RawKind::RcData),
+                TokenizerState::RawText => State::RawData(RawKind::RawText),
+                TokenizerState::ScriptData => State::RawData(RawKind::ScriptData),
+                TokenizerState::Plaintext => State::Plaintext,
+            };
+        }
     }
 
     fn emit_doctype(&mut self) {
@@ -195,29 +263,43 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
     }
 
     pub fn step(&mut self) -> bool {
+        if self.state.starts_token() {
+            self.start = self.buffer.position;
+        }
+
+        self.sink.set_position(self.start);
+
         match self.state {
             // https://html.spec.whatwg.org/multipage/parsing.html#data-state
             State::Data => match self.buffer.next() {
-                Some('&') => todo!(),
+                Some('&') => {
+                    let characters = character_reference::consume(&mut self.buffer, false);
+
+                    self.sink.emit(characters.chars().map(Token::Character));
+                },
                 Some('<') => self.state = State::TagOpen,
                 Some(c) => self.sink.emit([Token::Character(c)]),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-state
             State::RawData(kind) => match self.buffer.next() {
-                Some('&') if kind == RawKind::RcData => todo!(),
+                Some('&') if kind == RawKind::RcData => {
+                    let characters = character_reference::consume(&mut self.buffer, false);
+
+                    self.sink.emit(characters.chars().map(Token::Character));
+                },
                 Some('<') => self.state = State::RawLessThanSign(kind),
                 Some('\0') => self.sink.emit([Token::Character('\u{fffd}')]),
                 Some(c) => self.sink.emit([Token::Character(c)]),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#plaintext-state
             State::Plaintext => match self.buffer.next() {
                 Some('\0') => self.sink.emit([Token::Character('\u{fffd}')]),
                 Some(c) => self.sink.emit([Token::Character(c)]),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#tag-open-state
@@ -236,7 +318,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                     if next.is_some() {
                         self.reconsume(State::Data);
                     } else {
-                        self.sink.eof();
+                        self.emit_eof();
                     }
                 },
             },
@@ -253,7 +335,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 None => {
                     self.sink.emit([Token::Character('<'), Token::Character('/')]);
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -268,7 +350,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 },
                 Some('\0') => self.data.tag.borrow_mut().append_name('\u{fffd}'),
                 Some(c) => self.data.tag.borrow_mut().append_name(c.to_ascii_lowercase()),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-less-than-sign-state
@@ -321,7 +403,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 _ => {
                     self.sink.emit([Token::Character('<'), Token::Character('/')]);
 
-                    self.sink.emit(self.data.temp.chars().map(|c| Token::Character(c)));
+                    self.sink.emit(self.data.temp.chars().map(Token::Character));
 
                     self.reconsume(State::RawData(kind));
                 },
@@ -369,7 +451,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 }
                 Some('\0') => self.sink.emit([Token::Character('\u{fffd}')]),
                 Some(c) => self.sink.emit([Token::Character(c)]),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-dash-state
@@ -384,7 +466,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 },
                 Some('\0') => self.set_state_and_emit(State::ScriptDataEscaped(kind), [Token::Character('\u{fffd}')]),
                 Some(c) => self.set_state_and_emit(State::ScriptDataEscaped(kind), [Token::Character(c)]),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-dash-dash-state
@@ -400,11 +482,17 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 Some('>') => self.set_state_and_emit(State::RawData(RawKind::ScriptData), [Token::Character('>')]),
                 Some('\0') => self.set_state_and_emit(State::ScriptDataEscaped(kind), [Token::Character('\u{fffd}')]),
                 Some(c) => self.set_state_and_emit(State::ScriptDataEscaped(kind), [Token::Character(c)]),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-less-than-sign-state
             State::ScriptDataEscapedLessThanSign(kind) => match self.buffer.next() {
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escaped-less-than-sign-state
+                Some('/') if kind == EscapeKind::DoubleEscaped => {
+                    self.data.temp.drain(..);
+
+                    self.set_state_and_emit(State::ScriptDataDoubleEscapeEnd, [Token::Character('/')]);
+                },
                 Some('/') => {
                     self.data.temp.drain(..);
 
@@ -457,7 +545,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 _ => {
                     self.sink.emit([Token::Character('<'), Token::Character('/')]);
 
-                    self.sink.emit(self.data.temp.chars().map(|c| Token::Character(c)));
+                    self.sink.emit(self.data.temp.chars().map(Token::Character));
 
                     self.reconsume(State::ScriptDataEscaped(EscapeKind::Escaped));
                 },
@@ -502,11 +590,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 Some('=') => self.state = State::BeforeAttributeValue,
                 Some('\0') => self.data.tag.borrow_mut().update_attribute(|attribute| attribute.name.push('\u{fffd}')),
                 Some(c) => {
-                    let name = c.is_ascii_uppercase()
-                        .then(|| c.to_ascii_lowercase())
-                        .unwrap_or(c);
-
-                    self.data.tag.borrow_mut().update_attribute(|attribute| attribute.name.push(name));
+                    self.data.tag.borrow_mut().update_attribute(|attribute| attribute.name.push(c.to_ascii_lowercase()));
                 },
                 None => self.reconsume(State::AfterAttributeName),
             },
@@ -526,7 +610,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
 
                     self.reconsume(State::AttributeName);
                 },
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#before-attribute-value-state
@@ -546,16 +630,24 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
             State::AttributeValueDoubleQuoted | State::AttributeValueSingleQuoted => match self.buffer.next() {
                 Some('"') if self.state == State::AttributeValueDoubleQuoted => self.state = State::AfterAttributeValueQuoted,
                 Some('\'') if self.state == State::AttributeValueSingleQuoted => self.state = State::AfterAttributeValueQuoted,
-                Some('&') => todo!(),
+                Some('&') => {
+                    let characters = character_reference::consume(&mut self.buffer, true);
+
+                    self.data.tag.borrow_mut().update_attribute(|attribute| attribute.value.push_str(&characters));
+                },
                 Some('\0') => self.data.tag.borrow_mut().update_attribute(|attribute| attribute.value.push('\u{fffd}')),
                 Some(c) => self.data.tag.borrow_mut().update_attribute(|attribute| attribute.value.push(c)),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#attribute-value-(unquoted)-state
             State::AttributeValueUnquoted => match self.buffer.next() {
                 Some('\t' | '\n' | '\x0C' | ' ') => self.state = State::BeforeAttributeName,
-                Some('&') => todo!(),
+                Some('&') => {
+                    let characters = character_reference::consume(&mut self.buffer, true);
+
+                    self.data.tag.borrow_mut().update_attribute(|attribute| attribute.value.push_str(&characters));
+                },
                 Some('>') => {
                     self.state = State::Data;
 
@@ -563,7 +655,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 },
                 Some('\0') => self.data.tag.borrow_mut().update_attribute(|attribute| attribute.value.push('\u{fffd}')),
                 Some(c) => self.data.tag.borrow_mut().update_attribute(|attribute| attribute.value.push(c)),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#after-attribute-value-(quoted)-state
@@ -576,7 +668,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                     self.emit_tag();
                 },
                 Some(_) => self.reconsume(State::BeforeAttributeName),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#self-closing-start-tag-state
@@ -589,7 +681,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                     self.emit_tag();
                 },
                 Some(_) => self.reconsume(State::BeforeAttributeName),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#bogus-comment-state
@@ -601,7 +693,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 },
                 Some('\0') => self.data.comment.push('\u{fffd}'),
                 Some(c) => self.data.comment.push(c),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#markup-declaration-open-state
@@ -659,7 +751,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 None => {
                     self.sink.emit([Token::Comment(&self.data.comment)]);
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -676,7 +768,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 None => {
                     self.sink.emit([Token::Comment(&self.data.comment)]);
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -717,7 +809,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 None => {
                     self.sink.emit([Token::Comment(&self.data.comment)]);
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -738,7 +830,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 None => {
                     self.sink.emit([Token::Comment(&self.data.comment)]);
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -762,7 +854,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 None => {
                     self.sink.emit([Token::Comment(&self.data.comment)]);
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -777,7 +869,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
 
                     self.emit_doctype();
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -814,7 +906,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
 
                     self.emit_doctype();
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -833,7 +925,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
 
                     self.emit_doctype();
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -857,7 +949,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
 
                     self.emit_doctype();
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -868,7 +960,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 Some(c) if matches!(c, '"' | '\'') => {
                     self.data.doctype.get_id(kind).drain();
 
-                    self.state = State::DoctypeIdentifier((c == '"').then(|| IdentifierKind::DoubleQuoted).unwrap_or(IdentifierKind::SingleQuoted), kind);
+                    self.state = State::DoctypeIdentifier(IdentifierKind::from_quote(c), kind);
                 },
                 Some('>') => {
                     self.data.doctype.force_quirks = true;
@@ -887,7 +979,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
 
                     self.emit_doctype();
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -898,7 +990,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 Some(c) if matches!(c, '"' | '\'') => {
                     self.data.doctype.get_id(kind).drain();
 
-                    self.state = State::DoctypeIdentifier((c == '"').then(|| IdentifierKind::DoubleQuoted).unwrap_or(IdentifierKind::SingleQuoted), kind);
+                    self.state = State::DoctypeIdentifier(IdentifierKind::from_quote(c), kind);
                 },
                 Some('>') => {
                     self.data.doctype.force_quirks = true;
@@ -917,7 +1009,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
 
                     self.emit_doctype();
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -940,7 +1032,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
 
                     self.emit_doctype();
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -958,7 +1050,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
 
                     self.emit_doctype();
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -976,7 +1068,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 Some(c) if matches!(c, '"' | '\'') => {
                     self.data.doctype.system_id.drain();
 
-                    self.state = State::DoctypeIdentifier((c == '"').then(|| IdentifierKind::DoubleQuoted).unwrap_or(IdentifierKind::SingleQuoted), DoctypeKind::System);
+                    self.state = State::DoctypeIdentifier(IdentifierKind::from_quote(c), DoctypeKind::System);
                 },
                 Some(_) => {
                     self.data.doctype.force_quirks = true;
@@ -988,7 +1080,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
 
                     self.emit_doctype();
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -1003,7 +1095,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
                 None => {
                     self.emit_doctype();
 
-                    self.sink.eof();
+                    self.emit_eof();
                 },
             },
 
@@ -1011,7 +1103,7 @@ impl<'a, Sink: TokenSink> Tokenizer<'a, Sink> {
             State::CDataSection => match self.buffer.next() {
                 Some(']') => self.state = State::CDataSectionBracket,
                 Some(c) => self.sink.emit([Token::Character(c)]),
-                None => self.sink.eof(),
+                None => self.emit_eof(),
             },
 
             // https://html.spec.whatwg.org/multipage/parsing.html#cdata-section-bracket-state
@@ -12,6 +12,16 @@ pub enum IdentifierKind {
     SingleQuoted,
 }
 
+impl IdentifierKind {
+    /// The kind of the identifier opened by the quote.
+    pub fn from_quote(quote: char) -> IdentifierKind {
+        match quote {
+            '"' => IdentifierKind::DoubleQuoted,
+            _ => IdentifierKind::SingleQuoted,
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum EscapeKind {
     Escaped,
@@ -80,3 +90,17 @@ pub enum State {
     CDataSectionBracket,
     CDataSectionEnd,
 }
+
+impl State {
+    /// Returns true if the next character in the state starts a new token.
+    pub fn starts_token(&self) -> bool {
+        matches!(
+            self,
+            State::Data
+                | State::RawData(_)
+                | State::Plaintext
+                | State::ScriptDataEscaped(_)
+                | State::CDataSection
+        )
+    }
+}
@@ -4,7 +4,35 @@ use std::rc::Rc;
 use unicase::UniCase;
 
 
-#[derive(Debug, PartialEq)]
+/// A position in the source, lines and columns start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for Position {
+    fn default() -> Position {
+        Position {
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl Position {
+    pub(super) fn advance(&mut self, character: char) {
+        if character == '\n' {
+            self.line += 1;
+
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Doctype<'a> {
     pub name: Option<UniCase<&'a str>>,
     pub public_id: Option<UniCase<&'a str>>,
@@ -21,19 +49,19 @@ impl<'a> Doctype<'a> {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TagKind {
     Start,
     End,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Attribute {
     pub name: String,
     pub value: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Tag {
     pub kind: TagKind,
     pub name: String,
@@ -71,7 +99,7 @@ impl Tag {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 /// Represents a Token.
 pub enum Token<'a> {
     Tag(&'a Tag),
@@ -83,12 +111,28 @@ pub enum Token<'a> {
 impl<'a> Token<'a> {
     pub fn is_start_tag(&self, name: &str) -> bool {
         match self {
-            Token::Tag(tag) =>  tag.name.as_str() == name,
+            Token::Tag(tag) => tag.kind == TagKind::Start && tag.name.as_str() == name,
+            _ => false,
+        }
+    }
+
+    pub fn is_end_tag(&self, name: &str) -> bool {
+        match self {
+            Token::Tag(tag) => tag.kind == TagKind::End && tag.name.as_str() == name,
             _ => false,
         }
     }
 }
 
+/// A state the tokenizer can be switched to by the sink after a start tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenizerState {
+    RcData,
+    RawText,
+    ScriptData,
+    Plaintext,
+}
+
 /// Recieve tokens from the tokenizer in the TokenSink.
 pub trait TokenSink {
     fn process(&mut self, token: Token);
@@ -102,6 +146,15 @@ pub trait TokenSink {
     }
 
     fn adjusted_node_namespace(&self) -> bool { false }
+
+    /// Called before each step of the tokenizer with the start position of the token being tokenized.
+    fn set_position(&mut self, _position: Position) {}
+
+    /// Called after a start tag has been processed, returns the state the tokenizer should switch to.
+    fn next_state(&mut self) -> Option<TokenizerState> { None }
+
+    /// Returns true once the sink has encountered a parse error.
+    fn errored(&self) -> bool { false }
 }
 
 